@@ -0,0 +1,60 @@
+use anyhow::{Context, Result};
+use serde::Deserialize;
+use std::fs::File;
+use std::io::{self, Write};
+use std::path::Path;
+use walkdir::WalkDir;
+use zip::write::SimpleFileOptions;
+use zip::{CompressionMethod, ZipWriter};
+
+/// Which files of the generated site go into a ZIP backup
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ArchiveScope {
+    /// Everything in the output directory
+    #[default]
+    Site,
+    /// Only the `raw.md` markdown sources (see `write_raw_markdown`)
+    Markdown,
+}
+
+/// Write the files of `output_dir` as a ZIP archive into `writer`.
+///
+/// Files are read and compressed one at a time and the writer doesn't need to
+/// seek, so a response body or pipe can be written without holding the whole
+/// archive in memory. Returns the number of files written.
+pub fn write_zip<W: Write>(output_dir: &Path, scope: ArchiveScope, writer: W) -> Result<usize> {
+    let mut zip = ZipWriter::new_stream(writer);
+    let options = SimpleFileOptions::default()
+        .compression_method(CompressionMethod::Deflated)
+        .large_file(true);
+
+    let mut files = 0;
+    for entry in WalkDir::new(output_dir).sort_by_file_name() {
+        let entry = entry.with_context(|| format!("Failed to read {}", output_dir.display()))?;
+        if !entry.file_type().is_file() {
+            continue;
+        }
+        if scope == ArchiveScope::Markdown && entry.path().extension().is_none_or(|ext| ext != "md") {
+            continue;
+        }
+
+        // Archive paths always use `/`
+        let relative = entry.path().strip_prefix(output_dir)?;
+        let name = relative
+            .components()
+            .map(|c| c.as_os_str().to_string_lossy())
+            .collect::<Vec<_>>()
+            .join("/");
+
+        let mut file = File::open(entry.path())
+            .with_context(|| format!("Failed to open {}", entry.path().display()))?;
+        zip.start_file(name, options)?;
+        io::copy(&mut file, &mut zip)
+            .with_context(|| format!("Failed to archive {}", entry.path().display()))?;
+        files += 1;
+    }
+
+    zip.finish()?;
+    Ok(files)
+}
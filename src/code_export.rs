@@ -0,0 +1,75 @@
+use anyhow::Result;
+use std::io::Write;
+use syntect::parsing::SyntaxSet;
+use zip::write::SimpleFileOptions;
+use zip::{CompressionMethod, ZipWriter};
+
+use crate::templates::CodeBlock;
+
+/// Name of the file in a code ZIP with every block one after another
+pub const ALL_CODE_FILE: &str = "all.md";
+
+/// File name of a code block: its number and language, with the language's
+/// usual extension, e.g. `01-python.py`. Blocks without a known language are `.txt`.
+pub fn file_name(block: &CodeBlock, total: usize, ps: &SyntaxSet) -> String {
+    // Fence info strings are free text, keep only filename-safe characters
+    let lang: String = block
+        .lang
+        .split_whitespace()
+        .next()
+        .unwrap_or("")
+        .chars()
+        .filter(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '+'))
+        .collect::<String>()
+        .to_lowercase();
+    let extension = ps
+        .find_syntax_by_token(&lang)
+        .and_then(|syntax| syntax.file_extensions.first())
+        .map_or("txt", String::as_str);
+    let lang = if lang.is_empty() { "text" } else { &lang };
+
+    // Padded so the files sort in order
+    let width = total.to_string().len().max(2);
+    format!("{:0width$}-{}.{}", block.number, lang, extension, width = width)
+}
+
+/// Every block in one markdown document, each under a heading with its number and language
+pub fn concatenated(blocks: &[CodeBlock]) -> String {
+    let mut out = String::new();
+    for block in blocks {
+        let lang = if block.lang.is_empty() { "text" } else { &block.lang };
+        out.push_str(&format!("## {}. {}\n\n", block.number, lang));
+        // A fence longer than any backtick run inside the code
+        let longest_run = block
+            .code
+            .split(|c| c != '`')
+            .map(str::len)
+            .max()
+            .unwrap_or(0);
+        let fence = "`".repeat(longest_run.max(2) + 1);
+        out.push_str(&format!("{}{}\n{}", fence, block.lang, block.code));
+        if !block.code.ends_with('\n') {
+            out.push('\n');
+        }
+        out.push_str(&format!("{}\n\n", fence));
+    }
+    out
+}
+
+/// Write `blocks` as a ZIP archive: one file per block (see [`file_name`])
+/// plus [`ALL_CODE_FILE`] with all of them. Returns the number of files written.
+pub fn write_zip<W: Write>(blocks: &[CodeBlock], writer: W) -> Result<usize> {
+    let ps = SyntaxSet::load_defaults_newlines();
+    let mut zip = ZipWriter::new_stream(writer);
+    let options = SimpleFileOptions::default().compression_method(CompressionMethod::Deflated);
+
+    for block in blocks {
+        zip.start_file(file_name(block, blocks.len(), &ps), options)?;
+        zip.write_all(block.code.as_bytes())?;
+    }
+    zip.start_file(ALL_CODE_FILE, options)?;
+    zip.write_all(concatenated(blocks).as_bytes())?;
+
+    zip.finish()?;
+    Ok(blocks.len() + 1)
+}
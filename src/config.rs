@@ -1,29 +1,323 @@
 use anyhow::{Context, Result};
+use chrono::format::{Item, StrftimeItems};
+use chrono_tz::Tz;
 use serde::{Deserialize, Serialize};
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+
+use super::model::Role;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
 pub struct AppConfig {
     pub conversations_file_path: Option<String>,
+    /// Number of search results returned when the request doesn't specify a limit
+    pub search_default_limit: usize,
+    /// Upper bound for the `limit` a search request may ask for
+    pub search_max_limit: usize,
+    /// Upper bound for the `offset` a search request may ask for
+    pub search_max_offset: usize,
+    /// `/api/search` answers 503 if a search takes longer than this (0 = no limit)
+    pub search_timeout_ms: u64,
+    /// What a search with an empty query returns
+    pub empty_search: EmptySearch,
+    /// Font for page text, one of the names in `BODY_FONTS`
+    pub body_font: Option<String>,
+    /// Font for code, one of the names in `CODE_FONTS`
+    pub code_font: Option<String>,
+    /// syntect theme for code blocks (see `--list-themes`), `generator::SYNTAX_THEME` if unset
+    pub syntax_theme: Option<String>,
+    /// IANA timezone for dates on generated pages (e.g. "Europe/Moscow"), UTC if unset
+    pub timezone: Option<String>,
+    /// strftime pattern for conversation dates, `DEFAULT_DATE_FORMAT` if unset
+    pub date_format: Option<String>,
+    /// Language of numbers and month names in the index and sidebar
+    pub language: Language,
+    /// What to call a conversation with neither a title nor any text to take one from
+    pub untitled_fallback: UntitledFallback,
+    /// Conversations with more messages are split into several pages (0 = never split)
+    pub messages_per_page: usize,
+    /// Show this many chars of each conversation's first message under its title on the index (0 = titles only)
+    pub index_preview_chars: usize,
+    /// Conversations shown on each page of the all-conversations archive (0 = all on one page)
+    pub archive_page_conversations: usize,
+    /// Threads generating pages in parallel (0 = one per CPU core)
+    pub generation_threads: usize,
+    /// Strip comments and collapse whitespace in generated pages
+    pub minify: bool,
+    /// Write brotli-compressed `.br` copies of large pages and assets for the server to send as is
+    pub precompress: bool,
+    /// Also write each conversation's markdown source to `conversations/{id}/raw.md`
+    pub write_raw_markdown: bool,
+    /// Search score multiplier for title matches
+    pub search_title_boost: f32,
+    /// Search score multiplier for message content matches
+    pub search_content_boost: f32,
+    /// Extra weight of whole-word matches over substring ones (0 = rank by ngrams only)
+    pub search_word_boost: f32,
+    /// Also index each message as a document of its own, for `by_message`
+    /// search and counting matching messages when grouping
+    pub index_messages: bool,
+    /// Index at most this many chars of each conversation's text (0 = no limit)
+    pub max_index_chars_per_conversation: usize,
+    /// Index only the ngrams at the start of each field: a much smaller index
+    /// that finds prefixes but no longer arbitrary substrings
+    pub ngram_prefix_only: bool,
+    /// Tokenizer for titles and messages; `ngram_prefix_only` only applies to `Ngram`
+    pub index_tokenizer: IndexTokenizer,
+    /// Shortest ngram indexed, in chars; shorter queries find nothing
+    pub ngram_min: usize,
+    /// Longest ngram indexed, in chars; longer words match by their ngrams
+    pub ngram_max: usize,
+    /// What importing another export does to the conversations already imported
+    pub import_mode: ImportMode,
+    /// Show a cloud of this many frequent words on each conversation page (0 = no cloud)
+    pub tag_cloud_terms: usize,
+    /// List the code blocks (linked) and attachments at the top of each conversation page
+    pub code_index: bool,
+    /// Show consecutive messages of the same role and type as one message
+    pub merge_consecutive_messages: bool,
+    /// Show each conversation's latest message first
+    pub newest_first: bool,
+    /// Embedded images larger than this are written to files instead of inlined (0 = always inline)
+    pub inline_image_max_bytes: usize,
+    /// Where formulas on generated pages are rendered
+    pub math_render: MathRender,
+    /// Cache visited pages in the browser so they open without the server
+    pub offline_pages: bool,
+    /// Desktop import rejects files larger than this many bytes (0 = no limit)
+    pub max_import_bytes: u64,
+    /// Ids of favorite conversations, exported on their own with `--export-favorites`
+    pub favorites: Vec<String>,
+    /// Desktop app: ids of the conversations opened so far, to mark the others unread
+    pub read_conversations: Vec<String>,
+    /// Turn straight quotes, `--` and `...` in responses into typographic ones
+    pub smart_punctuation: bool,
+    /// Honor `{#id .class}` after a heading in responses
+    pub heading_attributes: bool,
+    /// Name shown above your messages, `DEFAULT_USER_LABEL` if unset
+    pub user_label: Option<String>,
+    /// Name shown above responses, `DEFAULT_ASSISTANT_LABEL` if unset
+    pub assistant_label: Option<String>,
+    /// Image URL for your avatar; the label's initials are shown if unset
+    pub user_avatar: Option<String>,
+    /// Image URL for the assistant's avatar; the label's initials are shown if unset
+    pub assistant_avatar: Option<String>,
+}
+
+/// Optional markdown extensions used when rendering responses
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct MarkdownOptions {
+    pub smart_punctuation: bool,
+    pub heading_attributes: bool,
+}
+
+/// How `$...$` / `$$...$$` formulas are displayed on generated pages
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum MathRender {
+    /// KaTeX renders them in the browser
+    #[default]
+    Client,
+    /// Converted to MathML at generation time, no JavaScript needed
+    Server,
+    /// Shown as written
+    None,
+}
+
+/// How titles and messages are split into searchable terms, chosen when
+/// the index is built
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum IndexTokenizer {
+    /// Ngrams of 2–10 chars: finds any substring of two chars or more
+    #[default]
+    Ngram,
+    /// Whole words: a small index and exact word and phrase search, but no substrings
+    Words,
+}
+
+/// Locale numbers and month names are formatted for
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Language {
+    /// "1 234", "Март 2024"
+    #[default]
+    Ru,
+    /// "1,234", "March 2024"
+    En,
+}
+
+/// Label of a conversation with no title that none can be derived for
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum UntitledFallback {
+    /// "Untitled"
+    #[default]
+    Untitled,
+    /// When it was created, in `date_format`
+    Date,
+    /// The start of its id
+    Id,
+}
+
+/// What `/api/search` does when the query is empty
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum EmptySearch {
+    /// 400 "empty query"
+    #[default]
+    Error,
+    /// The most recent conversations, newest first
+    Recent,
+}
+
+/// How the desktop app imports a new export
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ImportMode {
+    /// Throw away the previous import
+    #[default]
+    Replace,
+    /// Add the new export's conversations to the previous ones (same id: the new one wins)
+    Merge,
+}
+
+/// Date pattern used when `date_format` is unset or invalid
+pub const DEFAULT_DATE_FORMAT: &str = "%d.%m.%Y в %H:%M";
+
+/// Search boosts used when `search_title_boost` / `search_content_boost` /
+/// `search_word_boost` are invalid
+pub const DEFAULT_TITLE_BOOST: f32 = 2.0;
+pub const DEFAULT_CONTENT_BOOST: f32 = 1.0;
+pub const DEFAULT_WORD_BOOST: f32 = 3.0;
+
+/// Ngram sizes used when `ngram_min` / `ngram_max` are unset or invalid
+pub const DEFAULT_NGRAM_MIN: usize = 2;
+pub const DEFAULT_NGRAM_MAX: usize = 10;
+
+/// Default for `max_import_bytes`: 1 GiB
+pub const DEFAULT_MAX_IMPORT_BYTES: u64 = 1024 * 1024 * 1024;
+
+/// Default for `inline_image_max_bytes`: 64 KiB
+pub const DEFAULT_INLINE_IMAGE_MAX_BYTES: usize = 64 * 1024;
+
+/// Default for `search_max_offset`
+pub const DEFAULT_SEARCH_MAX_OFFSET: usize = 10_000;
+
+/// Default for `search_timeout_ms`
+pub const DEFAULT_SEARCH_TIMEOUT_MS: u64 = 5_000;
+
+/// Labels shown above messages when `user_label` / `assistant_label` are unset
+pub const DEFAULT_USER_LABEL: &str = "Вы";
+pub const DEFAULT_ASSISTANT_LABEL: &str = "DeepSeek";
+
+/// Fonts allowed for `body_font`, mapped to CSS font stacks (the first one is the default)
+pub const BODY_FONTS: &[(&str, &str)] = &[
+    ("inter", r#"Inter, -apple-system, BlinkMacSystemFont, "Segoe UI", Roboto, sans-serif"#),
+    ("system", r#"-apple-system, BlinkMacSystemFont, "Segoe UI", Roboto, sans-serif"#),
+    ("sans-serif", r#"Helvetica, Arial, sans-serif"#),
+    ("serif", r#"Georgia, "Times New Roman", serif"#),
+];
+
+/// Fonts allowed for `code_font`, mapped to CSS font stacks (the first one is the default)
+pub const CODE_FONTS: &[(&str, &str)] = &[
+    ("sf-mono", r#""SFMono-Regular", "Consolas", "Liberation Mono", "Menlo", monospace"#),
+    ("jetbrains-mono", r#""JetBrains Mono", monospace"#),
+    ("fira-code", r#""Fira Code", monospace"#),
+    ("cascadia-code", r#""Cascadia Code", monospace"#),
+    ("monospace", "monospace"),
+];
+
+/// Look up `name` in an allowlist, falling back to its first entry.
+fn font_stack(fonts: &[(&str, &'static str)], name: Option<&str>, setting: &str) -> &'static str {
+    let Some(name) = name else {
+        return fonts[0].1;
+    };
+
+    match fonts.iter().find(|(font, _)| font.eq_ignore_ascii_case(name)) {
+        Some((_, stack)) => stack,
+        None => {
+            tracing::warn!("Unknown {} '{}', using the default font", setting, name);
+            fonts[0].1
+        }
+    }
+}
+
+/// A boost must be a non-negative number, otherwise the default is used.
+fn boost(value: f32, default: f32, setting: &str) -> f32 {
+    if value.is_finite() && value >= 0.0 {
+        value
+    } else {
+        tracing::warn!("Invalid {} {}, using {}", setting, value, default);
+        default
+    }
 }
 
 impl Default for AppConfig {
     fn default() -> Self {
         Self {
             conversations_file_path: None,
+            search_default_limit: 20,
+            search_max_limit: 200,
+            search_max_offset: DEFAULT_SEARCH_MAX_OFFSET,
+            search_timeout_ms: DEFAULT_SEARCH_TIMEOUT_MS,
+            empty_search: EmptySearch::Error,
+            body_font: None,
+            code_font: None,
+            syntax_theme: None,
+            timezone: None,
+            date_format: None,
+            language: Language::Ru,
+            untitled_fallback: UntitledFallback::Untitled,
+            messages_per_page: 0,
+            index_preview_chars: 0,
+            archive_page_conversations: 50,
+            generation_threads: 0,
+            minify: false,
+            precompress: false,
+            write_raw_markdown: false,
+            search_title_boost: DEFAULT_TITLE_BOOST,
+            search_content_boost: DEFAULT_CONTENT_BOOST,
+            search_word_boost: DEFAULT_WORD_BOOST,
+            index_messages: false,
+            max_index_chars_per_conversation: 0,
+            ngram_prefix_only: false,
+            index_tokenizer: IndexTokenizer::Ngram,
+            ngram_min: DEFAULT_NGRAM_MIN,
+            ngram_max: DEFAULT_NGRAM_MAX,
+            import_mode: ImportMode::Replace,
+            tag_cloud_terms: 0,
+            code_index: false,
+            merge_consecutive_messages: false,
+            newest_first: false,
+            inline_image_max_bytes: DEFAULT_INLINE_IMAGE_MAX_BYTES,
+            math_render: MathRender::Client,
+            offline_pages: false,
+            max_import_bytes: DEFAULT_MAX_IMPORT_BYTES,
+            favorites: Vec::new(),
+            read_conversations: Vec::new(),
+            smart_punctuation: false,
+            heading_attributes: false,
+            user_label: None,
+            assistant_label: None,
+            user_avatar: None,
+            assistant_avatar: None,
         }
     }
 }
 
 impl AppConfig {
     pub fn load() -> Result<Self> {
-        let config_path = Self::config_file_path()?;
-        
+        Self::load_from(&Self::config_file_path()?)
+    }
+
+    /// Load the config in `config_path`, or the defaults if there is no such file
+    pub fn load_from(config_path: &Path) -> Result<Self> {
         if !config_path.exists() {
             return Ok(Self::default());
         }
         
-        let contents = std::fs::read_to_string(&config_path)
+        let contents = std::fs::read_to_string(config_path)
             .context("Failed to read config file")?;
         
         let config: AppConfig = serde_json::from_str(&contents)
@@ -33,8 +327,11 @@ impl AppConfig {
     }
     
     pub fn save(&self) -> Result<()> {
-        let config_path = Self::config_file_path()?;
-        
+        self.save_to(&Self::config_file_path()?)
+    }
+
+    /// Write the config to `config_path`, creating its directory if needed
+    pub fn save_to(&self, config_path: &Path) -> Result<()> {
         // Create parent directory if it doesn't exist
         if let Some(parent) = config_path.parent() {
             std::fs::create_dir_all(parent)
@@ -44,13 +341,108 @@ impl AppConfig {
         let contents = serde_json::to_string_pretty(self)
             .context("Failed to serialize config")?;
         
-        std::fs::write(&config_path, contents)
+        std::fs::write(config_path, contents)
             .context("Failed to write config file")?;
         
         Ok(())
     }
     
-    fn config_file_path() -> Result<PathBuf> {
+    /// CSS font stack for page text
+    pub fn body_font_stack(&self) -> &'static str {
+        font_stack(BODY_FONTS, self.body_font.as_deref(), "body_font")
+    }
+
+    /// CSS font stack for code blocks
+    pub fn code_font_stack(&self) -> &'static str {
+        font_stack(CODE_FONTS, self.code_font.as_deref(), "code_font")
+    }
+
+    /// Timezone generated pages show dates in
+    pub fn timezone(&self) -> Tz {
+        let Some(name) = self.timezone.as_deref() else {
+            return Tz::UTC;
+        };
+
+        name.parse().unwrap_or_else(|_| {
+            tracing::warn!("Unknown timezone '{}', using UTC", name);
+            Tz::UTC
+        })
+    }
+
+    /// strftime pattern for conversation dates
+    pub fn date_format(&self) -> &str {
+        let Some(format) = self.date_format.as_deref() else {
+            return DEFAULT_DATE_FORMAT;
+        };
+
+        // chrono panics when rendering an invalid pattern, so check it up front
+        if StrftimeItems::new(format).any(|item| matches!(item, Item::Error)) {
+            tracing::warn!("Invalid date_format '{}', using the default", format);
+            return DEFAULT_DATE_FORMAT;
+        }
+
+        format
+    }
+
+    /// Search score multiplier for title matches
+    pub fn title_boost(&self) -> f32 {
+        boost(self.search_title_boost, DEFAULT_TITLE_BOOST, "search_title_boost")
+    }
+
+    /// Search score multiplier for message content matches
+    pub fn content_boost(&self) -> f32 {
+        boost(self.search_content_boost, DEFAULT_CONTENT_BOOST, "search_content_boost")
+    }
+
+    /// Search score multiplier for whole-word matches
+    pub fn word_boost(&self) -> f32 {
+        boost(self.search_word_boost, DEFAULT_WORD_BOOST, "search_word_boost")
+    }
+
+    /// `(ngram_min, ngram_max)`, or the defaults if they aren't a range of at least one char
+    pub fn ngram_sizes(&self) -> (usize, usize) {
+        if self.ngram_min >= 1 && self.ngram_max >= self.ngram_min {
+            (self.ngram_min, self.ngram_max)
+        } else {
+            tracing::warn!(
+                "Invalid ngram_min {} / ngram_max {}, using {} / {}",
+                self.ngram_min,
+                self.ngram_max,
+                DEFAULT_NGRAM_MIN,
+                DEFAULT_NGRAM_MAX
+            );
+            (DEFAULT_NGRAM_MIN, DEFAULT_NGRAM_MAX)
+        }
+    }
+
+    /// Markdown extensions turned on for responses
+    pub fn markdown_options(&self) -> MarkdownOptions {
+        MarkdownOptions {
+            smart_punctuation: self.smart_punctuation,
+            heading_attributes: self.heading_attributes,
+        }
+    }
+
+    /// Name shown above the messages of `role`
+    pub fn role_label(&self, role: Role) -> &str {
+        let (label, default) = match role {
+            Role::User => (&self.user_label, DEFAULT_USER_LABEL),
+            Role::Assistant => (&self.assistant_label, DEFAULT_ASSISTANT_LABEL),
+        };
+        label.as_deref().map(str::trim).filter(|label| !label.is_empty()).unwrap_or(default)
+    }
+
+    /// Avatar image URL for `role`, if one is configured
+    pub fn role_avatar(&self, role: Role) -> Option<&str> {
+        let avatar = match role {
+            Role::User => &self.user_avatar,
+            Role::Assistant => &self.assistant_avatar,
+        };
+        avatar.as_deref().map(str::trim).filter(|url| !url.is_empty())
+    }
+
+    /// Where `load` and `save` keep the config
+    pub fn config_file_path() -> Result<PathBuf> {
         let config_dir = dirs::config_dir()
             .context("Failed to get config directory")?;
         
@@ -0,0 +1,291 @@
+use anyhow::{Context, Result};
+use serde::de::{self, DeserializeOwned, Deserializer, IgnoredAny, MapAccess, SeqAccess, Visitor};
+use serde::Deserialize;
+use std::fmt;
+use std::fs::File;
+use std::io::{BufRead, BufReader};
+use std::path::Path;
+
+/// Shape of a conversations export file
+#[derive(Debug, Clone, PartialEq)]
+pub enum ExportFormat {
+    /// Bare top-level array: `[ {...}, {...} ]`
+    Array,
+    /// Object wrapping the array: `{ "conversations": [...] }`
+    Wrapped,
+    /// Object with a version marker: `{ "version": ..., "conversations": [...] }`
+    Versioned(String),
+}
+
+/// What [`validate_export_file`] found in an export
+#[derive(Debug, Clone, PartialEq)]
+pub struct ExportSummary {
+    pub format: ExportFormat,
+    /// Number of conversations in the export
+    pub conversations: usize,
+}
+
+const UTF8_BOM: &[u8] = b"\xEF\xBB\xBF";
+
+/// Read buffer for streaming parses of export files
+const READ_BUFFER_BYTES: usize = 1024 * 1024;
+
+#[derive(Debug, Deserialize)]
+struct WrappedExport<T> {
+    #[serde(default)]
+    version: Option<serde_json::Value>,
+    conversations: Vec<T>,
+}
+
+/// Parse conversations from an export in any of the supported shapes.
+///
+/// A top-level array is tried first, then an object with a `conversations`
+/// field. The shape is picked from the first non-whitespace character, so
+/// the (potentially huge) file is only deserialized once.
+pub fn parse_conversations<T: DeserializeOwned>(data: &str) -> Result<(ExportFormat, Vec<T>)> {
+    if data.trim_start().starts_with('{') {
+        let export: WrappedExport<T> = serde_json::from_str(data)
+            .context("Failed to parse export object (expected a `conversations` array)")?;
+        let format = wrapped_format(export.version);
+        tracing::info!("Detected {:?} export", format);
+        Ok((format, export.conversations))
+    } else {
+        let conversations: Vec<T> = serde_json::from_str(data)
+            .context("Failed to parse conversations array")?;
+        Ok((ExportFormat::Array, conversations))
+    }
+}
+
+/// Like [`parse_conversations`] on [`read_export`], but parsing straight from
+/// the file so its text is never held in memory.
+///
+/// Falls back to reading the text (which tolerates invalid UTF-8) if the
+/// streaming parse fails.
+pub async fn load_export<T: DeserializeOwned + Send + 'static>(path: &str) -> Result<(ExportFormat, Vec<T>)> {
+    let file_path = path.to_string();
+    let streamed = tokio::task::spawn_blocking(move || parse_export_file(Path::new(&file_path))).await?;
+
+    match streamed {
+        Ok(parsed) => Ok(parsed),
+        Err(e) => {
+            tracing::debug!("Streaming parse of {} failed ({:#}), parsing its decoded text", path, e);
+            parse_conversations(&read_export(path).await?)
+        }
+    }
+}
+
+fn parse_export_file<T: DeserializeOwned>(path: &Path) -> Result<(ExportFormat, Vec<T>)> {
+    let mut reader = open_export(path)?;
+
+    // Pick the shape from the first non-whitespace byte, as `parse_conversations` does
+    let first = loop {
+        let buf = reader.fill_buf()?;
+        if buf.is_empty() {
+            break None;
+        }
+        match buf.iter().position(|b| !b.is_ascii_whitespace()) {
+            Some(idx) => break Some(buf[idx]),
+            None => {
+                let len = buf.len();
+                reader.consume(len);
+            }
+        }
+    };
+
+    if first == Some(b'{') {
+        let export: WrappedExport<T> = serde_json::from_reader(reader)
+            .context("Failed to parse export object (expected a `conversations` array)")?;
+        let format = wrapped_format(export.version);
+        tracing::info!("Detected {:?} export", format);
+        Ok((format, export.conversations))
+    } else {
+        let conversations: Vec<T> = serde_json::from_reader(reader)
+            .context("Failed to parse conversations array")?;
+        Ok((ExportFormat::Array, conversations))
+    }
+}
+
+fn wrapped_format(version: Option<serde_json::Value>) -> ExportFormat {
+    match version {
+        Some(serde_json::Value::String(s)) => ExportFormat::Versioned(s),
+        Some(other) => ExportFormat::Versioned(other.to_string()),
+        None => ExportFormat::Wrapped,
+    }
+}
+
+/// Open an export for a streaming parse, past its BOM if it has one
+fn open_export(path: &Path) -> Result<BufReader<File>> {
+    let file = File::open(path).with_context(|| format!("Failed to read {}", path.display()))?;
+    let mut reader = BufReader::with_capacity(READ_BUFFER_BYTES, file);
+    if reader.fill_buf()?.starts_with(UTF8_BOM) {
+        reader.consume(UTF8_BOM.len());
+    }
+    Ok(reader)
+}
+
+/// Read the export at `path` and return the conversation with id `id`, if any.
+pub async fn load_conversation(path: &str, id: &str) -> Result<Option<crate::model::Conversation>> {
+    let (_, conversations): (_, Vec<crate::model::Conversation>) = load_export(path).await?;
+    Ok(conversations.into_iter().find(|c| c.id == id))
+}
+
+/// Read the export at `path` and return the conversation with id `id`
+/// exactly as exported, including fields the viewer doesn't use.
+pub async fn load_raw_conversation(path: &str, id: &str) -> Result<Option<serde_json::Value>> {
+    let (_, conversations): (_, Vec<serde_json::Value>) = load_export(path).await?;
+    Ok(conversations
+        .into_iter()
+        .find(|c| c.get("id").and_then(|v| v.as_str()) == Some(id)))
+}
+
+/// Read an export file as text. See [`decode_export`].
+pub async fn read_export(path: &str) -> Result<String> {
+    let bytes = tokio::fs::read(path)
+        .await
+        .with_context(|| format!("Failed to read {}", path))?;
+    Ok(decode_export(bytes))
+}
+
+/// Check that `path` is an export no bigger than `max_bytes` (0 = no limit)
+/// without loading it: the size is checked before reading, then the JSON is
+/// parsed as a stream that only counts conversations and fails on the first
+/// malformed token or unexpected shape.
+pub fn validate_export_file(path: &Path, max_bytes: u64) -> Result<ExportSummary> {
+    let size = std::fs::metadata(path)
+        .with_context(|| format!("Failed to read {}", path.display()))?
+        .len();
+    if max_bytes > 0 && size > max_bytes {
+        anyhow::bail!(
+            "{} is {} MB, more than the {} MB import limit (max_import_bytes)",
+            path.display(),
+            size.div_ceil(1024 * 1024),
+            max_bytes / (1024 * 1024)
+        );
+    }
+
+    let mut deserializer = serde_json::Deserializer::from_reader(open_export(path)?);
+    let ExportShape(summary) = ExportShape::deserialize(&mut deserializer)
+        .and_then(|shape| deserializer.end().map(|_| shape))
+        .context("Invalid JSON format")?;
+
+    Ok(summary)
+}
+
+/// An export deserialized without keeping anything but its shape
+struct ExportShape(ExportSummary);
+
+impl<'de> Deserialize<'de> for ExportShape {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct ShapeVisitor;
+
+        impl<'de> Visitor<'de> for ShapeVisitor {
+            type Value = ExportShape;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                f.write_str("an array of conversations or an object with a `conversations` array")
+            }
+
+            fn visit_seq<A: SeqAccess<'de>>(self, seq: A) -> Result<Self::Value, A::Error> {
+                Ok(ExportShape(ExportSummary {
+                    format: ExportFormat::Array,
+                    conversations: count_conversations(seq)?,
+                }))
+            }
+
+            fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Self::Value, A::Error> {
+                let mut version = None;
+                let mut conversations = None;
+                while let Some(key) = map.next_key::<String>()? {
+                    match key.as_str() {
+                        "version" => version = map.next_value()?,
+                        "conversations" => conversations = Some(map.next_value::<ConversationCount>()?.0),
+                        _ => {
+                            map.next_value::<IgnoredAny>()?;
+                        }
+                    }
+                }
+
+                Ok(ExportShape(ExportSummary {
+                    format: wrapped_format(version),
+                    conversations: conversations.ok_or_else(|| de::Error::missing_field("conversations"))?,
+                }))
+            }
+        }
+
+        deserializer.deserialize_any(ShapeVisitor)
+    }
+}
+
+/// Length of a `conversations` array
+struct ConversationCount(usize);
+
+impl<'de> Deserialize<'de> for ConversationCount {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct CountVisitor;
+
+        impl<'de> Visitor<'de> for CountVisitor {
+            type Value = ConversationCount;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                f.write_str("an array of conversations")
+            }
+
+            fn visit_seq<A: SeqAccess<'de>>(self, seq: A) -> Result<Self::Value, A::Error> {
+                count_conversations(seq).map(ConversationCount)
+            }
+        }
+
+        deserializer.deserialize_seq(CountVisitor)
+    }
+}
+
+fn count_conversations<'de, A: SeqAccess<'de>>(mut seq: A) -> Result<usize, A::Error> {
+    let mut count = 0;
+    while seq.next_element::<ConversationObject>()?.is_some() {
+        count += 1;
+    }
+    Ok(count)
+}
+
+/// A conversation, skipped over after checking it is an object
+struct ConversationObject;
+
+impl<'de> Deserialize<'de> for ConversationObject {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct ObjectVisitor;
+
+        impl<'de> Visitor<'de> for ObjectVisitor {
+            type Value = ConversationObject;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                f.write_str("a conversation object")
+            }
+
+            fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Self::Value, A::Error> {
+                while map.next_entry::<IgnoredAny, IgnoredAny>()?.is_some() {}
+                Ok(ConversationObject)
+            }
+        }
+
+        deserializer.deserialize_map(ObjectVisitor)
+    }
+}
+
+/// Decode raw export bytes, stripping a leading UTF-8 BOM and lossily
+/// replacing invalid sequences (with a warning) instead of failing.
+pub fn decode_export(mut bytes: Vec<u8>) -> String {
+    if bytes.starts_with(UTF8_BOM) {
+        bytes.drain(..UTF8_BOM.len());
+    }
+
+    match String::from_utf8(bytes) {
+        Ok(text) => text,
+        Err(e) => {
+            tracing::warn!(
+                "⚠️  Export is not valid UTF-8 (first invalid byte at offset {}), replacing invalid sequences",
+                e.utf8_error().valid_up_to()
+            );
+            String::from_utf8_lossy(e.as_bytes()).into_owned()
+        }
+    }
+}
@@ -0,0 +1,84 @@
+use anyhow::{Context, Result};
+use serde::Deserialize;
+use std::collections::HashSet;
+
+use crate::model::Conversation;
+use crate::{export, json_export, markdown_export};
+
+/// What favorite conversations are exported as
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FavoritesFormat {
+    /// A versioned export the importer reads back, as `--export-json` writes
+    #[default]
+    Json,
+    /// All favorites in one markdown document
+    Markdown,
+}
+
+impl FavoritesFormat {
+    /// Markdown for `.md` files, JSON for anything else
+    pub fn from_path(path: &str) -> Self {
+        if path.to_lowercase().ends_with(".md") {
+            Self::Markdown
+        } else {
+            Self::Json
+        }
+    }
+}
+
+/// The conversations whose ids are in `favorites`, in export order
+pub fn select_favorites(conversations: Vec<Conversation>, favorites: &[String]) -> Vec<Conversation> {
+    let wanted: HashSet<&str> = favorites.iter().map(String::as_str).collect();
+    let selected: Vec<Conversation> = conversations
+        .into_iter()
+        .filter(|conv| wanted.contains(conv.id.as_str()))
+        .collect();
+
+    let found: HashSet<&str> = selected.iter().map(|conv| conv.id.as_str()).collect();
+    let missing = wanted.difference(&found).count();
+    if missing > 0 {
+        tracing::warn!("⚠️  {} favorite conversations are not in the export", missing);
+    }
+
+    selected
+}
+
+/// Render favorite conversations in `format`; `include_metadata` is as for `--export-json`
+pub fn render_favorites(conversations: &[Conversation], format: FavoritesFormat, include_metadata: bool) -> Result<String> {
+    match format {
+        FavoritesFormat::Json => serde_json::to_string_pretty(&json_export::export_document(conversations, include_metadata))
+            .context("Failed to serialize favorites"),
+        FavoritesFormat::Markdown => Ok(conversations
+            .iter()
+            .map(markdown_export::conversation_to_markdown)
+            .collect::<Vec<_>>()
+            .join("\n---\n\n")),
+    }
+}
+
+/// Export only the conversations in `favorites` from `conversations_path`
+/// to `output_path`. Returns the number of conversations written.
+pub async fn export_favorites(
+    conversations_path: &str,
+    output_path: &str,
+    favorites: &[String],
+    format: FavoritesFormat,
+    include_metadata: bool,
+) -> Result<usize> {
+    if favorites.is_empty() {
+        tracing::warn!("⚠️  No favorites configured, add conversation ids to `favorites` in config.json");
+    }
+
+    let (_, conversations): (_, Vec<Conversation>) = export::load_export(conversations_path).await?;
+    let selected = select_favorites(conversations, favorites);
+
+    let contents = render_favorites(&selected, format, include_metadata)?;
+    tokio::fs::write(output_path, contents)
+        .await
+        .with_context(|| format!("Failed to write {}", output_path))?;
+
+    tracing::info!("✅ Exported {} favorite conversations to {}", selected.len(), output_path);
+
+    Ok(selected.len())
+}
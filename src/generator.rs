@@ -1,223 +1,1213 @@
-use anyhow::Result;
+use anyhow::{Context, Result};
 use askama::Template;
-use chrono::{DateTime, Utc};
+use chrono::{DateTime, Datelike, Utc};
+use chrono_tz::Tz;
 use pulldown_cmark::{html, CodeBlockKind, Event, Options, Parser, Tag, TagEnd};
 use rayon::prelude::*;
-use serde::Deserialize;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::fs;
 use std::path::Path;
 use std::sync::{Arc, Mutex};
-use syntect::highlighting::ThemeSet;
+use syntect::highlighting::{Theme, ThemeSet};
 use syntect::parsing::SyntaxSet;
 
+use super::config::{AppConfig, Language, MarkdownOptions, MathRender, BODY_FONTS, CODE_FONTS};
+use super::manifest::{self, GenerationManifest};
+use super::locale;
+use super::markdown_export;
+use super::math;
+use super::minify;
+use super::model::{self, Conversation, Role};
+use super::shortlinks::{self, ShortLinks};
 use super::templates::*;
+use super::terms;
 
-#[derive(Debug, Deserialize)]
-struct Conversation {
-    id: String,
-    title: Option<String>,
-    inserted_at: Option<String>,
-    updated_at: Option<String>,
-    mapping: serde_json::Value,
+/// syntect theme used for code blocks when `syntax_theme` isn't set
+pub const SYNTAX_THEME: &str = "base16-ocean.light";
+
+/// Directory of the archive with every conversation inlined, served at `/all/`
+const ARCHIVE_DIR: &str = "all";
+/// Scratch directory inside `ARCHIVE_DIR`, removed once the archive is written
+const ARCHIVE_SECTIONS_DIR: &str = ".sections";
+
+/// Directory next to a conversation's pages for its embedded images that are too large to inline
+const IMAGE_ASSETS_DIR: &str = "assets";
+
+/// Markdown nested deeper than this (lists in quotes in lists, ...) is cut
+/// off there; pathological nesting makes rendering slow and pages huge
+const MAX_MARKDOWN_DEPTH: usize = 64;
+
+/// Files smaller than this aren't worth a `.br` copy
+const PRECOMPRESS_MIN_BYTES: u64 = 1024;
+/// Extensions of the generated files that get `.br` copies
+const PRECOMPRESS_EXTENSIONS: &[&str] = &["html", "css", "js", "svg", "webmanifest", "json"];
+
+/// Scripts copied to `assets/js/`, with the copies bundled into the binary
+const SCRIPTS: &[(&str, &str)] = &[
+    ("search.js", include_str!("../static/search.js")),
+    ("code-actions.js", include_str!("../static/code-actions.js")),
+    ("conversation-actions.js", include_str!("../static/conversation-actions.js")),
+    ("scroll.js", include_str!("../static/scroll.js")),
+    ("index-filter.js", include_str!("../static/index-filter.js")),
+    ("read-tracking.js", include_str!("../static/read-tracking.js")),
+    ("command-palette.js", include_str!("../static/command-palette.js")),
+    ("settings.js", include_str!("../static/settings.js")),
+];
+
+/// Names of the syntax highlighting themes bundled with syntect, sorted
+pub fn syntax_themes() -> Vec<String> {
+    // BTreeMap keys, so already sorted
+    ThemeSet::load_defaults().themes.into_keys().collect()
+}
+
+/// Name of the configured `syntax_theme` if syntect has it, [`SYNTAX_THEME`] otherwise
+pub fn syntax_theme_name(config: &AppConfig) -> String {
+    let themes = ThemeSet::load_defaults();
+    theme_name(&themes, config).to_string()
+}
+
+fn theme_name<'a>(themes: &ThemeSet, config: &'a AppConfig) -> &'a str {
+    let Some(name) = config.syntax_theme.as_deref() else {
+        return SYNTAX_THEME;
+    };
+
+    if themes.themes.contains_key(name) {
+        name
+    } else {
+        tracing::warn!("Unknown syntax_theme '{}', using {}", name, SYNTAX_THEME);
+        SYNTAX_THEME
+    }
+}
+
+/// The theme code blocks are highlighted with
+fn syntax_theme<'a>(themes: &'a ThemeSet, config: &AppConfig) -> &'a Theme {
+    &themes.themes[theme_name(themes, config)]
 }
 
-pub async fn generate_site(conversations_path: &str, output_dir: &str) -> Result<()> {
+pub async fn generate_site(conversations_path: &str, output_dir: &str, config: &AppConfig) -> Result<()> {
     tracing::info!("📚 Reading conversations from {}", conversations_path);
     
-    let data = tokio::fs::read_to_string(conversations_path).await?;
-    let conversations: Vec<Conversation> = serde_json::from_str(&data)?;
+    let (_, conversations): (_, Vec<Conversation>) = super::export::load_export(conversations_path).await?;
     
     tracing::info!("Found {} conversations", conversations.len());
 
-    // Create output directories
+    // A rename only needs the renamed conversations' pages and the index
     let output_path = Path::new(output_dir);
-    fs::create_dir_all(output_path.join("conversations"))?;
-    fs::create_dir_all(output_path.join("assets/css"))?;
-    fs::create_dir_all(output_path.join("assets/js"))?;
+    let manifest = GenerationManifest::new(&conversations, config)?;
+    let previous = manifest::load(output_path);
+    if let Some(renamed) = previous.as_ref().and_then(|previous| manifest.renamed_since(previous)) {
+        if !renamed.is_empty() && output_path.join("index.html").exists() {
+            update_titles(&conversations, &renamed, output_path, config)?;
+            manifest::save(output_path, &manifest)?;
+            return Ok(());
+        }
+    }
+
+    // Create output directories
+    create_dir(&output_path.join("conversations"))?;
+    create_dir(&output_path.join("assets/css"))?;
+    create_dir(&output_path.join("assets/js"))?;
+    // First, so pages can link to the assets by content hash
+    let assets = copy_static_assets(output_path, config)?;
+
+    // Archive sections are written here while pages are generated in parallel
+    let sections_dir = output_path.join(ARCHIVE_DIR).join(ARCHIVE_SECTIONS_DIR);
+    let _ = fs::remove_dir_all(&sections_dir);
+    create_dir(&sections_dir)?;
 
     // Initialize syntax highlighting
     let ps = SyntaxSet::load_defaults_newlines();
     let ts = ThemeSet::load_defaults();
-    let theme = &ts.themes["base16-ocean.light"];
+    let theme = syntax_theme(&ts, config);
+
+    // Assign short ids oldest-first so existing links stay stable
+    let mut ids_by_age: Vec<(Option<DateTime<Utc>>, &str)> = conversations
+        .iter()
+        .map(|c| (parse_datetime(&c.inserted_at), c.id.as_str()))
+        .collect();
+    ids_by_age.sort();
+    let ids_by_age: Vec<&str> = ids_by_age.into_iter().map(|(_, id)| id).collect();
+    let short_links = shortlinks::assign(&shortlinks::load(output_path), &ids_by_age);
+    let short_ids: HashMap<&str, &str> = short_links
+        .iter()
+        .map(|(short, id)| (id.as_str(), short.as_str()))
+        .collect();
+
+    // Dates are shown in the configured timezone
+    let tz = config.timezone();
+    let date_format = config.date_format();
 
     // Generate sidebar HTML once (shared across all pages)
-    let sidebar_html = generate_sidebar_html(&conversations);
+    let sidebar_html = generate_sidebar_html(&conversations, config);
+    let page_context = PageContext {
+        output_path,
+        ps: &ps,
+        theme,
+        config,
+        tz,
+        date_format,
+        assets: &assets,
+        sidebar_html: &sidebar_html,
+    };
     
     // Generate conversation pages in PARALLEL! 🚀
     let counter = Arc::new(Mutex::new(0usize));
     let total = conversations.len();
     
-    let all_conversations: Vec<ConversationMeta> = conversations
-        .par_iter()
-        .filter_map(|conv| {
-            // Progress counter
-            {
-                let mut count = counter.lock().unwrap();
-                *count += 1;
-                if *count % 100 == 0 {
-                    tracing::info!("Generated {}/{} pages", *count, total);
+    let all_conversations: Vec<ConversationMeta> = with_threads(config.generation_threads, || conversations
+            .par_iter()
+            .filter_map(|conv| {
+                // Progress counter
+                {
+                    let mut count = counter.lock().unwrap();
+                    *count += 1;
+                    if *count % 100 == 0 {
+                        tracing::info!("Generated {}/{} pages", *count, total);
+                    }
                 }
-            }
 
-            let conv_id = &conv.id;
-            let title = conv.title.as_deref().unwrap_or("Untitled");
-            let inserted_at = parse_datetime(&conv.inserted_at);
-            let updated_at = parse_datetime(&conv.updated_at);
-
-            // Extract and render messages
-            let messages = match extract_and_render_messages(&conv.mapping, &ps, theme) {
-                Ok(m) => m,
-                Err(e) => {
-                    tracing::warn!("Failed to process conversation {}: {}", conv_id, e);
-                    return None;
-                }
-            };
-            
-            // Generate conversation page
-            let conversation_html = match (ConversationTemplate {
-                title,
-                inserted_at,
-                updated_at,
-                message_count: messages.len(),
-                messages: &messages,
-            }).render() {
-                Ok(h) => h,
-                Err(e) => {
-                    tracing::warn!("Failed to render conversation {}: {}", conv_id, e);
-                    return None;
-                }
-            };
+                let conv_id = &conv.id;
+                let short_id = short_ids.get(conv_id.as_str()).copied().unwrap_or(conv_id);
+                // Extracted once, for the pages, the archive section and the index
+                let extracted = model::extract_messages(&conv.mapping);
+                let (messages, tag_cloud) = match write_conversation_pages(conv, &extracted, short_id, &page_context) {
+                    Ok(rendered) => rendered,
+                    Err(e) => {
+                        tracing::warn!("Skipping conversation {}: {:#}", conv_id, e);
+                        return None;
+                    }
+                };
 
-            let page_html = match (BaseTemplate {
-                title,
-                content: conversation_html,
-                conversations_html: sidebar_html.clone(),
-            }).render() {
-                Ok(h) => h,
-                Err(e) => {
-                    tracing::warn!("Failed to render page {}: {}", conv_id, e);
-                    return None;
+                // The whole conversation on one page, inlined in the archive later;
+                // its pages are written, so it stays listed even if this fails
+                let section = archive_section(conv, &extracted, messages, &tag_cloud, short_id, &page_context)
+                    .and_then(|section_html| write_file(&sections_dir.join(format!("{}.html", conv_id)), section_html));
+                if let Err(e) = section {
+                    tracing::warn!("Leaving conversation {} out of the archive: {:#}", conv_id, e);
                 }
-            };
-
-            // Write to file
-            let conv_dir = output_path.join("conversations").join(conv_id);
-            if let Err(e) = fs::create_dir_all(&conv_dir) {
-                tracing::warn!("Failed to create dir for {}: {}", conv_id, e);
-                return None;
-            }
-            if let Err(e) = fs::write(conv_dir.join("index.html"), page_html) {
-                tracing::warn!("Failed to write file for {}: {}", conv_id, e);
-                return None;
-            }
 
-            // Return metadata
-            Some(ConversationMeta {
-                id: conv_id.clone(),
-                title: title.to_string(),
-                url: format!("/conversations/{}/", conv_id),
-                inserted_at,
+                // Return metadata
+                Some(ConversationMeta {
+                    id: conv_id.clone(),
+                    title: conv.display_title_from(&extracted, config),
+                    preview: index_preview(&extracted, config),
+                    url: format!("/conversations/{}/", conv_id),
+                    inserted_at: parse_datetime(&conv.inserted_at).map(|dt| dt.with_timezone(&tz)),
+                })
             })
-        })
-        .collect();
+        .collect())?;
 
-    // Generate index page
-    let conversations_by_month = group_by_month(&all_conversations);
+    write_index_page(output_path, conversations.len(), &all_conversations, &sidebar_html, &assets, config)?;
+    shortlinks::save(output_path, &short_links)?;
+
+    write_archive_pages(output_path, &all_conversations, &sidebar_html, &assets, config)?;
+    write_settings_page(output_path, &sidebar_html, &assets, config)?;
+    with_threads(config.generation_threads, || precompress(output_path, config.precompress))??;
+    // Last, so an interrupted run is never taken for a complete one
+    manifest::save(output_path, &manifest)?;
+
+    tracing::info!("✅ Generated {} conversation pages", conversations.len());
+
+    Ok(())
+}
+
+fn write_index_page(
+    output_path: &Path,
+    total_conversations: usize,
+    conversations: &[ConversationMeta],
+    sidebar_html: &str,
+    assets: &AssetVersions,
+    config: &AppConfig,
+) -> Result<()> {
     let index_content = IndexTemplate {
-        total_conversations: conversations.len(),
-        conversations_by_month: conversations_by_month.clone(),
+        total_conversations: locale::format_count(total_conversations, config.language),
+        conversations_by_month: group_by_month(conversations, config.language),
     }.render()?;
 
-    let conversations_html = generate_sidebar_html(&conversations);
     let index_page = BaseTemplate {
         title: "Главная",
+        client_math: config.math_render == MathRender::Client,
+        offline_pages: config.offline_pages,
+        body_font: config.body_font_stack(),
+        code_font: config.code_font_stack(),
+        assets,
         content: index_content,
-        conversations_html,
+        conversations_html: sidebar_html.to_string(),
     }.render()?;
 
-    fs::write(output_path.join("index.html"), index_page)?;
+    write_page(&output_path.join("index.html"), index_page, config)
+}
 
-    // Copy CSS (simplified version from Jekyll)
-    copy_static_assets(output_path)?;
+/// The settings form at `/settings/`; only the server can save it
+fn write_settings_page(output_path: &Path, sidebar_html: &str, assets: &AssetVersions, config: &AppConfig) -> Result<()> {
+    let settings_content = SettingsTemplate {
+        body_fonts: BODY_FONTS.iter().map(|(name, _)| *name).collect(),
+        code_fonts: CODE_FONTS.iter().map(|(name, _)| *name).collect(),
+        syntax_themes: syntax_themes(),
+        script: assets.url("/assets/js/settings.js"),
+    }.render()?;
 
-    tracing::info!("✅ Generated {} conversation pages", conversations.len());
+    let settings_page = BaseTemplate {
+        title: "Настройки",
+        client_math: false,
+        offline_pages: config.offline_pages,
+        body_font: config.body_font_stack(),
+        code_font: config.code_font_stack(),
+        assets,
+        content: settings_content,
+        conversations_html: sidebar_html.to_string(),
+    }.render()?;
+
+    let settings_dir = output_path.join("settings");
+    create_dir(&settings_dir)?;
+    write_page(&settings_dir.join("index.html"), settings_page, config)
+}
+
+/// Rewrite the pages of the `renamed` conversations and the index page. In
+/// every other page only the sidebar is swapped for one with the new titles,
+/// and so are the titles of the renamed conversations' sections in `/all/`.
+fn update_titles(conversations: &[Conversation], renamed: &[&str], output_path: &Path, config: &AppConfig) -> Result<()> {
+    // Pages link to the assets already in the site
+    let assets = asset_versions(output_path)?;
+
+    let ps = SyntaxSet::load_defaults_newlines();
+    let ts = ThemeSet::load_defaults();
+    let tz = config.timezone();
+    let sidebar_html = generate_sidebar_html(conversations, config);
+    let page_context = PageContext {
+        output_path,
+        ps: &ps,
+        theme: syntax_theme(&ts, config),
+        config,
+        tz,
+        date_format: config.date_format(),
+        assets: &assets,
+        sidebar_html: &sidebar_html,
+    };
+
+    let short_links = shortlinks::load(output_path);
+    let mut metas = Vec::with_capacity(conversations.len());
+    for conv in conversations {
+        let messages = model::extract_messages(&conv.mapping);
+        if renamed.contains(&conv.id.as_str()) {
+            write_conversation_pages(conv, &messages, short_id(&short_links, &conv.id), &page_context)?;
+        }
+        metas.push(ConversationMeta {
+            id: conv.id.clone(),
+            title: conv.display_title_from(&messages, config),
+            preview: index_preview(&messages, config),
+            url: format!("/conversations/{}/", conv.id),
+            inserted_at: parse_datetime(&conv.inserted_at).map(|dt| dt.with_timezone(&tz)),
+        });
+    }
+    write_index_page(output_path, conversations.len(), &metas, &sidebar_html, &assets, config)?;
+
+    let titles: HashMap<&str, &str> = metas
+        .iter()
+        .filter(|meta| renamed.contains(&meta.id.as_str()))
+        .map(|meta| (meta.id.as_str(), meta.title.as_str()))
+        .collect();
+    for entry in walkdir::WalkDir::new(output_path) {
+        let entry = entry.with_context(|| format!("Failed to read {}", output_path.display()))?;
+        let path = entry.path();
+        if !entry.file_type().is_file() || path.extension().is_none_or(|ext| ext != "html") {
+            continue;
+        }
 
+        let html = read_file(path)?;
+        let mut updated = replace_sidebar(&html, &sidebar_html);
+        if path.starts_with(output_path.join(ARCHIVE_DIR)) {
+            for (id, title) in &titles {
+                updated = replace_section_title(&updated, id, title);
+            }
+        }
+        if updated != html {
+            write_file(path, updated)?;
+        }
+    }
+    with_threads(config.generation_threads, || precompress(output_path, config.precompress))??;
+
+    tracing::info!("✏️  Only titles changed: regenerated {} conversation(s) and the index", renamed.len());
     Ok(())
 }
 
-fn extract_and_render_messages(
-    mapping: &serde_json::Value,
+/// `html` with the conversation list in its sidebar replaced by `sidebar_html`
+fn replace_sidebar(html: &str, sidebar_html: &str) -> String {
+    const LIST_START: &str = r#"<div id="conversationsList">"#;
+    let Some(start) = html.find(LIST_START).map(|idx| idx + LIST_START.len()) else {
+        return html.to_string();
+    };
+    // The list and the element around it close right before the sidebar does
+    let end = html[start..]
+        .find("</aside>")
+        .and_then(|aside| html[start..start + aside].rfind("</div>"))
+        .and_then(|outer| html[start..start + outer].rfind("</div>"))
+        .map(|list_end| start + list_end);
+    match end {
+        Some(end) => format!("{}{}{}", &html[..start], sidebar_html, &html[end..]),
+        None => html.to_string(),
+    }
+}
+
+/// `html` with the heading of conversation `id`'s archive section set to `title`
+fn replace_section_title(html: &str, id: &str, title: &str) -> String {
+    let article = format!(
+        r#"<article class="conversation" data-conversation-id="{}">"#,
+        html_escape::encode_double_quoted_attribute(id)
+    );
+    let heading = html.find(&article).and_then(|idx| {
+        let start = idx + html[idx..].find("<h1>")? + "<h1>".len();
+        let end = start + html[start..].find("</h1>")?;
+        Some((start, end))
+    });
+    match heading {
+        Some((start, end)) => format!("{}{}{}", &html[..start], html_escape::encode_text(title), &html[end..]),
+        None => html.to_string(),
+    }
+}
+
+/// Short id of conversation `id`, or the id itself if it has none
+fn short_id<'a>(links: &'a ShortLinks, id: &'a str) -> &'a str {
+    links
+        .iter()
+        .find(|(_, conv_id)| conv_id.as_str() == id)
+        .map_or(id, |(short, _)| short.as_str())
+}
+
+/// Run `work`, and the parallel iterators in it, on a pool of `threads`
+/// threads, or on rayon's global pool (one thread per core) if it's 0.
+fn with_threads<T: Send>(threads: usize, work: impl FnOnce() -> T + Send) -> Result<T> {
+    if threads == 0 {
+        return Ok(work());
+    }
+    let pool = rayon::ThreadPoolBuilder::new()
+        .num_threads(threads)
+        .thread_name(|idx| format!("generate-{}", idx))
+        .build()
+        .context("Failed to start generation threads")?;
+    Ok(pool.install(work))
+}
+
+/// Everything rendering a conversation's pages needs besides the conversation
+struct PageContext<'a> {
+    output_path: &'a Path,
+    ps: &'a SyntaxSet,
+    theme: &'a Theme,
+    config: &'a AppConfig,
+    tz: Tz,
+    date_format: &'a str,
+    assets: &'a AssetVersions,
+    sidebar_html: &'a str,
+}
+
+/// Render `conv`, whose messages are already `extracted`, and write its pages
+/// (and `raw.md`, if enabled) to `conversations/{id}/`. Returns the rendered
+/// messages and tag cloud for the archive section.
+fn write_conversation_pages(
+    conv: &Conversation,
+    extracted: &[model::ExtractedMessage],
+    short_id: &str,
+    ctx: &PageContext,
+) -> Result<(Vec<Message>, Vec<CloudTerm>)> {
+    let config = ctx.config;
+    let conv_id = &conv.id;
+    let title = conv.display_title_from(extracted, config);
+    let title = title.as_str();
+    let inserted_at = parse_datetime(&conv.inserted_at).map(|dt| dt.with_timezone(&ctx.tz));
+    let updated_at = parse_datetime(&conv.updated_at).map(|dt| dt.with_timezone(&ctx.tz));
+
+    // Extract and render messages
+    let mut messages = render_messages(conv, extracted, ctx.ps, ctx.theme, config)
+        .context("Failed to process messages")?;
+
+    let conv_dir = ctx.output_path.join("conversations").join(conv_id);
+    create_dir(&conv_dir)?;
+    remove_extra_pages(&conv_dir);
+    write_large_images(&mut messages, &conv_dir, conv_id, config.inline_image_max_bytes)?;
+
+    let raw_path = conv_dir.join(markdown_export::RAW_MARKDOWN_FILE);
+    if config.write_raw_markdown {
+        write_file(&raw_path, markdown_export::conversation_to_markdown(conv))?;
+    } else if raw_path.exists() {
+        // Left over from a run with the option enabled
+        let _ = fs::remove_file(&raw_path);
+    }
+
+    let tag_cloud = tag_cloud(extracted, config);
+    let attachments = attachment_index(conv, config);
+    let word_count = word_count(extracted);
+
+    // Long conversations are split into several pages
+    let per_page = config.messages_per_page;
+    let pages: Vec<&[Message]> = if per_page == 0 || messages.len() <= per_page {
+        vec![&messages]
+    } else {
+        messages.chunks(per_page).collect()
+    };
+    let page_count = pages.len();
+
+    for (idx, page_messages) in pages.iter().enumerate() {
+        let page = idx + 1;
+        let code_index = if config.code_index { code_index(&pages, page) } else { Vec::new() };
+
+        let conversation_html = ConversationTemplate {
+            title,
+            conversation_id: conv_id,
+            short_id: Some(short_id),
+            date_format: ctx.date_format,
+            inserted_at,
+            updated_at,
+            message_count: messages.len(),
+            word_count,
+            tags: &conv.tags,
+            messages: page_messages,
+            page,
+            page_count,
+            prev_url: (page > 1).then(|| page_link(page - 1)),
+            next_url: (page < page_count).then(|| page_link(page + 1)),
+            has_raw_markdown: config.write_raw_markdown,
+            tag_cloud: &tag_cloud,
+            code_index: &code_index,
+            attachments: &attachments,
+        }.render().context("Failed to render conversation")?;
+
+        let page_html = BaseTemplate {
+            title,
+            client_math: config.math_render == MathRender::Client,
+            offline_pages: config.offline_pages,
+            body_font: config.body_font_stack(),
+            code_font: config.code_font_stack(),
+            assets: ctx.assets,
+            content: conversation_html,
+            conversations_html: ctx.sidebar_html.to_string(),
+        }.render().context("Failed to render page")?;
+
+        write_page(&conv_dir.join(page_file_name(page)), page_html, config)?;
+    }
+
+    Ok((messages, tag_cloud))
+}
+
+/// Re-render the pages of conversation `id` in a site generated before, and
+/// its section in `/all/`, leaving every other file alone: the index and the
+/// other conversations' pages and sections keep showing what they did. Much
+/// faster than `generate_site` when checking a rendering change on one
+/// conversation. Returns `false` if the export has no conversation with this id.
+pub async fn regenerate_one(conversations_path: &str, output_dir: &str, id: &str, config: &AppConfig) -> Result<bool> {
+    let (_, conversations): (_, Vec<Conversation>) = super::export::load_export(conversations_path).await?;
+
+    // Highlighting and writing the pages would hold up the async runtime
+    let (output_dir, id, config) = (output_dir.to_string(), id.to_string(), config.clone());
+    tokio::task::spawn_blocking(move || write_one(&conversations, Path::new(&output_dir), &id, &config)).await?
+}
+
+/// [`regenerate_one`] once the export is loaded
+fn write_one(conversations: &[Conversation], output_path: &Path, id: &str, config: &AppConfig) -> Result<bool> {
+    let Some(conv) = conversations.iter().find(|c| c.id == id) else {
+        return Ok(false);
+    };
+
+    // Pages link to the assets already in the site
+    let assets = asset_versions(output_path)?;
+
+    let ps = SyntaxSet::load_defaults_newlines();
+    let ts = ThemeSet::load_defaults();
+    let tz = config.timezone();
+    let sidebar_html = generate_sidebar_html(conversations, config);
+    let page_context = PageContext {
+        output_path,
+        ps: &ps,
+        theme: syntax_theme(&ts, config),
+        config,
+        tz,
+        date_format: config.date_format(),
+        assets: &assets,
+        sidebar_html: &sidebar_html,
+    };
+
+    let short_links = shortlinks::load(output_path);
+    let short_id = short_id(&short_links, id);
+    let extracted = model::extract_messages(&conv.mapping);
+    let (messages, tag_cloud) = write_conversation_pages(conv, &extracted, short_id, &page_context)?;
+    let section_html = archive_section(conv, &extracted, messages, &tag_cloud, short_id, &page_context)?;
+    replace_archive_section(output_path, id, &section_html, config)?;
+    // Only the rewritten pages differ from their `.br` copies
+    with_threads(config.generation_threads, || precompress(output_path, config.precompress))??;
+    tracing::info!("✅ Regenerated conversation {}", id);
+
+    Ok(true)
+}
+
+/// The section of `conv` in `/all/`: the whole conversation, from the
+/// `messages` rendered for its pages. Their ids, and the links to them, get
+/// [`archive_id_prefix`], as they'd clash between the conversations on one
+/// archive page otherwise.
+fn archive_section(
+    conv: &Conversation,
+    extracted: &[model::ExtractedMessage],
+    mut messages: Vec<Message>,
+    tag_cloud: &[CloudTerm],
+    short_id: &str,
+    ctx: &PageContext,
+) -> Result<String> {
+    let prefix = archive_id_prefix(&conv.id);
+    for message in &mut messages {
+        message.content_html = prefix_ids(&message.content_html, &prefix);
+        message.anchor = format!("{}{}", prefix, message.anchor);
+        for block in &mut message.code_blocks {
+            block.id = format!("{}{}", prefix, block.id);
+        }
+    }
+
+    let config = ctx.config;
+    let code_index = if config.code_index { code_index(&[&messages], 1) } else { Vec::new() };
+    ConversationTemplate {
+        title: &conv.display_title_from(extracted, config),
+        conversation_id: &conv.id,
+        short_id: Some(short_id),
+        date_format: ctx.date_format,
+        inserted_at: parse_datetime(&conv.inserted_at).map(|dt| dt.with_timezone(&ctx.tz)),
+        updated_at: parse_datetime(&conv.updated_at).map(|dt| dt.with_timezone(&ctx.tz)),
+        message_count: messages.len(),
+        word_count: word_count(extracted),
+        tags: &conv.tags,
+        messages: &messages,
+        page: 1,
+        page_count: 1,
+        prev_url: None,
+        next_url: None,
+        has_raw_markdown: false,
+        tag_cloud,
+        code_index: &code_index,
+        attachments: &[],
+    }
+    .render()
+    .context("Failed to render archive section")
+}
+
+/// Swap the section of conversation `id` in the `/all/` page showing it for
+/// `section_html`; nothing to do if the archive doesn't have it
+fn replace_archive_section(output_path: &Path, id: &str, section_html: &str, config: &AppConfig) -> Result<()> {
+    const SECTION_START: &str = r#"<section class="archive-section">"#;
+    const ARTICLE_END: &str = "</article>";
+    let article = format!(
+        r#"<article class="conversation" data-conversation-id="{}">"#,
+        html_escape::encode_double_quoted_attribute(id)
+    );
+    let Ok(entries) = fs::read_dir(output_path.join(ARCHIVE_DIR)) else {
+        return Ok(());
+    };
+
+    for entry in entries.flatten() {
+        let path = entry.path();
+        if path.extension().is_none_or(|ext| ext != "html") {
+            continue;
+        }
+        let html = read_file(&path)?;
+        let Some(start) = html.find(&article) else {
+            continue;
+        };
+        // The section ends with the last `</article>` before the next one starts
+        let next = html[start..].find(SECTION_START).map_or(html.len(), |idx| start + idx);
+        let Some(end) = html[start..next].rfind(ARTICLE_END).map(|idx| start + idx + ARTICLE_END.len()) else {
+            continue;
+        };
+        let section_html = if config.minify { minify::minify_html(section_html) } else { section_html.to_string() };
+        return write_file(&path, format!("{}{}{}", &html[..start], section_html, &html[end..]));
+    }
+    Ok(())
+}
+
+/// `html` with `prefix` put in front of every `id` in its tags and of every
+/// link to one (`href="#..."`). Text can't be mistaken for a tag: it has its
+/// `<` escaped, and attribute values their `"`.
+fn prefix_ids(html: &str, prefix: &str) -> String {
+    let id = format!(r#" id="{}"#, prefix);
+    let href = format!(r##" href="#{}"##, prefix);
+
+    let mut prefixed = String::with_capacity(html.len());
+    let mut rest = html;
+    while let Some(start) = rest.find('<') {
+        prefixed.push_str(&rest[..start]);
+        let tag = &rest[start..];
+        let mut in_value = false;
+        let end = tag
+            .char_indices()
+            .find(|&(_, c)| {
+                if c == '"' {
+                    in_value = !in_value;
+                }
+                c == '>' && !in_value
+            })
+            .map_or(tag.len(), |(idx, _)| idx + 1);
+        prefixed.push_str(&tag[..end].replace(r#" id=""#, &id).replace(r##" href="#"##, &href));
+        rest = &tag[end..];
+    }
+    prefixed.push_str(rest);
+    prefixed
+}
+
+/// Write the pages of `/all/`, each with up to `archive_page_conversations`
+/// conversations inlined, newest first, from the sections written while
+/// generating the conversation pages. Only one page is held in memory at a time.
+fn write_archive_pages(
+    output_path: &Path,
+    conversations: &[ConversationMeta],
+    sidebar_html: &str,
+    assets: &AssetVersions,
+    config: &AppConfig,
+) -> Result<()> {
+    let archive_dir = output_path.join(ARCHIVE_DIR);
+    let sections_dir = archive_dir.join(ARCHIVE_SECTIONS_DIR);
+    remove_extra_pages(&archive_dir);
+
+    let mut newest_first: Vec<&ConversationMeta> = conversations.iter().collect();
+    newest_first.sort_by_key(|conv| std::cmp::Reverse(conv.inserted_at));
+
+    let per_page = match config.archive_page_conversations {
+        0 => newest_first.len().max(1),
+        n => n,
+    };
+    let chunks: Vec<&[&ConversationMeta]> = if newest_first.is_empty() {
+        vec![&[]]
+    } else {
+        newest_first.chunks(per_page).collect()
+    };
+    let page_count = chunks.len();
+
+    for (idx, chunk) in chunks.into_iter().enumerate() {
+        let page = idx + 1;
+        let sections: Vec<ArchiveSection> = chunk
+            .iter()
+            .filter_map(|conv| {
+                let content_html = fs::read_to_string(sections_dir.join(format!("{}.html", conv.id))).ok()?;
+                Some(ArchiveSection { url: conv.url.clone(), content_html })
+            })
+            .collect();
+
+        let content = ArchiveTemplate {
+            total_conversations: locale::format_count(conversations.len(), config.language),
+            sections: &sections,
+            page,
+            page_count,
+            prev_url: (page > 1).then(|| page_link(page - 1)),
+            next_url: (page < page_count).then(|| page_link(page + 1)),
+        }.render()?;
+
+        let page_html = BaseTemplate {
+            title: "Все беседы",
+            client_math: config.math_render == MathRender::Client,
+            offline_pages: config.offline_pages,
+            body_font: config.body_font_stack(),
+            code_font: config.code_font_stack(),
+            assets,
+            content,
+            conversations_html: sidebar_html.to_string(),
+        }.render()?;
+
+        write_page(&archive_dir.join(page_file_name(page)), page_html, config)?;
+    }
+
+    if let Err(e) = fs::remove_dir_all(&sections_dir) {
+        tracing::warn!("Failed to remove {}: {}", sections_dir.display(), e);
+    }
+
+    tracing::info!("📜 Wrote {} archive page(s) to {}", page_count, archive_dir.display());
+    Ok(())
+}
+
+/// The fenced code blocks of a conversation's responses, numbered as on its
+/// page when rendered with `config`
+pub fn extract_code_blocks(conv: &Conversation, config: &AppConfig) -> Result<Vec<CodeBlock>> {
+    let ps = SyntaxSet::load_defaults_newlines();
+    let ts = ThemeSet::load_defaults();
+    let messages = render_messages(
+        conv,
+        &model::extract_messages(&conv.mapping),
+        &ps,
+        syntax_theme(&ts, config),
+        config,
+    )?;
+    let mut blocks: Vec<CodeBlock> = messages.into_iter().flat_map(|message| message.code_blocks).collect();
+    // Pages may show the newest message first
+    blocks.sort_by_key(|block| block.number);
+    Ok(blocks)
+}
+
+/// Render one conversation as a single self-contained HTML page: styles and
+/// scripts are inlined and formulas are converted to MathML, so the file can
+/// be emailed or archived and still display correctly offline.
+pub fn render_standalone(conv: &Conversation, config: &AppConfig) -> Result<String> {
+    let ps = SyntaxSet::load_defaults_newlines();
+    let ts = ThemeSet::load_defaults();
+    let theme = syntax_theme(&ts, config);
+    let tz = config.timezone();
+
+    // There's no KaTeX on a standalone page, so client-side math is done here
+    let render_config = match config.math_render {
+        MathRender::Client => AppConfig { math_render: MathRender::Server, ..config.clone() },
+        _ => config.clone(),
+    };
+    let extracted = model::extract_messages(&conv.mapping);
+    let messages = render_messages(conv, &extracted, &ps, theme, &render_config)?;
+
+    let title = conv.display_title_from(&extracted, config);
+    let tag_cloud = tag_cloud(&extracted, config);
+    let code_index = if config.code_index { code_index(&[&messages], 1) } else { Vec::new() };
+    let attachments = attachment_index(conv, config);
+    let content = ConversationTemplate {
+        title: &title,
+        conversation_id: &conv.id,
+        short_id: None,
+        date_format: config.date_format(),
+        inserted_at: parse_datetime(&conv.inserted_at).map(|dt| dt.with_timezone(&tz)),
+        updated_at: parse_datetime(&conv.updated_at).map(|dt| dt.with_timezone(&tz)),
+        message_count: messages.len(),
+        word_count: word_count(&extracted),
+        tags: &conv.tags,
+        messages: &messages,
+        page: 1,
+        page_count: 1,
+        prev_url: None,
+        next_url: None,
+        has_raw_markdown: false,
+        tag_cloud: &tag_cloud,
+        code_index: &code_index,
+        attachments: &attachments,
+    }.render()?;
+
+    let styles = format!("{}\n{}", include_str!("../static/main.css"), syntax_css(theme)?);
+
+    Ok(StandaloneTemplate {
+        title: &title,
+        body_font: config.body_font_stack(),
+        code_font: config.code_font_stack(),
+        styles: &styles,
+        scripts: concat!(
+            include_str!("../static/code-actions.js"),
+            "\n",
+            include_str!("../static/conversation-actions.js"),
+            "\n",
+            include_str!("../static/scroll.js"),
+        ),
+        content,
+    }.render()?)
+}
+
+/// The `tag_cloud_terms` most frequent words of a conversation's `messages`, alphabetically
+fn tag_cloud(messages: &[model::ExtractedMessage], config: &AppConfig) -> Vec<CloudTerm> {
+    if config.tag_cloud_terms == 0 {
+        return Vec::new();
+    }
+
+    let terms = terms::top_terms(messages, config.tag_cloud_terms);
+    let max_count = terms.first().map_or(1, |t| t.count);
+
+    let mut cloud: Vec<CloudTerm> = terms
+        .into_iter()
+        .map(|t| CloudTerm {
+            weight: (t.count * 5).div_ceil(max_count),
+            term: t.term,
+            count: t.count,
+        })
+        .collect();
+    cloud.sort_by(|a, b| a.term.cmp(&b.term));
+
+    cloud
+}
+
+/// File holding page `page` (1-based) of a conversation
+fn page_file_name(page: usize) -> String {
+    if page == 1 {
+        "index.html".to_string()
+    } else {
+        format!("page-{}.html", page)
+    }
+}
+
+/// Link to page `page` from another page of the same conversation
+fn page_link(page: usize) -> String {
+    if page == 1 {
+        "./".to_string()
+    } else {
+        page_file_name(page)
+    }
+}
+
+/// Site path of a conversation's page, if it was generated into `output_dir`
+pub fn conversation_path(output_dir: &Path, id: &str) -> Option<String> {
+    // Ids become directory names, so one that could point outside `conversations/` can't exist
+    if id.is_empty() || id == "." || id == ".." || id.contains(['/', '\\']) {
+        return None;
+    }
+
+    output_dir
+        .join("conversations")
+        .join(id)
+        .join("index.html")
+        .is_file()
+        .then(|| format!("/conversations/{}/", id))
+}
+
+/// Drop `page-N.html` files left over from a previous run, when the
+/// conversation (or `messages_per_page`) may have produced more pages.
+fn remove_extra_pages(conv_dir: &Path) {
+    let Ok(entries) = fs::read_dir(conv_dir) else {
+        return;
+    };
+
+    for entry in entries.flatten() {
+        let name = entry.file_name();
+        let name = name.to_string_lossy();
+        if name.starts_with("page-") && name.ends_with(".html") {
+            if let Err(e) = fs::remove_file(entry.path()) {
+                tracing::warn!("Failed to remove stale page {}: {}", entry.path().display(), e);
+            }
+        }
+    }
+}
+
+/// Render `messages`, as extracted from `conv`, to HTML
+fn render_messages(
+    conv: &Conversation,
+    messages: &[model::ExtractedMessage],
     ps: &SyntaxSet,
     theme: &syntect::highlighting::Theme,
+    config: &AppConfig,
 ) -> Result<Vec<Message>> {
-    let mut messages = Vec::new();
-    
-    if let Some(mapping_obj) = mapping.as_object() {
-        if let Some(root) = mapping_obj.get("root") {
-            if let Some(children) = root.get("children").and_then(|c| c.as_array()) {
-                extract_messages_recursive(mapping_obj, children, &mut messages, ps, theme)?;
+    let markdown = config.markdown_options();
+    let tz = config.timezone();
+    let (messages, _) = display_messages(messages.to_vec(), config);
+    let anchors = message_anchors(&messages);
+
+    // Code blocks are numbered across the conversation, for the code index
+    let mut code_count = 0;
+    let mut rendered = messages
+        .into_iter()
+        .zip(anchors)
+        .enumerate()
+        .map(|(index, (message, anchor))| {
+            let author = author(message.role, config);
+            let (mut content_html, code_blocks) = if message.role == Role::User {
+                // Simple HTML escape for requests
+                (html_escape::encode_text(&message.content).replace('\n', "<br>"), Vec::new())
+            } else {
+                // Render markdown for responses
+                let rendered = render_markdown(
+                    &message.content,
+                    &format!("m{}", index),
+                    code_count + 1,
+                    markdown,
+                    ps,
+                    theme,
+                )?;
+                if rendered.truncated {
+                    tracing::warn!(
+                        "✂️  Message {} of conversation {} is nested more than {} levels deep, cutting it off there",
+                        index + 1,
+                        conv.id,
+                        MAX_MARKDOWN_DEPTH
+                    );
+                }
+                (rendered.html, rendered.code_blocks)
+            };
+            code_count += code_blocks.len();
+            // Requests can hold formulas too, so this runs on the finished HTML
+            if config.math_render == MathRender::Server {
+                content_html = math::render_offline(&content_html);
             }
+
+            Ok(Message {
+                tool_label: tool_label(&message.fragment_type),
+                message_type: message.fragment_type,
+                content_html,
+                inserted_at: message.inserted_at.map(|dt| dt.with_timezone(&tz)),
+                code_blocks,
+                author,
+                anchor,
+                images: message
+                    .images
+                    .into_iter()
+                    .map(|image| MessageImage { src: image.data_url, alt: image.alt })
+                    .collect(),
+            })
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    // Rendered in order first, so footnotes and code blocks are numbered the same either way
+    if config.newest_first {
+        rendered.reverse();
+    }
+    Ok(rendered)
+}
+
+/// The messages of a conversation as its pages show them, from `messages`
+/// as [`model::extract_messages`] returns them, and for each of those the
+/// position of the message it is shown in
+fn display_messages(
+    mut messages: Vec<model::ExtractedMessage>,
+    config: &AppConfig,
+) -> (Vec<model::ExtractedMessage>, Vec<usize>) {
+    // Tool fragments whose data isn't in `content` show it as markdown
+    for message in &mut messages {
+        if let Some(markdown) = message.raw.as_ref().and_then(tool_fragment_markdown) {
+            message.content = markdown;
+        }
+    }
+
+    // A streamed response can be split into several fragments of one message;
+    // render them together so tables, lists and code blocks stay whole.
+    // Parts break anywhere, even mid-word, so they are joined as they are
+    let (mut messages, mut positions) = merge_runs(messages, false, |prev, next| {
+        next.role != Role::User && prev.node_id == next.node_id && prev.fragment_type == next.fragment_type
+    });
+    if config.merge_consecutive_messages {
+        // Same type too, so thinking doesn't end up inside the answer
+        let (merged, merged_positions) = merge_runs(messages, true, |prev, next| {
+            prev.role == next.role && prev.fragment_type == next.fragment_type
+        });
+        messages = merged;
+        for position in &mut positions {
+            *position = merged_positions[*position];
         }
     }
 
-    Ok(messages)
+    (messages, positions)
 }
 
-fn extract_messages_recursive(
-    mapping: &serde_json::Map<String, serde_json::Value>,
-    children: &[serde_json::Value],
-    messages: &mut Vec<Message>,
-    ps: &SyntaxSet,
-    theme: &syntect::highlighting::Theme,
-) -> Result<()> {
-    for child_id in children {
-        if let Some(child_id_str) = child_id.as_str() {
-            if let Some(child) = mapping.get(child_id_str) {
-                if let Some(message) = child.get("message") {
-                    if let Some(fragments) = message.get("fragments").and_then(|f| f.as_array()) {
-                        for fragment in fragments {
-                            let msg_type = fragment.get("type")
-                                .and_then(|t| t.as_str())
-                                .unwrap_or("UNKNOWN");
-                            
-                            let content = fragment.get("content")
-                                .and_then(|c| c.as_str())
-                                .unwrap_or("");
-
-                            let content_html = if msg_type == "REQUEST" {
-                                // Simple HTML escape for requests
-                                html_escape::encode_text(content).replace('\n', "<br>")
-                            } else {
-                                // Render markdown for responses
-                                render_markdown(content, ps, theme)?
-                            };
-
-                            let inserted_at = message.get("inserted_at")
-                                .and_then(|d| d.as_str())
-                                .and_then(|s| DateTime::parse_from_rfc3339(s).ok())
-                                .map(|dt| dt.with_timezone(&Utc));
-
-                            messages.push(Message {
-                                message_type: msg_type.to_string(),
-                                content_html,
-                                inserted_at,
-                            });
-                        }
-                    }
+/// Site path of each of `messages` (as [`model::extract_messages`] returns
+/// them for `conv`): the page of the conversation showing it and the anchor
+/// of the message it is part of
+pub fn message_urls(conv: &Conversation, messages: &[model::ExtractedMessage], config: &AppConfig) -> Vec<String> {
+    let (shown, positions) = display_messages(messages.to_vec(), config);
+    let anchors = message_anchors(&shown);
+
+    let per_page = config.messages_per_page;
+    positions
+        .into_iter()
+        .map(|position| {
+            let order = if config.newest_first { shown.len() - 1 - position } else { position };
+            let page = if per_page == 0 || shown.len() <= per_page { 1 } else { order / per_page + 1 };
+            let file = if page == 1 { String::new() } else { page_file_name(page) };
+            format!("/conversations/{}/{}#{}", conv.id, file, anchors[position])
+        })
+        .collect()
+}
+
+/// Markdown for a fragment whose data isn't in `content`: search `results`
+/// as a list of links with their snippets, a tool call's `name` and
+/// `arguments`, or a tool's `output`
+fn tool_fragment_markdown(fragment: &serde_json::Value) -> Option<String> {
+    let text = |item: &serde_json::Value, keys: &[&str]| {
+        keys.iter()
+            .filter_map(|key| item.get(*key).and_then(|v| v.as_str()))
+            .map(str::trim)
+            .find(|text| !text.is_empty())
+            .map(str::to_string)
+    };
+
+    if let Some(results) = fragment.get("results").and_then(|r| r.as_array()) {
+        let items: Vec<String> = results
+            .iter()
+            .filter_map(|result| {
+                let url = text(result, &["url", "link"]);
+                let title = text(result, &["title", "name"]).or_else(|| url.clone())?;
+                let mut item = match url {
+                    Some(url) => format!("- [{}](<{}>)", title.replace(['[', ']'], ""), url),
+                    None => format!("- {}", title),
+                };
+                if let Some(snippet) = text(result, &["snippet", "summary", "content"]) {
+                    item.push_str("\n  ");
+                    item.push_str(&snippet.split_whitespace().collect::<Vec<_>>().join(" "));
                 }
-                
-                if let Some(grandchildren) = child.get("children").and_then(|c| c.as_array()) {
-                    extract_messages_recursive(mapping, grandchildren, messages, ps, theme)?;
+                Some(item)
+            })
+            .collect();
+        return (!items.is_empty()).then(|| items.join("\n"));
+    }
+
+    // Arguments and output may be a string or any JSON
+    let value_text = |value: &serde_json::Value| match value {
+        serde_json::Value::String(text) => text.trim().to_string(),
+        serde_json::Value::Null => String::new(),
+        value => format!("```json\n{}\n```", serde_json::to_string_pretty(value).unwrap_or_default()),
+    };
+
+    let mut parts = Vec::new();
+    if let Some(name) = text(fragment, &["name", "tool", "function"]) {
+        parts.push(format!("`{}`", name));
+    }
+    for key in ["arguments", "input", "output", "result"] {
+        if let Some(value) = fragment.get(key).map(value_text).filter(|text| !text.is_empty()) {
+            parts.push(value);
+        }
+    }
+    (!parts.is_empty()).then(|| parts.join("\n\n"))
+}
+
+/// Label of the folded block showing a tool fragment (see [`model::is_tool_fragment`])
+fn tool_label(fragment_type: &str) -> Option<&'static str> {
+    match fragment_type {
+        "SEARCH" => Some("Результаты веб-поиска"),
+        "TOOL_CALL" => Some("Вызов инструмента"),
+        "TOOL_RESULT" => Some("Результат инструмента"),
+        _ => None,
+    }
+}
+
+/// Anchors of `messages`, in order (see [`message_anchor`])
+fn message_anchors(messages: &[model::ExtractedMessage]) -> Vec<String> {
+    let mut used = HashSet::new();
+    messages
+        .iter()
+        .map(|message| message_anchor(author_role(message.role), &message.content, &mut used))
+        .collect()
+}
+
+/// Anchor of a message, `m-` and a hash of its role and text, so links to it
+/// keep working when messages are added or removed elsewhere in the
+/// conversation. Repeats of an identical message get `-2`, `-3`, ...
+fn message_anchor(role: &str, content: &str, used: &mut HashSet<String>) -> String {
+    let base = format!("m-{}", content_hash(format!("{}\n{}", role, content).as_bytes()));
+    let mut anchor = base.clone();
+    let mut repeat = 1;
+    while !used.insert(anchor.clone()) {
+        repeat += 1;
+        anchor = format!("{}-{}", base, repeat);
+    }
+    anchor
+}
+
+/// Write embedded images over `max_bytes` to `{conv_dir}/assets/`, named by
+/// content hash, and point their `src` there, so they don't bloat the page
+/// (and the archive, which inlines it). Smaller ones stay `data:` URLs.
+fn write_large_images(messages: &mut [Message], conv_dir: &Path, conv_id: &str, max_bytes: usize) -> Result<()> {
+    use base64::Engine;
+
+    let assets_dir = conv_dir.join(IMAGE_ASSETS_DIR);
+    // Images of an earlier run that may be gone from the export by now
+    let _ = fs::remove_dir_all(&assets_dir);
+    if max_bytes == 0 {
+        return Ok(());
+    }
+
+    for image in messages.iter_mut().flat_map(|message| message.images.iter_mut()) {
+        if image.src.len() <= max_bytes {
+            continue;
+        }
+        let inline = model::InlineImage { data_url: std::mem::take(&mut image.src), alt: String::new() };
+        // Line breaks are allowed in base64 but not by the decoder
+        let data: String = inline.base64_data().chars().filter(|c| !c.is_ascii_whitespace()).collect();
+        let bytes = match base64::engine::general_purpose::STANDARD.decode(data) {
+            Ok(bytes) => bytes,
+            Err(e) => {
+                tracing::warn!("Keeping an undecodable image inline in {}: {}", conv_id, e);
+                image.src = inline.data_url;
+                continue;
+            }
+        };
+
+        let extension = match inline.mime_type().to_ascii_lowercase().as_str() {
+            "image/png" => "png",
+            "image/jpeg" | "image/jpg" => "jpg",
+            "image/gif" => "gif",
+            "image/webp" => "webp",
+            "image/svg+xml" => "svg",
+            _ => "img",
+        };
+        let name = format!("{}.{}", content_hash(&bytes), extension);
+        create_dir(&assets_dir)?;
+        write_file(&assets_dir.join(&name), &bytes)?;
+        image.src = format!("/conversations/{}/{}/{}", conv_id, IMAGE_ASSETS_DIR, name);
+    }
+
+    Ok(())
+}
+
+/// Header label and avatar for messages of `role`
+fn author(role: Role, config: &AppConfig) -> Author {
+    let label = config.role_label(role);
+    Author {
+        role: author_role(role),
+        label: label.to_string(),
+        avatar_url: config.role_avatar(role).map(str::to_string),
+        initials: initials(label),
+    }
+}
+
+/// How [`Author::role`] spells `role`
+fn author_role(role: Role) -> &'static str {
+    match role {
+        Role::User => "user",
+        Role::Assistant => "assistant",
+    }
+}
+
+/// First letters of the first two words, e.g. "DS" for "Deep Seek"
+fn initials(label: &str) -> String {
+    label
+        .split_whitespace()
+        .filter_map(|word| word.chars().find(|c| c.is_alphanumeric()))
+        .take(2)
+        .flat_map(char::to_uppercase)
+        .collect()
+}
+
+/// Join each run of messages for which `same_block(previous, next)` holds
+/// into one message, so markdown split between them renders as a whole.
+/// With `line_break`, a part not ending in a newline gets one before the
+/// next part; otherwise parts are concatenated exactly. Also returns, for
+/// each of `messages`, the position of the message it ended up in.
+fn merge_runs(
+    messages: Vec<model::ExtractedMessage>,
+    line_break: bool,
+    same_block: impl Fn(&model::ExtractedMessage, &model::ExtractedMessage) -> bool,
+) -> (Vec<model::ExtractedMessage>, Vec<usize>) {
+    let mut merged: Vec<model::ExtractedMessage> = Vec::with_capacity(messages.len());
+    let mut positions = Vec::with_capacity(messages.len());
+
+    for message in messages {
+        positions.push(match merged.last() {
+            Some(prev) if same_block(prev, &message) => merged.len() - 1,
+            _ => merged.len(),
+        });
+        match merged.last_mut() {
+            Some(prev) if same_block(prev, &message) => {
+                // Separate messages each end their last line
+                if line_break && !prev.content.ends_with('\n') && !message.content.is_empty() {
+                    prev.content.push('\n');
                 }
+                prev.content.push_str(&message.content);
+                prev.images.extend(message.images);
             }
+            _ => merged.push(message),
         }
     }
 
-    Ok(())
+    (merged, positions)
 }
 
-fn render_markdown(content: &str, ps: &SyntaxSet, theme: &syntect::highlighting::Theme) -> Result<String> {
+/// A response rendered to HTML
+struct RenderedMarkdown {
+    html: String,
+    code_blocks: Vec<CodeBlock>,
+    /// Nested deeper than [`MAX_MARKDOWN_DEPTH`]: the rest was left out
+    truncated: bool,
+}
+
+/// Render a response's markdown to HTML.
+///
+/// Footnote ids get `footnote_prefix` so several messages on one page don't
+/// link to each other's notes. Code blocks are anchored as `#code-N`,
+/// counting from `first_code_number`, and returned for the code index.
+fn render_markdown(
+    content: &str,
+    footnote_prefix: &str,
+    first_code_number: usize,
+    markdown: MarkdownOptions,
+    ps: &SyntaxSet,
+    theme: &syntect::highlighting::Theme,
+) -> Result<RenderedMarkdown> {
     // Конвертируем LaTeX триггеры в KaTeX формат
     let content = convert_latex_delimiters(content);
     
@@ -225,15 +1215,41 @@ fn render_markdown(content: &str, ps: &SyntaxSet, theme: &syntect::highlighting:
     options.insert(Options::ENABLE_STRIKETHROUGH);
     options.insert(Options::ENABLE_TABLES);
     options.insert(Options::ENABLE_TASKLISTS);
+    options.insert(Options::ENABLE_FOOTNOTES);
+    // Off by default, they change how existing text renders
+    options.set(Options::ENABLE_SMART_PUNCTUATION, markdown.smart_punctuation);
+    options.set(Options::ENABLE_HEADING_ATTRIBUTES, markdown.heading_attributes);
 
     let parser = Parser::new_ext(&content, options);
     
-    let mut html_output = String::new();
+    // Everything goes through one writer, which keeps track of tables and
+    // lists; code blocks and footnotes are swapped for our own HTML
+    let mut events = Vec::new();
     let mut in_code_block = false;
     let mut code_buffer = String::new();
     let mut code_lang = String::new();
+    let mut footnote_numbers: HashMap<String, usize> = HashMap::new();
+    let mut referenced_footnotes = HashSet::new();
+    let mut code_blocks = Vec::new();
+    // Ends of the elements open so far, to close them if the rest is cut off
+    let mut open = Vec::new();
+    let mut truncated = false;
 
     for event in parser {
+        match &event {
+            Event::Start(_) if open.len() == MAX_MARKDOWN_DEPTH => {
+                // The parser has already split the whole text into blocks;
+                // only the events of the rest (and their HTML) are skipped
+                truncated = true;
+                break;
+            }
+            Event::Start(tag) => open.push(tag.to_end()),
+            Event::End(_) => {
+                open.pop();
+            }
+            _ => {}
+        }
+
         match event {
             Event::Start(Tag::CodeBlock(CodeBlockKind::Fenced(lang))) => {
                 in_code_block = true;
@@ -256,11 +1272,23 @@ fn render_markdown(content: &str, ps: &SyntaxSet, theme: &syntect::highlighting:
                     // Escape code for data attribute
                     let escaped_code = html_escape::encode_double_quoted_attribute(&code_buffer);
                     
+                    let number = first_code_number + code_blocks.len();
+                    let id = format!("code-{}", number);
+                    code_blocks.push(CodeBlock {
+                        number,
+                        id: id.clone(),
+                        lang: code_lang.clone(),
+                        lines: code_buffer.lines().count(),
+                        code: code_buffer.clone(),
+                    });
+                    
                     // Wrap in div with highlight class and toolbar
-                    html_output.push_str(r#"<div class="code-block-wrapper">"#);
+                    let mut html_output = String::new();
+                    html_output.push_str(&format!(r#"<div class="code-block-wrapper" id="{}">"#, id));
                     html_output.push_str(r#"<div class="code-toolbar">"#);
                     html_output.push_str(&format!(r#"<span class="code-lang">{}</span>"#, code_lang));
                     html_output.push_str(r#"<div class="code-actions">"#);
+                    html_output.push_str(&format!(r##"<a class="code-btn code-link" href="#{}" title="Link to this block"><svg width="16" height="16" viewBox="0 0 16 16" fill="none"><path d="M6.5 9.5L9.5 6.5M7 4.5L8.25 3.25C9.35 2.15 11.15 2.15 12.25 3.25L12.75 3.75C13.85 4.85 13.85 6.65 12.75 7.75L11.5 9M9 11.5L7.75 12.75C6.65 13.85 4.85 13.85 3.75 12.75L3.25 12.25C2.15 11.15 2.15 9.35 3.25 8.25L4.5 7" stroke="currentColor" stroke-width="1.5" stroke-linecap="round"/></svg></a>"##, id));
                     html_output.push_str(r#"<button class="code-btn copy-btn" title="Copy code"><svg width="16" height="16" viewBox="0 0 16 16" fill="none"><path d="M4 4V2.5C4 1.67157 4.67157 1 5.5 1H13.5C14.3284 1 15 1.67157 15 2.5V10.5C15 11.3284 14.3284 12 13.5 12H12V13.5C12 14.3284 11.3284 15 10.5 15H2.5C1.67157 15 1 14.3284 1 13.5V5.5C1 4.67157 1.67157 4 2.5 4H4Z" stroke="currentColor" stroke-width="1.5"/></svg>Copy</button>"#);
                     html_output.push_str(r#"<button class="code-btn download-btn" title="Download code"><svg width="16" height="16" viewBox="0 0 16 16" fill="none"><path d="M8 1V11M8 11L11 8M8 11L5 8M2 11V13.5C2 14.3284 2.67157 15 3.5 15H12.5C13.3284 15 14 14.3284 14 13.5V11" stroke="currentColor" stroke-width="1.5" stroke-linecap="round" stroke-linejoin="round"/></svg>Download</button>"#);
                     html_output.push_str(r#"</div></div>"#);
@@ -268,28 +1296,113 @@ fn render_markdown(content: &str, ps: &SyntaxSet, theme: &syntect::highlighting:
                     html_output.push_str(r#"<div class="syntax">"#);
                     html_output.push_str(&highlighted);
                     html_output.push_str("</div></div></div>");
+                    events.push(Event::Html(html_output.into()));
                     
                     in_code_block = false;
                 }
             }
-            Event::Text(text) => {
-                if in_code_block {
-                    code_buffer.push_str(&text);
+            Event::Text(text) if in_code_block => {
+                code_buffer.push_str(&text);
+            }
+            Event::FootnoteReference(label) => {
+                let number = footnote_number(&mut footnote_numbers, &label);
+                let id = footnote_id(footnote_prefix, &label);
+                // The definition links back to the first reference
+                let anchor = if referenced_footnotes.insert(id.clone()) {
+                    format!(r#" id="fnref-{id}""#)
                 } else {
-                    html_output.push_str(&html_escape::encode_text(&text));
-                }
+                    String::new()
+                };
+                events.push(Event::InlineHtml(format!(
+                    r##"<sup class="footnote-reference"{anchor}><a href="#fn-{id}">{number}</a></sup>"##
+                ).into()));
+            }
+            Event::Start(Tag::FootnoteDefinition(label)) => {
+                let number = footnote_number(&mut footnote_numbers, &label);
+                let id = footnote_id(footnote_prefix, &label);
+                events.push(Event::Html(format!(
+                    r##"<div class="footnote-definition" id="fn-{id}"><sup class="footnote-definition-label"><a href="#fnref-{id}">{number}</a></sup>"##
+                ).into()));
+            }
+            Event::End(TagEnd::FootnoteDefinition) => {
+                events.push(Event::Html("</div>\n".into()));
             }
             other => {
                 if !in_code_block {
-                    let mut temp = String::new();
-                    html::push_html(&mut temp, std::iter::once(other));
-                    html_output.push_str(&temp);
+                    events.push(other);
                 }
             }
         }
     }
 
-    Ok(html_output)
+    if truncated {
+        events.extend(open.into_iter().rev().map(Event::End));
+        events.push(Event::Html(
+            r#"<p class="markdown-truncated">Дальше сообщение слишком глубоко вложено и не показано</p>"#.into(),
+        ));
+    }
+
+    let mut html_output = String::new();
+    html::push_html(&mut html_output, events.into_iter());
+    Ok(RenderedMarkdown { html: html_output, code_blocks, truncated })
+}
+
+/// Attachment names listed with the code index, if it's on
+fn attachment_index(conv: &Conversation, config: &AppConfig) -> Vec<String> {
+    if config.code_index {
+        model::extract_attachments(&conv.mapping)
+    } else {
+        Vec::new()
+    }
+}
+
+/// Words in the text of all of a conversation's `messages`
+fn word_count(messages: &[model::ExtractedMessage]) -> usize {
+    messages
+        .iter()
+        .map(|message| message.text().split_whitespace().count())
+        .sum()
+}
+
+/// Code index for page `current` of a conversation split into `pages`
+fn code_index(pages: &[&[Message]], current: usize) -> Vec<CodeIndexEntry> {
+    let mut entries = Vec::new();
+    for (idx, page_messages) in pages.iter().enumerate() {
+        let page = idx + 1;
+        for block in page_messages.iter().flat_map(|m| &m.code_blocks) {
+            let anchor = format!("#{}", block.id);
+            entries.push(CodeIndexEntry {
+                label: if block.lang.is_empty() { "код".to_string() } else { block.lang.clone() },
+                lines: block.lines,
+                url: if page == current { anchor } else { format!("{}{}", page_link(page), anchor) },
+            });
+        }
+    }
+    entries
+}
+
+/// Prefix of the ids in the archive section of conversation `conv_id`
+fn archive_id_prefix(conv_id: &str) -> String {
+    let id: String = conv_id
+        .chars()
+        .map(|c| if c.is_alphanumeric() || c == '-' || c == '_' { c } else { '-' })
+        .collect();
+    format!("c-{id}-")
+}
+
+/// Footnotes are numbered in the order they first appear
+fn footnote_number(numbers: &mut HashMap<String, usize>, label: &str) -> usize {
+    let next = numbers.len() + 1;
+    *numbers.entry(label.to_string()).or_insert(next)
+}
+
+/// Attribute-safe id of a footnote, unique on the page
+fn footnote_id(prefix: &str, label: &str) -> String {
+    let label: String = label
+        .chars()
+        .map(|c| if c.is_alphanumeric() || c == '-' || c == '_' { c } else { '-' })
+        .collect();
+    format!("{prefix}-{label}")
 }
 
 fn convert_latex_delimiters(content: &str) -> String {
@@ -313,56 +1426,43 @@ fn parse_datetime(date_str: &Option<String>) -> Option<DateTime<Utc>> {
         .map(|dt| dt.to_utc())
 }
 
-fn generate_sidebar_html(conversations: &[Conversation]) -> String {
+fn generate_sidebar_html(conversations: &[Conversation], config: &AppConfig) -> String {
+    let tz = config.timezone();
+    let language = config.language;
     let mut html = String::from(r#"<h3>Всего чатов: "#);
-    html.push_str(&conversations.len().to_string());
+    html.push_str(&locale::format_count(conversations.len(), language));
     html.push_str("</h3>");
 
     // Group by month for better organization
-    let mut conversations_by_month: HashMap<String, Vec<&Conversation>> = HashMap::new();
+    let mut conversations_by_month: HashMap<(i32, u32), Vec<&Conversation>> = HashMap::new();
     
     for conv in conversations {
         if let Some(date_str) = &conv.inserted_at {
             if let Ok(dt) = DateTime::parse_from_rfc3339(date_str) {
-                // Convert to UTC for consistent grouping
-                let utc_dt = dt.to_utc();
-                let month_key = utc_dt.format("%Y-%m").to_string();
-                conversations_by_month.entry(month_key).or_default().push(conv);
+                // Group in the configured timezone so the month matches the page dates
+                let dt = dt.with_timezone(&tz);
+                conversations_by_month.entry((dt.year(), dt.month())).or_default().push(conv);
             }
         }
     }
 
     // Sort months descending
-    let mut months: Vec<_> = conversations_by_month.keys().collect();
+    let mut months: Vec<_> = conversations_by_month.keys().copied().collect();
     months.sort_by(|a, b| b.cmp(a));
 
-    // Russian month names
-    let month_names = [
-        "", "Январь", "Февраль", "Март", "Апрель", "Май", "Июнь",
-        "Июль", "Август", "Сентябрь", "Октябрь", "Ноябрь", "Декабрь"
-    ];
-
-    for month_key in months.iter().take(12) { // Limit to 12 months
-        if let Some(convs) = conversations_by_month.get(*month_key) {
-            let parts: Vec<&str> = month_key.split('-').collect();
-            let year = parts[0];
-            let month_num: usize = parts[1].parse().unwrap_or(0);
-            let month_label = if month_num > 0 && month_num < 13 {
-                format!("{} {}", month_names[month_num], year)
-            } else {
-                month_key.to_string()
-            };
-
+    for (year, month) in months.into_iter().take(12) { // Limit to 12 months
+        if let Some(convs) = conversations_by_month.get(&(year, month)) {
             html.push_str(r#"<div class="month-group">"#);
-            html.push_str(&format!(r#"<div class="month-header">{}</div>"#, month_label));
+            html.push_str(&format!(r#"<div class="month-header">{}</div>"#, locale::month_label(year, month, language)));
             html.push_str(r#"<ul class="month-conversations">"#);
 
             for conv in convs.iter().take(50) { // Limit per month
-                let title = conv.title.as_deref().unwrap_or("Untitled");
+                let title = conv.display_title_for(config);
                 html.push_str(&format!(
-                    r#"<li class="conversation-item"><a href="/conversations/{}/" class="conversation-link"><div class="conversation-title">{}</div></a></li>"#,
+                    r#"<li class="conversation-item" data-id="{}"><a href="/conversations/{}/" class="conversation-link"><div class="conversation-title">{}</div></a></li>"#,
+                    html_escape::encode_double_quoted_attribute(&conv.id),
                     conv.id,
-                    html_escape::encode_text(title)
+                    html_escape::encode_text(&title)
                 ));
             }
 
@@ -373,49 +1473,48 @@ fn generate_sidebar_html(conversations: &[Conversation]) -> String {
     html
 }
 
-fn group_by_month(conversations: &[ConversationMeta]) -> Vec<MonthGroup> {
-    let mut grouped: HashMap<String, Vec<ConversationMeta>> = HashMap::new();
+/// Preview of a conversation shown on the index page, from its `messages`, if they're turned on
+fn index_preview(messages: &[model::ExtractedMessage], config: &AppConfig) -> Option<String> {
+    match config.index_preview_chars {
+        0 => None,
+        chars => model::preview(messages, chars),
+    }
+}
+
+fn group_by_month(conversations: &[ConversationMeta], language: Language) -> Vec<MonthGroup> {
+    let mut grouped: HashMap<(i32, u32), Vec<ConversationMeta>> = HashMap::new();
 
     for conv in conversations {
         if let Some(date) = conv.inserted_at {
-            let month_key = date.format("%Y-%m").to_string();
-            grouped.entry(month_key).or_default().push(conv.clone());
+            grouped.entry((date.year(), date.month())).or_default().push(conv.clone());
         }
     }
 
-    let mut groups: Vec<MonthGroup> = grouped
-        .into_iter()
-        .map(|(key, convs)| {
-            let label = if let Some(first) = convs.first() {
-                if let Some(date) = first.inserted_at {
-                    date.format("%B %Y").to_string()
-                } else {
-                    key.clone()
-                }
-            } else {
-                key
-            };
+    // Newest month first; sorting the labels would put April before March
+    let mut months: Vec<_> = grouped.into_iter().collect();
+    months.sort_by_key(|(month, _)| std::cmp::Reverse(*month));
 
-            MonthGroup {
-                label,
-                conversations: convs,
-            }
+    months
+        .into_iter()
+        .map(|((year, month), convs)| MonthGroup {
+            label: locale::month_label(year, month, language),
+            count: locale::format_count(convs.len(), language),
+            conversations: convs,
         })
-        .collect();
-
-    groups.sort_by(|a, b| b.label.cmp(&a.label));
-    groups
+        .collect()
 }
 
-fn copy_static_assets(output_path: &Path) -> Result<()> {
+/// Write the CSS, scripts and other static files, returning the versions
+/// pages use to link to the stylesheets and scripts
+fn copy_static_assets(output_path: &Path, config: &AppConfig) -> Result<AssetVersions> {
     tracing::info!("📦 Copying static assets...");
     
     // Copy CSS from static folder if exists, otherwise from Jekyll
     let css_source = if Path::new("static/main.css").exists() {
-        fs::read_to_string("static/main.css")?
+        read_file(Path::new("static/main.css"))?
     } else if Path::new("deepseek-chat-viewer/assets/css/main.scss").exists() {
         // Read SCSS (we'll use it as-is, browsers can handle basic CSS)
-        let scss = fs::read_to_string("deepseek-chat-viewer/assets/css/main.scss")?;
+        let scss = read_file(Path::new("deepseek-chat-viewer/assets/css/main.scss"))?;
         // Remove Jekyll front matter
         scss.lines().skip(3).collect::<Vec<_>>().join("\n")
     } else {
@@ -423,12 +1522,178 @@ fn copy_static_assets(output_path: &Path) -> Result<()> {
         include_str!("../static/main.css").to_string()
     };
     
-    fs::write(output_path.join("assets/css/main.css"), css_source)?;
+    let mut assets = AssetVersions::default();
+    assets.insert("/assets/css/main.css", css_source.as_bytes());
+    write_file(&output_path.join("assets/css/main.css"), css_source)?;
     tracing::info!("✅ CSS copied");
 
     // Generate syntax highlighting CSS from syntect
     let ts = ThemeSet::load_defaults();
-    let theme = &ts.themes["base16-ocean.light"];
+    let css = syntax_css(syntax_theme(&ts, config))?;
+    assets.insert("/assets/css/syntax.css", css.as_bytes());
+    write_file(&output_path.join("assets/css/syntax.css"), css)?;
+    tracing::info!("✅ Syntax highlighting CSS generated");
+
+    // Copy JavaScript
+    for &(name, bundled) in SCRIPTS {
+        let script = static_asset(name, bundled)?;
+        assets.insert(&format!("/assets/js/{}", name), script.as_bytes());
+        write_file(&output_path.join("assets/js").join(name), script)?;
+    }
+    tracing::info!("✅ JavaScript copied");
+
+    // Web app manifest and icon, so browsers can install the site
+    write_file(
+        &output_path.join("manifest.webmanifest"),
+        static_asset("manifest.webmanifest", include_str!("../static/manifest.webmanifest"))?,
+    )?;
+    write_file(&output_path.join("favicon.svg"), static_asset("favicon.svg", include_str!("../static/favicon.svg"))?)?;
+
+    // The service worker must sit at the root to control every page. A new
+    // cache version per run makes browsers drop what they cached before.
+    let service_worker = static_asset("sw.js", include_str!("../static/sw.js"))?
+        .replace("__CACHE_VERSION__", &Utc::now().timestamp_millis().to_string());
+    write_file(&output_path.join("sw.js"), service_worker)?;
+    tracing::info!("✅ Web app manifest and service worker copied");
+
+    Ok(assets)
+}
+
+/// Bring the `.br` copies under `output_path` up to date: with `enabled`,
+/// compress each page or asset over `PRECOMPRESS_MIN_BYTES` that has no copy
+/// or whose copy holds something else, and remove the other copies.
+/// Otherwise remove every copy, so none is served in place of a newer file.
+/// Returns the number of files compressed.
+pub fn precompress(output_path: &Path, enabled: bool) -> Result<usize> {
+    let mut stale = Vec::new();
+    let mut pending = Vec::new();
+
+    for entry in walkdir::WalkDir::new(output_path) {
+        let entry = entry.with_context(|| format!("Failed to read {}", output_path.display()))?;
+        if !entry.file_type().is_file() {
+            continue;
+        }
+        let path = entry.path();
+
+        if path.extension().is_some_and(|ext| ext == "br") {
+            // A copy whose original is gone
+            if !path.with_extension("").exists() {
+                stale.push(path.to_path_buf());
+            }
+            continue;
+        }
+        let compressible = path
+            .extension()
+            .and_then(|ext| ext.to_str())
+            .is_some_and(|ext| PRECOMPRESS_EXTENSIONS.contains(&ext));
+        if !compressible {
+            continue;
+        }
+
+        let br_path = brotli_path(path);
+        if !enabled || entry.metadata()?.len() < PRECOMPRESS_MIN_BYTES {
+            if br_path.exists() {
+                stale.push(br_path);
+            }
+            continue;
+        }
+        pending.push(path.to_path_buf());
+    }
+
+    for path in stale {
+        fs::remove_file(&path).with_context(|| format!("Failed to remove {}", path.display()))?;
+    }
+
+    // Brotli's best quality is slow, so compress in parallel
+    let compressed = pending
+        .par_iter()
+        .map(|path| -> Result<usize> {
+            let contents = fs::read(path).with_context(|| format!("Failed to read {}", path.display()))?;
+            // Pages are written again on every run, often unchanged, and file
+            // times may be too coarse to tell apart writes close together
+            let br_path = brotli_path(path);
+            if decompress(&br_path).is_some_and(|copy| copy == contents) {
+                return Ok(0);
+            }
+
+            let mut compressed = Vec::new();
+            {
+                let mut writer = brotli::CompressorWriter::new(&mut compressed, 4096, 11, 22);
+                std::io::Write::write_all(&mut writer, &contents)?;
+            }
+            write_file(&br_path, compressed)?;
+            Ok(1)
+        })
+        .sum::<Result<usize>>()?;
+
+    if compressed > 0 {
+        tracing::info!("🗜️  Precompressed {} files", compressed);
+    }
+    Ok(compressed)
+}
+
+/// Contents of the `.br` copy at `path`, if there is a readable one
+fn decompress(path: &Path) -> Option<Vec<u8>> {
+    let file = fs::File::open(path).ok()?;
+    let mut contents = Vec::new();
+    std::io::Read::read_to_end(&mut brotli::Decompressor::new(file, 4096), &mut contents).ok()?;
+    Some(contents)
+}
+
+/// `page.html` -> `page.html.br`
+fn brotli_path(path: &Path) -> std::path::PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    name.push(".br");
+    name.into()
+}
+
+/// Content hashes of the assets an earlier `generate_site` wrote to `output_path`
+fn asset_versions(output_path: &Path) -> Result<AssetVersions> {
+    let mut assets = AssetVersions::default();
+    let stylesheets = ["/assets/css/main.css", "/assets/css/syntax.css"];
+    let scripts = SCRIPTS.iter().map(|(name, _)| format!("/assets/js/{}", name));
+    for url in stylesheets.into_iter().map(String::from).chain(scripts) {
+        let path = output_path.join(url.trim_start_matches('/'));
+        let contents = fs::read(&path).with_context(|| format!("Failed to read {}", path.display()))?;
+        assets.insert(&url, &contents);
+    }
+    Ok(assets)
+}
+
+/// Contents of `static/{name}` when run from the source tree (so edits show
+/// up without rebuilding), otherwise the copy bundled into the binary.
+fn static_asset(name: &str, bundled: &str) -> Result<String> {
+    let path = Path::new("static").join(name);
+    if path.exists() {
+        read_file(&path)
+    } else {
+        Ok(bundled.to_string())
+    }
+}
+
+fn create_dir(path: &Path) -> Result<()> {
+    fs::create_dir_all(path).with_context(|| format!("Failed to create directory {}", path.display()))
+}
+
+fn write_file(path: &Path, contents: impl AsRef<[u8]>) -> Result<()> {
+    fs::write(path, contents).with_context(|| format!("Failed to write {}", path.display()))
+}
+
+/// Write a rendered page, minified if `minify` is on
+fn write_page(path: &Path, html: String, config: &AppConfig) -> Result<()> {
+    if config.minify {
+        write_file(path, minify::minify_html(&html))
+    } else {
+        write_file(path, html)
+    }
+}
+
+fn read_file(path: &Path) -> Result<String> {
+    fs::read_to_string(path).with_context(|| format!("Failed to read {}", path.display()))
+}
+
+/// Syntax highlighting CSS for the classes syntect emits, plus code block wrapper styles
+fn syntax_css(theme: &syntect::highlighting::Theme) -> Result<String> {
     let mut css = syntect::html::css_for_theme_with_class_style(theme, syntect::html::ClassStyle::Spaced)?;
     
     // Add wrapper styles for code blocks
@@ -443,29 +1708,6 @@ fn copy_static_assets(output_path: &Path) -> Result<()> {
     css.push_str("    margin: 0;\n");
     css.push_str("    padding: 0;\n");
     css.push_str("}\n");
-    
-    fs::write(output_path.join("assets/css/syntax.css"), css)?;
-    tracing::info!("✅ Syntax highlighting CSS generated");
 
-    // Copy search JS
-    let js_source = if Path::new("static/search.js").exists() {
-        fs::read_to_string("static/search.js")?
-    } else {
-        include_str!("../static/search.js").to_string()
-    };
-    
-    fs::write(output_path.join("assets/js/search.js"), js_source)?;
-    
-    // Copy code-actions JS
-    let code_actions_source = if Path::new("static/code-actions.js").exists() {
-        fs::read_to_string("static/code-actions.js")?
-    } else {
-        include_str!("../static/code-actions.js").to_string()
-    };
-    
-    fs::write(output_path.join("assets/js/code-actions.js"), code_actions_source)?;
-    tracing::info!("✅ JavaScript copied");
-
-    Ok(())
+    Ok(css)
 }
-
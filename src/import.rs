@@ -0,0 +1,83 @@
+use anyhow::{Context, Result};
+use serde::Serialize;
+use std::collections::HashMap;
+
+use crate::export;
+use crate::model::Conversation;
+
+/// What merging an export into the previous one changed
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+pub struct MergeSummary {
+    pub added: usize,
+    pub updated: usize,
+    pub unchanged: usize,
+}
+
+/// Merge `incoming` into `existing` by conversation id, the incoming version
+/// winning. Existing conversations keep their order; new ones are appended.
+///
+/// Returns the merged list and the conversations that were added or updated.
+pub fn merge_conversations(
+    existing: Vec<Conversation>,
+    incoming: Vec<Conversation>,
+) -> (Vec<Conversation>, Vec<Conversation>, MergeSummary) {
+    let mut merged = existing;
+    let mut positions: HashMap<String, usize> = merged
+        .iter()
+        .enumerate()
+        .map(|(idx, conv)| (conv.id.clone(), idx))
+        .collect();
+
+    let mut changed = Vec::new();
+    let mut summary = MergeSummary::default();
+
+    for conv in incoming {
+        match positions.get(&conv.id) {
+            Some(&idx) if merged[idx] == conv => summary.unchanged += 1,
+            Some(&idx) => {
+                summary.updated += 1;
+                merged[idx] = conv.clone();
+                changed.push(conv);
+            }
+            None => {
+                summary.added += 1;
+                positions.insert(conv.id.clone(), merged.len());
+                merged.push(conv.clone());
+                changed.push(conv);
+            }
+        }
+    }
+
+    (merged, changed, summary)
+}
+
+/// Merge the export at `incoming_path` into the one at `existing_path` and
+/// write the result to `merged_path` (which may be `existing_path`).
+///
+/// Returns the conversations that were added or updated.
+pub async fn merge_exports(
+    existing_path: &str,
+    incoming_path: &str,
+    merged_path: &str,
+) -> Result<(Vec<Conversation>, MergeSummary)> {
+    let (_, existing): (_, Vec<Conversation>) = export::load_export(existing_path).await?;
+    let (_, incoming): (_, Vec<Conversation>) = export::load_export(incoming_path).await?;
+
+    let (merged, changed, summary) = merge_conversations(existing, incoming);
+
+    let contents = serde_json::to_string(&merged).context("Failed to serialize merged conversations")?;
+    tokio::fs::write(merged_path, contents)
+        .await
+        .with_context(|| format!("Failed to write {}", merged_path))?;
+
+    tracing::info!(
+        "🔀 Merged {} into {}: {} added, {} updated, {} unchanged",
+        incoming_path,
+        merged_path,
+        summary.added,
+        summary.updated,
+        summary.unchanged
+    );
+
+    Ok((changed, summary))
+}
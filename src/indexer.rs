@@ -1,52 +1,582 @@
-use anyhow::Result;
-use serde::Deserialize;
+use anyhow::{Context, Result};
+use chrono::{DateTime, NaiveDate};
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::fmt;
+use std::path::Path;
+use tantivy::collector::DocSetCollector;
+use tantivy::query::AllQuery;
 use tantivy::schema::*;
 use tantivy::tokenizer::{NgramTokenizer, LowerCaser, TextAnalyzer};
-use tantivy::{doc, Index, IndexWriter};
+use tantivy::{doc, DocSet, Index, IndexWriter, TantivyDocument, Term, TERMINATED};
 
-#[derive(Debug, Deserialize)]
-struct Conversation {
-    id: String,
-    title: Option<String>,
-    inserted_at: Option<String>,
-    updated_at: Option<String>,
-    mapping: serde_json::Value,
+use crate::config::{AppConfig, IndexTokenizer, DEFAULT_NGRAM_MAX, DEFAULT_NGRAM_MIN};
+use crate::generator;
+use crate::model::{self, Conversation, Role};
+
+/// Version of the index layout written by this build. Bump it whenever
+/// `schema()` or what goes into the documents changes, so indexes built
+/// before are rebuilt instead of failing in confusing ways.
+pub const SCHEMA_VERSION: u32 = 9;
+
+/// Our own metadata next to tantivy's `meta.json`
+pub const INDEX_META_FILE: &str = "index_meta.json";
+
+#[derive(Debug, Serialize, Deserialize)]
+struct IndexMeta {
+    schema_version: u32,
+    /// Tokenizer the ngram fields were built with, so searches use the same
+    #[serde(default)]
+    ngram_prefix_only: bool,
+    /// Indexes built before this was recorded used ngrams
+    #[serde(default)]
+    tokenizer: IndexTokenizer,
+    /// Ngram sizes in chars; indexes built before they were recorded used the defaults
+    #[serde(default = "default_ngram_min")]
+    ngram_min: usize,
+    #[serde(default = "default_ngram_max")]
+    ngram_max: usize,
+    /// Whether each message has a document of its own (`index_messages`)
+    #[serde(default)]
+    index_messages: bool,
+    /// Page layout the message documents' links were made for
+    #[serde(default)]
+    message_pages: MessagePages,
+}
+
+/// The settings that decide which page and anchor a message is shown at
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+struct MessagePages {
+    messages_per_page: usize,
+    newest_first: bool,
+    merge_consecutive_messages: bool,
+}
+
+impl MessagePages {
+    fn of(config: &AppConfig) -> Self {
+        Self {
+            messages_per_page: config.messages_per_page,
+            newest_first: config.newest_first,
+            merge_consecutive_messages: config.merge_consecutive_messages,
+        }
+    }
+}
+
+fn default_ngram_min() -> usize {
+    DEFAULT_NGRAM_MIN
+}
+
+fn default_ngram_max() -> usize {
+    DEFAULT_NGRAM_MAX
+}
+
+/// The index at `path` was built with a different [`SCHEMA_VERSION`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IndexOutdated {
+    pub path: String,
+    /// `None` for indexes built before versions were recorded
+    pub found: Option<u32>,
+    pub expected: u32,
+}
+
+impl fmt::Display for IndexOutdated {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.found {
+            Some(found) => write!(
+                f,
+                "Search index at {} has schema version {}, this version needs {}; rebuild it",
+                self.path, found, self.expected
+            ),
+            None => write!(
+                f,
+                "Search index at {} was built by an older version, this version needs schema {}; rebuild it",
+                self.path, self.expected
+            ),
+        }
+    }
+}
+
+impl std::error::Error for IndexOutdated {}
+
+/// The ngram index at `path` was built with other ngram sizes than the
+/// config asks for, so queries would be split differently and find nothing
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NgramMismatch {
+    pub path: String,
+    /// `(min, max)` the index was built with
+    pub index: (usize, usize),
+    /// `(ngram_min, ngram_max)` from the config
+    pub config: (usize, usize),
+}
+
+impl fmt::Display for NgramMismatch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Search index at {} was built with ngrams of {}–{} chars, but ngram_min/ngram_max are {}/{}; rebuild it",
+            self.path, self.index.0, self.index.1, self.config.0, self.config.1
+        )
+    }
+}
+
+impl std::error::Error for NgramMismatch {}
+
+/// The message documents of the index at `path` link to pages laid out with
+/// other settings than the config has, so message results would point at
+/// the wrong page or anchor
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MessageLinksOutdated {
+    pub path: String,
+}
+
+impl fmt::Display for MessageLinksOutdated {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Search index at {} links messages to pages with other messages_per_page, newest_first or merge_consecutive_messages settings; rebuild it",
+            self.path
+        )
+    }
+}
+
+impl std::error::Error for MessageLinksOutdated {}
+
+fn read_index_meta(index_path: &str) -> Option<IndexMeta> {
+    let contents = std::fs::read_to_string(Path::new(index_path).join(INDEX_META_FILE)).ok()?;
+    serde_json::from_str(&contents).ok()
+}
+
+/// Schema version recorded in the index at `index_path`, if any
+pub fn schema_version(index_path: &str) -> Option<u32> {
+    read_index_meta(index_path).map(|meta| meta.schema_version)
 }
 
-#[derive(Debug, Deserialize)]
-struct Message {
-    message: MessageData,
+/// Whether the index at `index_path` was built with `ngram_prefix_only`
+pub fn ngram_prefix_only(index_path: &str) -> bool {
+    read_index_meta(index_path).is_some_and(|meta| meta.ngram_prefix_only)
 }
 
-#[derive(Debug, Deserialize)]
-struct MessageData {
-    fragments: Vec<Fragment>,
+/// Tokenizer the index at `index_path` was built with (see `index_tokenizer`)
+pub fn index_tokenizer(index_path: &str) -> IndexTokenizer {
+    read_index_meta(index_path).map(|meta| meta.tokenizer).unwrap_or_default()
 }
 
-#[derive(Debug, Deserialize)]
-struct Fragment {
-    #[serde(rename = "type")]
-    fragment_type: String,
-    content: String,
+/// `(min, max)` ngram sizes the index at `index_path` was built with
+pub fn ngram_sizes(index_path: &str) -> (usize, usize) {
+    read_index_meta(index_path).map_or((DEFAULT_NGRAM_MIN, DEFAULT_NGRAM_MAX), |meta| (meta.ngram_min, meta.ngram_max))
+}
+
+/// Fail with [`NgramMismatch`] if the ngram index at `index_path` was built
+/// with other ngram sizes than `config` has
+pub fn check_ngram_sizes(index_path: &str, config: &AppConfig) -> Result<()> {
+    if index_tokenizer(index_path) != IndexTokenizer::Ngram {
+        return Ok(());
+    }
+    let index = ngram_sizes(index_path);
+    let config = config.ngram_sizes();
+    if index != config {
+        return Err(NgramMismatch { path: index_path.to_string(), index, config }.into());
+    }
+    Ok(())
+}
+
+/// Whether the index at `index_path` has a document per message (see `index_messages`)
+pub fn indexes_messages(index_path: &str) -> bool {
+    read_index_meta(index_path).is_some_and(|meta| meta.index_messages)
+}
+
+/// Fail with [`MessageLinksOutdated`] if the index at `index_path` has
+/// message documents linking to pages laid out with other settings than `config`
+pub fn check_message_pages(index_path: &str, config: &AppConfig) -> Result<()> {
+    let Some(meta) = read_index_meta(index_path) else {
+        return Ok(());
+    };
+    if meta.index_messages && meta.message_pages != MessagePages::of(config) {
+        return Err(MessageLinksOutdated { path: index_path.to_string() }.into());
+    }
+    Ok(())
+}
+
+/// Fail with [`IndexOutdated`] unless the index was built with [`SCHEMA_VERSION`]
+pub fn check_schema_version(index_path: &str) -> Result<()> {
+    let found = schema_version(index_path);
+    if found != Some(SCHEMA_VERSION) {
+        return Err(IndexOutdated { path: index_path.to_string(), found, expected: SCHEMA_VERSION }.into());
+    }
+    Ok(())
+}
+
+fn write_index_meta(index_path: &str, config: &AppConfig) -> Result<()> {
+    let path = Path::new(index_path).join(INDEX_META_FILE);
+    let (ngram_min, ngram_max) = config.ngram_sizes();
+    let contents = serde_json::to_string(&IndexMeta {
+        schema_version: SCHEMA_VERSION,
+        ngram_prefix_only: config.ngram_prefix_only,
+        tokenizer: config.index_tokenizer,
+        ngram_min,
+        ngram_max,
+        index_messages: config.index_messages,
+        message_pages: MessagePages::of(config),
+    })?;
+    std::fs::write(&path, contents).with_context(|| format!("Failed to write {}", path.display()))
 }
 
 pub async fn build_index(conversations_path: &str, index_path: &str) -> Result<()> {
+    build_index_with_config(conversations_path, index_path, &AppConfig::default()).await
+}
+
+/// Like [`build_index`], honouring `max_index_chars_per_conversation`
+pub async fn build_index_with_config(
+    conversations_path: &str,
+    index_path: &str,
+    config: &AppConfig,
+) -> Result<()> {
     tracing::info!("Reading conversations from {}", conversations_path);
     
-    let data = tokio::fs::read_to_string(conversations_path).await?;
-    let conversations: Vec<Conversation> = serde_json::from_str(&data)?;
+    let (_, conversations): (_, Vec<Conversation>) = crate::export::load_export(conversations_path).await?;
     
     tracing::info!("Found {} conversations", conversations.len());
 
+    // Create index
+    let (schema, fields) = schema(config.index_tokenizer);
+    std::fs::create_dir_all(index_path)?;
+    let index = Index::create_in_dir(index_path, schema)?;
+    register_tokenizers(&index, config.ngram_prefix_only, config.ngram_sizes())?;
+    
+    let mut index_writer: IndexWriter = index.writer(50_000_000)?;
+
+    // Index conversations
+    for (idx, conv) in conversations.iter().enumerate() {
+        if idx % 100 == 0 {
+            tracing::info!("Indexed {}/{} conversations", idx, conversations.len());
+        }
+
+        add_conversation(&index_writer, &fields, conv, config, config.index_messages)?;
+    }
+
+    index_writer.commit()?;
+    write_index_meta(index_path, config)?;
+    tracing::info!("✅ Successfully indexed {} conversations", conversations.len());
+
+    Ok(())
+}
+
+/// Replace (or add) `conversations` in the existing index at `index_path`,
+/// leaving every other conversation as it is.
+///
+/// Fails with [`IndexOutdated`] if the index was built by a version with a
+/// different schema, and with [`MessageLinksOutdated`] if its message
+/// documents link to pages laid out differently; the caller should rebuild it then.
+pub fn update_index(index_path: &str, conversations: &[Conversation], config: &AppConfig) -> Result<()> {
+    check_schema_version(index_path)?;
+    check_message_pages(index_path, config)?;
+    let messages = indexes_messages(index_path);
+    let index = Index::open_in_dir(index_path)?;
+    let fields = IndexFields::from_schema(&index.schema())?;
+    register_tokenizers(&index, ngram_prefix_only(index_path), ngram_sizes(index_path))?;
+
+    let mut index_writer: IndexWriter = index.writer(50_000_000)?;
+    for conv in conversations {
+        index_writer.delete_term(Term::from_field_text(fields.conversation_id, &conv.id));
+        add_conversation(&index_writer, &fields, conv, config, messages)?;
+    }
+
+    index_writer.commit()?;
+    tracing::info!("✅ Updated {} conversations in the index", conversations.len());
+
+    Ok(())
+}
+
+/// Reindex only the conversations created between `from` and `to` (inclusive
+/// days, either bound may be open), leaving the rest of the index untouched.
+///
+/// Conversations in the range that are gone from the export are removed from
+/// the index. Returns the number of conversations re-added.
+pub async fn reindex_range(
+    conversations_path: &str,
+    index_path: &str,
+    from: Option<NaiveDate>,
+    to: Option<NaiveDate>,
+) -> Result<usize> {
+    reindex_range_with_config(conversations_path, index_path, from, to, &AppConfig::default()).await
+}
+
+/// Like [`reindex_range`], comparing dates in the configured timezone
+pub async fn reindex_range_with_config(
+    conversations_path: &str,
+    index_path: &str,
+    from: Option<NaiveDate>,
+    to: Option<NaiveDate>,
+    config: &AppConfig,
+) -> Result<usize> {
+    let tz = config.timezone();
+    let in_range = |inserted_at: Option<&str>| {
+        let Some(date) = inserted_at
+            .and_then(|s| DateTime::parse_from_rfc3339(s).ok())
+            .map(|dt| dt.with_timezone(&tz).date_naive())
+        else {
+            return false;
+        };
+        from.is_none_or(|from| date >= from) && to.is_none_or(|to| date <= to)
+    };
+
+    check_schema_version(index_path)?;
+    check_message_pages(index_path, config)?;
+    let messages = indexes_messages(index_path);
+    let (_, conversations): (_, Vec<Conversation>) = crate::export::load_export(conversations_path).await?;
+
+    let index = Index::open_in_dir(index_path)?;
+    let fields = IndexFields::from_schema(&index.schema())?;
+    register_tokenizers(&index, ngram_prefix_only(index_path), ngram_sizes(index_path))?;
+
+    // Indexed conversations in the range: their date may have changed or
+    // they may have been deleted since, so they go too
+    let searcher = index.reader()?.searcher();
+    let mut stale: HashSet<String> = HashSet::new();
+    for address in searcher.search(&AllQuery, &DocSetCollector)? {
+        let doc: TantivyDocument = searcher.doc(address)?;
+        // Only conversation documents carry a date, message documents don't
+        let Some(date) = doc.get_first(fields.date).and_then(|v| v.as_str()) else {
+            continue;
+        };
+        if in_range(Some(date)) {
+            if let Some(id) = doc.get_first(fields.conversation_id).and_then(|v| v.as_str()) {
+                stale.insert(id.to_string());
+            }
+        }
+    }
+
+    let mut index_writer: IndexWriter = index.writer(50_000_000)?;
+    let mut reindexed = 0;
+    for conv in &conversations {
+        if stale.remove(&conv.id) || in_range(conv.inserted_at.as_deref()) {
+            index_writer.delete_term(Term::from_field_text(fields.conversation_id, &conv.id));
+            add_conversation(&index_writer, &fields, conv, config, messages)?;
+            reindexed += 1;
+        }
+    }
+    // Whatever is left isn't in the export anymore
+    for id in &stale {
+        index_writer.delete_term(Term::from_field_text(fields.conversation_id, id));
+    }
+
+    index_writer.commit()?;
+    tracing::info!(
+        "✅ Reindexed {} conversations, removed {} that are no longer exported",
+        reindexed,
+        stale.len()
+    );
+
+    Ok(reindexed)
+}
+
+/// Differences between an export and the search index built from it (see [`verify`])
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct VerifyReport {
+    /// Conversations in the export
+    pub source_conversations: usize,
+    /// Conversations in the index
+    pub indexed_conversations: usize,
+    /// In the export but not in the index, sorted
+    pub missing: Vec<String>,
+    /// In the index but no longer in the export, sorted
+    pub orphaned: Vec<String>,
+}
+
+impl VerifyReport {
+    /// Whether the index holds exactly the export's conversations
+    pub fn is_consistent(&self) -> bool {
+        self.missing.is_empty() && self.orphaned.is_empty()
+    }
+}
+
+/// Check that the index at `index_path` has a document for every
+/// conversation in the export and none for conversations that aren't in it,
+/// e.g. after an interrupted build or incremental updates.
+pub async fn verify(conversations_path: &str, index_path: &str) -> Result<VerifyReport> {
+    check_schema_version(index_path)?;
+    let (_, conversations): (_, Vec<Conversation>) = crate::export::load_export(conversations_path).await?;
+    let source: HashSet<String> = conversations.into_iter().map(|conv| conv.id).collect();
+
+    let index = Index::open_in_dir(index_path)?;
+    let fields = IndexFields::from_schema(&index.schema())?;
+    let indexed = indexed_conversation_ids(&index, fields.conversation_id)?;
+
+    let mut missing: Vec<String> = source.difference(&indexed).cloned().collect();
+    let mut orphaned: Vec<String> = indexed.difference(&source).cloned().collect();
+    missing.sort();
+    orphaned.sort();
+
+    Ok(VerifyReport {
+        source_conversations: source.len(),
+        indexed_conversations: indexed.len(),
+        missing,
+        orphaned,
+    })
+}
+
+/// Ids with at least one live document, read from the term dictionary
+/// rather than the stored documents
+fn indexed_conversation_ids(index: &Index, conversation_id: Field) -> Result<HashSet<String>> {
+    let searcher = index.reader()?.searcher();
+    let mut ids = HashSet::new();
+
+    for segment_reader in searcher.segment_readers() {
+        let inverted_index = segment_reader.inverted_index(conversation_id)?;
+        let alive = segment_reader.alive_bitset();
+        let mut terms = inverted_index.terms().stream()?;
+
+        while terms.advance() {
+            let Ok(id) = std::str::from_utf8(terms.key()) else {
+                continue;
+            };
+            // Deleted documents keep their terms until segments are merged
+            let is_live = match alive {
+                None => true,
+                Some(alive) => {
+                    let mut postings =
+                        inverted_index.read_postings_from_terminfo(terms.value(), IndexRecordOption::Basic)?;
+                    let mut doc = postings.doc();
+                    while doc != TERMINATED && !alive.is_alive(doc) {
+                        doc = postings.advance();
+                    }
+                    doc != TERMINATED
+                }
+            };
+            if is_live {
+                ids.insert(id.to_string());
+            }
+        }
+    }
+
+    Ok(ids)
+}
+
+/// Size and layout of a search index (see [`index_info`])
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct IndexInfo {
+    /// Live documents: one per conversation, plus one per message with `index_messages`
+    pub documents: u64,
+    /// Deleted documents still taking up space until their segments are merged
+    pub deleted_documents: u64,
+    /// Conversations with at least one live document
+    pub conversations: usize,
+    pub segments: usize,
+    /// Bytes taken by all files under the index directory
+    pub size_bytes: u64,
+    pub files: usize,
+    /// Layout version from `index_meta.json`, `None` if it has none
+    pub schema_version: Option<u32>,
+    /// Whether the ngram fields only hold prefixes (`ngram_prefix_only`)
+    pub ngram_prefix_only: bool,
+    /// Tokenizer of titles and messages (`index_tokenizer`)
+    pub tokenizer: IndexTokenizer,
+    /// Ngram sizes in chars (`ngram_min`, `ngram_max`)
+    pub ngram_min: usize,
+    pub ngram_max: usize,
+    /// Whether each message has a document of its own (`index_messages`)
+    pub index_messages: bool,
+}
+
+/// Statistics of the index at `index_path`: documents, segments, disk usage
+/// and the tokenizer settings it was built with. Works on outdated indexes
+/// too, so it can be used to see why one misbehaves.
+pub fn index_info(index_path: &str) -> Result<IndexInfo> {
+    let index = Index::open_in_dir(index_path)
+        .with_context(|| format!("Failed to open search index {}", index_path))?;
+    let segments = index.searchable_segment_metas()?;
+    let fields = IndexFields::from_schema(&index.schema())?;
+
+    let mut size_bytes = 0;
+    let mut files = 0;
+    for entry in walkdir::WalkDir::new(index_path) {
+        let entry = entry.with_context(|| format!("Failed to read {}", index_path))?;
+        if entry.file_type().is_file() {
+            size_bytes += entry.metadata()?.len();
+            files += 1;
+        }
+    }
+
+    let meta = read_index_meta(index_path);
+    Ok(IndexInfo {
+        documents: segments.iter().map(|segment| u64::from(segment.num_docs())).sum(),
+        deleted_documents: segments.iter().map(|segment| u64::from(segment.num_deleted_docs())).sum(),
+        conversations: indexed_conversation_ids(&index, fields.conversation_id)?.len(),
+        segments: segments.len(),
+        size_bytes,
+        files,
+        schema_version: meta.as_ref().map(|meta| meta.schema_version),
+        ngram_prefix_only: meta.as_ref().is_some_and(|meta| meta.ngram_prefix_only),
+        ngram_min: meta.as_ref().map_or(DEFAULT_NGRAM_MIN, |meta| meta.ngram_min),
+        ngram_max: meta.as_ref().map_or(DEFAULT_NGRAM_MAX, |meta| meta.ngram_max),
+        index_messages: meta.as_ref().is_some_and(|meta| meta.index_messages),
+        tokenizer: meta.map(|meta| meta.tokenizer).unwrap_or_default(),
+    })
+}
+
+struct IndexFields {
+    conversation_id: Field,
+    title: Field,
+    content: Field,
+    date: Field,
+    summary: Field,
+    attachment: Field,
+    message: Field,
+    message_index: Field,
+    message_url: Field,
+    role: Field,
+    request_content: Field,
+    response_content: Field,
+    words: Field,
+    title_words: Field,
+    content_words: Field,
+    updated_at: Field,
+    message_count: Field,
+    tags: Field,
+    created: Field,
+}
+
+impl IndexFields {
+    fn from_schema(schema: &Schema) -> Result<Self> {
+        Ok(Self {
+            conversation_id: schema.get_field("conversation_id")?,
+            title: schema.get_field("title")?,
+            content: schema.get_field("content")?,
+            date: schema.get_field("date")?,
+            summary: schema.get_field("summary")?,
+            attachment: schema.get_field("attachment")?,
+            message: schema.get_field("message")?,
+            message_index: schema.get_field("message_index")?,
+            message_url: schema.get_field("message_url")?,
+            role: schema.get_field("role")?,
+            request_content: schema.get_field("request_content")?,
+            response_content: schema.get_field("response_content")?,
+            words: schema.get_field("words")?,
+            title_words: schema.get_field("title_words")?,
+            content_words: schema.get_field("content_words")?,
+            updated_at: schema.get_field("updated_at")?,
+            message_count: schema.get_field("message_count")?,
+            tags: schema.get_field("tags")?,
+            created: schema.get_field("created")?,
+        })
+    }
+}
+
+fn schema(tokenizer: IndexTokenizer) -> (Schema, IndexFields) {
     // Create schema with ngram tokenizer for BLAZING FAST substring search (min=2 chars!)
     let mut schema_builder = Schema::builder();
     let conversation_id = schema_builder.add_text_field("conversation_id", STRING | STORED);
-    
-    // Ngram tokenizer for substring matching: "гр" -> "гравитация"
+
+    // Ngram tokenizer for substring matching: "гр" -> "гравитация",
+    // or tantivy's whole-word one for exact search
+    let text_tokenizer = match tokenizer {
+        IndexTokenizer::Ngram => "ngram2",
+        IndexTokenizer::Words => "default",
+    };
     let ngram_text_options = tantivy::schema::TextOptions::default()
         .set_indexing_options(
             tantivy::schema::TextFieldIndexing::default()
-                .set_tokenizer("ngram2")
+                .set_tokenizer(text_tokenizer)
                 .set_index_option(tantivy::schema::IndexRecordOption::WithFreqsAndPositions)
         )
         .set_stored();
@@ -54,77 +584,228 @@ pub async fn build_index(conversations_path: &str, index_path: &str) -> Result<(
     let title = schema_builder.add_text_field("title", ngram_text_options.clone());
     let content = schema_builder.add_text_field("content", ngram_text_options.clone());
     let date = schema_builder.add_text_field("date", STRING | STORED);
-    let schema = schema_builder.build();
+    let summary = schema_builder.add_text_field("summary", ngram_text_options.clone());
+    // Attachment file names and alt text, searched with an `attachment:` prefix
+    let attachment = schema_builder.add_text_field("attachment", ngram_text_options.clone());
 
-    // Create index
-    std::fs::create_dir_all(index_path)?;
-    let index = Index::create_in_dir(index_path, schema.clone())?;
-    
-    // Register ngram tokenizer for substring search (min=2, max=10, prefix_only=false)
-    let ngram_tokenizer = TextAnalyzer::builder(NgramTokenizer::new(2, 10, false).unwrap())
-        .filter(LowerCaser)
-        .build();
+    // One extra document per message with `index_messages`, so grouped search
+    // can count matching messages and message search can say which matched
+    let message_text_options = tantivy::schema::TextOptions::default()
+        .set_indexing_options(
+            tantivy::schema::TextFieldIndexing::default()
+                .set_tokenizer(text_tokenizer)
+                .set_index_option(tantivy::schema::IndexRecordOption::WithFreqsAndPositions)
+        )
+        .set_stored();
+    let message = schema_builder.add_text_field("message", message_text_options);
+    // Position of the message in its conversation
+    let message_index = schema_builder.add_u64_field("message_index", STORED);
+    // Link to the message on the conversation's pages
+    let message_url = schema_builder.add_text_field("message_url", STORED);
+    // Who wrote the message, as `role_name` spells it
+    let role = schema_builder.add_text_field("role", STRING);
+
+    // `content` split by who wrote it, so a search can look at one side only.
+    // Not stored: snippets come from `content`
+    let role_text_options = tantivy::schema::TextOptions::default()
+        .set_indexing_options(
+            tantivy::schema::TextFieldIndexing::default()
+                .set_tokenizer(text_tokenizer)
+                .set_index_option(tantivy::schema::IndexRecordOption::WithFreqsAndPositions)
+        );
+    let request_content = schema_builder.add_text_field("request_content", role_text_options.clone());
+    let response_content = schema_builder.add_text_field("response_content", role_text_options);
+
+    // Whole words (title + content) for autocomplete via the term dictionary
+    let word_text_options = tantivy::schema::TextOptions::default()
+        .set_indexing_options(
+            tantivy::schema::TextFieldIndexing::default()
+                .set_tokenizer("default")
+                .set_index_option(tantivy::schema::IndexRecordOption::Basic)
+        );
+    let words = schema_builder.add_text_field("words", word_text_options);
+
+    // Whole-word copies of title and content next to the ngram fields, so
+    // search can rank whole-word matches above matches inside longer words
+    let ranking_text_options = tantivy::schema::TextOptions::default()
+        .set_indexing_options(
+            tantivy::schema::TextFieldIndexing::default()
+                .set_tokenizer("default")
+                .set_index_option(tantivy::schema::IndexRecordOption::WithFreqsAndPositions)
+        );
+    let title_words = schema_builder.add_text_field("title_words", ranking_text_options.clone());
+    let content_words = schema_builder.add_text_field("content_words", ranking_text_options);
+
+    // Stored, for listing conversations without reading their pages
+    let updated_at = schema_builder.add_text_field("updated_at", STRING | STORED);
+    // Also fast, for filtering searches by conversation length
+    let message_count = schema_builder.add_u64_field("message_count", STORED | FAST);
+    let tags = schema_builder.add_text_field("tags", STRING | STORED);
+    // `date` as a date, for filtering searches by when conversations were created
+    let created = schema_builder.add_date_field("created", FAST);
+
+    let fields = IndexFields {
+        conversation_id,
+        title,
+        content,
+        date,
+        summary,
+        attachment,
+        message,
+        message_index,
+        message_url,
+        role,
+        request_content,
+        response_content,
+        words,
+        title_words,
+        content_words,
+        updated_at,
+        message_count,
+        tags,
+        created,
+    };
+    (schema_builder.build(), fields)
+}
+
+/// How the `role` field of message documents spells `role`
+pub(crate) fn role_name(role: Role) -> &'static str {
+    match role {
+        Role::User => "user",
+        Role::Assistant => "assistant",
+    }
+}
+
+/// Register the tokenizers the schema refers to. `prefix_only` and the
+/// `(min, max)` ngram sizes must match how the index was built (see
+/// [`ngram_prefix_only`] and [`ngram_sizes`]).
+pub(crate) fn register_tokenizers(index: &Index, prefix_only: bool, (min, max): (usize, usize)) -> Result<()> {
+    // All ngrams for substring search, or only those at the start of each
+    // field for a much smaller index
+    let ngram_tokenizer = NgramTokenizer::new(min, max, prefix_only)
+        .with_context(|| format!("Invalid ngram sizes {}–{}", min, max))?;
+    let ngram_tokenizer = TextAnalyzer::builder(ngram_tokenizer).filter(LowerCaser).build();
     index.tokenizers().register("ngram2", ngram_tokenizer);
-    
-    let mut index_writer: IndexWriter = index.writer(50_000_000)?;
+    Ok(())
+}
 
-    // Index conversations
-    for (idx, conv) in conversations.iter().enumerate() {
-        if idx % 100 == 0 {
-            tracing::info!("Indexed {}/{} conversations", idx, conversations.len());
+/// Add the documents of one conversation: the conversation itself, plus one
+/// per message if `with_messages`
+fn add_conversation(
+    index_writer: &IndexWriter,
+    fields: &IndexFields,
+    conv: &Conversation,
+    config: &AppConfig,
+    with_messages: bool,
+) -> Result<()> {
+    let messages = model::extract_messages(&conv.mapping);
+    let conv_summary = conv.summary_from(&messages).unwrap_or_default();
+    let conv_title = conv.display_title_from(&messages, config);
+    let mut full_content = String::new();
+
+    // Concatenate every fragment's text
+    for message in &messages {
+        full_content.push_str(&message.text());
+        full_content.push(' ');
+    }
+
+    // Bound the ngram postings of huge conversations; pages still show everything
+    let max_chars = config.max_index_chars_per_conversation;
+    if max_chars > 0 {
+        let total_chars = full_content.chars().count();
+        if total_chars > max_chars {
+            tracing::info!(
+                "✂️  Indexing only the first {} of {} chars of conversation {}",
+                max_chars,
+                total_chars,
+                conv.id
+            );
+            full_content = truncate_chars(&full_content, max_chars).to_string();
         }
+    }
+
+    // Add document
+    let mut document = doc!(
+        fields.conversation_id => conv.id.clone(),
+        fields.words => conv_title.clone(),
+        fields.words => full_content.clone(),
+        fields.summary => conv_summary,
+        fields.date => conv.inserted_at.clone().unwrap_or_default(),
+        fields.message_count => messages.len() as u64,
+    );
+    if let Some(updated_at) = &conv.updated_at {
+        document.add_text(fields.updated_at, updated_at);
+    }
+    for tag in &conv.tags {
+        document.add_text(fields.tags, tag);
+    }
+    if let Some(created) = conv.inserted_at.as_deref().and_then(|s| DateTime::parse_from_rfc3339(s).ok()) {
+        document.add_date(fields.created, tantivy::DateTime::from_timestamp_secs(created.timestamp()));
+    }
+    for name in model::extract_attachments(&conv.mapping) {
+        document.add_text(fields.attachment, name);
+    }
+    // A whole-word index already ranks by words
+    if config.index_tokenizer == IndexTokenizer::Ngram {
+        document.add_text(fields.title_words, &conv_title);
+        document.add_text(fields.content_words, &full_content);
+    }
+    document.add_text(fields.title, conv_title);
+    document.add_text(fields.content, full_content);
 
-        let conv_title = conv.title.clone().unwrap_or_else(|| format!("Conversation {}", idx + 1));
-        let mut full_content = String::new();
+    // The same text as `content` (and the same budget), by who wrote it
+    let mut request_content = String::new();
+    let mut response_content = String::new();
+    let mut budget = if max_chars > 0 { max_chars } else { usize::MAX };
+    for msg in &messages {
+        if budget == 0 {
+            break;
+        }
+        let text = msg.text();
+        let text = truncate_chars(&text, budget);
+        budget = budget.saturating_sub(text.chars().count() + 1);
 
-        // Extract messages from mapping
-        if let Some(mapping) = conv.mapping.as_object() {
-            if let Some(root) = mapping.get("root") {
-                if let Some(children) = root.get("children").and_then(|c| c.as_array()) {
-                    extract_messages(mapping, children, &mut full_content);
-                }
-            }
+        let side = match msg.role {
+            Role::User => &mut request_content,
+            Role::Assistant => &mut response_content,
+        };
+        side.push_str(text);
+        side.push(' ');
+    }
+    document.add_text(fields.request_content, request_content);
+    document.add_text(fields.response_content, response_content);
+    index_writer.add_document(document)?;
+    if !with_messages {
+        return Ok(());
+    }
+
+    // Messages share the same budget as the content
+    let urls = generator::message_urls(conv, &messages, config);
+    let mut budget = if max_chars > 0 { max_chars } else { usize::MAX };
+    for (index, msg) in messages.iter().enumerate().filter(|(_, m)| !m.text().trim().is_empty()) {
+        if budget == 0 {
+            break;
         }
+        let text = msg.text();
+        let text = truncate_chars(&text, budget);
+        budget = budget.saturating_sub(text.chars().count() + 1);
 
-        // Add document
         index_writer.add_document(doc!(
-            conversation_id => conv.id.clone(),
-            title => conv_title,
-            content => full_content,
-            date => conv.inserted_at.clone().unwrap_or_default(),
+            fields.conversation_id => conv.id.clone(),
+            fields.message => text.to_string(),
+            fields.message_index => index as u64,
+            fields.message_url => urls[index].clone(),
+            fields.role => role_name(msg.role),
         ))?;
     }
 
-    index_writer.commit()?;
-    tracing::info!("✅ Successfully indexed {} conversations", conversations.len());
-
     Ok(())
 }
 
-fn extract_messages(
-    mapping: &serde_json::Map<String, serde_json::Value>,
-    children: &[serde_json::Value],
-    content: &mut String,
-) {
-    for child_id in children {
-        if let Some(child_id_str) = child_id.as_str() {
-            if let Some(child) = mapping.get(child_id_str) {
-                if let Some(message) = child.get("message") {
-                    if let Some(fragments) = message.get("fragments").and_then(|f| f.as_array()) {
-                        for fragment in fragments {
-                            if let Some(text) = fragment.get("content").and_then(|c| c.as_str()) {
-                                content.push_str(text);
-                                content.push(' ');
-                            }
-                        }
-                    }
-                }
-                
-                if let Some(grandchildren) = child.get("children").and_then(|c| c.as_array()) {
-                    extract_messages(mapping, grandchildren, content);
-                }
-            }
-        }
+/// The first `max` chars of `text`
+fn truncate_chars(text: &str, max: usize) -> &str {
+    match text.char_indices().nth(max) {
+        Some((idx, _)) => &text[..idx],
+        None => text,
     }
 }
-
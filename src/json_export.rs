@@ -0,0 +1,220 @@
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use std::collections::BTreeMap;
+
+use crate::model::{Conversation, Role};
+
+/// Version marker written into exports produced by this tool
+pub const EXPORT_VERSION: &str = "deepseek-viewer/1";
+
+/// Top-level export document (the versioned shape understood by the importer)
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ExportDocument {
+    pub version: String,
+    pub conversations: Vec<ExportConversation>,
+}
+
+/// A conversation with its messages laid out as a linear node chain
+/// starting at `root`, so the export can be imported again.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ExportConversation {
+    pub id: String,
+    pub title: Option<String>,
+    pub inserted_at: Option<String>,
+    pub updated_at: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub summary: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
+    pub mapping: BTreeMap<String, ExportNode>,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct ExportNode {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub parent: Option<String>,
+    pub children: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub message: Option<ExportMessage>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ExportMessage {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub role: Option<Role>,
+    /// As exported, whatever its format
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub inserted_at: Option<String>,
+    #[serde(default)]
+    pub fragments: Vec<ExportFragment>,
+    /// The message's other keys as exported, e.g. `files`
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ExportFragment {
+    #[serde(rename = "type", default, skip_serializing_if = "String::is_empty")]
+    pub fragment_type: String,
+    /// Everything else as exported: `content` (a string or an array of
+    /// parts), image URLs, search `results`, tool `output`, ...
+    #[serde(flatten)]
+    pub data: Map<String, Value>,
+}
+
+/// Message keys kept in an export without metadata: they hold content
+const CONTENT_MESSAGE_KEYS: &[&str] = &["files"];
+
+/// Export all conversations from `conversations_path` as JSON to `output_path`.
+///
+/// Fragments are written as they are in the source export, so nothing is
+/// lost on export and import. With `include_metadata` the original node
+/// ids, parent links, roles, timestamps and other message keys are kept;
+/// otherwise nodes are renumbered and only the fragments and `files` are
+/// written.
+pub async fn export_json(conversations_path: &str, output_path: &str, include_metadata: bool) -> Result<()> {
+    tracing::info!("Reading conversations from {}", conversations_path);
+
+    let (_, conversations): (_, Vec<Conversation>) = crate::export::load_export(conversations_path).await?;
+
+    let document = export_document(&conversations, include_metadata);
+
+    let contents = serde_json::to_string_pretty(&document)
+        .context("Failed to serialize export")?;
+    tokio::fs::write(output_path, contents)
+        .await
+        .with_context(|| format!("Failed to write {}", output_path))?;
+
+    tracing::info!("✅ Exported {} conversations to {}", document.conversations.len(), output_path);
+
+    Ok(())
+}
+
+/// Export document for `conversations`, see [`export_json`] for `include_metadata`
+pub fn export_document(conversations: &[Conversation], include_metadata: bool) -> ExportDocument {
+    ExportDocument {
+        version: EXPORT_VERSION.to_string(),
+        conversations: conversations
+            .iter()
+            .map(|conv| export_conversation(conv, include_metadata))
+            .collect(),
+    }
+}
+
+fn export_conversation(conv: &Conversation, include_metadata: bool) -> ExportConversation {
+    // Every node in reading order, the same walk as `model::extract_messages`
+    let mut nodes = Vec::new();
+    if let Some(mapping) = conv.mapping.as_object() {
+        if let Some(children) = mapping.get("root").and_then(|root| root.get("children")).and_then(|c| c.as_array()) {
+            collect_nodes(mapping, children, &mut nodes);
+        }
+    }
+
+    // Chain the messages: root -> first -> second -> ...
+    let mut mapping = BTreeMap::new();
+    let mut parent_id = "root".to_string();
+    mapping.insert(parent_id.clone(), ExportNode::default());
+
+    for (idx, (node_id, node)) in nodes.into_iter().enumerate() {
+        let node_id = if include_metadata { node_id.to_string() } else { (idx + 1).to_string() };
+        let message = node.get("message").and_then(|m| m.as_object()).map(|message| export_message(message, include_metadata));
+
+        if let Some(parent) = mapping.get_mut(&parent_id) {
+            parent.children.push(node_id.clone());
+        }
+        mapping.insert(
+            node_id.clone(),
+            ExportNode {
+                parent: include_metadata.then(|| parent_id.clone()),
+                children: Vec::new(),
+                message,
+            },
+        );
+        parent_id = node_id;
+    }
+
+    ExportConversation {
+        id: conv.id.clone(),
+        title: conv.title.clone(),
+        inserted_at: conv.inserted_at.clone(),
+        updated_at: conv.updated_at.clone(),
+        summary: conv.summary.clone(),
+        tags: conv.tags.clone(),
+        mapping,
+    }
+}
+
+/// Nodes below `children`, depth-first, skipping ids with no node
+fn collect_nodes<'a>(mapping: &'a Map<String, Value>, children: &'a [Value], nodes: &mut Vec<(&'a str, &'a Value)>) {
+    for child_id in children.iter().filter_map(|id| id.as_str()) {
+        if let Some(child) = mapping.get(child_id) {
+            nodes.push((child_id, child));
+            if let Some(grandchildren) = child.get("children").and_then(|c| c.as_array()) {
+                collect_nodes(mapping, grandchildren, nodes);
+            }
+        }
+    }
+}
+
+/// A message as exported, fragments untouched
+fn export_message(message: &Map<String, Value>, include_metadata: bool) -> ExportMessage {
+    let fragments: Vec<ExportFragment> = message
+        .get("fragments")
+        .and_then(|f| f.as_array())
+        .into_iter()
+        .flatten()
+        .filter_map(|fragment| fragment.as_object())
+        .map(|fragment| {
+            let mut data = fragment.clone();
+            let fragment_type = match data.remove("type") {
+                Some(Value::String(fragment_type)) => fragment_type,
+                Some(other) => {
+                    // Not a type this tool understands, keep it as it was
+                    data.insert("type".to_string(), other);
+                    String::new()
+                }
+                None => String::new(),
+            };
+            ExportFragment { fragment_type, data }
+        })
+        .collect();
+
+    let mut extra = message.clone();
+    extra.remove("fragments");
+    if !include_metadata {
+        extra.retain(|key, _| CONTENT_MESSAGE_KEYS.contains(&key.as_str()));
+        return ExportMessage { role: None, inserted_at: None, fragments, extra };
+    }
+
+    // Values of another shape than these fields' stay in `extra` as they were
+    let role = match extra.remove("role") {
+        None => Some(message_role(&fragments)),
+        Some(role) => match serde_json::from_value(role.clone()) {
+            Ok(role) => Some(role),
+            Err(_) => {
+                extra.insert("role".to_string(), role);
+                None
+            }
+        },
+    };
+    let inserted_at = match extra.remove("inserted_at") {
+        Some(Value::String(inserted_at)) => Some(inserted_at),
+        Some(other) => {
+            extra.insert("inserted_at".to_string(), other);
+            None
+        }
+        None => None,
+    };
+
+    ExportMessage { role, inserted_at, fragments, extra }
+}
+
+/// DeepSeek doesn't store roles; user turns are the ones made of REQUEST fragments.
+fn message_role(fragments: &[ExportFragment]) -> Role {
+    if fragments.iter().any(|f| f.fragment_type == "REQUEST") {
+        Role::User
+    } else {
+        Role::Assistant
+    }
+}
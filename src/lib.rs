@@ -1,9 +1,27 @@
 // Public modules for testing
+pub mod archive;
+pub mod code_export;
+pub mod config;
+pub mod export;
+pub mod favorites;
 pub mod generator;
+pub mod import;
 pub mod indexer;
+pub mod json_export;
+pub mod locale;
+pub mod logging;
+pub mod manifest;
+pub mod markdown_export;
+pub mod math;
+pub mod minify;
+pub mod model;
 pub mod search;
+pub mod server;
+pub mod shortlinks;
 pub mod templates;
+pub mod terms;
 
 // Re-export main types
+pub use model::Conversation;
 pub use search::SearchEngine;
 
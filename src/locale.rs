@@ -0,0 +1,44 @@
+use crate::config::Language;
+
+const RU_MONTHS: [&str; 12] = [
+    "Январь", "Февраль", "Март", "Апрель", "Май", "Июнь",
+    "Июль", "Август", "Сентябрь", "Октябрь", "Ноябрь", "Декабрь",
+];
+
+const EN_MONTHS: [&str; 12] = [
+    "January", "February", "March", "April", "May", "June",
+    "July", "August", "September", "October", "November", "December",
+];
+
+/// `n` with its thousands grouped the way `language` writes them:
+/// "1 234 567" (with non-breaking spaces) in Russian, "1,234,567" in English
+pub fn format_count(n: usize, language: Language) -> String {
+    let separator = match language {
+        Language::Ru => '\u{a0}',
+        Language::En => ',',
+    };
+
+    let digits = n.to_string();
+    let mut out = String::with_capacity(digits.len() * 2);
+    for (idx, digit) in digits.chars().enumerate() {
+        if idx > 0 && (digits.len() - idx).is_multiple_of(3) {
+            out.push(separator);
+        }
+        out.push(digit);
+    }
+    out
+}
+
+/// Heading of a month's conversations, e.g. "Март 2024" or "March 2024".
+/// `month` is 1-based; anything else falls back to "2024-13".
+pub fn month_label(year: i32, month: u32, language: Language) -> String {
+    let names = match language {
+        Language::Ru => &RU_MONTHS,
+        Language::En => &EN_MONTHS,
+    };
+
+    match month.checked_sub(1).and_then(|idx| names.get(idx as usize)) {
+        Some(name) => format!("{} {}", name, year),
+        None => format!("{}-{:02}", year, month),
+    }
+}
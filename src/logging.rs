@@ -0,0 +1,37 @@
+use tracing_subscriber::{fmt, layer::SubscriberExt, util::SubscriberInitExt, EnvFilter};
+
+/// How log lines are written to stderr
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum LogFormat {
+    /// Human-readable lines (the default)
+    #[default]
+    Human,
+    /// One JSON object per line, for log aggregators
+    Json,
+}
+
+impl std::str::FromStr for LogFormat {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> anyhow::Result<Self> {
+        match s {
+            "human" | "text" => Ok(Self::Human),
+            "json" => Ok(Self::Json),
+            other => anyhow::bail!("Unknown log format '{}' (expected 'human' or 'json')", other),
+        }
+    }
+}
+
+/// Used when `RUST_LOG` isn't set: info from the library and both binaries
+const DEFAULT_FILTER: &str = "deepseek_app=info,deepseek_viewer=info,deepseek_desktop=info";
+
+/// Install the global tracing subscriber
+pub fn init(format: LogFormat) {
+    let registry = tracing_subscriber::registry()
+        .with(EnvFilter::try_from_default_env().unwrap_or_else(|_| DEFAULT_FILTER.into()));
+
+    match format {
+        LogFormat::Human => registry.with(fmt::layer()).init(),
+        LogFormat::Json => registry.with(fmt::layer().json()).init(),
+    }
+}
@@ -1,28 +1,166 @@
-use anyhow::Result;
+use anyhow::{Context, Result};
+use chrono::NaiveDate;
 use std::net::SocketAddr;
-use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt};
 
-mod generator;
-mod server;
-mod templates;
+mod selftest;
 
 // Use from lib
-use deepseek_app::{indexer, search};
+use deepseek_app::logging::{self, LogFormat};
+use deepseek_app::{config, favorites, generator, indexer, json_export, search, server};
 use std::path::PathBuf;
 
+/// Command-line flags
+#[derive(Debug, Default)]
+struct Args {
+    /// Rebuild outputs even if they already exist
+    force: bool,
+    /// Only build the search index, then exit
+    index_only: bool,
+    /// Only generate the HTML site, then exit
+    html_only: bool,
+    /// Export conversations as JSON to this path, then exit
+    export_json: Option<String>,
+    /// Export the favorite conversations to this path (markdown if it ends in `.md`), then exit
+    export_favorites: Option<String>,
+    /// Keep node ids, roles and timestamps in the JSON export
+    export_metadata: bool,
+    /// Run the end-to-end self test on built-in sample data, then exit
+    selftest: bool,
+    /// Print the available syntax highlighting themes, then exit
+    list_themes: bool,
+    /// Log line format
+    log_format: LogFormat,
+    /// Reindex only conversations created on or after this day, then exit
+    reindex_from: Option<NaiveDate>,
+    /// Reindex only conversations created on or before this day, then exit
+    reindex_to: Option<NaiveDate>,
+    /// Conversation to open instead of the index page
+    open_conversation: Option<String>,
+    /// Check the search index against the conversations file, then exit
+    verify: bool,
+    /// Print search index statistics, then exit
+    index_info: bool,
+    /// Print `--index-info` as JSON
+    json: bool,
+}
+
+impl Args {
+    fn parse() -> Result<Self> {
+        let mut args = Self::default();
+
+        let mut argv = std::env::args().skip(1);
+        while let Some(arg) = argv.next() {
+            match arg.as_str() {
+                "--force" => args.force = true,
+                "--index-only" => args.index_only = true,
+                "--html-only" => args.html_only = true,
+                "--export-json" => {
+                    args.export_json = Some(argv.next().context("--export-json requires a path")?);
+                }
+                "--export-favorites" => {
+                    args.export_favorites = Some(argv.next().context("--export-favorites requires a path")?);
+                }
+                "--export-metadata" => args.export_metadata = true,
+                "--selftest" => args.selftest = true,
+                "--list-themes" => args.list_themes = true,
+                "--verify" => args.verify = true,
+                "--index-info" => args.index_info = true,
+                "--json" => args.json = true,
+                "--log-format" => {
+                    args.log_format = argv.next().context("--log-format requires a value")?.parse()?;
+                }
+                "--open-conversation" => {
+                    args.open_conversation = Some(argv.next().context("--open-conversation requires an id")?);
+                }
+                "--reindex-from" => {
+                    args.reindex_from = Some(parse_day(argv.next(), "--reindex-from")?);
+                }
+                "--reindex-to" => {
+                    args.reindex_to = Some(parse_day(argv.next(), "--reindex-to")?);
+                }
+                other => anyhow::bail!("Unknown argument: {}", other),
+            }
+        }
+
+        if args.json && !args.index_info {
+            anyhow::bail!("--json only applies to --index-info");
+        }
+
+        if args.index_only && args.html_only {
+            anyhow::bail!("--index-only and --html-only can't be used together");
+        }
+
+        if let (Some(from), Some(to)) = (args.reindex_from, args.reindex_to) {
+            if from > to {
+                anyhow::bail!("--reindex-from {} is after --reindex-to {}", from, to);
+            }
+        }
+
+        Ok(args)
+    }
+}
+
+/// A `YYYY-MM-DD` flag value
+fn parse_day(value: Option<String>, flag: &str) -> Result<NaiveDate> {
+    let value = value.with_context(|| format!("{} requires a date", flag))?;
+    NaiveDate::parse_from_str(&value, "%Y-%m-%d")
+        .with_context(|| format!("{} expects a date like 2024-01-31, got '{}'", flag, value))
+}
+
+/// `bytes` in the largest binary unit that keeps it at least 1, e.g. "12.3 MiB"
+fn format_bytes(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["KiB", "MiB", "GiB", "TiB"];
+    if bytes < 1024 {
+        return format!("{} B", bytes);
+    }
+    let mut size = bytes as f64 / 1024.0;
+    let mut unit = 0;
+    while size >= 1024.0 && unit < UNITS.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+    format!("{:.1} {}", size, UNITS[unit])
+}
+
 #[tokio::main]
 async fn main() -> Result<()> {
+    let args = Args::parse()?;
+
+    if args.list_themes {
+        let configured = generator::syntax_theme_name(&config::AppConfig::load().unwrap_or_default());
+        for theme in generator::syntax_themes() {
+            let mut marks = Vec::new();
+            if theme == generator::SYNTAX_THEME {
+                marks.push("default");
+            }
+            if theme == configured {
+                marks.push("configured");
+            }
+            if marks.is_empty() {
+                println!("{}", theme);
+            } else {
+                println!("{} ({})", theme, marks.join(", "));
+            }
+        }
+        return Ok(());
+    }
+
     // Initialize tracing
-    tracing_subscriber::registry()
-        .with(
-            tracing_subscriber::EnvFilter::try_from_default_env()
-                .unwrap_or_else(|_| "deepseek_viewer=info".into()),
-        )
-        .with(tracing_subscriber::fmt::layer())
-        .init();
+    logging::init(args.log_format);
 
     tracing::info!("🚀 DeepSeek Chat Viewer - Pure Rust Edition");
 
+    if args.selftest {
+        if selftest::run().await? {
+            println!("Self test passed");
+            return Ok(());
+        }
+        println!("Self test FAILED");
+        std::process::exit(1);
+    }
+
+    let config = config::AppConfig::load().unwrap_or_default();
+
     let conversations_path = "conversations.json";
     // Use user-local data directory to avoid permission issues
     let base_data_dir: PathBuf = dirs::data_local_dir()
@@ -46,40 +184,168 @@ async fn main() -> Result<()> {
         empty_path.to_string_lossy().to_string()
     };
 
+    if let Some(export_path) = &args.export_json {
+        json_export::export_json(&conversations_source, export_path, args.export_metadata).await?;
+        return Ok(());
+    }
+
+    if let Some(export_path) = &args.export_favorites {
+        favorites::export_favorites(
+            &conversations_source,
+            export_path,
+            &config.favorites,
+            favorites::FavoritesFormat::from_path(export_path),
+            args.export_metadata,
+        )
+        .await?;
+        return Ok(());
+    }
+
+    if args.verify {
+        if !index_path.exists() {
+            anyhow::bail!("No search index at {}, run with --index-only first", index_path.display());
+        }
+        let report = indexer::verify(&conversations_source, index_path.to_str().unwrap()).await?;
+        for id in &report.missing {
+            println!("missing from the index: {}", id);
+        }
+        for id in &report.orphaned {
+            println!("not in {}: {}", conversations_source, id);
+        }
+        println!(
+            "{} conversations in {}, {} in the index, {} missing, {} orphaned",
+            report.source_conversations,
+            conversations_source,
+            report.indexed_conversations,
+            report.missing.len(),
+            report.orphaned.len()
+        );
+        if report.is_consistent() {
+            println!("Index matches the conversations file");
+            return Ok(());
+        }
+        println!("Index does NOT match the conversations file, rebuild it with --force --index-only");
+        std::process::exit(1);
+    }
+
+    if args.index_info {
+        if !index_path.exists() {
+            anyhow::bail!("No search index at {}, run with --index-only first", index_path.display());
+        }
+        let info = indexer::index_info(index_path.to_str().unwrap())?;
+        if args.json {
+            println!("{}", serde_json::to_string_pretty(&info)?);
+            return Ok(());
+        }
+        println!("Index: {}", index_path.display());
+        println!("Documents: {} ({} deleted)", info.documents, info.deleted_documents);
+        println!("Conversations: {}", info.conversations);
+        println!("Segments: {}", info.segments);
+        println!("Size on disk: {} in {} files", format_bytes(info.size_bytes), info.files);
+        match info.schema_version {
+            Some(version) if version == indexer::SCHEMA_VERSION => println!("Schema version: {}", version),
+            Some(version) => println!(
+                "Schema version: {} (this build expects {}, rebuild with --force --index-only)",
+                version,
+                indexer::SCHEMA_VERSION
+            ),
+            None => println!("Schema version: unknown (no {})", indexer::INDEX_META_FILE),
+        }
+        match info.tokenizer {
+            config::IndexTokenizer::Ngram => println!(
+                "Tokenizer: ngrams of {}–{} chars, {}",
+                info.ngram_min,
+                info.ngram_max,
+                if info.ngram_prefix_only { "prefixes only (ngram_prefix_only)" } else { "all substrings" }
+            ),
+            config::IndexTokenizer::Words => println!("Tokenizer: whole words"),
+        }
+        println!("Message documents: {}", if info.index_messages { "yes (index_messages)" } else { "no" });
+        return Ok(());
+    }
+
+    if args.reindex_from.is_some() || args.reindex_to.is_some() {
+        if !index_path.exists() {
+            anyhow::bail!("No search index at {}, run with --index-only first", index_path.display());
+        }
+        tracing::info!(
+            "📚 Reindexing conversations from {} to {}...",
+            args.reindex_from.map_or("the start".to_string(), |d| d.to_string()),
+            args.reindex_to.map_or("now".to_string(), |d| d.to_string())
+        );
+        indexer::reindex_range_with_config(
+            &conversations_source,
+            index_path.to_str().unwrap(),
+            args.reindex_from,
+            args.reindex_to,
+            &config,
+        )
+        .await?;
+        return Ok(());
+    }
+
     // Step 1: Generate HTML site
     let index_file = output_dir.join("index.html");
-    if !index_file.exists() {
+    if args.index_only {
+        tracing::info!("⏭️  Skipping HTML site (--index-only)");
+    } else if args.force || !index_file.exists() {
+        if args.force && output_dir.exists() {
+            std::fs::remove_dir_all(&output_dir)?;
+        }
         tracing::info!("📦 Generating HTML site in {}...", output_dir.display());
         std::fs::create_dir_all(&output_dir)?;
-        generator::generate_site(&conversations_source, output_dir.to_str().unwrap()).await?;
+        generator::generate_site(&conversations_source, output_dir.to_str().unwrap(), &config).await?;
         tracing::info!("✅ HTML site generated in {}/", output_dir.display());
     } else {
         tracing::info!("✅ Using existing HTML site in {}/", output_dir.display());
     }
 
     // Step 2: Build search index
-    if !index_path.exists() {
+    if args.html_only {
+        tracing::info!("⏭️  Skipping search index (--html-only)");
+    } else if args.force || !index_path.exists() {
+        if args.force && index_path.exists() {
+            std::fs::remove_dir_all(&index_path)?;
+        }
         tracing::info!("📚 Building search index in {}...", index_path.display());
         std::fs::create_dir_all(&index_path)?;
-        indexer::build_index(&conversations_source, index_path.to_str().unwrap()).await?;
+        indexer::build_index_with_config(&conversations_source, index_path.to_str().unwrap(), &config).await?;
         tracing::info!("✅ Search index built");
     } else {
         tracing::info!("✅ Using existing search index");
     }
 
-    // Step 3: Start server
-    let search_engine = search::SearchEngine::new(index_path.to_str().unwrap())?;
+    if args.index_only || args.html_only {
+        tracing::info!("✅ Partial run complete");
+        return Ok(());
+    }
+
+    // Step 3: Start server (rebuilding the index if it turns out to be corrupt)
+    let search_engine =
+        search::SearchEngine::open_or_rebuild(index_path.to_str().unwrap(), &conversations_source, &config).await?;
     let addr = SocketAddr::from(([127, 0, 0, 1], 8080));
     
     tracing::info!("🌐 Starting web server on http://{}", addr);
     tracing::info!("📁 Serving files from {}/", output_dir.display());
     tracing::info!("");
-    tracing::info!("✨ Ready! Open http://127.0.0.1:8080 in your browser");
+    let start_path = match args.open_conversation.as_deref() {
+        Some(id) => generator::conversation_path(&output_dir, id).unwrap_or_else(|| {
+            tracing::warn!("⚠️  Conversation {} not found, opening the index instead", id);
+            "/".to_string()
+        }),
+        None => "/".to_string(),
+    };
+    tracing::info!("✨ Ready! Open http://{}{} in your browser", addr, start_path);
     tracing::info!("🔍 Search API: http://127.0.0.1:8080/api/search?q=<query>");
     tracing::info!("");
     tracing::info!("Press Ctrl+C to stop");
     
-    server::serve(addr, search_engine, output_dir.to_str().unwrap()).await?;
+    // The server reads single conversations (standalone export) from the same file
+    let config = config::AppConfig {
+        conversations_file_path: Some(conversations_source),
+        ..config
+    };
+    server::serve(addr, search_engine, output_dir.to_str().unwrap(), config).await?;
 
     Ok(())
 }
@@ -0,0 +1,109 @@
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::Path;
+
+use crate::config::AppConfig;
+use crate::model::Conversation;
+use crate::templates::content_hash;
+
+/// File (inside the output directory) recording what the site was generated from
+pub const MANIFEST_FILE: &str = ".generation.json";
+
+/// What a generated site was generated from, to tell a rename from a change
+/// that needs every page regenerated
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct GenerationManifest {
+    /// Version of the generator; templates may differ between versions
+    pub version: String,
+    /// Hash of the config the pages were rendered with
+    pub settings: String,
+    /// Conversation id -> what went into its pages
+    pub conversations: BTreeMap<String, ManifestEntry>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ManifestEntry {
+    /// The title shown (see [`Conversation::display_title`])
+    pub title: String,
+    /// Renaming a conversation may bump it
+    pub updated_at: Option<String>,
+    /// Hash of everything else: messages, creation date, summary, tags
+    pub content: String,
+}
+
+impl GenerationManifest {
+    pub fn new(conversations: &[Conversation], config: &AppConfig) -> Result<Self> {
+        // Settings the pages don't depend on, which the desktop app changes often
+        let config = AppConfig {
+            conversations_file_path: None,
+            favorites: Vec::new(),
+            read_conversations: Vec::new(),
+            ..config.clone()
+        };
+        let settings = serde_json::to_vec(&config).context("Failed to serialize config")?;
+
+        let mut entries = BTreeMap::new();
+        for conv in conversations {
+            let content = serde_json::to_vec(&(&conv.inserted_at, &conv.summary, &conv.tags, &conv.mapping))
+                .with_context(|| format!("Failed to serialize conversation {}", conv.id))?;
+            entries.insert(
+                conv.id.clone(),
+                ManifestEntry {
+                    title: conv.display_title_for(&config),
+                    updated_at: conv.updated_at.clone(),
+                    content: content_hash(&content),
+                },
+            );
+        }
+
+        Ok(Self {
+            version: env!("CARGO_PKG_VERSION").to_string(),
+            settings: content_hash(&settings),
+            conversations: entries,
+        })
+    }
+
+    /// Ids of the conversations that were only renamed since `previous`, or
+    /// `None` if anything else changed: the config, a conversation's
+    /// messages, or which conversations there are.
+    pub fn renamed_since(&self, previous: &GenerationManifest) -> Option<Vec<&str>> {
+        if self.version != previous.version
+            || self.settings != previous.settings
+            || self.conversations.len() != previous.conversations.len()
+        {
+            return None;
+        }
+
+        let mut renamed = Vec::new();
+        for (id, entry) in &self.conversations {
+            let before = previous.conversations.get(id)?;
+            if entry.content != before.content {
+                return None;
+            }
+            if entry.title != before.title {
+                renamed.push(id.as_str());
+            } else if entry.updated_at != before.updated_at {
+                // Updated for some reason we can't see in the hash
+                return None;
+            }
+        }
+        Some(renamed)
+    }
+}
+
+/// Load the manifest of the site in `output_dir`, if it has a valid one
+pub fn load(output_dir: &Path) -> Option<GenerationManifest> {
+    let path = output_dir.join(MANIFEST_FILE);
+    let contents = std::fs::read_to_string(&path).ok()?;
+    serde_json::from_str(&contents)
+        .map_err(|e| tracing::warn!("Ignoring invalid {}: {}", path.display(), e))
+        .ok()
+}
+
+/// Save `manifest`
+pub fn save(output_dir: &Path, manifest: &GenerationManifest) -> Result<()> {
+    let path = output_dir.join(MANIFEST_FILE);
+    let contents = serde_json::to_string(manifest).context("Failed to serialize generation manifest")?;
+    std::fs::write(&path, contents).with_context(|| format!("Failed to write {}", path.display()))
+}
@@ -0,0 +1,47 @@
+use crate::model::{self, Conversation, Role};
+
+/// File (inside each conversation's directory) holding its markdown source
+pub const RAW_MARKDOWN_FILE: &str = "raw.md";
+
+/// Render a conversation as markdown: a heading per message, with the
+/// original markdown of model output kept as is.
+///
+/// Reasoning (THINK) fragments are quoted; web search results and tool
+/// calls are skipped, as they're folded away on the HTML pages.
+pub fn conversation_to_markdown(conv: &Conversation) -> String {
+    let mut output = format!("# {}\n", conv.display_title());
+    if let Some(inserted_at) = &conv.inserted_at {
+        output.push_str(&format!("\n_{}_\n", inserted_at));
+    }
+
+    let mut current_node: Option<String> = None;
+    for message in model::extract_messages(&conv.mapping) {
+        if model::is_tool_fragment(&message.fragment_type) {
+            continue;
+        }
+
+        // One heading per node, even if it has several fragments
+        if current_node.as_deref() != Some(message.node_id.as_str()) {
+            let heading = match message.role {
+                Role::User => "User",
+                Role::Assistant => "Assistant",
+            };
+            output.push_str(&format!("\n## {}\n", heading));
+            current_node = Some(message.node_id.clone());
+        }
+
+        output.push('\n');
+        if message.fragment_type == "THINK" {
+            for line in message.content.trim_end().lines() {
+                output.push_str(if line.is_empty() { ">" } else { "> " });
+                output.push_str(line);
+                output.push('\n');
+            }
+        } else {
+            output.push_str(message.content.trim_end());
+            output.push('\n');
+        }
+    }
+
+    output
+}
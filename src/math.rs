@@ -0,0 +1,60 @@
+use once_cell::sync::Lazy;
+use regex::{Captures, Regex};
+
+/// Code blocks and tags (attributes such as `data-code` hold raw code), which must be left alone
+static SKIPPED: Lazy<Regex> = Lazy::new(|| Regex::new(r"(?s)<pre[\s>].*?</pre>|<code[\s>].*?</code>|<[^>]*>").unwrap());
+
+/// `$$display$$` or `$inline$` formulas, the delimiters the pages give KaTeX
+static FORMULA: Lazy<Regex> = Lazy::new(|| Regex::new(r"(?s)\$\$(.+?)\$\$|\$([^$\n]+?)\$").unwrap());
+
+/// Replace the formulas in rendered message HTML with MathML, which browsers
+/// display without KaTeX's scripts, styles or fonts.
+///
+/// Formulas KaTeX can't parse are left as they are. On targets without a
+/// JavaScript engine for KaTeX (Windows MSVC) the HTML is returned unchanged.
+pub fn render_offline(html: &str) -> String {
+    let mut output = String::with_capacity(html.len());
+    let mut last = 0;
+
+    for skipped in SKIPPED.find_iter(html) {
+        output.push_str(&replace_formulas(&html[last..skipped.start()]));
+        output.push_str(skipped.as_str());
+        last = skipped.end();
+    }
+    output.push_str(&replace_formulas(&html[last..]));
+
+    output
+}
+
+fn replace_formulas(html: &str) -> String {
+    FORMULA
+        .replace_all(html, |caps: &Captures| {
+            let (tex, display) = match caps.get(1) {
+                Some(tex) => (tex.as_str(), true),
+                None => (&caps[2], false),
+            };
+
+            // The markdown renderer has already escaped `<`, `&`, ...
+            let tex = html_escape::decode_html_entities(tex);
+            to_mathml(&tex, display).unwrap_or_else(|| caps[0].to_string())
+        })
+        .into_owned()
+}
+
+#[cfg(any(unix, all(windows, target_env = "gnu")))]
+fn to_mathml(tex: &str, display: bool) -> Option<String> {
+    let opts = katex::Opts::builder()
+        .display_mode(display)
+        .output_type(katex::OutputType::Mathml)
+        .build()
+        .ok()?;
+
+    katex::render_with_opts(tex, &opts)
+        .map_err(|e| tracing::debug!("Leaving formula '{}' as is: {}", tex, e))
+        .ok()
+}
+
+#[cfg(not(any(unix, all(windows, target_env = "gnu"))))]
+fn to_mathml(_tex: &str, _display: bool) -> Option<String> {
+    None
+}
@@ -0,0 +1,102 @@
+/// Elements whose content is copied as is: whitespace matters in them, or
+/// (for scripts and styles) collapsing it could change their meaning
+const RAW_ELEMENTS: &[&str] = &["pre", "textarea", "script", "style"];
+
+/// Shrink a rendered page: drop comments and collapse each run of whitespace
+/// between tags to a single space (or newline, if the run had one).
+///
+/// Tags are copied unchanged, so attribute values such as a code block's
+/// `data-code` keep their line breaks, and so is everything inside
+/// `<pre>`, `<textarea>`, `<script>` and `<style>`.
+pub fn minify_html(html: &str) -> String {
+    let mut out = String::with_capacity(html.len());
+    let mut rest = html;
+
+    while !rest.is_empty() {
+        if let Some(comment) = rest.strip_prefix("<!--") {
+            rest = comment.find("-->").map_or("", |end| &comment[end + 3..]);
+            continue;
+        }
+
+        if rest.starts_with('<') {
+            let end = tag_end(rest);
+            let tag = &rest[..end];
+            out.push_str(tag);
+            rest = &rest[end..];
+
+            if let Some(name) = RAW_ELEMENTS.iter().find(|name| opens(tag, name)) {
+                let end = closing_tag_start(rest, name).unwrap_or(rest.len());
+                out.push_str(&rest[..end]);
+                rest = &rest[end..];
+            }
+            continue;
+        }
+
+        let end = rest.find('<').unwrap_or(rest.len());
+        collapse_whitespace(&rest[..end], &mut out);
+        rest = &rest[end..];
+    }
+
+    out
+}
+
+/// Length of the tag `html` starts with, up to and including its `>`.
+/// A `>` inside a quoted attribute value doesn't end the tag.
+fn tag_end(html: &str) -> usize {
+    let mut quote = None;
+    for (idx, c) in html.char_indices() {
+        match (quote, c) {
+            (None, '"' | '\'') => quote = Some(c),
+            (Some(q), _) if c == q => quote = None,
+            (None, '>') => return idx + 1,
+            _ => {}
+        }
+    }
+    html.len()
+}
+
+/// Whether `tag` is an opening tag of the element `name`
+fn opens(tag: &str, name: &str) -> bool {
+    let Some(rest) = tag.strip_prefix('<') else {
+        return false;
+    };
+    rest.get(..name.len()).is_some_and(|prefix| prefix.eq_ignore_ascii_case(name))
+        && rest[name.len()..].starts_with(|c: char| c == '>' || c == '/' || c.is_ascii_whitespace())
+}
+
+/// Byte offset of the first `</name` in `html`, ignoring case
+fn closing_tag_start(html: &str, name: &str) -> Option<usize> {
+    let needle = format!("</{}", name);
+    html.as_bytes()
+        .windows(needle.len())
+        .position(|window| window.eq_ignore_ascii_case(needle.as_bytes()))
+}
+
+fn collapse_whitespace(text: &str, out: &mut String) {
+    let mut pending: Option<char> = None;
+    for c in text.chars() {
+        // Only ASCII whitespace: a non-breaking space is content
+        if c.is_ascii_whitespace() {
+            // Keep a newline if the run has one, so the output still has lines
+            if c == '\n' || pending.is_none() {
+                pending = Some(if c == '\n' { '\n' } else { ' ' });
+            }
+            continue;
+        }
+        if let Some(space) = pending.take() {
+            push_space(out, space);
+        }
+        out.push(c);
+    }
+    if let Some(space) = pending {
+        push_space(out, space);
+    }
+}
+
+/// Add collapsed whitespace, unless the text before a dropped comment
+/// already ended with some
+fn push_space(out: &mut String, space: char) {
+    if !out.ends_with([' ', '\n']) {
+        out.push(space);
+    }
+}
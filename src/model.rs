@@ -0,0 +1,462 @@
+use chrono::{DateTime, Utc};
+use std::borrow::Cow;
+use serde::{Deserialize, Serialize};
+
+use crate::config::{AppConfig, UntitledFallback};
+
+/// A conversation as found in a DeepSeek export
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Conversation {
+    pub id: String,
+    pub title: Option<String>,
+    pub inserted_at: Option<String>,
+    pub updated_at: Option<String>,
+    /// Short description, if the export has one (see [`Conversation::summary`])
+    #[serde(default)]
+    pub summary: Option<String>,
+    /// Labels attached to the conversation, if the export has any
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
+    /// Node id -> node, starting from the `root` node. A node has the ids of
+    /// its `children` and may have a `message` with `fragments`.
+    ///
+    /// Kept as raw JSON so one odd node doesn't make the whole export unreadable.
+    pub mapping: serde_json::Value,
+}
+
+impl Conversation {
+    /// The exported summary, or one derived from the opening messages
+    pub fn summary(&self) -> Option<String> {
+        non_empty(self.summary.as_deref())
+            .or_else(|| summarize(&extract_messages(&self.mapping)))
+    }
+
+    /// Like [`Conversation::summary`], with the conversation's `messages`
+    /// already extracted
+    pub fn summary_from(&self, messages: &[ExtractedMessage]) -> Option<String> {
+        non_empty(self.summary.as_deref()).or_else(|| summarize(messages))
+    }
+
+    /// The title to show: the exported one, else one derived from the first
+    /// user message, else "Untitled"
+    pub fn display_title(&self) -> String {
+        self.title_or_derived().unwrap_or_else(|| UNTITLED.to_string())
+    }
+
+    /// Like [`Conversation::display_title`], with the configured
+    /// `untitled_fallback` for conversations that have neither a title nor text
+    pub fn display_title_for(&self, config: &AppConfig) -> String {
+        self.title_or_derived().unwrap_or_else(|| self.untitled_label(config))
+    }
+
+    /// Like [`Conversation::display_title_for`], with the conversation's
+    /// `messages` already extracted
+    pub fn display_title_from(&self, messages: &[ExtractedMessage], config: &AppConfig) -> String {
+        non_empty(self.title.as_deref())
+            .or_else(|| derive_title(messages))
+            .unwrap_or_else(|| self.untitled_label(config))
+    }
+
+    fn title_or_derived(&self) -> Option<String> {
+        non_empty(self.title.as_deref()).or_else(|| derive_title(&extract_messages(&self.mapping)))
+    }
+
+    fn untitled_label(&self, config: &AppConfig) -> String {
+        let date = || {
+            let date = DateTime::parse_from_rfc3339(self.inserted_at.as_deref()?).ok()?;
+            Some(date.with_timezone(&config.timezone()).format(config.date_format()).to_string())
+        };
+        let id = || {
+            let short: String = self.id.chars().take(UNTITLED_ID_CHARS).collect();
+            if short.is_empty() {
+                UNTITLED.to_string()
+            } else if short.len() < self.id.len() {
+                format!("{}…", short)
+            } else {
+                short
+            }
+        };
+
+        match config.untitled_fallback {
+            UntitledFallback::Untitled => UNTITLED.to_string(),
+            // Without a date the id is all there is to tell it apart
+            UntitledFallback::Date => date().unwrap_or_else(id),
+            UntitledFallback::Id => id(),
+        }
+    }
+}
+
+/// Title of a conversation with nothing to take one from
+const UNTITLED: &str = "Untitled";
+
+/// Chars of the id an `untitled_fallback` of `id` shows
+const UNTITLED_ID_CHARS: usize = 8;
+
+fn non_empty(text: Option<&str>) -> Option<String> {
+    text.map(str::trim).filter(|t| !t.is_empty()).map(str::to_string)
+}
+
+/// Longest summary [`summarize`] produces, in chars
+const SUMMARY_MAX_CHARS: usize = 120;
+
+/// Longest title [`derive_title`] produces, in chars (before the ellipsis)
+const TITLE_MAX_CHARS: usize = 60;
+
+/// Derive a title from the first user message (or the first message, if the
+/// user never spoke), cut at a word boundary.
+pub fn derive_title(messages: &[ExtractedMessage]) -> Option<String> {
+    let text = messages
+        .iter()
+        .find(|m| m.role == Role::User)
+        .or_else(|| messages.first())
+        .map(|m| plain_text(&m.content))
+        .filter(|t| !t.is_empty())?;
+
+    if text.chars().count() <= TITLE_MAX_CHARS {
+        return Some(text);
+    }
+
+    let truncated: String = text.chars().take(TITLE_MAX_CHARS).collect();
+    // Don't cut a word in half, unless it's one very long word
+    let cut = match truncated.rfind(char::is_whitespace) {
+        Some(idx) if idx > 0 => &truncated[..idx],
+        _ => &truncated,
+    };
+    Some(format!("{}…", cut.trim_end()))
+}
+
+/// Derive a summary from the first user message and the first sentence of
+/// the first answer, e.g. "What is gravitation? — Gravitation is the attraction between masses."
+pub fn summarize(messages: &[ExtractedMessage]) -> Option<String> {
+    let question = messages
+        .iter()
+        .find(|m| m.role == Role::User)
+        .map(|m| plain_text(&m.content))
+        .filter(|t| !t.is_empty());
+    let answer = messages
+        .iter()
+        .find(|m| m.fragment_type == "RESPONSE")
+        .map(|m| first_sentence(&plain_text(&m.content)).to_string())
+        .filter(|t| !t.is_empty());
+
+    let summary = match (question, answer) {
+        (Some(q), Some(a)) => format!("{} — {}", q, a),
+        (Some(text), None) | (None, Some(text)) => text,
+        (None, None) => return None,
+    };
+
+    if summary.chars().count() > SUMMARY_MAX_CHARS {
+        let truncated: String = summary.chars().take(SUMMARY_MAX_CHARS - 1).collect();
+        Some(format!("{}…", truncated.trim_end()))
+    } else {
+        Some(summary)
+    }
+}
+
+/// The start of the first message with any text, at most `max_chars` chars
+/// (ellipsis included), in plain text
+pub fn preview(messages: &[ExtractedMessage], max_chars: usize) -> Option<String> {
+    let text = messages
+        .iter()
+        .filter(|m| !is_tool_fragment(&m.fragment_type))
+        .map(|m| plain_text(&m.content))
+        .find(|t| !t.is_empty())?;
+
+    if text.chars().count() <= max_chars {
+        return Some(text);
+    }
+    let truncated: String = text.chars().take(max_chars.saturating_sub(1)).collect();
+    Some(format!("{}…", truncated.trim_end()))
+}
+
+/// Collapse whitespace and drop the most common markdown markers
+fn plain_text(content: &str) -> String {
+    content
+        .split_whitespace()
+        .map(|word| word.trim_matches(|c| matches!(c, '*' | '`' | '#')))
+        .filter(|word| !word.is_empty())
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+fn first_sentence(text: &str) -> &str {
+    let mut chars = text.char_indices().peekable();
+    while let Some((idx, c)) = chars.next() {
+        let at_boundary = chars.peek().is_none_or(|(_, next)| next.is_whitespace());
+        if matches!(c, '.' | '!' | '?') && at_boundary {
+            return &text[..idx + c.len_utf8()];
+        }
+    }
+    text
+}
+
+/// Who a message came from
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Role {
+    User,
+    Assistant,
+}
+
+/// One fragment of a conversation, in reading order
+#[derive(Debug, Clone)]
+pub struct ExtractedMessage {
+    /// Id of the `mapping` node the fragment belongs to
+    pub node_id: String,
+    /// Fragment type as exported (REQUEST, RESPONSE, THINK, SEARCH, ...)
+    pub fragment_type: String,
+    pub role: Role,
+    /// Raw fragment content (markdown for model output)
+    pub content: String,
+    pub inserted_at: Option<DateTime<Utc>>,
+    /// Images embedded in the export as `data:` URLs: an image fragment's
+    /// own, plus those in its message's `files` on the message's first fragment
+    pub images: Vec<InlineImage>,
+    /// The fragment as exported, for tool fragments whose data isn't in
+    /// `content` (search `results`, a call's `arguments`, ...)
+    pub raw: Option<serde_json::Value>,
+}
+
+impl ExtractedMessage {
+    /// Text to search: `content`, or the words of a tool fragment's data
+    pub fn text(&self) -> Cow<'_, str> {
+        match &self.raw {
+            Some(fragment) if self.content.is_empty() => Cow::Owned(tool_fragment_text(fragment)),
+            _ => Cow::Borrowed(&self.content),
+        }
+    }
+}
+
+/// An image embedded in the export as a base64 `data:` URL
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InlineImage {
+    pub data_url: String,
+    /// File name or alt text, empty if the export has neither
+    pub alt: String,
+}
+
+impl InlineImage {
+    /// The image in a fragment or `files` entry, if it has a `data:image/` URL
+    fn from_item(item: &serde_json::Value) -> Option<Self> {
+        let data_url = ["url", "data", "content"]
+            .iter()
+            .filter_map(|key| item.get(*key).and_then(|v| v.as_str()))
+            .find(|value| is_image_data_url(value))?;
+        let alt = ["alt", "file_name", "name"]
+            .iter()
+            .filter_map(|key| item.get(*key).and_then(|v| v.as_str()))
+            .map(str::trim)
+            .find(|alt| !alt.is_empty())
+            .unwrap_or_default();
+
+        Some(Self { data_url: data_url.to_string(), alt: alt.to_string() })
+    }
+
+    /// MIME type from the URL, e.g. `image/png`
+    pub fn mime_type(&self) -> &str {
+        self.data_url["data:".len()..].split([';', ',']).next().unwrap_or_default()
+    }
+
+    /// Base64 payload after the comma
+    pub fn base64_data(&self) -> &str {
+        self.data_url.split_once(',').map_or("", |(_, data)| data)
+    }
+}
+
+fn is_image_data_url(value: &str) -> bool {
+    value.get(..11).is_some_and(|scheme| scheme.eq_ignore_ascii_case("data:image/"))
+        && value.split_once(',').is_some_and(|(header, _)| header.ends_with(";base64"))
+}
+
+/// Names of the files attached to a conversation's messages: `file_name`s
+/// from each message's `files` list, plus the file name or alt text of
+/// non-text fragments (images, documents).
+pub fn extract_attachments(mapping: &serde_json::Value) -> Vec<String> {
+    const NAME_KEYS: &[&str] = &["file_name", "name", "alt"];
+
+    let mut names = Vec::new();
+    let mut push_names = |item: &serde_json::Value| {
+        for key in NAME_KEYS {
+            if let Some(name) = item.get(*key).and_then(|n| n.as_str()).map(str::trim) {
+                if !name.is_empty() && !names.iter().any(|n| n == name) {
+                    names.push(name.to_string());
+                }
+            }
+        }
+    };
+
+    let messages = mapping
+        .as_object()
+        .into_iter()
+        .flat_map(|nodes| nodes.values())
+        .filter_map(|node| node.get("message"));
+
+    for message in messages {
+        for key in ["files", "fragments"] {
+            if let Some(items) = message.get(key).and_then(|f| f.as_array()) {
+                items.iter().for_each(&mut push_names);
+            }
+        }
+    }
+
+    names
+}
+
+/// Walk a conversation's `mapping` depth-first from `root` and return its
+/// fragments in order. Every branch is visited.
+pub fn extract_messages(mapping: &serde_json::Value) -> Vec<ExtractedMessage> {
+    let mut messages = Vec::new();
+
+    if let Some(mapping_obj) = mapping.as_object() {
+        if let Some(root) = mapping_obj.get("root") {
+            if let Some(children) = root.get("children").and_then(|c| c.as_array()) {
+                extract_messages_recursive(mapping_obj, children, &mut messages);
+            }
+        }
+    }
+
+    messages
+}
+
+fn extract_messages_recursive(
+    mapping: &serde_json::Map<String, serde_json::Value>,
+    children: &[serde_json::Value],
+    messages: &mut Vec<ExtractedMessage>,
+) {
+    for child_id in children {
+        if let Some(child_id_str) = child_id.as_str() {
+            if let Some(child) = mapping.get(child_id_str) {
+                if let Some(message) = child.get("message") {
+                    let inserted_at = message.get("inserted_at")
+                        .and_then(|d| d.as_str())
+                        .and_then(|s| DateTime::parse_from_rfc3339(s).ok())
+                        .map(|dt| dt.with_timezone(&Utc));
+
+                    let first_fragment = messages.len();
+                    if let Some(fragments) = message.get("fragments").and_then(|f| f.as_array()) {
+                        // Image fragments belong to whoever sent the message
+                        let from_user = fragments
+                            .iter()
+                            .any(|f| f.get("type").and_then(|t| t.as_str()) == Some("REQUEST"));
+
+                        for fragment in fragments {
+                            let fragment_type = fragment.get("type")
+                                .and_then(|t| t.as_str())
+                                .unwrap_or("UNKNOWN");
+
+                            let image = InlineImage::from_item(fragment);
+                            let from_user = fragment_type == "REQUEST" || (image.is_some() && from_user);
+
+                            // An image fragment's `content` is the image itself
+                            let (content, part_images) = match (&image, fragment.get("content")) {
+                                (None, Some(content)) => fragment_content(content),
+                                _ => (String::new(), Vec::new()),
+                            };
+                            let raw = (content.is_empty() && is_tool_fragment(fragment_type)).then(|| fragment.clone());
+
+                            messages.push(ExtractedMessage {
+                                node_id: child_id_str.to_string(),
+                                fragment_type: fragment_type.to_string(),
+                                role: if from_user { Role::User } else { Role::Assistant },
+                                content,
+                                inserted_at,
+                                images: image.into_iter().chain(part_images).collect(),
+                                raw,
+                            });
+                        }
+                    }
+
+                    let file_images = message
+                        .get("files")
+                        .and_then(|f| f.as_array())
+                        .into_iter()
+                        .flatten()
+                        .filter_map(InlineImage::from_item);
+                    if let Some(first) = messages.get_mut(first_fragment) {
+                        first.images.extend(file_images);
+                    }
+                }
+
+                if let Some(grandchildren) = child.get("children").and_then(|c| c.as_array()) {
+                    extract_messages_recursive(mapping, grandchildren, messages);
+                }
+            }
+        }
+    }
+}
+
+/// Text of a fragment's `content`: usually a string, but some exports store
+/// an array of parts. String parts and the text of `{"type": "text", ...}`
+/// parts are concatenated; embedded images are returned apart, and any other
+/// part is noted in the text as `[type: name]` instead of being dropped.
+fn fragment_content(content: &serde_json::Value) -> (String, Vec<InlineImage>) {
+    let Some(parts) = content.as_array() else {
+        return (content.as_str().unwrap_or_default().to_string(), Vec::new());
+    };
+
+    let mut text = String::new();
+    let mut images = Vec::new();
+    for part in parts {
+        if let Some(part_text) = part.as_str().or_else(|| part.get("text").and_then(|t| t.as_str())) {
+            text.push_str(part_text);
+        } else if let Some(image) = InlineImage::from_item(part) {
+            images.push(image);
+        } else if let Some(part_text) = part.get("content").and_then(|c| c.as_str()) {
+            text.push_str(part_text);
+        } else if !part.is_null() {
+            let kind = part.get("type").and_then(|t| t.as_str()).unwrap_or("вложение");
+            let name = ["file_name", "name", "alt"]
+                .iter()
+                .filter_map(|key| part.get(*key).and_then(|n| n.as_str()))
+                .map(str::trim)
+                .find(|name| !name.is_empty());
+            let note = match name {
+                Some(name) => format!("[{}: {}]", kind, name),
+                None => format!("[{}]", kind),
+            };
+            // On a line of its own
+            if !text.is_empty() && !text.ends_with('\n') {
+                text.push_str("\n\n");
+            }
+            text.push_str(&note);
+            text.push_str("\n\n");
+        }
+    }
+
+    (text.trim_end().to_string(), images)
+}
+
+/// Types of the fragments recording what the model looked up or ran rather
+/// than what it said: web search results, tool calls and their output
+pub const TOOL_FRAGMENT_TYPES: &[&str] = &["SEARCH", "TOOL_CALL", "TOOL_RESULT"];
+
+pub fn is_tool_fragment(fragment_type: &str) -> bool {
+    TOOL_FRAGMENT_TYPES.contains(&fragment_type)
+}
+
+/// Words of a tool fragment's data, for search: the title, address and
+/// snippet of each search result, or a call's name and `arguments` and its
+/// `output`, as exported
+pub fn tool_fragment_text(fragment: &serde_json::Value) -> String {
+    let mut words = Vec::new();
+    let mut push = |value: &serde_json::Value| match value {
+        serde_json::Value::String(text) => words.push(text.trim().to_string()),
+        serde_json::Value::Null => {}
+        value => words.push(value.to_string()),
+    };
+
+    for result in fragment.get("results").and_then(|r| r.as_array()).into_iter().flatten() {
+        ["title", "name", "url", "link", "snippet", "summary", "content"]
+            .iter()
+            .filter_map(|key| result.get(*key))
+            .for_each(&mut push);
+    }
+    ["name", "tool", "function", "arguments", "input", "output", "result"]
+        .iter()
+        .filter_map(|key| fragment.get(*key))
+        .for_each(&mut push);
+
+    words.retain(|text| !text.is_empty());
+    words.join("\n")
+}
+
@@ -1,45 +1,287 @@
 use anyhow::Result;
-use serde::Serialize;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::ops::Bound;
+use std::path::Path;
 use std::sync::Arc;
-use tantivy::collector::TopDocs;
-use tantivy::query::QueryParser;
+use tantivy::collector::{Count, TopDocs};
+use tantivy::query::{
+    BooleanQuery, BoostQuery, ExistsQuery, Occur, PhrasePrefixQuery, Query, QueryParser, RangeQuery, TermQuery,
+};
 use tantivy::schema::*;
-use tantivy::tokenizer::{NgramTokenizer, LowerCaser, TextAnalyzer};
-use tantivy::{Index, ReloadPolicy};
+use tantivy::snippet::SnippetGenerator;
+use tantivy::tokenizer::TokenStream;
+use tantivy::{Index, Order, ReloadPolicy, Searcher, TantivyDocument, Term};
+
+use crate::config::{AppConfig, IndexTokenizer};
+use crate::indexer::role_name;
+use crate::model::Role;
 
 #[derive(Debug, Clone)]
 pub struct SearchEngine {
     index: Arc<Index>,
     schema: Schema,
+    title_boost: f32,
+    content_boost: f32,
+    word_boost: f32,
+    /// How the index was built, which decides how queries are matched
+    tokenizer: IndexTokenizer,
+    /// Longest ngram in the index, in chars
+    ngram_max: usize,
+    /// Most results a search may skip
+    max_offset: usize,
+    /// Whether the index has a document per message (`index_messages`)
+    messages: bool,
+}
+
+/// How a search went, for the API's timing fields
+#[derive(Debug, Clone, Copy, Default, Serialize)]
+pub struct SearchStats {
+    /// Number of results handed out
+    pub results: usize,
+    /// Index segments the query ran over
+    pub segments: usize,
+    /// Time spent parsing the query and collecting top docs (excludes loading documents)
+    pub query_ms: u128,
+    /// Documents matching the query, regardless of `limit` and `offset`
+    pub total_hits: usize,
+}
+
+/// Knobs for a single search
+#[derive(Debug, Clone, Default)]
+pub struct SearchOptions {
+    /// Return only the best hit of each conversation, counting its matching
+    /// messages in [`SearchResult::match_count`] if the index has message documents
+    pub group_by_conversation: bool,
+    /// Fill in [`SearchResult::normalized_score`]
+    pub normalize_scores: bool,
+    /// Skip this many of the best results (or conversations, when grouping)
+    pub offset: usize,
+    /// Only conversations with at least this many messages
+    pub min_messages: Option<u64>,
+    /// Only conversations with at most this many messages
+    pub max_messages: Option<u64>,
+    /// Only conversations created at or after this
+    pub from: Option<DateTime<Utc>>,
+    /// Only conversations created at or before this
+    pub to: Option<DateTime<Utc>>,
+    /// Whose messages to look in
+    pub role: RoleFilter,
+}
+
+/// Whose words a search matches
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RoleFilter {
+    /// Titles, summaries and every message
+    #[default]
+    Any,
+    /// Only your prompts
+    User,
+    /// Only the model's responses
+    Assistant,
+}
+
+impl RoleFilter {
+    fn role(self) -> Option<Role> {
+        match self {
+            RoleFilter::Any => None,
+            RoleFilter::User => Some(Role::User),
+            RoleFilter::Assistant => Some(Role::Assistant),
+        }
+    }
 }
 
 #[derive(Debug, Serialize)]
 pub struct SearchResult {
     pub conversation_id: String,
     pub title: String,
+    /// Site path of the conversation's page, or of the matching message
+    /// from [`SearchEngine::search_messages`]
+    pub url: String,
     pub date: String,
     pub score: f32,
     pub snippet: String,
+    /// `snippet`, HTML-escaped, with the query's matches in `<mark>`
+    pub snippet_html: String,
+    /// Messages in this conversation that match, only when results are
+    /// grouped and the index has message documents (`index_messages`)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub match_count: Option<usize>,
+    /// `score` relative to the query's best hit (0–1), only when asked for
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub normalized_score: Option<f32>,
+    /// Position of the matching message in the conversation, only from
+    /// [`SearchEngine::search_messages`]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub message_index: Option<u64>,
+}
+
+/// A conversation whose title matches a prefix (see [`SearchEngine::suggest_titles`])
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct TitleSuggestion {
+    pub conversation_id: String,
+    pub title: String,
+    /// Site path of the conversation's page
+    pub url: String,
+}
+
+/// A conversation as recorded in the index, for listing all of them
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ConversationMetadata {
+    pub conversation_id: String,
+    pub title: String,
+    /// Site path of the conversation's page
+    pub url: String,
+    pub inserted_at: Option<String>,
+    pub updated_at: Option<String>,
+    pub message_count: u64,
+    pub tags: Vec<String>,
 }
 
 impl SearchEngine {
     pub fn new(index_path: &str) -> Result<Self> {
+        Self::with_config(index_path, &AppConfig::default())
+    }
+
+    /// Open the index, ranking with the field boosts from `config`
+    ///
+    /// Fails with [`IndexOutdated`](crate::indexer::IndexOutdated) if the index
+    /// was built with another schema version, and with
+    /// [`NgramMismatch`](crate::indexer::NgramMismatch) if it was built with
+    /// other ngram sizes than `config` has, and with
+    /// [`MessageLinksOutdated`](crate::indexer::MessageLinksOutdated) if its
+    /// message links were made for another page layout.
+    pub fn with_config(index_path: &str, config: &AppConfig) -> Result<Self> {
+        crate::indexer::check_schema_version(index_path)?;
+        crate::indexer::check_ngram_sizes(index_path, config)?;
+        crate::indexer::check_message_pages(index_path, config)?;
         let index = Index::open_in_dir(index_path)?;
         let schema = index.schema();
         
-        // Register the same ngram tokenizer for searching (min=2, max=10)
-        let ngram_tokenizer = TextAnalyzer::builder(NgramTokenizer::new(2, 10, false).unwrap())
-            .filter(LowerCaser)
-            .build();
-        index.tokenizers().register("ngram2", ngram_tokenizer);
+        // Search with the tokenizers the index was built with
+        let ngram_sizes = crate::indexer::ngram_sizes(index_path);
+        crate::indexer::register_tokenizers(&index, crate::indexer::ngram_prefix_only(index_path), ngram_sizes)?;
         
         Ok(Self {
             index: Arc::new(index),
             schema,
+            title_boost: config.title_boost(),
+            content_boost: config.content_boost(),
+            word_boost: config.word_boost(),
+            tokenizer: crate::indexer::index_tokenizer(index_path),
+            ngram_max: ngram_sizes.1,
+            max_offset: config.search_max_offset,
+            messages: crate::indexer::indexes_messages(index_path),
         })
     }
 
+    /// Open the index, deleting and rebuilding it from `conversations_path`
+    /// if it can't be opened (built by an older version, partial write,
+    /// interrupted build, ...).
+    pub async fn open_or_rebuild(
+        index_path: &str,
+        conversations_path: &str,
+        config: &AppConfig,
+    ) -> Result<Self> {
+        match Self::with_config(index_path, config) {
+            Ok(engine) => Ok(engine),
+            Err(e) if e.downcast_ref::<crate::indexer::IndexOutdated>().is_some() => {
+                tracing::info!("🔄 Search index at {} is outdated, rebuilding from {}", index_path, conversations_path);
+                Self::rebuild(index_path, conversations_path, config).await
+            }
+            Err(e)
+                if e.downcast_ref::<crate::indexer::NgramMismatch>().is_some()
+                    || e.downcast_ref::<crate::indexer::MessageLinksOutdated>().is_some() =>
+            {
+                tracing::warn!("⚠️  {}, rebuilding from {}", e, conversations_path);
+                Self::rebuild(index_path, conversations_path, config).await
+            }
+            Err(e) => {
+                tracing::warn!(
+                    "⚠️  Failed to open search index at {} ({}), rebuilding from {}",
+                    index_path,
+                    e,
+                    conversations_path
+                );
+                Self::rebuild(index_path, conversations_path, config).await
+            }
+        }
+    }
+
+    /// Delete the index at `index_path` and build it again from `conversations_path`
+    async fn rebuild(index_path: &str, conversations_path: &str, config: &AppConfig) -> Result<Self> {
+        if Path::new(index_path).exists() {
+            std::fs::remove_dir_all(index_path)?;
+        }
+        crate::indexer::build_index_with_config(conversations_path, index_path, config).await?;
+        tracing::info!("✅ Search index rebuilt");
+
+        Self::with_config(index_path, config)
+    }
+
+    /// Whether the index has a document per message, which
+    /// [`search_messages`](Self::search_messages) needs
+    pub fn indexes_messages(&self) -> bool {
+        self.messages
+    }
+
+    /// Number of documents in the index, one per conversation plus one per
+    /// message with `index_messages`; 0 until something was imported
+    pub fn num_docs(&self) -> Result<u64> {
+        let reader: tantivy::IndexReader = self
+            .index
+            .reader_builder()
+            .reload_policy(ReloadPolicy::OnCommitWithDelay)
+            .try_into()?;
+        Ok(reader.searcher().num_docs())
+    }
+
     pub fn search(&self, query_str: &str, limit: usize) -> Result<Vec<SearchResult>> {
+        self.search_with_stats(query_str, limit, &SearchOptions::default())
+            .map(|(results, _)| results)
+    }
+
+    /// One page of results: the `limit` best after skipping `offset`, with
+    /// the number of documents matching the query
+    pub fn search_paged(&self, query_str: &str, limit: usize, offset: usize) -> Result<(Vec<SearchResult>, usize)> {
+        let options = SearchOptions {
+            offset,
+            ..SearchOptions::default()
+        };
+        self.search_with_stats(query_str, limit, &options)
+            .map(|(results, stats)| (results, stats.total_hits))
+    }
+
+    /// Like [`search`](Self::search) with options, also returning timing and segment counts
+    pub fn search_with_stats(
+        &self,
+        query_str: &str,
+        limit: usize,
+        options: &SearchOptions,
+    ) -> Result<(Vec<SearchResult>, SearchStats)> {
+        let mut results = Vec::new();
+        let stats = self.search_each(query_str, limit, options, |result| {
+            results.push(result);
+            true
+        })?;
+
+        Ok((results, stats))
+    }
+
+    /// Run a search, handing each result to `on_result` as soon as its
+    /// document is loaded. Returning `false` from the callback stops early.
+    ///
+    /// An empty query matches every conversation, most recently created first.
+    pub fn search_each(
+        &self,
+        query_str: &str,
+        limit: usize,
+        options: &SearchOptions,
+        mut on_result: impl FnMut(SearchResult) -> bool,
+    ) -> Result<SearchStats> {
+        self.check_window(limit, options.offset)?;
         let reader = self
             .index
             .reader_builder()
@@ -52,64 +294,806 @@ impl SearchEngine {
         let conversation_id = self.schema.get_field("conversation_id").unwrap();
         let title_field = self.schema.get_field("title").unwrap();
         let content_field = self.schema.get_field("content").unwrap();
-        let date_field = self.schema.get_field("date").unwrap();
 
         // BLAZING FAST ngram search - работает с 2 символов!
         // Ngram tokenizer сам разобьёт "гр" на биграммы и найдёт "гравитация"
-        let mut query_parser = QueryParser::for_index(&self.index, vec![title_field, content_field]);
-        query_parser.set_field_boost(title_field, 2.0); // Boost title results
+        let mut query_parser = match options.role.role() {
+            // Only what one side wrote: no titles or summaries either
+            Some(Role::User) => QueryParser::for_index(&self.index, vec![self.schema.get_field("request_content")?]),
+            Some(Role::Assistant) => QueryParser::for_index(&self.index, vec![self.schema.get_field("response_content")?]),
+            None => {
+                let mut search_fields = vec![title_field, content_field];
+                // Indexes built before summaries existed don't have the field
+                let summary_field = self.schema.get_field("summary").ok();
+                search_fields.extend(summary_field);
+
+                let mut query_parser = QueryParser::for_index(&self.index, search_fields);
+                query_parser.set_field_boost(title_field, self.title_boost);
+                query_parser.set_field_boost(content_field, self.content_boost);
+                if let Some(summary_field) = summary_field {
+                    query_parser.set_field_boost(summary_field, 1.5);
+                }
+                query_parser
+            }
+        };
+        // Whole words: every word of the query must be there ("quotes" for a phrase)
+        if self.tokenizer == IndexTokenizer::Words {
+            query_parser.set_conjunction_by_default();
+        }
         
+        let query_start = std::time::Instant::now();
+        // Nothing to match: every conversation (only conversation documents have a message count)
+        let recent = query_str.trim().is_empty();
+        let query: Box<dyn Query> = if recent {
+            Box::new(ExistsQuery::new_exists_query("message_count".to_string()))
+        } else if options.role != RoleFilter::Any {
+            // The whole-word fields mix both sides, so no phrases or ranking by them
+            query_parser.parse_query(&query_str.to_lowercase())?
+        } else {
+            self.parse_query(&query_parser, query_str)?
+        };
+        let query = filter(query, options);
+        let highlighter = SnippetQuery::new(&searcher, content_field, query_str, self.tokenizer, self.ngram_max)?;
+
+        // Search: (score, document, matching messages in its conversation)
+        let hits: Vec<(f32, TantivyDocument, Option<usize>)> = if recent {
+            // Newest first, unscored
+            searcher
+                .search(
+                    &query,
+                    &TopDocs::with_limit(limit)
+                        .and_offset(options.offset)
+                        .order_by_fast_field::<tantivy::DateTime>("created", Order::Desc),
+                )?
+                .into_iter()
+                .map(|(_, address)| Ok((0.0, searcher.doc(address)?, None)))
+                .collect::<Result<_>>()?
+        } else if options.group_by_conversation {
+            // Per-message documents only exist in indexes built with `index_messages`
+            let message_query = match self.schema.get_field("message") {
+                Ok(message_field) if self.messages => {
+                    let mut message_parser = QueryParser::for_index(&self.index, vec![message_field]);
+                    if self.tokenizer == IndexTokenizer::Words {
+                        message_parser.set_conjunction_by_default();
+                    }
+                    let message_query = message_parser.parse_query(&query_str.to_lowercase())?;
+                    Some(self.by_role(message_query, options.role)?)
+                }
+                _ => None,
+            };
+            self.grouped_hits(
+                &searcher,
+                query.as_ref(),
+                message_query.as_deref(),
+                limit,
+                options.offset,
+                conversation_id,
+            )?
+        } else {
+            searcher
+                .search(&query, &TopDocs::with_limit(limit).and_offset(options.offset))?
+                .into_iter()
+                .map(|(score, address)| Ok((score, searcher.doc(address)?, None)))
+                .collect::<Result<_>>()?
+        };
+        let total_hits = searcher.search(&query, &Count)?;
+
+        let mut stats = SearchStats {
+            results: 0,
+            segments: searcher.segment_readers().len(),
+            query_ms: query_start.elapsed().as_millis(),
+            total_hits,
+        };
+
+        // Hits come best first; past the first page the best one isn't among them
+        let max_score = if options.normalize_scores && options.offset > 0 {
+            searcher
+                .search(&query, &TopDocs::with_limit(1))?
+                .first()
+                .map(|(score, _)| *score)
+                .unwrap_or_default()
+        } else {
+            hits.first().map(|(score, _, _)| *score).unwrap_or_default()
+        };
+
+        // Hand out results one by one
+        for (score, doc, match_count) in hits {
+            stats.results += 1;
+            let mut result = self.to_result(&doc, score, match_count, Some(&highlighter));
+            if options.normalize_scores {
+                result.normalized_score = Some(if max_score > 0.0 { score / max_score } else { 1.0 });
+            }
+            let keep_going = on_result(result);
+            if !keep_going {
+                break;
+            }
+        }
+
+        Ok(stats)
+    }
+
+    /// Search single messages instead of whole conversations: one result per
+    /// matching message, best first, with its
+    /// [`message_index`](SearchResult::message_index) and a snippet of that
+    /// message. `offset`, `normalize_scores` and `role` apply as in
+    /// [`search_with_stats`](Self::search_with_stats); grouping and the
+    /// conversation filters don't.
+    ///
+    /// Fails unless the index was built with `index_messages`.
+    pub fn search_messages(
+        &self,
+        query_str: &str,
+        limit: usize,
+        options: &SearchOptions,
+    ) -> Result<(Vec<SearchResult>, SearchStats)> {
+        if !self.messages {
+            anyhow::bail!("The search index has no message documents; set index_messages and rebuild it");
+        }
+        self.check_window(limit, options.offset)?;
+        let reader: tantivy::IndexReader = self
+            .index
+            .reader_builder()
+            .reload_policy(ReloadPolicy::OnCommitWithDelay)
+            .try_into()?;
+        let searcher = reader.searcher();
+
+        let conversation_id = self.schema.get_field("conversation_id")?;
+        let message_field = self.schema.get_field("message")?;
+        let message_index = self.schema.get_field("message_index")?;
+        let message_url = self.schema.get_field("message_url")?;
+
+        let query_start = std::time::Instant::now();
+        let mut query_parser = QueryParser::for_index(&self.index, vec![message_field]);
+        if self.tokenizer == IndexTokenizer::Words {
+            query_parser.set_conjunction_by_default();
+        }
+        let query = self.by_role(query_parser.parse_query(&query_str.to_lowercase())?, options.role)?;
+        let top_docs = searcher.search(&query, &TopDocs::with_limit(limit).and_offset(options.offset))?;
+        let stats = SearchStats {
+            results: top_docs.len(),
+            segments: searcher.segment_readers().len(),
+            query_ms: query_start.elapsed().as_millis(),
+            total_hits: searcher.search(&query, &Count)?,
+        };
+
+        let max_score = if options.offset > 0 {
+            searcher
+                .search(&query, &TopDocs::with_limit(1))?
+                .first()
+                .map(|(score, _)| *score)
+                .unwrap_or_default()
+        } else {
+            top_docs.first().map(|(score, _)| *score).unwrap_or_default()
+        };
+        let highlighter = SnippetQuery::new(&searcher, message_field, query_str, self.tokenizer, self.ngram_max)?;
+
+        // Message documents only have the text; title and date come from their conversation's
+        let mut conversations: HashMap<String, Option<TantivyDocument>> = HashMap::new();
+        let mut results = Vec::with_capacity(top_docs.len());
+        for (score, address) in top_docs {
+            let doc: TantivyDocument = searcher.doc(address)?;
+            let id = doc.get_first(conversation_id).and_then(|v| v.as_str()).unwrap_or("");
+            if !conversations.contains_key(id) {
+                let conversation = self.conversation_doc(&searcher, conversation_id, id)?;
+                conversations.insert(id.to_string(), conversation);
+            }
+
+            let message = doc.get_first(message_field).and_then(|v| v.as_str()).unwrap_or("");
+            let window = SnippetWindow::new(message, query_str);
+            let mut result = match &conversations[id] {
+                Some(conversation) => self.to_result(conversation, score, None, None),
+                None => self.to_result(&doc, score, None, None),
+            };
+            result.snippet = window.to_text();
+            result.snippet_html = window.to_html(Some(&highlighter));
+            result.message_index = doc.get_first(message_index).and_then(|v| v.as_u64());
+            if let Some(url) = doc.get_first(message_url).and_then(|v| v.as_str()) {
+                result.url = url.to_string();
+            }
+            if options.normalize_scores {
+                result.normalized_score = Some(if max_score > 0.0 { score / max_score } else { 1.0 });
+            }
+            results.push(result);
+        }
+
+        Ok((results, stats))
+    }
+
+    /// Tantivy allocates room for `limit + offset` hits up front, so a huge
+    /// offset would abort the process rather than fail the search
+    fn check_window(&self, limit: usize, offset: usize) -> Result<()> {
+        if offset > self.max_offset {
+            anyhow::bail!("offset {} is past the {} results a search may skip", offset, self.max_offset);
+        }
+        limit
+            .checked_add(offset)
+            .map(|_| ())
+            .ok_or_else(|| anyhow::anyhow!("limit {} and offset {} are too large", limit, offset))
+    }
+
+    /// `query` over message documents, restricted to those of `role`
+    fn by_role(&self, query: Box<dyn Query>, role: RoleFilter) -> Result<Box<dyn Query>> {
+        let Some(role) = role.role() else {
+            return Ok(query);
+        };
+        let role_field = self.schema.get_field("role")?;
+        Ok(Box::new(BooleanQuery::new(vec![
+            (Occur::Must, query),
+            (
+                Occur::Must,
+                Box::new(TermQuery::new(
+                    Term::from_field_text(role_field, role_name(role)),
+                    IndexRecordOption::Basic,
+                )),
+            ),
+        ])))
+    }
+
+    /// The conversation document (not a message one) of conversation `id`
+    fn conversation_doc(&self, searcher: &Searcher, conversation_id: Field, id: &str) -> Result<Option<TantivyDocument>> {
+        let query = BooleanQuery::new(vec![
+            (
+                Occur::Must,
+                Box::new(TermQuery::new(
+                    Term::from_field_text(conversation_id, id),
+                    IndexRecordOption::Basic,
+                )) as Box<dyn Query>,
+            ),
+            // Only conversation documents have a message count
+            (Occur::Must, Box::new(ExistsQuery::new_exists_query("message_count".to_string()))),
+        ]);
+        searcher
+            .search(&query, &TopDocs::with_limit(1))?
+            .first()
+            .map(|&(_, address)| searcher.doc(address))
+            .transpose()
+            .map_err(Into::into)
+    }
+
+    /// Best-scoring document of each of the top `limit` conversations after
+    /// skipping the best `offset`, with the number of the conversation's
+    /// messages matching `message_query` if there is one.
+    fn grouped_hits(
+        &self,
+        searcher: &Searcher,
+        query: &dyn Query,
+        message_query: Option<&dyn Query>,
+        limit: usize,
+        offset: usize,
+        conversation_id: Field,
+    ) -> Result<Vec<(f32, TantivyDocument, Option<usize>)>> {
+        // The same conversation can appear more than once (merged exports), so
+        // fetch more top documents until there are `limit` distinct ones
+        let wanted = limit
+            .checked_add(offset)
+            .ok_or_else(|| anyhow::anyhow!("limit {} and offset {} are too large", limit, offset))?;
+        let mut fetch = wanted.max(1);
+        let mut groups: Vec<(String, f32, TantivyDocument)> = Vec::new();
+        loop {
+            let top_docs = searcher.search(query, &TopDocs::with_limit(fetch))?;
+            let exhausted = top_docs.len() < fetch;
+
+            groups.clear();
+            let mut seen = HashSet::new();
+            for (score, address) in top_docs {
+                let doc: TantivyDocument = searcher.doc(address)?;
+                let id = doc
+                    .get_first(conversation_id)
+                    .and_then(|v| v.as_str())
+                    .unwrap_or("")
+                    .to_string();
+                if seen.insert(id.clone()) {
+                    groups.push((id, score, doc));
+                }
+            }
+
+            if groups.len() >= wanted || exhausted {
+                break;
+            }
+            fetch *= 4;
+        }
+        groups.truncate(wanted);
+        groups.drain(..offset.min(groups.len()));
+
+        let Some(message_query) = message_query else {
+            return Ok(groups.into_iter().map(|(_, score, doc)| (score, doc, None)).collect());
+        };
+        groups
+            .into_iter()
+            .map(|(id, score, doc)| {
+                let in_conversation = BooleanQuery::new(vec![
+                    (Occur::Must, message_query.box_clone()),
+                    (
+                        Occur::Must,
+                        Box::new(TermQuery::new(
+                            Term::from_field_text(conversation_id, &id),
+                            IndexRecordOption::Basic,
+                        )),
+                    ),
+                ]);
+                let match_count = searcher.search(&in_conversation, &Count)?;
+                Ok((score, doc, Some(match_count)))
+            })
+            .collect()
+    }
+
+    /// Parse `query_str` for the ngram fields. In an ngram index the ngrams of
+    /// a "quoted phrase" would match its chars scattered anywhere, so phrases
+    /// are looked up in the whole-word fields instead, word for word.
+    fn parse_query(&self, query_parser: &QueryParser, query_str: &str) -> Result<Box<dyn Query>> {
+        let (phrases, rest) = split_phrases(query_str);
+        let word_fields = (self.schema.get_field("title_words"), self.schema.get_field("content_words"));
+        // Indexes built before these fields existed match phrases by ngrams
+        let (Ok(title_words), Ok(content_words)) = word_fields else {
+            return self.parse_terms(query_parser, query_str);
+        };
+        if self.tokenizer != IndexTokenizer::Ngram || phrases.is_empty() {
+            return self.parse_terms(query_parser, query_str);
+        }
+
+        let mut phrase_parser = QueryParser::for_index(&self.index, vec![title_words, content_words]);
+        phrase_parser.set_field_boost(title_words, self.title_boost);
+        phrase_parser.set_field_boost(content_words, self.content_boost);
+
+        let mut clauses = Vec::new();
+        if !rest.trim().is_empty() {
+            clauses.push((Occur::Must, self.parse_terms(query_parser, &rest)?));
+        }
+        for phrase in phrases {
+            let phrase = phrase_parser.parse_query(&format!("\"{}\"", phrase.to_lowercase()))?;
+            clauses.push((Occur::Must, phrase));
+        }
+        Ok(Box::new(BooleanQuery::new(clauses)))
+    }
+
+    fn parse_terms(&self, query_parser: &QueryParser, query_str: &str) -> Result<Box<dyn Query>> {
         let query = query_parser.parse_query(&query_str.to_lowercase())?;
+        Ok(self.rank_whole_words(query, query_str))
+    }
 
-        // Search
-        let top_docs = searcher.search(&query, &TopDocs::with_limit(limit))?;
+    /// Ngrams find every conversation containing the query anywhere, but rank
+    /// by how many ngrams match, so a long word that merely contains the query
+    /// can outrank the word itself. Keep `query` for recall and add the same
+    /// query over whole words as an optional, boosted clause for precision.
+    fn rank_whole_words(&self, query: Box<dyn Query>, query_str: &str) -> Box<dyn Query> {
+        if self.tokenizer != IndexTokenizer::Ngram || self.word_boost == 0.0 {
+            return query;
+        }
+        // Indexes built before these fields existed rank by ngrams alone
+        let (Ok(title_words), Ok(content_words)) =
+            (self.schema.get_field("title_words"), self.schema.get_field("content_words"))
+        else {
+            return query;
+        };
 
-        // Collect results
-        let mut results = Vec::new();
-        for (score, doc_address) in top_docs {
-            let retrieved_doc: tantivy::TantivyDocument = searcher.doc(doc_address)?;
-            
-            let conv_id = retrieved_doc
-                .get_first(conversation_id)
-                .and_then(|v| v.as_str())
-                .unwrap_or("")
-                .to_string();
-            
-            let title = retrieved_doc
-                .get_first(title_field)
+        let mut word_parser = QueryParser::for_index(&self.index, vec![title_words, content_words]);
+        word_parser.set_field_boost(title_words, self.title_boost);
+        word_parser.set_field_boost(content_words, self.content_boost);
+        // Whatever parsed for the ngram query is good enough here
+        let (word_query, _) = word_parser.parse_query_lenient(&query_str.to_lowercase());
+
+        Box::new(BooleanQuery::new(vec![
+            (Occur::Must, query),
+            (Occur::Should, Box::new(BoostQuery::new(word_query, self.word_boost))),
+        ]))
+    }
+
+    /// Metadata of every conversation in the index, in no particular order.
+    /// Read from the stored fields of the conversation documents, so it
+    /// doesn't need the generated pages.
+    pub fn conversation_metadata(&self) -> Result<Vec<ConversationMetadata>> {
+        let title_field = self.schema.get_field("title")?;
+        let reader: tantivy::IndexReader = self
+            .index
+            .reader_builder()
+            .reload_policy(ReloadPolicy::OnCommitWithDelay)
+            .try_into()?;
+        let searcher = reader.searcher();
+
+        let mut conversations = Vec::new();
+        for segment_reader in searcher.segment_readers() {
+            let store = segment_reader.get_store_reader(1)?;
+            for doc_id in segment_reader.doc_ids_alive() {
+                let doc: TantivyDocument = store.get(doc_id)?;
+                // Per-message documents have no title
+                if doc.get_first(title_field).is_none() {
+                    continue;
+                }
+                conversations.push(self.to_metadata(&doc));
+            }
+        }
+        Ok(conversations)
+    }
+
+    fn to_metadata(&self, doc: &TantivyDocument) -> ConversationMetadata {
+        let field = |name: &str| self.schema.get_field(name).ok();
+        let text = |name: &str| {
+            field(name)
+                .and_then(|field| doc.get_first(field))
                 .and_then(|v| v.as_str())
-                .unwrap_or("Untitled")
-                .to_string();
-            
-            let date = retrieved_doc
-                .get_first(date_field)
+                .filter(|text| !text.is_empty())
+                .map(str::to_string)
+        };
+
+        let result = self.to_result(doc, 0.0, None, None);
+        ConversationMetadata {
+            conversation_id: result.conversation_id,
+            title: result.title,
+            url: result.url,
+            inserted_at: text("date"),
+            updated_at: text("updated_at"),
+            message_count: field("message_count")
+                .and_then(|field| doc.get_first(field))
+                .and_then(|v| v.as_u64())
+                .unwrap_or(0),
+            tags: field("tags")
+                .map(|field| doc.get_all(field).filter_map(|v| v.as_str()).map(str::to_string).collect())
+                .unwrap_or_default(),
+        }
+    }
+
+    /// The result for `doc`, with a snippet around the first match of the query, if there is one
+    fn to_result(
+        &self,
+        doc: &TantivyDocument,
+        score: f32,
+        match_count: Option<usize>,
+        query: Option<&SnippetQuery>,
+    ) -> SearchResult {
+        let text = |name: &str| {
+            self.schema
+                .get_field(name)
+                .ok()
+                .and_then(|field| doc.get_first(field))
                 .and_then(|v| v.as_str())
                 .unwrap_or("")
-                .to_string();
+        };
 
-            // Create snippet (first 200 chars from content) - UTF-8 safe!
-            let content_text = retrieved_doc
-                .get_first(content_field)
-                .and_then(|v| v.as_str())
-                .unwrap_or("");
-            
-            let snippet = if content_text.chars().count() > 200 {
-                let truncated: String = content_text.chars().take(200).collect();
-                format!("{}...", truncated)
-            } else {
-                content_text.to_string()
-            };
+        let window = SnippetWindow::new(text("content"), query.map_or("", |query| query.text));
+        let snippet = window.to_text();
+        let snippet_html = window.to_html(query);
+
+        let title = match text("title") {
+            "" => "Untitled",
+            title => title,
+        };
+
+        let conversation_id = text("conversation_id");
 
-            results.push(SearchResult {
-                conversation_id: conv_id,
-                title,
-                date,
-                score,
-                snippet,
-            });
+        SearchResult {
+            conversation_id: conversation_id.to_string(),
+            title: title.to_string(),
+            url: format!("/conversations/{}/", conversation_id),
+            date: text("date").to_string(),
+            score,
+            snippet,
+            snippet_html,
+            match_count,
+            normalized_score: None,
+            message_index: None,
         }
+    }
+
+    /// Conversations with a word in the title starting with `prefix`, best
+    /// match first; titles starting with it come before the rest. Unlike
+    /// [`search`](Self::search) only titles are looked at, and each title is
+    /// suggested once.
+    pub fn suggest_titles(&self, prefix: &str, limit: usize) -> Result<Vec<TitleSuggestion>> {
+        let prefix = prefix.trim().to_lowercase();
+        let title_field = self.schema.get_field("title")?;
+
+        // Every ngram of the prefix must be in the title. Built by hand
+        // rather than parsed, so `:` or quotes in a title aren't query syntax.
+        let mut analyzer = self.index.tokenizer_for_field(title_field)?;
+        let mut stream = analyzer.token_stream(&prefix);
+        let mut terms = Vec::new();
+        while stream.advance() {
+            terms.push(stream.token().text.clone());
+        }
+        // Shorter than the smallest ngram
+        if terms.is_empty() || limit == 0 {
+            return Ok(Vec::new());
+        }
+        let query: Box<dyn Query> = match self.tokenizer {
+            IndexTokenizer::Ngram => {
+                let terms: HashSet<String> = terms.into_iter().collect();
+                Box::new(BooleanQuery::new(
+                    terms
+                        .into_iter()
+                        .map(|text| {
+                            let term = Term::from_field_text(title_field, &text);
+                            let query: Box<dyn Query> = Box::new(TermQuery::new(term, IndexRecordOption::WithFreqs));
+                            (Occur::Must, query)
+                        })
+                        .collect(),
+                ))
+            }
+            // Whole words: the words typed so far, the last one possibly unfinished
+            IndexTokenizer::Words => Box::new(PhrasePrefixQuery::new(
+                terms.iter().map(|text| Term::from_field_text(title_field, text)).collect(),
+            )),
+        };
+
+        let reader = self
+            .index
+            .reader_builder()
+            .reload_policy(ReloadPolicy::OnCommitWithDelay)
+            .try_into()?;
+        let searcher = reader.searcher();
 
-        Ok(results)
+        // Ngrams match anywhere in the title, and titles repeat, so fetch
+        // more until there are `limit` that pass
+        let mut fetch = limit * 4;
+        loop {
+            let top_docs = searcher.search(&query, &TopDocs::with_limit(fetch))?;
+            let exhausted = top_docs.len() < fetch;
+
+            let mut seen = HashSet::new();
+            let mut suggestions = Vec::new();
+            for (_, address) in top_docs {
+                let doc: TantivyDocument = searcher.doc(address)?;
+                let result = self.to_result(&doc, 0.0, None, None);
+                let title = result.title.to_lowercase();
+                if word_starts_with(&title, &prefix) && seen.insert(title) {
+                    suggestions.push(TitleSuggestion {
+                        conversation_id: result.conversation_id,
+                        title: result.title,
+                        url: result.url,
+                    });
+                }
+            }
+
+            if suggestions.len() >= limit || exhausted {
+                // Stable, so the score order holds within each group
+                suggestions.sort_by_key(|s| !s.title.to_lowercase().starts_with(&prefix));
+                suggestions.truncate(limit);
+                return Ok(suggestions);
+            }
+            fetch *= 4;
+        }
     }
+
+    /// Complete `prefix` to whole words from titles and content, most
+    /// frequent first.
+    pub fn autocomplete(&self, prefix: &str, limit: usize) -> Result<Vec<String>> {
+        let prefix = prefix.trim().to_lowercase();
+
+        // Indexes built before the words field existed have nothing to offer
+        let words_field = match self.schema.get_field("words") {
+            Ok(field) if !prefix.is_empty() => field,
+            _ => return Ok(Vec::new()),
+        };
+
+        let reader = self
+            .index
+            .reader_builder()
+            .reload_policy(ReloadPolicy::OnCommitWithDelay)
+            .try_into()?;
+        let searcher = reader.searcher();
+
+        // Sum document frequencies across segments
+        let mut frequencies: HashMap<String, u32> = HashMap::new();
+        for segment_reader in searcher.segment_readers() {
+            let inverted_index = segment_reader.inverted_index(words_field)?;
+            let mut terms = inverted_index
+                .terms()
+                .range()
+                .ge(prefix.as_bytes())
+                .into_stream()?;
+
+            while terms.advance() {
+                if !terms.key().starts_with(prefix.as_bytes()) {
+                    break;
+                }
+                if let Ok(term) = std::str::from_utf8(terms.key()) {
+                    *frequencies.entry(term.to_string()).or_default() += terms.value().doc_freq;
+                }
+            }
+        }
+
+        let mut suggestions: Vec<(String, u32)> = frequencies.into_iter().collect();
+        suggestions.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+
+        Ok(suggestions
+            .into_iter()
+            .take(limit)
+            .map(|(term, _)| term)
+            .collect())
+    }
+}
+
+/// The "quoted phrases" of `query` (without quotes, empty ones dropped) and
+/// the rest of it. An unclosed quote counts as closed at the end.
+fn split_phrases(query: &str) -> (Vec<&str>, String) {
+    let mut phrases = Vec::new();
+    let mut rest = String::new();
+    for (idx, part) in query.split('"').enumerate() {
+        if idx.is_multiple_of(2) {
+            rest.push_str(part);
+            rest.push(' ');
+        } else if !part.trim().is_empty() {
+            phrases.push(part.trim());
+        }
+    }
+    (phrases, rest)
+}
+
+/// Keep only the conversations within the options' message count and date bounds
+fn filter(query: Box<dyn Query>, options: &SearchOptions) -> Box<dyn Query> {
+    let mut clauses: Vec<(Occur, Box<dyn Query>)> = Vec::new();
+    if options.min_messages.is_some() || options.max_messages.is_some() {
+        clauses.push((
+            Occur::Must,
+            Box::new(RangeQuery::new_u64_bounds(
+                "message_count".to_string(),
+                options.min_messages.map_or(Bound::Unbounded, Bound::Included),
+                options.max_messages.map_or(Bound::Unbounded, Bound::Included),
+            )),
+        ));
+    }
+    if options.from.is_some() || options.to.is_some() {
+        // Creation dates are indexed to the second
+        let bound = |date: Option<DateTime<Utc>>| {
+            date.map_or(Bound::Unbounded, |date| {
+                Bound::Included(tantivy::DateTime::from_timestamp_secs(date.timestamp()))
+            })
+        };
+        clauses.push((
+            Occur::Must,
+            Box::new(RangeQuery::new_date_bounds("created".to_string(), bound(options.from), bound(options.to))),
+        ));
+    }
+    if clauses.is_empty() {
+        return query;
+    }
+    clauses.insert(0, (Occur::Must, query));
+    Box::new(BooleanQuery::new(clauses))
+}
+
+/// Longest snippet, in chars (not counting the "..." around it)
+const SNIPPET_CHARS: usize = 200;
+
+/// Chars a snippet shows before the match
+const SNIPPET_CONTEXT_CHARS: usize = 100;
+
+/// What a search's snippets are centered on and highlight
+struct SnippetQuery<'a> {
+    text: &'a str,
+    generator: SnippetGenerator,
+}
+
+impl<'a> SnippetQuery<'a> {
+    /// The query's words as `content` terms. The parsed query would do, but
+    /// in an ngram index it also has every bigram of the words, which would
+    /// light up all over the snippet; a word's own ngram (or, for a word
+    /// longer than any ngram, its longest ones) matches only where the word is.
+    /// A whole-word index has the words themselves, as its tokenizer splits them.
+    /// `ngram_max` is the longest ngram in the index.
+    fn new(
+        searcher: &Searcher,
+        content_field: Field,
+        text: &'a str,
+        index_tokenizer: IndexTokenizer,
+        ngram_max: usize,
+    ) -> Result<Self> {
+        let mut tokenizer = searcher.index().tokenizer_for_field(content_field)?;
+        let mut terms = BTreeMap::new();
+        for word in query_words(text) {
+            match index_tokenizer {
+                IndexTokenizer::Ngram => {
+                    let chars: Vec<char> = word.chars().collect();
+                    for window in chars.windows(chars.len().min(ngram_max)) {
+                        terms.insert(window.iter().collect(), 1.0);
+                    }
+                }
+                IndexTokenizer::Words => {
+                    let mut tokens = tokenizer.token_stream(&word);
+                    while let Some(token) = tokens.next() {
+                        terms.insert(token.text.clone(), 1.0);
+                    }
+                }
+            }
+        }
+
+        // One fragment: the whole snippet window
+        let generator = SnippetGenerator::new(terms, tokenizer, content_field, usize::MAX);
+        Ok(Self { text, generator })
+    }
+}
+
+/// Up to [`SNIPPET_CHARS`] of a result's content starting about
+/// [`SNIPPET_CONTEXT_CHARS`] before the first match of the query, or its
+/// start if the match is near the start (or there's none). Counted in
+/// chars, so it's UTF-8 safe.
+struct SnippetWindow {
+    text: String,
+    /// Whether content was cut off before and after `text`
+    before: bool,
+    after: bool,
 }
 
+impl SnippetWindow {
+    fn new(content: &str, query: &str) -> Self {
+        let chars: Vec<char> = content.chars().collect();
+        let start = match_position(&chars, query)
+            .map_or(0, |pos| pos.saturating_sub(SNIPPET_CONTEXT_CHARS))
+            // Don't show less than a full snippet near the end
+            .min(chars.len().saturating_sub(SNIPPET_CHARS));
+        let end = (start + SNIPPET_CHARS).min(chars.len());
+
+        Self {
+            text: chars[start..end].iter().collect(),
+            before: start > 0,
+            after: end < chars.len(),
+        }
+    }
+
+    fn to_text(&self) -> String {
+        self.decorate(self.text.clone())
+    }
+
+    fn to_html(&self, query: Option<&SnippetQuery>) -> String {
+        let mut snippet = match query {
+            Some(query) => query.generator.snippet(&self.text),
+            None => tantivy::snippet::Snippet::empty(),
+        };
+        if snippet.is_empty() {
+            return self.decorate(html_escape::encode_text(&self.text).into_owned());
+        }
+
+        snippet.set_snippet_prefix_postfix("<mark>", "</mark>");
+        // The fragment ends with the last token; the rest is only punctuation and spaces
+        let rest = &self.text[snippet.fragment().len()..];
+        self.decorate(format!("{}{}", snippet.to_html(), html_escape::encode_text(rest)))
+    }
+
+    fn decorate(&self, text: String) -> String {
+        format!(
+            "{}{}{}",
+            if self.before { "..." } else { "" },
+            text,
+            if self.after { "..." } else { "" }
+        )
+    }
+}
+
+/// Words of a search query, lowercase, without operators, field names and
+/// quotes; words shorter than two chars (shorter than any ngram) are left out
+fn query_words(query: &str) -> Vec<String> {
+    query
+        .split_whitespace()
+        .filter(|word| !matches!(*word, "AND" | "OR" | "NOT"))
+        // `field:word` searches `word`
+        .map(|word| word.rsplit_once(':').map_or(word, |(_, word)| word))
+        .map(|word| word.trim_matches(|c: char| !c.is_alphanumeric()).to_lowercase())
+        .filter(|word| word.chars().count() >= 2)
+        .collect()
+}
+
+/// Char index of the first occurrence of a word of `query` in `chars`,
+/// ignoring case. The index is of ngrams, so a word that isn't there as a
+/// whole may have matched by its start: then the longest start that is
+/// there, down to two chars, is looked for.
+fn match_position(chars: &[char], query: &str) -> Option<usize> {
+    let lowercase = |c: char| c.to_lowercase().next().unwrap_or(c);
+    let haystack: Vec<char> = chars.iter().map(|&c| lowercase(c)).collect();
+    let words: Vec<Vec<char>> = query_words(query)
+        .iter()
+        .map(|word| word.chars().collect())
+        .collect();
+
+    let longest = words.iter().map(Vec::len).max()?;
+    (2..=longest).rev().find_map(|len| {
+        words
+            .iter()
+            .filter(|word| word.len() >= len)
+            .filter_map(|word| haystack.windows(len).position(|window| window == &word[..len]))
+            .min()
+    })
+}
+
+/// Whether a word in `text` starts with `prefix` (both lowercase)
+fn word_starts_with(text: &str, prefix: &str) -> bool {
+    text.match_indices(prefix)
+        .any(|(idx, _)| text[..idx].chars().next_back().is_none_or(|c| !c.is_alphanumeric()))
+}
@@ -0,0 +1,117 @@
+use anyhow::{ensure, Context, Result};
+use std::path::Path;
+
+use deepseek_app::config::AppConfig;
+use deepseek_app::{generator, indexer, search::SearchEngine};
+
+/// Tiny export used to exercise the whole pipeline
+const SAMPLE_CONVERSATIONS: &str = r#"[
+    {
+        "id": "selftest-1",
+        "title": "Selftest conversation",
+        "inserted_at": "2024-01-01T10:00:00Z",
+        "updated_at": "2024-01-01T10:01:00Z",
+        "mapping": {
+            "root": { "children": ["msg1"] },
+            "msg1": {
+                "message": {
+                    "inserted_at": "2024-01-01T10:00:00Z",
+                    "fragments": [{ "type": "REQUEST", "content": "What is gravitation?" }]
+                },
+                "children": ["msg2"]
+            },
+            "msg2": {
+                "message": {
+                    "inserted_at": "2024-01-01T10:01:00Z",
+                    "fragments": [{ "type": "RESPONSE", "content": "Gravitation is the **attraction** between masses.\n\n```rust\nfn main() {}\n```" }]
+                },
+                "children": []
+            }
+        }
+    }
+]"#;
+
+/// Run the end-to-end self test, printing a line per check.
+///
+/// Returns `true` if every check passed.
+pub async fn run() -> Result<bool> {
+    let work_dir = std::env::temp_dir().join(format!("deepseek-viewer-selftest-{}", std::process::id()));
+    std::fs::create_dir_all(&work_dir).context("Failed to create self-test directory")?;
+
+    let passed = run_checks(&work_dir).await;
+
+    if let Err(e) = std::fs::remove_dir_all(&work_dir) {
+        tracing::warn!("Failed to clean up {}: {}", work_dir.display(), e);
+    }
+
+    passed
+}
+
+async fn run_checks(work_dir: &Path) -> Result<bool> {
+    let conversations_path = work_dir.join("conversations.json");
+    let index_path = work_dir.join("search_index");
+    let output_dir = work_dir.join("dist");
+    std::fs::write(&conversations_path, SAMPLE_CONVERSATIONS)?;
+
+    let conversations_path = conversations_path.to_string_lossy().to_string();
+    let index_path = index_path.to_string_lossy().to_string();
+
+    let mut all_passed = true;
+    let mut report = |name: &str, result: Result<()>| {
+        match result {
+            Ok(()) => println!("✅ PASS  {}", name),
+            Err(e) => {
+                println!("❌ FAIL  {}: {:#}", name, e);
+                all_passed = false;
+            }
+        }
+    };
+
+    let indexed = indexer::build_index(&conversations_path, &index_path).await;
+    let index_ok = indexed.is_ok();
+    report("build search index", indexed);
+
+    if index_ok {
+        report("search finds sample conversation", (|| {
+            let engine = SearchEngine::new(&index_path)?;
+            let results = engine.search("gravitation", 10)?;
+            ensure!(
+                results.iter().any(|r| r.conversation_id == "selftest-1"),
+                "expected selftest-1 in {} results",
+                results.len()
+            );
+            Ok(())
+        })());
+
+        report("search ignores unknown terms", (|| {
+            let engine = SearchEngine::new(&index_path)?;
+            let results = engine.search("zzqqxxjj", 10)?;
+            ensure!(results.is_empty(), "expected no results, got {}", results.len());
+            Ok(())
+        })());
+    }
+
+    let generated = generator::generate_site(&conversations_path, &output_dir.to_string_lossy(), &AppConfig::default()).await;
+    let site_ok = generated.is_ok();
+    report("generate HTML site", generated);
+
+    if site_ok {
+        report("render conversation page", (|| {
+            let page = std::fs::read_to_string(output_dir.join("conversations/selftest-1/index.html"))
+                .context("conversation page missing")?;
+            ensure!(page.contains("Selftest conversation"), "title missing from page");
+            ensure!(page.contains("<strong>attraction</strong>"), "markdown not rendered");
+            ensure!(page.contains("code-block-wrapper"), "code block not highlighted");
+            Ok(())
+        })());
+
+        report("write index page and assets", (|| {
+            for file in ["index.html", "assets/css/main.css", "assets/css/syntax.css", "assets/js/search.js", "assets/js/conversation-actions.js", "assets/js/scroll.js", "assets/js/index-filter.js", "manifest.webmanifest", "favicon.svg", "sw.js"] {
+                ensure!(output_dir.join(file).exists(), "{} missing", file);
+            }
+            Ok(())
+        })());
+    }
+
+    Ok(all_passed)
+}
@@ -1,34 +1,301 @@
 use axum::{
-    extract::{Query, State},
-    http::StatusCode,
-    response::IntoResponse,
-    routing::get,
+    body::{Body, Bytes},
+    extract::{
+        ws::{Message as WsMessage, WebSocket, WebSocketUpgrade},
+        Path, Query, State,
+    },
+    http::{header, HeaderMap, StatusCode},
+    response::{
+        sse::{Event, KeepAlive, Sse},
+        Html, IntoResponse, Redirect,
+    },
+    routing::{get, post},
     Json, Router,
 };
-use serde::{Deserialize, Serialize};
-use std::{net::SocketAddr, sync::Arc};
+use chrono::{DateTime, NaiveDate, TimeZone, Utc};
+use chrono_tz::Tz;
+use serde::{Deserialize, Deserializer, Serialize};
+use std::{
+    convert::Infallible,
+    io::{self, BufWriter, Write},
+    net::SocketAddr,
+    path::PathBuf,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+use tokio_stream::{wrappers::ReceiverStream, Stream};
 use tower_http::{
-    compression::CompressionLayer,
+    compression::{
+        predicate::{DefaultPredicate, NotForContentType, Predicate, SizeAbove},
+        CompressionLayer,
+    },
     cors::CorsLayer,
     services::ServeDir,
 };
 
-use deepseek_app::search::{SearchEngine, SearchResult};
+use crate::archive::{self, ArchiveScope};
+use crate::code_export;
+use crate::config::{AppConfig, EmptySearch, Language, MathRender, BODY_FONTS, CODE_FONTS};
+use crate::favorites::{self, FavoritesFormat};
+use crate::search::{ConversationMetadata, RoleFilter, SearchEngine, SearchOptions, SearchResult, TitleSuggestion};
+use crate::terms::{self, TermCount};
+use crate::{export, generator, model, shortlinks};
+
+/// Responses smaller than this aren't worth gzipping
+const COMPRESSION_MIN_BYTES: u16 = 1024;
+
+/// ZIP exports are sent to the client in chunks of about this size
+const ARCHIVE_CHUNK_BYTES: usize = 64 * 1024;
 
 #[derive(Clone)]
 struct AppState {
     search_engine: Arc<SearchEngine>,
+    output_dir: String,
+    config: Arc<AppConfig>,
+    /// Config file `PATCH /api/config` saves to, if there is one
+    config_path: Option<PathBuf>,
+}
+
+impl AppState {
+    /// Clamp a requested limit so a client can't make tantivy collect a huge TopDocs
+    fn search_limit(&self, requested: Option<usize>) -> usize {
+        requested
+            .unwrap_or(self.config.search_default_limit)
+            .clamp(1, self.config.search_max_limit.max(1))
+    }
+
+    /// Refuse an offset past `search_max_offset`: tantivy would try to
+    /// allocate room for that many hits and abort the whole server
+    fn search_offset(&self, requested: usize) -> Result<usize, (StatusCode, &'static str)> {
+        if requested > self.config.search_max_offset {
+            return Err((StatusCode::BAD_REQUEST, "offset too large"));
+        }
+        Ok(requested)
+    }
+
+    /// Wait for `search` for at most `search_timeout_ms`; `None` if it takes longer
+    async fn search_in_time<T>(&self, query: &str, search: impl std::future::Future<Output = T>) -> Option<T> {
+        match self.config.search_timeout_ms {
+            0 => Some(search.await),
+            ms => match tokio::time::timeout(Duration::from_millis(ms), search).await {
+                Ok(outcome) => Some(outcome),
+                Err(_) => {
+                    // The search itself can't be interrupted and finishes in the background
+                    tracing::warn!(query = %query, timeout_ms = ms, "Search timed out");
+                    None
+                }
+            },
+        }
+    }
+
+    /// The `saved` settings, and whether the running site was generated with others
+    fn config_response(&self, saved: &AppConfig) -> ConfigResponse {
+        let settings = Settings::of(saved);
+        let regenerate_required = settings != Settings::of(&self.config);
+        ConfigResponse { settings, regenerate_required }
+    }
+
+    /// Whether to search for `query`: an empty one lists recent conversations
+    /// only if `empty_search` says so
+    fn accepts_query(&self, query: &str) -> bool {
+        !query.trim().is_empty() || self.config.empty_search == EmptySearch::Recent
+    }
 }
 
 #[derive(Debug, Deserialize)]
 struct SearchQuery {
     q: String,
-    #[serde(default = "default_limit")]
+    limit: Option<usize>,
+    /// One result per conversation, with a count of its matching messages if
+    /// the index has message documents
+    #[serde(default)]
+    group_by_conversation: bool,
+    /// One result per matching message, with its `message_index` and a `url`
+    /// linking to it; needs an index built with `index_messages`
+    #[serde(default)]
+    by_message: bool,
+    /// Add a 0–1 `normalized_score` to every result
+    #[serde(default)]
+    normalize_scores: bool,
+    /// Skip this many of the best results, to page through them
+    #[serde(default)]
+    offset: usize,
+    /// Only conversations with at least this many messages
+    min_messages: Option<u64>,
+    /// Only conversations with at most this many messages
+    max_messages: Option<u64>,
+    /// Only conversations created since this date
+    from: Option<DateParam>,
+    /// Only conversations created until this date
+    to: Option<DateParam>,
+    /// Match only your prompts (`user`) or the model's responses (`assistant`)
+    #[serde(default)]
+    role: RoleFilter,
+}
+
+impl SearchQuery {
+    /// Whole-day `from`/`to` dates are days in `tz`
+    fn options(&self, tz: Tz) -> SearchOptions {
+        SearchOptions {
+            group_by_conversation: self.group_by_conversation,
+            normalize_scores: self.normalize_scores,
+            offset: self.offset,
+            min_messages: self.min_messages,
+            max_messages: self.max_messages,
+            from: self.from.map(|from| from.start(tz)),
+            to: self.to.map(|to| to.end(tz)),
+            role: self.role,
+        }
+    }
+}
+
+/// A search's `from`/`to`: an RFC 3339 timestamp, or a whole day as `YYYY-MM-DD`
+#[derive(Debug, Clone, Copy)]
+enum DateParam {
+    Instant(DateTime<Utc>),
+    Day(NaiveDate),
+}
+
+impl DateParam {
+    /// The first second it covers
+    fn start(self, tz: Tz) -> DateTime<Utc> {
+        match self {
+            DateParam::Instant(instant) => instant,
+            DateParam::Day(day) => start_of_day(day, tz),
+        }
+    }
+
+    /// The last second it covers
+    fn end(self, tz: Tz) -> DateTime<Utc> {
+        match self {
+            DateParam::Instant(instant) => instant,
+            DateParam::Day(day) => start_of_day(day.succ_opt().unwrap_or(day), tz) - chrono::Duration::seconds(1),
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for DateParam {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let value = String::deserialize(deserializer)?;
+        if let Ok(instant) = DateTime::parse_from_rfc3339(&value) {
+            return Ok(DateParam::Instant(instant.with_timezone(&Utc)));
+        }
+        NaiveDate::parse_from_str(&value, "%Y-%m-%d")
+            .map(DateParam::Day)
+            .map_err(|_| serde::de::Error::custom(format!("expected a date like 2024-01-31, got '{}'", value)))
+    }
+}
+
+/// Midnight of `day` in `tz` (UTC midnight if the clocks skip it there)
+fn start_of_day(day: NaiveDate, tz: Tz) -> DateTime<Utc> {
+    let midnight = day.and_time(chrono::NaiveTime::MIN);
+    tz.from_local_datetime(&midnight)
+        .earliest()
+        .map(|start| start.with_timezone(&Utc))
+        .unwrap_or_else(|| Utc.from_utc_datetime(&midnight))
+}
+
+#[derive(Debug, Deserialize)]
+struct AutocompleteQuery {
+    prefix: String,
+    #[serde(default = "default_autocomplete_limit")]
+    limit: usize,
+}
+
+fn default_autocomplete_limit() -> usize {
+    10
+}
+
+#[derive(Debug, Deserialize)]
+struct TermsQuery {
+    #[serde(default = "default_terms_limit")]
     limit: usize,
 }
 
-fn default_limit() -> usize {
-    20
+fn default_terms_limit() -> usize {
+    50
+}
+
+/// Most terms `/api/conversation/:id/terms` returns
+const MAX_TERMS: usize = 500;
+
+/// Column `/api/conversations/meta` sorts by
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum MetaSort {
+    #[default]
+    InsertedAt,
+    UpdatedAt,
+    Title,
+    MessageCount,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum SortOrder {
+    Asc,
+    #[default]
+    Desc,
+}
+
+#[derive(Debug, Deserialize)]
+struct ConversationsMetaQuery {
+    #[serde(default)]
+    sort: MetaSort,
+    #[serde(default)]
+    order: SortOrder,
+    #[serde(default)]
+    offset: usize,
+    #[serde(default = "default_meta_limit")]
+    limit: usize,
+}
+
+fn default_meta_limit() -> usize {
+    50
+}
+
+/// Most conversations `/api/conversations/meta` returns at once
+const MAX_META_LIMIT: usize = 1000;
+
+#[derive(Debug, Serialize)]
+struct ConversationsMetaResponse {
+    /// Conversations in the index, not just on this page
+    total: usize,
+    offset: usize,
+    limit: usize,
+    conversations: Vec<ConversationMetadata>,
+}
+
+#[derive(Debug, Deserialize)]
+struct FavoritesExportQuery {
+    #[serde(default)]
+    format: FavoritesFormat,
+}
+
+#[derive(Debug, Deserialize)]
+struct ExportZipQuery {
+    #[serde(default)]
+    scope: ArchiveScope,
+}
+
+/// Hands what the ZIP writer produces to the response body
+struct ChannelWriter(tokio::sync::mpsc::Sender<io::Result<Bytes>>);
+
+impl Write for ChannelWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0
+            .blocking_send(Ok(Bytes::copy_from_slice(buf)))
+            // The client has gone away
+            .map_err(|_| io::Error::from(io::ErrorKind::BrokenPipe))?;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
 }
 
 #[derive(Debug, Serialize)]
@@ -36,7 +303,45 @@ struct SearchResponse {
     query: String,
     results: Vec<SearchResult>,
     total: usize,
+    limit: usize,
+    offset: usize,
+    /// Results there are in all, for page numbers
+    total_hits: usize,
+    /// Same as `total_ms`, kept for existing clients
     time_ms: u128,
+    /// Time the engine spent running the query
+    query_ms: u128,
+    /// Time spent in the handler, including loading the matched documents
+    total_ms: u128,
+    /// Index segments searched
+    segments: usize,
+}
+
+/// A query sent over `/ws/search`; plain text frames are treated as `q`
+#[derive(Debug, Deserialize)]
+struct WsSearchRequest {
+    seq: Option<u64>,
+    q: String,
+    limit: Option<usize>,
+    #[serde(default)]
+    offset: usize,
+    #[serde(default)]
+    group_by_conversation: bool,
+    #[serde(default)]
+    normalize_scores: bool,
+}
+
+/// Results for one `/ws/search` query, tagged with the request's sequence number
+#[derive(Debug, Serialize)]
+struct WsSearchResponse {
+    seq: u64,
+    query: String,
+    results: Vec<SearchResult>,
+    total: usize,
+    time_ms: u128,
+    /// Why there are no results, if the query was refused or timed out
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<&'static str>,
 }
 
 #[derive(Debug, Serialize)]
@@ -45,6 +350,110 @@ struct HealthResponse {
     version: String,
 }
 
+/// What `/api/ready` found; the server is ready once both are true
+#[derive(Debug, Serialize)]
+struct ReadyResponse {
+    ready: bool,
+    /// `index.html` exists in the output directory
+    site: bool,
+    /// Documents in the search index: one per conversation, plus one per
+    /// message with `index_messages`
+    documents: u64,
+}
+
+/// The settings `/settings/` can change; all of them only reach the pages
+/// once the site is generated again
+#[derive(Debug, PartialEq, Serialize)]
+struct Settings {
+    language: Language,
+    math_render: MathRender,
+    body_font: String,
+    code_font: String,
+    syntax_theme: String,
+    messages_per_page: usize,
+    archive_page_conversations: usize,
+    index_preview_chars: usize,
+}
+
+impl Settings {
+    fn of(config: &AppConfig) -> Self {
+        Self {
+            language: config.language,
+            math_render: config.math_render,
+            body_font: font_name(BODY_FONTS, config.body_font.as_deref()),
+            code_font: font_name(CODE_FONTS, config.code_font.as_deref()),
+            syntax_theme: generator::syntax_theme_name(config),
+            messages_per_page: config.messages_per_page,
+            archive_page_conversations: config.archive_page_conversations,
+            index_preview_chars: config.index_preview_chars,
+        }
+    }
+}
+
+/// `PATCH /api/config` body: the settings to change, the others are kept
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct SettingsPatch {
+    language: Option<Language>,
+    math_render: Option<MathRender>,
+    body_font: Option<String>,
+    code_font: Option<String>,
+    syntax_theme: Option<String>,
+    messages_per_page: Option<usize>,
+    archive_page_conversations: Option<usize>,
+    index_preview_chars: Option<usize>,
+}
+
+impl SettingsPatch {
+    fn apply(self, config: &mut AppConfig) -> Result<(), &'static str> {
+        if let Some(font) = self.body_font {
+            if !is_font(BODY_FONTS, &font) {
+                return Err("unknown body_font");
+            }
+            config.body_font = Some(font);
+        }
+        if let Some(font) = self.code_font {
+            if !is_font(CODE_FONTS, &font) {
+                return Err("unknown code_font");
+            }
+            config.code_font = Some(font);
+        }
+        if let Some(theme) = self.syntax_theme {
+            if !generator::syntax_themes().contains(&theme) {
+                return Err("unknown syntax_theme");
+            }
+            config.syntax_theme = Some(theme);
+        }
+        config.language = self.language.unwrap_or(config.language);
+        config.math_render = self.math_render.unwrap_or(config.math_render);
+        config.messages_per_page = self.messages_per_page.unwrap_or(config.messages_per_page);
+        config.archive_page_conversations = self
+            .archive_page_conversations
+            .unwrap_or(config.archive_page_conversations);
+        config.index_preview_chars = self.index_preview_chars.unwrap_or(config.index_preview_chars);
+        Ok(())
+    }
+}
+
+fn is_font(fonts: &[(&str, &str)], name: &str) -> bool {
+    fonts.iter().any(|(font, _)| font.eq_ignore_ascii_case(name))
+}
+
+/// The configured font if it's allowed, the default (first) one otherwise
+fn font_name(fonts: &[(&str, &str)], name: Option<&str>) -> String {
+    name.filter(|name| is_font(fonts, name))
+        .unwrap_or(fonts[0].0)
+        .to_string()
+}
+
+#[derive(Debug, Serialize)]
+struct ConfigResponse {
+    /// As saved in the config file
+    settings: Settings,
+    /// The saved settings differ from the ones the running site was generated with
+    regenerate_required: bool,
+}
+
 #[derive(Debug, Serialize)]
 struct ConversationMeta {
     id: String,
@@ -52,39 +461,98 @@ struct ConversationMeta {
     url: String,
 }
 
-pub async fn serve(addr: SocketAddr, search_engine: SearchEngine, output_dir: &str) -> anyhow::Result<()> {
+pub async fn serve(
+    addr: SocketAddr,
+    search_engine: SearchEngine,
+    output_dir: &str,
+    config: AppConfig,
+) -> anyhow::Result<()> {
+    let app = router(search_engine, output_dir, config);
+
+    // Run server
+    let listener = tokio::net::TcpListener::bind(addr).await?;
+    tracing::info!("🚀 Server listening on http://{}", addr);
+    tracing::info!("📁 Serving static files from {}/", output_dir);
+    tracing::info!("🔍 Search API available at http://{}/api/search?q=<query>", addr);
+    
+    axum::serve(listener, app).await?;
+
+    Ok(())
+}
+
+/// Build the application router (API routes plus the generated site)
+pub fn router(search_engine: SearchEngine, output_dir: &str, config: AppConfig) -> Router {
+    let config_path = AppConfig::config_file_path()
+        .map_err(|e| tracing::warn!("⚠️  Settings can't be saved: {:#}", e))
+        .ok();
+    router_with_config_file(search_engine, output_dir, config, config_path)
+}
+
+/// Like [`router`], with `/api/config` reading and saving `config_path`
+/// instead of the user's config file
+pub fn router_with_config_file(
+    search_engine: SearchEngine,
+    output_dir: &str,
+    config: AppConfig,
+    config_path: Option<PathBuf>,
+) -> Router {
     let state = AppState {
         search_engine: Arc::new(search_engine),
+        output_dir: output_dir.to_string(),
+        config: Arc::new(config),
+        config_path,
     };
 
-    // Build router
-    let app = Router::new()
-        // API routes
-        .route("/api/health", get(health_handler))
-        .route("/api/search", get(search_handler))
-        .route("/api/conversations", get(conversations_handler))
+    Router::new()
+        // API routes; the unversioned paths are aliases of v1 until there's a v2
+        .nest("/api/v1", api_v1())
+        .nest("/api", api_v1())
+        .route("/ws/search", get(ws_search_handler))
         // Import pages
         .route("/import", get(import_page_handler))
         .route("/import/process", get(processing_page_handler))
+        // Short links
+        .route("/c/:short_id", get(shortlink_handler))
         // Serve static files from generated dist directory
         .nest_service(
             "/",
             ServeDir::new(output_dir)
-                .append_index_html_on_directories(true),
+                .append_index_html_on_directories(true)
+                // `.br` copies written with `precompress`
+                .precompressed_br(),
         )
-        .layer(CompressionLayer::new())
+        .layer(CompressionLayer::new().compress_when(
+            DefaultPredicate::new()
+                .and(SizeAbove::new(COMPRESSION_MIN_BYTES))
+                // Already compressed
+                .and(NotForContentType::const_new("application/zip")),
+        ))
         .layer(CorsLayer::permissive())
-        .with_state(state);
-
-    // Run server
-    let listener = tokio::net::TcpListener::bind(addr).await?;
-    tracing::info!("🚀 Server listening on http://{}", addr);
-    tracing::info!("📁 Serving static files from {}/", output_dir);
-    tracing::info!("🔍 Search API available at http://{}/api/search?q=<query>", addr);
-    
-    axum::serve(listener, app).await?;
+        .with_state(state)
+}
 
-    Ok(())
+/// Version 1 of the JSON API, relative to the prefix it is nested under.
+///
+/// Changing a response shape incompatibly means adding a `/api/v2` next to
+/// it; `/api/...` keeps pointing at v1 so existing pages don't break.
+fn api_v1() -> Router<AppState> {
+    Router::new()
+        .route("/health", get(health_handler))
+        .route("/ready", get(ready_handler))
+        .route("/config", get(config_handler).patch(config_patch_handler))
+        .route("/search", get(search_handler))
+        .route("/search/stream", get(search_stream_handler))
+        .route("/autocomplete", get(autocomplete_handler))
+        .route("/suggest-titles", get(suggest_titles_handler))
+        .route("/conversations", get(conversations_handler))
+        .route("/conversations/meta", get(conversations_meta_handler))
+        .route("/conversation/:id/standalone", get(standalone_handler))
+        .route("/conversation/:id/terms", get(terms_handler))
+        .route("/conversation/:id/raw", get(raw_handler))
+        .route("/conversation/:id/code.zip", get(code_zip_handler))
+        .route("/conversation/:id/regenerate", post(regenerate_handler))
+        .route("/export.zip", get(export_zip_handler))
+        .route("/favorites/export", get(favorites_export_handler))
 }
 
 async fn health_handler() -> impl IntoResponse {
@@ -94,6 +562,79 @@ async fn health_handler() -> impl IntoResponse {
     })
 }
 
+/// The settings in the config file, for the settings page
+async fn config_handler(State(state): State<AppState>) -> Result<Json<ConfigResponse>, StatusCode> {
+    let saved = match &state.config_path {
+        Some(path) => AppConfig::load_from(path).map_err(|e| {
+            tracing::error!("Failed to load config: {:#}", e);
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?,
+        None => (*state.config).clone(),
+    };
+    Ok(Json(state.config_response(&saved)))
+}
+
+/// Change some settings and save them to the config file. The running site
+/// keeps the old ones until it is generated again, see `regenerate_required`.
+/// Only the site's own pages may: CORS is open for the read-only API, so a
+/// request from another origin is refused.
+async fn config_patch_handler(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Json(patch): Json<SettingsPatch>,
+) -> Result<Json<ConfigResponse>, (StatusCode, &'static str)> {
+    if !same_origin(&headers) {
+        tracing::warn!("⚠️  Refused to change settings from {:?}", headers.get(header::ORIGIN));
+        return Err((StatusCode::FORBIDDEN, "cross-origin request"));
+    }
+    let path = state.config_path.as_deref().ok_or((StatusCode::NOT_FOUND, "no config file"))?;
+
+    // Start from the file, not the running config: `serve` overrides some of it
+    let mut saved = AppConfig::load_from(path).map_err(|e| {
+        tracing::error!("Failed to load config: {:#}", e);
+        (StatusCode::INTERNAL_SERVER_ERROR, "failed to load config")
+    })?;
+    patch.apply(&mut saved).map_err(|message| (StatusCode::BAD_REQUEST, message))?;
+    saved.save_to(path).map_err(|e| {
+        tracing::error!("Failed to save config: {:#}", e);
+        (StatusCode::INTERNAL_SERVER_ERROR, "failed to save config")
+    })?;
+
+    tracing::info!("⚙️  Settings saved to {}", path.display());
+    Ok(Json(state.config_response(&saved)))
+}
+
+/// Whether a request comes from a page of this server: its `Origin`, if the
+/// browser sent one, names the host it was sent to
+fn same_origin(headers: &HeaderMap) -> bool {
+    let Some(origin) = headers.get(header::ORIGIN) else {
+        // Not from a browser page, e.g. curl
+        return true;
+    };
+    let host = headers.get(header::HOST).and_then(|host| host.to_str().ok());
+    let origin_host = origin
+        .to_str()
+        .ok()
+        .and_then(|origin| origin.split_once("://"))
+        .map(|(_, host)| host);
+    matches!((origin_host, host), (Some(origin_host), Some(host)) if origin_host.eq_ignore_ascii_case(host))
+}
+
+/// Readiness probe: 503 until the site is generated and the search index
+/// has documents, e.g. while the desktop app is still importing
+async fn ready_handler(State(state): State<AppState>) -> impl IntoResponse {
+    let site = std::path::Path::new(&state.output_dir).join("index.html").is_file();
+    let engine = state.search_engine.clone();
+    let documents = blocking(move || engine.num_docs()).await.unwrap_or_else(|e| {
+        tracing::warn!("⚠️  Failed to read the search index: {}", e);
+        0
+    });
+
+    let ready = site && documents > 0;
+    let status = if ready { StatusCode::OK } else { StatusCode::SERVICE_UNAVAILABLE };
+    (status, Json(ReadyResponse { ready, site, documents }))
+}
+
 async fn conversations_handler() -> Result<Json<Vec<serde_json::Value>>, StatusCode> {
     use std::fs;
     
@@ -130,6 +671,43 @@ async fn conversations_handler() -> Result<Json<Vec<serde_json::Value>>, StatusC
     Ok(Json(conversations))
 }
 
+/// Every conversation's metadata from the index, sorted and paged for a table
+async fn conversations_meta_handler(
+    State(state): State<AppState>,
+    Query(params): Query<ConversationsMetaQuery>,
+) -> Result<Json<ConversationsMetaResponse>, StatusCode> {
+    // Reads every conversation document from the index
+    let engine = state.search_engine.clone();
+    let mut conversations = tokio::task::spawn_blocking(move || engine.conversation_metadata())
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+        .map_err(|e| {
+            tracing::error!("Failed to list conversations: {}", e);
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?;
+
+    // Ties (and equal dates) keep a stable order by id
+    conversations.sort_by(|a, b| {
+        let by_column = match params.sort {
+            MetaSort::InsertedAt => a.inserted_at.cmp(&b.inserted_at),
+            MetaSort::UpdatedAt => a.updated_at.cmp(&b.updated_at),
+            MetaSort::Title => a.title.to_lowercase().cmp(&b.title.to_lowercase()),
+            MetaSort::MessageCount => a.message_count.cmp(&b.message_count),
+        };
+        let by_column = match params.order {
+            SortOrder::Asc => by_column,
+            SortOrder::Desc => by_column.reverse(),
+        };
+        by_column.then_with(|| a.conversation_id.cmp(&b.conversation_id))
+    });
+
+    let total = conversations.len();
+    let limit = params.limit.clamp(1, MAX_META_LIMIT);
+    let conversations = conversations.into_iter().skip(params.offset).take(limit).collect();
+
+    Ok(Json(ConversationsMetaResponse { total, offset: params.offset, limit, conversations }))
+}
+
 fn extract_title_from_html(html: &str) -> String {
     // Try to find <title> tag
     if let Some(start) = html.find("<title>") {
@@ -155,35 +733,532 @@ fn extract_title_from_html(html: &str) -> String {
 async fn search_handler(
     State(state): State<AppState>,
     Query(params): Query<SearchQuery>,
-) -> Result<Json<SearchResponse>, StatusCode> {
+) -> Result<Json<SearchResponse>, (StatusCode, &'static str)> {
     let start = std::time::Instant::now();
 
-    let results = state
-        .search_engine
-        .search(&params.q, params.limit)
-        .map_err(|e| {
-            tracing::error!("Search error: {}", e);
-            StatusCode::INTERNAL_SERVER_ERROR
-        })?;
+    if !state.accepts_query(&params.q) {
+        return Err((StatusCode::BAD_REQUEST, "empty query"));
+    }
+    let limit = state.search_limit(params.limit);
+    state.search_offset(params.offset)?;
+    // Message documents have no conversation fields to filter or group by
+    let filtered = params.min_messages.is_some()
+        || params.max_messages.is_some()
+        || params.from.is_some()
+        || params.to.is_some();
+    if params.by_message && (params.group_by_conversation || filtered) {
+        return Err((StatusCode::BAD_REQUEST, "by_message can't be grouped or filtered"));
+    }
+    if params.by_message && !state.search_engine.indexes_messages() {
+        return Err((StatusCode::BAD_REQUEST, "messages aren't indexed, set index_messages and rebuild the index"));
+    }
+
+    let engine = state.search_engine.clone();
+    let query = params.q.clone();
+    let options = params.options(state.config.timezone());
+    let by_message = params.by_message;
+    let search = blocking(move || {
+        if by_message {
+            engine.search_messages(&query, limit, &options)
+        } else {
+            engine.search_with_stats(&query, limit, &options)
+        }
+    });
+
+    let outcome = state
+        .search_in_time(&params.q, search)
+        .await
+        .ok_or((StatusCode::SERVICE_UNAVAILABLE, "search timed out"))?;
+
+    let (results, stats) = outcome.map_err(|e| {
+        tracing::error!("Search error: {}", e);
+        (StatusCode::INTERNAL_SERVER_ERROR, "search failed")
+    })?;
 
     let total = results.len();
-    let time_ms = start.elapsed().as_millis();
+    let total_ms = start.elapsed().as_millis();
 
     tracing::info!(
-        "Search query='{}' returned {} results in {}ms",
-        params.q,
+        query = %params.q,
         total,
-        time_ms
+        // u128 fields would be logged as strings
+        total_ms = total_ms as u64,
+        query_ms = stats.query_ms as u64,
+        segments = stats.segments,
+        "Search completed"
     );
 
     Ok(Json(SearchResponse {
         query: params.q.clone(),
         results,
         total,
-        time_ms,
+        limit,
+        offset: params.offset,
+        total_hits: stats.total_hits,
+        time_ms: total_ms,
+        query_ms: stats.query_ms,
+        total_ms,
+        segments: stats.segments,
     }))
 }
 
+async fn shortlink_handler(
+    State(state): State<AppState>,
+    Path(short_id): Path<String>,
+) -> Result<Redirect, StatusCode> {
+    // Read on every request so links follow regenerations of the site
+    let links = shortlinks::load(std::path::Path::new(&state.output_dir));
+
+    links
+        .get(&short_id)
+        .map(|id| Redirect::temporary(&format!("/conversations/{}/", id)))
+        .ok_or(StatusCode::NOT_FOUND)
+}
+
+/// Stream search results as Server-Sent Events: one `result` event per hit,
+/// then a `done` event with totals (or an `error` event).
+async fn search_stream_handler(
+    State(state): State<AppState>,
+    Query(params): Query<SearchQuery>,
+) -> Sse<impl Stream<Item = Result<Event, Infallible>>> {
+    let limit = state.search_limit(params.limit);
+    let (tx, rx) = tokio::sync::mpsc::channel(32);
+
+    tokio::task::spawn_blocking(move || {
+        let start = std::time::Instant::now();
+
+        if !state.accepts_query(&params.q) {
+            let _ = tx.blocking_send(Ok(Event::default().event("error").data("empty query")));
+            return;
+        }
+        if let Err((_, message)) = state.search_offset(params.offset) {
+            let _ = tx.blocking_send(Ok(Event::default().event("error").data(message)));
+            return;
+        }
+
+        let outcome = state.search_engine.search_each(&params.q, limit, &params.options(state.config.timezone()), |result| {
+            match Event::default().event("result").json_data(&result) {
+                // Stop searching once the client has gone away
+                Ok(event) => tx.blocking_send(Ok(event)).is_ok(),
+                Err(e) => {
+                    tracing::warn!("Failed to serialize search result: {}", e);
+                    true
+                }
+            }
+        });
+
+        let final_event = match outcome {
+            Ok(stats) => {
+                let time_ms = start.elapsed().as_millis();
+                tracing::info!(
+                    query = %params.q,
+                    total = stats.results,
+                    total_ms = time_ms as u64,
+                    query_ms = stats.query_ms as u64,
+                    segments = stats.segments,
+                    "Streamed search completed"
+                );
+                Event::default().event("done").json_data(serde_json::json!({
+                    "query": params.q,
+                    "total": stats.results,
+                    "limit": limit,
+                    "time_ms": time_ms,
+                    "query_ms": stats.query_ms,
+                    "total_ms": time_ms,
+                    "segments": stats.segments,
+                }))
+            }
+            Err(e) => {
+                tracing::error!("Search error: {}", e);
+                Ok(Event::default().event("error").data(e.to_string()))
+            }
+        };
+
+        if let Ok(event) = final_event {
+            let _ = tx.blocking_send(Ok(event));
+        }
+    });
+
+    Sse::new(ReceiverStream::new(rx)).keep_alive(KeepAlive::default())
+}
+
+/// Download one conversation as a self-contained HTML file
+async fn standalone_handler(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+) -> Result<impl IntoResponse, StatusCode> {
+    let conversations_path = state.config.conversations_file_path.as_deref().ok_or(StatusCode::NOT_FOUND)?;
+
+    let conversation = export::load_conversation(conversations_path, &id)
+        .await
+        .map_err(|e| {
+            tracing::error!("Failed to load conversations: {}", e);
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?
+        .ok_or(StatusCode::NOT_FOUND)?;
+
+    // Highlighting and math rendering are CPU-bound
+    let config = state.config.clone();
+    let html = tokio::task::spawn_blocking(move || generator::render_standalone(&conversation, &config))
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+        .map_err(|e| {
+            tracing::error!(conversation_id = %id, "Failed to render standalone page: {}", e);
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?;
+
+    // Ids come from the export file, keep only filename-safe characters
+    let filename: String = id
+        .chars()
+        .filter(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_'))
+        .collect();
+
+    Ok((
+        [(
+            header::CONTENT_DISPOSITION,
+            format!("attachment; filename=\"{}.html\"", filename),
+        )],
+        Html(html),
+    ))
+}
+
+/// The code blocks of one conversation as a ZIP, one file per block plus all of them in one
+async fn code_zip_handler(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+) -> Result<impl IntoResponse, StatusCode> {
+    let conversations_path = state.config.conversations_file_path.as_deref().ok_or(StatusCode::NOT_FOUND)?;
+
+    let conversation = export::load_conversation(conversations_path, &id)
+        .await
+        .map_err(|e| {
+            tracing::error!("Failed to load conversations: {}", e);
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?
+        .ok_or(StatusCode::NOT_FOUND)?;
+
+    // Blocks are found by rendering the conversation, which is CPU-bound
+    let config = state.config.clone();
+    let zip = tokio::task::spawn_blocking(move || -> anyhow::Result<Vec<u8>> {
+        let blocks = generator::extract_code_blocks(&conversation, &config)?;
+        let mut zip = Vec::new();
+        code_export::write_zip(&blocks, &mut zip)?;
+        Ok(zip)
+    })
+    .await
+    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+    .map_err(|e| {
+        tracing::error!(conversation_id = %id, "Failed to export code blocks: {:#}", e);
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+
+    // Ids come from the export file, keep only filename-safe characters
+    let filename: String = id
+        .chars()
+        .filter(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_'))
+        .collect();
+
+    Ok((
+        [
+            (header::CONTENT_TYPE, "application/zip".to_string()),
+            (header::CONTENT_DISPOSITION, format!("attachment; filename=\"{}-code.zip\"", filename)),
+        ],
+        zip,
+    ))
+}
+
+/// The conversation's entry in the export file, pretty-printed, for debugging
+async fn raw_handler(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+) -> Result<impl IntoResponse, StatusCode> {
+    let conversations_path = state.config.conversations_file_path.as_deref().ok_or(StatusCode::NOT_FOUND)?;
+
+    let conversation = export::load_raw_conversation(conversations_path, &id)
+        .await
+        .map_err(|e| {
+            tracing::error!("Failed to load conversations: {}", e);
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?
+        .ok_or(StatusCode::NOT_FOUND)?;
+
+    let json = serde_json::to_string_pretty(&conversation).map_err(|e| {
+        tracing::error!("Failed to serialize conversation {}: {}", id, e);
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+
+    Ok(([(header::CONTENT_TYPE, "application/json; charset=utf-8")], json))
+}
+
+/// Re-render one conversation's pages from the export, e.g. after changing
+/// rendering options, without regenerating the whole site. Like changing
+/// settings, only pages of this server may ask for it.
+async fn regenerate_handler(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+    headers: HeaderMap,
+) -> Result<StatusCode, StatusCode> {
+    if !same_origin(&headers) {
+        tracing::warn!("⚠️  Refused to regenerate {} from {:?}", id, headers.get(header::ORIGIN));
+        return Err(StatusCode::FORBIDDEN);
+    }
+    let conversations_path = state.config.conversations_file_path.as_deref().ok_or(StatusCode::NOT_FOUND)?;
+
+    let found = generator::regenerate_one(conversations_path, &state.output_dir, &id, &state.config)
+        .await
+        .map_err(|e| {
+            tracing::error!(conversation_id = %id, "Failed to regenerate conversation: {:#}", e);
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?;
+
+    if found {
+        Ok(StatusCode::NO_CONTENT)
+    } else {
+        Err(StatusCode::NOT_FOUND)
+    }
+}
+
+/// Download the favorite conversations as JSON (or markdown with `?format=markdown`)
+async fn favorites_export_handler(
+    State(state): State<AppState>,
+    Query(params): Query<FavoritesExportQuery>,
+) -> Result<impl IntoResponse, StatusCode> {
+    let conversations_path = state.config.conversations_file_path.as_deref().ok_or(StatusCode::NOT_FOUND)?;
+
+    let (_, conversations): (_, Vec<model::Conversation>) = export::load_export(conversations_path)
+        .await
+        .map_err(|e| {
+            tracing::error!("Failed to load conversations: {}", e);
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?;
+    let selected = favorites::select_favorites(conversations, &state.config.favorites);
+
+    let contents = favorites::render_favorites(&selected, params.format, false).map_err(|e| {
+        tracing::error!("Failed to export favorites: {}", e);
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+
+    let (content_type, filename) = match params.format {
+        FavoritesFormat::Json => ("application/json; charset=utf-8", "favorites.json"),
+        FavoritesFormat::Markdown => ("text/markdown; charset=utf-8", "favorites.md"),
+    };
+
+    Ok((
+        [
+            (header::CONTENT_TYPE, content_type.to_string()),
+            (header::CONTENT_DISPOSITION, format!("attachment; filename=\"{}\"", filename)),
+        ],
+        contents,
+    ))
+}
+
+/// Download the generated site (or just its markdown sources with
+/// `?scope=markdown`) as a ZIP, streamed while it is being written
+async fn export_zip_handler(
+    State(state): State<AppState>,
+    Query(params): Query<ExportZipQuery>,
+) -> impl IntoResponse {
+    let (tx, rx) = tokio::sync::mpsc::channel(8);
+
+    tokio::task::spawn_blocking(move || {
+        let writer = BufWriter::with_capacity(ARCHIVE_CHUNK_BYTES, ChannelWriter(tx.clone()));
+        match archive::write_zip(std::path::Path::new(&state.output_dir), params.scope, writer) {
+            Ok(files) => tracing::info!("📦 Exported {} files as ZIP", files),
+            Err(e) => {
+                tracing::error!("Failed to write ZIP export: {:#}", e);
+                // Ends the body with an error so the client doesn't keep a truncated archive
+                let _ = tx.blocking_send(Err(io::Error::other(e.to_string())));
+            }
+        }
+    });
+
+    let filename = match params.scope {
+        ArchiveScope::Site => "deepseek-archive.zip",
+        ArchiveScope::Markdown => "deepseek-markdown.zip",
+    };
+
+    (
+        [
+            (header::CONTENT_TYPE, "application/zip".to_string()),
+            (header::CONTENT_DISPOSITION, format!("attachment; filename=\"{}\"", filename)),
+        ],
+        Body::from_stream(ReceiverStream::new(rx)),
+    )
+}
+
+/// Most frequent words of one conversation, for a tag cloud
+async fn terms_handler(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+    Query(params): Query<TermsQuery>,
+) -> Result<Json<Vec<TermCount>>, StatusCode> {
+    let conversations_path = state.config.conversations_file_path.as_deref().ok_or(StatusCode::NOT_FOUND)?;
+
+    let conversation = export::load_conversation(conversations_path, &id)
+        .await
+        .map_err(|e| {
+            tracing::error!("Failed to load conversations: {}", e);
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?
+        .ok_or(StatusCode::NOT_FOUND)?;
+
+    let messages = model::extract_messages(&conversation.mapping);
+    Ok(Json(terms::top_terms(&messages, params.limit.clamp(1, MAX_TERMS))))
+}
+
+async fn ws_search_handler(ws: WebSocketUpgrade, State(state): State<AppState>) -> impl IntoResponse {
+    ws.on_upgrade(move |socket| ws_search_session(socket, state))
+}
+
+/// Search-as-you-type session: every incoming query supersedes the previous
+/// one, which is cancelled and never answered.
+async fn ws_search_session(mut socket: WebSocket, state: AppState) {
+    // Bumped for every query; a search keeps going only while it is the latest
+    let latest = Arc::new(AtomicU64::new(0));
+    let (tx, mut rx) = tokio::sync::mpsc::channel::<(u64, WsSearchResponse)>(8);
+    let mut next_seq = 0;
+
+    loop {
+        tokio::select! {
+            incoming = socket.recv() => {
+                let text = match incoming {
+                    Some(Ok(WsMessage::Text(text))) => text,
+                    Some(Ok(WsMessage::Close(_))) | Some(Err(_)) | None => break,
+                    Some(Ok(_)) => continue,
+                };
+
+                let request = serde_json::from_str::<WsSearchRequest>(&text).unwrap_or(WsSearchRequest {
+                    seq: None,
+                    q: text,
+                    limit: None,
+                    offset: 0,
+                    group_by_conversation: false,
+                    normalize_scores: false,
+                });
+                // The client may send any number, the largest one too
+                let seq = request.seq.unwrap_or(next_seq);
+                next_seq = seq.wrapping_add(1);
+
+                let generation = latest.fetch_add(1, Ordering::SeqCst) + 1;
+                let latest = latest.clone();
+                let tx = tx.clone();
+                let state = state.clone();
+
+                tokio::spawn(async move {
+                    let start = std::time::Instant::now();
+                    let query = request.q.clone();
+                    // Refused the same way as on `/api/search`
+                    let outcome = match state.search_offset(request.offset) {
+                        Ok(offset) => {
+                            let engine = state.search_engine.clone();
+                            let accepted = state.accepts_query(&request.q);
+                            let limit = state.search_limit(request.limit);
+                            let options = SearchOptions {
+                                group_by_conversation: request.group_by_conversation,
+                                normalize_scores: request.normalize_scores,
+                                offset,
+                                ..SearchOptions::default()
+                            };
+                            let latest = latest.clone();
+                            let search = blocking(move || {
+                                // Not an error while typing: just nothing to show
+                                let mut results = Vec::new();
+                                if accepted {
+                                    engine.search_each(&request.q, limit, &options, |result| {
+                                        results.push(result);
+                                        latest.load(Ordering::SeqCst) == generation
+                                    })?;
+                                }
+                                Ok(results)
+                            });
+                            match state.search_in_time(&query, search).await {
+                                Some(Ok(results)) => Ok(results),
+                                Some(Err(e)) => {
+                                    tracing::error!("Search error: {}", e);
+                                    return;
+                                }
+                                None => Err("search timed out"),
+                            }
+                        }
+                        Err((_, message)) => Err(message),
+                    };
+                    if latest.load(Ordering::SeqCst) != generation {
+                        return;
+                    }
+
+                    let (results, error) = match outcome {
+                        Ok(results) => (results, None),
+                        Err(message) => (Vec::new(), Some(message)),
+                    };
+                    let _ = tx.send((generation, WsSearchResponse {
+                        seq,
+                        query,
+                        total: results.len(),
+                        results,
+                        time_ms: start.elapsed().as_millis(),
+                        error,
+                    })).await;
+                });
+            }
+            Some((generation, response)) = rx.recv() => {
+                // A newer query may have arrived while this one was queued
+                if generation != latest.load(Ordering::SeqCst) {
+                    continue;
+                }
+                let Ok(payload) = serde_json::to_string(&response) else {
+                    continue;
+                };
+                if socket.send(WsMessage::Text(payload)).await.is_err() {
+                    break;
+                }
+            }
+        }
+    }
+
+    // Cancel whatever is still running
+    latest.fetch_add(1, Ordering::SeqCst);
+}
+
+/// Run synchronous index work (tantivy blocks) on the blocking thread pool,
+/// so it doesn't hold up the runtime's worker threads
+async fn blocking<T: Send + 'static>(work: impl FnOnce() -> anyhow::Result<T> + Send + 'static) -> anyhow::Result<T> {
+    tokio::task::spawn_blocking(work).await?
+}
+
+async fn autocomplete_handler(
+    State(state): State<AppState>,
+    Query(params): Query<AutocompleteQuery>,
+) -> Result<Json<Vec<String>>, StatusCode> {
+    let limit = params.limit.min(state.config.search_max_limit);
+
+    let engine = state.search_engine.clone();
+    let suggestions = blocking(move || engine.autocomplete(&params.prefix, limit))
+        .await
+        .map_err(|e| {
+            tracing::error!("Autocomplete error: {}", e);
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?;
+
+    Ok(Json(suggestions))
+}
+
+async fn suggest_titles_handler(
+    State(state): State<AppState>,
+    Query(params): Query<AutocompleteQuery>,
+) -> Result<Json<Vec<TitleSuggestion>>, StatusCode> {
+    let limit = params.limit.min(state.config.search_max_limit);
+
+    let engine = state.search_engine.clone();
+    let suggestions = blocking(move || engine.suggest_titles(&params.prefix, limit))
+        .await
+        .map_err(|e| {
+            tracing::error!("Title suggestion error: {}", e);
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?;
+
+    Ok(Json(suggestions))
+}
+
 async fn import_page_handler() -> impl IntoResponse {
     let html = include_str!("../templates/import.html");
     axum::response::Html(html)
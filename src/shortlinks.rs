@@ -0,0 +1,76 @@
+use anyhow::{Context, Result};
+use std::collections::{BTreeMap, HashSet};
+use std::path::Path;
+
+/// File (inside the output directory) holding the short id -> conversation id map
+pub const SHORTLINKS_FILE: &str = "shortlinks.json";
+
+/// Preferred short id length (prefix of the conversation id)
+const SHORT_ID_LEN: usize = 8;
+
+/// Short id -> full conversation id
+pub type ShortLinks = BTreeMap<String, String>;
+
+/// Load a previously saved mapping, or an empty one if there is none.
+pub fn load(output_dir: &Path) -> ShortLinks {
+    let path = output_dir.join(SHORTLINKS_FILE);
+
+    match std::fs::read_to_string(&path) {
+        Ok(contents) => serde_json::from_str(&contents).unwrap_or_else(|e| {
+            tracing::warn!("Ignoring invalid {}: {}", path.display(), e);
+            ShortLinks::new()
+        }),
+        Err(_) => ShortLinks::new(),
+    }
+}
+
+pub fn save(output_dir: &Path, links: &ShortLinks) -> Result<()> {
+    let contents = serde_json::to_string_pretty(links)
+        .context("Failed to serialize short links")?;
+
+    let path = output_dir.join(SHORTLINKS_FILE);
+    std::fs::write(&path, contents)
+        .with_context(|| format!("Failed to write {}", path.display()))?;
+
+    Ok(())
+}
+
+/// Assign a short id to every conversation id.
+///
+/// Links from `existing` are kept for ids that are still present. New ids get
+/// the shortest free prefix of at least [`SHORT_ID_LEN`] chars, so as long as
+/// `ids` is ordered oldest-first a new conversation never steals the link of
+/// an older one, even if the saved mapping was lost.
+pub fn assign(existing: &ShortLinks, ids: &[&str]) -> ShortLinks {
+    let wanted: HashSet<&str> = ids.iter().copied().collect();
+
+    let mut links: ShortLinks = existing
+        .iter()
+        .filter(|(_, id)| wanted.contains(id.as_str()))
+        .map(|(short, id)| (short.clone(), id.clone()))
+        .collect();
+    let mut linked: HashSet<String> = links.values().cloned().collect();
+
+    for id in ids {
+        if linked.contains(*id) {
+            continue;
+        }
+
+        let chars: Vec<char> = id.chars().collect();
+        let short = (SHORT_ID_LEN.min(chars.len())..=chars.len())
+            .map(|len| chars[..len].iter().collect::<String>())
+            .find(|candidate| !links.contains_key(candidate))
+            .unwrap_or_else(|| {
+                // The full id is already someone else's short id
+                (2..)
+                    .map(|n| format!("{}-{}", id, n))
+                    .find(|candidate| !links.contains_key(candidate))
+                    .unwrap()
+            });
+
+        links.insert(short, id.to_string());
+        linked.insert(id.to_string());
+    }
+
+    links
+}
@@ -2,23 +2,43 @@
 
 use anyhow::Result;
 use tauri::{generate_handler, Emitter, Manager, State, Window};
-use tracing_subscriber::prelude::*;
 use std::sync::{Arc, Mutex};
 
-mod config;
-mod generator;
-mod server;
-mod templates;
-
-use config::AppConfig;
-use deepseek_app::indexer;
+use deepseek_app::archive::{self, ArchiveScope};
+use deepseek_app::config::{AppConfig, EmptySearch, ImportMode};
+use deepseek_app::logging::{self, LogFormat};
+use deepseek_app::favorites::{self, FavoritesFormat};
+use deepseek_app::{code_export, export, generator, import, indexer, server};
 use deepseek_app::search::SearchEngine;
 use std::path::PathBuf;
 
+/// Merged imports are written next to the `dist` directory under this name
+const MERGED_CONVERSATIONS_FILE: &str = "merged_conversations.json";
+
 pub struct AppState {
     pub index_path: String,
     pub output_dir: String,
     pub config: Arc<Mutex<AppConfig>>,
+    /// Opened on the first search and reopened after each import
+    pub search_engine: Arc<Mutex<Option<SearchEngine>>>,
+}
+
+/// The cached search engine, opening the index if it isn't open yet
+async fn search_engine(state: &AppState) -> Result<SearchEngine, String> {
+    let cached = state.search_engine.lock().unwrap().clone();
+    if let Some(engine) = cached {
+        return Ok(engine);
+    }
+
+    let index_path = state.index_path.clone();
+    let config = state.config.lock().unwrap().clone();
+    let engine = tokio::task::spawn_blocking(move || SearchEngine::with_config(&index_path, &config))
+        .await
+        .map_err(|e| format!("Failed to create search engine: {}", e))?
+        .map_err(|e| format!("Failed to create search engine: {}", e))?;
+
+    *state.search_engine.lock().unwrap() = Some(engine.clone());
+    Ok(engine)
 }
 
 // Tauri command to check if we have conversations
@@ -46,6 +66,24 @@ async fn process_conversations_file(
     file_path: String,
     window: Window,
     state: State<'_, AppState>,
+) -> Result<(), String> {
+    let result = import_conversations_file(file_path, &window, &state).await;
+    
+    // Tell the processing page, so it shows the error instead of spinning
+    if let Err(message) = &result {
+        tracing::error!("❌ Import failed: {}", message);
+        let _ = window.emit_to("main", "import-error", serde_json::json!({
+            "message": message
+        }));
+    }
+    
+    result
+}
+
+async fn import_conversations_file(
+    file_path: String,
+    window: &Window,
+    state: &State<'_, AppState>,
 ) -> Result<(), String> {
     tracing::info!("📦 Processing conversations file: {}", file_path);
     
@@ -65,33 +103,58 @@ async fn process_conversations_file(
     }
     tracing::info!("File exists");
     
-    // Verify file is valid JSON
-    tracing::info!("Reading file content...");
-    let content = std::fs::read_to_string(&file_path)
-        .map_err(|e| {
-            tracing::error!("Failed to read file: {}", e);
-            format!("Failed to read file: {}", e)
-        })?;
-    
-    tracing::info!("Parsing JSON...");
-    let _: serde_json::Value = serde_json::from_str(&content)
-        .map_err(|e| {
-            tracing::error!("Invalid JSON format: {}", e);
-            format!("Invalid JSON format: {}", e)
-        })?;
+    // Verify file isn't too big and is valid JSON, without loading it
+    tracing::info!("Validating file...");
+    let max_bytes = state.config.lock().unwrap().max_import_bytes;
+    let path = PathBuf::from(&file_path);
+    let summary = tokio::task::spawn_blocking(move || export::validate_export_file(&path, max_bytes))
+        .await
+        .map_err(|e| format!("Failed to validate file: {}", e))?
+        .map_err(|e| format!("{:#}", e))?;
     
     tracing::info!("Emitting progress: 20%");
     let _ = window.emit_to("main", "import-progress", serde_json::json!({
         "percent": 20,
-        "message": "File validated successfully"
+        "message": format!("File validated: {} conversations", summary.conversations),
+        "conversations": summary.conversations
     }));
     
+    // Merge into the previous import if asked to and there is one to merge into
+    let merge_from = {
+        let config = state.config.lock().unwrap();
+        config.conversations_file_path.clone().filter(|previous| {
+            config.import_mode == ImportMode::Merge
+                && std::path::Path::new(previous).exists()
+                && std::path::Path::new(&state.index_path).exists()
+        })
+    };
+    
+    let (site_source, merge) = match merge_from {
+        Some(previous) => {
+            let _ = window.emit_to("main", "import-progress", serde_json::json!({
+                "percent": 25,
+                "message": "Merging with existing conversations..."
+            }));
+            
+            let merged_path = std::path::Path::new(&state.output_dir)
+                .with_file_name(MERGED_CONVERSATIONS_FILE)
+                .to_string_lossy()
+                .to_string();
+            let merge = import::merge_exports(&previous, &file_path, &merged_path)
+                .await
+                .map_err(|e| format!("Failed to merge conversations: {}", e))?;
+            (merged_path, Some(merge))
+        }
+        None => (file_path.clone(), None),
+    };
+    
     // Save file path to config
-    {
+    let config = {
         let mut config = state.config.lock().unwrap();
-        config.conversations_file_path = Some(file_path.clone());
+        config.conversations_file_path = Some(site_source.clone());
         config.save().map_err(|e| format!("Failed to save config: {}", e))?;
-    }
+        config.clone()
+    };
     
     let _ = window.emit_to("main", "import-progress", serde_json::json!({
         "percent": 30,
@@ -105,7 +168,7 @@ async fn process_conversations_file(
     }
     
     // Generate HTML site
-    generator::generate_site(&file_path, &state.output_dir)
+    generator::generate_site(&site_source, &state.output_dir, &config)
         .await
         .map_err(|e| format!("Failed to generate site: {}", e))?;
     
@@ -114,20 +177,55 @@ async fn process_conversations_file(
         "message": "Building search index..."
     }));
     
-    // Clean up old index
-    if std::path::Path::new(&state.index_path).exists() {
-        std::fs::remove_dir_all(&state.index_path)
-            .map_err(|e| format!("Failed to clean index directory: {}", e))?;
+    // Only re-index what changed when merging; rebuild if that's not possible
+    let updated_in_place = match &merge {
+        Some((changed, _)) => match indexer::update_index(&state.index_path, changed, &config) {
+            Ok(()) => true,
+            Err(e) => {
+                tracing::warn!("⚠️  Failed to update the search index ({}), rebuilding it", e);
+                false
+            }
+        },
+        None => false,
+    };
+    
+    if !updated_in_place {
+        // Clean up old index
+        if std::path::Path::new(&state.index_path).exists() {
+            std::fs::remove_dir_all(&state.index_path)
+                .map_err(|e| format!("Failed to clean index directory: {}", e))?;
+        }
+        
+        // Build search index
+        indexer::build_index_with_config(&site_source, &state.index_path, &config)
+            .await
+            .map_err(|e| format!("Failed to build index: {}", e))?;
     }
     
-    // Build search index
-    indexer::build_index(&file_path, &state.index_path)
-        .await
-        .map_err(|e| format!("Failed to build index: {}", e))?;
+    // The cached engine may point at the index that was just deleted, and the
+    // config may have changed; reopen it so searches see the new import
+    let reopened = match SearchEngine::with_config(&state.index_path, &config) {
+        Ok(engine) => Some(engine),
+        Err(e) => {
+            tracing::warn!("⚠️  Failed to reopen the search index ({}), will retry on the next search", e);
+            None
+        }
+    };
+    *state.search_engine.lock().unwrap() = reopened;
     
+    let message = match &merge {
+        Some((_, summary)) => {
+            let _ = window.emit_to("main", "import-summary", summary);
+            format!(
+                "Processing complete: {} added, {} updated, {} unchanged",
+                summary.added, summary.updated, summary.unchanged
+            )
+        }
+        None => "Processing complete!".to_string(),
+    };
     let _ = window.emit_to("main", "import-progress", serde_json::json!({
         "percent": 100,
-        "message": "Processing complete!"
+        "message": message
     }));
     
     tracing::info!("✅ Processing complete");
@@ -140,11 +238,17 @@ async fn process_conversations_file(
 async fn search(query: String, state: State<'_, AppState>) -> Result<Vec<serde_json::Value>, String> {
     tracing::info!("🔍 Searching for: {}", query);
     
-    let search_engine = SearchEngine::new(&state.index_path)
-        .map_err(|e| format!("Failed to create search engine: {}", e))?;
+    // An empty query lists recent conversations only if the config says so
+    if query.trim().is_empty() && state.config.lock().unwrap().empty_search != EmptySearch::Recent {
+        return Ok(Vec::new());
+    }
+    
+    let search_engine = search_engine(&state).await?;
     
-    let results = search_engine
-        .search(&query, 10)
+    // Searching blocks, so keep it off the async runtime
+    let results = tokio::task::spawn_blocking(move || search_engine.search(&query, 10))
+        .await
+        .map_err(|e| format!("Search failed: {}", e))?
         .map_err(|e| format!("Search failed: {}", e))?;
     
     // Convert SearchResult to JSON
@@ -153,9 +257,11 @@ async fn search(query: String, state: State<'_, AppState>) -> Result<Vec<serde_j
         .map(|r| serde_json::json!({
             "conversation_id": r.conversation_id,
             "title": r.title,
+            "url": r.url,
             "date": r.date,
             "score": r.score,
             "snippet": r.snippet,
+            "snippet_html": r.snippet_html,
         }))
         .collect();
     
@@ -179,15 +285,181 @@ async fn get_conversations(state: State<'_, AppState>) -> Result<serde_json::Val
     Ok(conversations)
 }
 
+// Tauri command to get one conversation's entry from the export, pretty-printed
+#[tauri::command]
+async fn get_raw_conversation(conversation_id: String, state: State<'_, AppState>) -> Result<String, String> {
+    let path = state.config.lock().unwrap().conversations_file_path.clone()
+        .ok_or_else(|| "No conversations file configured".to_string())?;
+    
+    let conversation = export::load_raw_conversation(&path, &conversation_id)
+        .await
+        .map_err(|e| format!("Failed to read conversations: {}", e))?
+        .ok_or_else(|| format!("Conversation not found: {}", conversation_id))?;
+    
+    serde_json::to_string_pretty(&conversation)
+        .map_err(|e| format!("Failed to serialize conversation: {}", e))
+}
+
+// Tauri command to save one conversation as a self-contained HTML file
+#[tauri::command]
+async fn export_standalone(
+    conversation_id: String,
+    output_path: String,
+    state: State<'_, AppState>,
+) -> Result<(), String> {
+    let config = state.config.lock().unwrap().clone();
+    
+    let path = config.conversations_file_path.as_deref()
+        .ok_or_else(|| "No conversations file configured".to_string())?;
+    
+    let conversation = export::load_conversation(path, &conversation_id)
+        .await
+        .map_err(|e| format!("Failed to read conversations: {}", e))?
+        .ok_or_else(|| format!("Conversation not found: {}", conversation_id))?;
+    
+    let html = tokio::task::spawn_blocking(move || generator::render_standalone(&conversation, &config))
+        .await
+        .map_err(|e| format!("Failed to render conversation: {}", e))?
+        .map_err(|e| format!("Failed to render conversation: {}", e))?;
+    
+    std::fs::write(&output_path, html)
+        .map_err(|e| format!("Failed to write {}: {}", output_path, e))?;
+    
+    tracing::info!("✅ Exported {} to {}", conversation_id, output_path);
+    Ok(())
+}
+
+// Tauri command to save one conversation's code blocks as a ZIP
+#[tauri::command]
+async fn export_code(
+    conversation_id: String,
+    output_path: String,
+    state: State<'_, AppState>,
+) -> Result<usize, String> {
+    let config = state.config.lock().unwrap().clone();
+    
+    let path = config.conversations_file_path.as_deref()
+        .ok_or_else(|| "No conversations file configured".to_string())?;
+    
+    let conversation = export::load_conversation(path, &conversation_id)
+        .await
+        .map_err(|e| format!("Failed to read conversations: {}", e))?
+        .ok_or_else(|| format!("Conversation not found: {}", conversation_id))?;
+    
+    let target = output_path.clone();
+    let files = tokio::task::spawn_blocking(move || -> anyhow::Result<usize> {
+        let blocks = generator::extract_code_blocks(&conversation, &config)?;
+        let file = std::fs::File::create(&target)?;
+        code_export::write_zip(&blocks, std::io::BufWriter::new(file))
+    })
+    .await
+    .map_err(|e| format!("Failed to write {}: {}", output_path, e))?
+    .map_err(|e| format!("Failed to write {}: {:#}", output_path, e))?;
+    
+    tracing::info!("✅ Exported the code of {} to {}", conversation_id, output_path);
+    Ok(files)
+}
+
+// Tauri command to re-render one conversation's pages, leaving the rest of the site as is
+#[tauri::command]
+async fn regenerate_conversation(conversation_id: String, state: State<'_, AppState>) -> Result<(), String> {
+    let config = state.config.lock().unwrap().clone();
+    
+    let path = config.conversations_file_path.as_deref()
+        .ok_or_else(|| "No conversations file configured".to_string())?;
+    
+    let found = generator::regenerate_one(path, &state.output_dir, &conversation_id, &config)
+        .await
+        .map_err(|e| format!("Failed to regenerate conversation: {:#}", e))?;
+    
+    if found {
+        Ok(())
+    } else {
+        Err(format!("Conversation not found: {}", conversation_id))
+    }
+}
+
+// Tauri command to list the conversations opened so far
+#[tauri::command]
+async fn get_read_conversations(state: State<'_, AppState>) -> Result<Vec<String>, String> {
+    Ok(state.config.lock().unwrap().read_conversations.clone())
+}
+
+// Tauri command to remember that a conversation was opened, across imports
+#[tauri::command]
+async fn mark_conversation_read(conversation_id: String, state: State<'_, AppState>) -> Result<(), String> {
+    let mut config = state.config.lock().unwrap();
+    if config.read_conversations.contains(&conversation_id) {
+        return Ok(());
+    }
+    config.read_conversations.push(conversation_id);
+    config.save().map_err(|e| format!("Failed to save config: {}", e))
+}
+
+// Tauri command to save the favorite conversations (markdown if the path ends in `.md`)
+#[tauri::command]
+async fn export_favorites(output_path: String, state: State<'_, AppState>) -> Result<usize, String> {
+    let config = state.config.lock().unwrap().clone();
+    
+    let path = config.conversations_file_path.as_deref()
+        .ok_or_else(|| "No conversations file configured".to_string())?;
+    
+    favorites::export_favorites(
+        path,
+        &output_path,
+        &config.favorites,
+        FavoritesFormat::from_path(&output_path),
+        false,
+    )
+    .await
+    .map_err(|e| format!("Failed to export favorites: {:#}", e))
+}
+
+// Tauri command to save the generated site (or its markdown sources) as a ZIP backup
+#[tauri::command]
+async fn export_zip(
+    output_path: String,
+    scope: Option<ArchiveScope>,
+    state: State<'_, AppState>,
+) -> Result<usize, String> {
+    let output_dir = state.output_dir.clone();
+    let target = output_path.clone();
+    
+    let files = tokio::task::spawn_blocking(move || -> anyhow::Result<usize> {
+        let file = std::fs::File::create(&target)?;
+        archive::write_zip(
+            std::path::Path::new(&output_dir),
+            scope.unwrap_or_default(),
+            std::io::BufWriter::new(file),
+        )
+    })
+    .await
+    .map_err(|e| format!("Failed to write {}: {}", output_path, e))?
+    .map_err(|e| format!("Failed to write {}: {:#}", output_path, e))?;
+    
+    tracing::info!("📦 Exported {} files to {}", files, output_path);
+    Ok(files)
+}
+
 #[tokio::main]
 async fn main() -> Result<()> {
-    tracing_subscriber::registry()
-        .with(
-            tracing_subscriber::EnvFilter::try_from_default_env()
-                .unwrap_or_else(|_| "deepseek_viewer=info".into()),
-        )
-        .with(tracing_subscriber::fmt::layer())
-        .init();
+    // `--log-format json` for structured logs; other arguments belong to Tauri
+    let args: Vec<String> = std::env::args().collect();
+    let log_format = args
+        .iter()
+        .position(|arg| arg == "--log-format")
+        .and_then(|idx| args.get(idx + 1))
+        .map(|value| value.parse::<LogFormat>())
+        .transpose()?
+        .unwrap_or_default();
+    logging::init(log_format);
+
+    // `--open-conversation <id>` starts on that conversation instead of the index
+    let open_conversation = args
+        .iter()
+        .position(|arg| arg == "--open-conversation")
+        .and_then(|idx| args.get(idx + 1))
+        .cloned();
 
     tracing::info!("🚀 DeepSeek Chat Viewer - Desktop Edition");
 
@@ -220,7 +492,8 @@ async fn main() -> Result<()> {
         if !output_dir.exists() {
             tracing::info!("📦 Generating HTML site in {}...", output_dir.display());
             std::fs::create_dir_all(&output_dir)?;
-            generator::generate_site(&conversations_path, output_dir.to_str().unwrap()).await?;
+            let site_config = config.lock().unwrap().clone();
+            generator::generate_site(&conversations_path, output_dir.to_str().unwrap(), &site_config).await?;
             tracing::info!("✅ HTML site generated");
         } else {
             tracing::info!("✅ Using existing HTML site in {}", output_dir.display());
@@ -230,7 +503,8 @@ async fn main() -> Result<()> {
         if !index_path.exists() {
             tracing::info!("📚 Building search index in {}...", index_path.display());
             std::fs::create_dir_all(&index_path)?;
-            indexer::build_index(&conversations_path, index_path.to_str().unwrap()).await?;
+            let index_config = config.lock().unwrap().clone();
+            indexer::build_index_with_config(&conversations_path, index_path.to_str().unwrap(), &index_config).await?;
             tracing::info!("✅ Search index built");
         } else {
             tracing::info!("✅ Using existing search index");
@@ -249,47 +523,43 @@ async fn main() -> Result<()> {
         }
     }
 
+    // Source to rebuild the index from if it is missing or corrupt
+    let rebuild_source = {
+        let cfg = config.lock().unwrap();
+        match cfg.conversations_file_path.clone().filter(|p| std::path::Path::new(p).exists()) {
+            Some(path) => path,
+            None => {
+                let temp_file = std::env::temp_dir().join("empty_conversations.json");
+                if let Err(e) = std::fs::write(&temp_file, "[]") {
+                    tracing::error!("Failed to create temp file: {}", e);
+                }
+                temp_file.to_string_lossy().to_string()
+            }
+        }
+    };
+
     // Always start embedded web server
     let server_output_dir = output_dir.to_string_lossy().to_string();
     let server_index_path = index_path.to_string_lossy().to_string();
+    let server_config = config.lock().unwrap().clone();
     
     tokio::spawn(async move {
         tracing::info!("🌐 Starting embedded web server on http://127.0.0.1:8080");
         
-        // Try to create search engine, create empty one if it fails
-        let search_engine = match SearchEngine::new(&server_index_path) {
+        let search_engine = match SearchEngine::open_or_rebuild(&server_index_path, &rebuild_source, &server_config).await {
             Ok(engine) => {
                 tracing::info!("✅ Search engine loaded");
                 engine
             },
             Err(e) => {
-                tracing::warn!("⚠️  No search index available, creating empty one: {}", e);
-                // Create an empty index
-                let empty_json = "[]";
-                let temp_file = std::env::temp_dir().join("empty_conversations.json");
-                if let Err(e) = std::fs::write(&temp_file, empty_json) {
-                    tracing::error!("Failed to create temp file: {}", e);
-                }
-                
-                // Try to build empty index
-                if let Err(e) = indexer::build_index(temp_file.to_str().unwrap(), &server_index_path).await {
-                    tracing::error!("Failed to create empty index: {}", e);
-                }
-                
-                // Try again
-                match SearchEngine::new(&server_index_path) {
-                    Ok(engine) => engine,
-                    Err(e) => {
-                        tracing::error!("❌ Failed to create search engine: {}", e);
-                        return;
-                    }
-                }
+                tracing::error!("❌ Failed to create search engine: {}", e);
+                return;
             }
         };
         
         // Start server
         let addr = "127.0.0.1:8080".parse().unwrap();
-        if let Err(e) = server::serve(addr, search_engine, &server_output_dir).await {
+        if let Err(e) = server::serve(addr, search_engine, &server_output_dir, server_config).await {
             tracing::error!("❌ Server error: {}", e);
         }
     });
@@ -301,6 +571,7 @@ async fn main() -> Result<()> {
         index_path: index_path.to_string_lossy().to_string(),
         output_dir: output_dir.to_string_lossy().to_string(),
         config: config.clone(),
+        search_engine: Arc::new(Mutex::new(None)),
     };
 
     tracing::info!("✨ Opening application window...");
@@ -315,7 +586,15 @@ async fn main() -> Result<()> {
             get_current_file_path,
             process_conversations_file,
             search,
-            get_conversations
+            get_conversations,
+            get_raw_conversation,
+            export_standalone,
+            export_code,
+            regenerate_conversation,
+            get_read_conversations,
+            mark_conversation_read,
+            export_zip,
+            export_favorites
         ])
         .setup(|app| {
             let window = app.get_webview_window("main").unwrap();
@@ -327,6 +606,14 @@ async fn main() -> Result<()> {
             if config.conversations_file_path.is_none() {
                 // Navigate to import page
                 let _ = window.eval("window.location.href = '/import'");
+            } else if let Some(id) = &open_conversation {
+                match generator::conversation_path(std::path::Path::new(&state.output_dir), id) {
+                    // JSON-encode so the id can't break out of the string
+                    Some(path) => {
+                        let _ = window.eval(&format!("window.location.href = {}", serde_json::json!(path)));
+                    }
+                    None => tracing::warn!("⚠️  Conversation {} not found, opening the index instead", id),
+                }
             }
             
             Ok(())
@@ -1,41 +1,214 @@
 use askama::Template;
-use chrono::{DateTime, Utc};
+use chrono::DateTime;
+use chrono_tz::Tz;
+use std::collections::HashMap;
 
 #[derive(Template)]
 #[template(path = "base.html")]
 pub struct BaseTemplate<'a> {
     pub title: &'a str,
+    /// Load KaTeX to render formulas in the browser
+    pub client_math: bool,
+    /// Register the service worker that keeps pages readable offline
+    pub offline_pages: bool,
+    pub body_font: &'a str,
+    pub code_font: &'a str,
+    /// Versioned URLs of the stylesheets and scripts
+    pub assets: &'a AssetVersions,
     pub content: String,
     pub conversations_html: String,
 }
 
+/// Content hashes of the generated CSS and JS, so pages link to
+/// `main.css?v=<hash>` and browsers fetch an asset again once it changes
+#[derive(Debug, Clone, Default)]
+pub struct AssetVersions {
+    hashes: HashMap<String, String>,
+}
+
+impl AssetVersions {
+    /// Record the hash of the asset served at `path`
+    pub fn insert(&mut self, path: &str, contents: &[u8]) {
+        self.hashes.insert(path.to_string(), content_hash(contents));
+    }
+
+    /// `path` with its version appended, or as is if the asset is unknown
+    pub fn url(&self, path: &str) -> String {
+        match self.hashes.get(path) {
+            Some(hash) => format!("{}?v={}", path, hash),
+            None => path.to_string(),
+        }
+    }
+}
+
+/// Short FNV-1a hash: stable across builds, unlike `DefaultHasher`
+pub(crate) fn content_hash(contents: &[u8]) -> String {
+    let hash = contents.iter().fold(0xcbf2_9ce4_8422_2325u64, |hash, &byte| {
+        (hash ^ u64::from(byte)).wrapping_mul(0x0100_0000_01b3)
+    });
+    format!("{:016x}", hash)[..10].to_string()
+}
+
+/// Self-contained page for a single conversation (see `generator::render_standalone`)
+#[derive(Template)]
+#[template(path = "standalone.html")]
+pub struct StandaloneTemplate<'a> {
+    pub title: &'a str,
+    pub body_font: &'a str,
+    pub code_font: &'a str,
+    pub styles: &'a str,
+    pub scripts: &'a str,
+    pub content: String,
+}
+
 #[derive(Template)]
 #[template(path = "conversation.html")]
 pub struct ConversationTemplate<'a> {
     pub title: &'a str,
-    pub inserted_at: Option<DateTime<Utc>>,
-    pub updated_at: Option<DateTime<Utc>>,
+    /// Id from the export, for the "copy ID" and source JSON buttons
+    pub conversation_id: &'a str,
+    /// Not shown on standalone pages, where `/c/` links lead nowhere
+    pub short_id: Option<&'a str>,
+    pub date_format: &'a str,
+    pub inserted_at: Option<DateTime<Tz>>,
+    pub updated_at: Option<DateTime<Tz>>,
+    /// Messages in the whole conversation
     pub message_count: usize,
+    /// Words in the whole conversation's text
+    pub word_count: usize,
+    /// Tags from the export, empty if it has none
+    pub tags: &'a [String],
+    /// Messages on this page
     pub messages: &'a [Message],
+    /// 1-based page number
+    pub page: usize,
+    pub page_count: usize,
+    pub prev_url: Option<String>,
+    pub next_url: Option<String>,
+    /// Whether a `raw.md` with the markdown source sits next to the page
+    pub has_raw_markdown: bool,
+    /// Frequent words of the conversation, empty when the tag cloud is off
+    pub tag_cloud: &'a [CloudTerm],
+    /// Links to the conversation's code blocks, empty when `code_index` is off
+    pub code_index: &'a [CodeIndexEntry],
+    /// Names of the attached files, listed with the code index
+    pub attachments: &'a [String],
+}
+
+/// A code block in the index at the top of a conversation page
+#[derive(Debug, Clone)]
+pub struct CodeIndexEntry {
+    /// Language of the fence, or "код" if it has none
+    pub label: String,
+    pub lines: usize,
+    /// `#code-N` on the same page, prefixed with the page's file otherwise
+    pub url: String,
+}
+
+/// One page of the archive with every conversation inlined (`/all/`)
+#[derive(Template)]
+#[template(path = "all.html")]
+pub struct ArchiveTemplate<'a> {
+    /// Formatted for the configured language
+    pub total_conversations: String,
+    pub sections: &'a [ArchiveSection],
+    /// 1-based page number
+    pub page: usize,
+    pub page_count: usize,
+    pub prev_url: Option<String>,
+    pub next_url: Option<String>,
+}
+
+/// The settings form (`/settings/`); `settings.js` fills it in from `/api/config`
+#[derive(Template)]
+#[template(path = "settings.html")]
+pub struct SettingsTemplate {
+    /// Names allowed for `body_font` / `code_font`
+    pub body_fonts: Vec<&'static str>,
+    pub code_fonts: Vec<&'static str>,
+    /// Names allowed for `syntax_theme`
+    pub syntax_themes: Vec<String>,
+    /// Versioned URL of `settings.js`
+    pub script: String,
+}
+
+/// A conversation rendered on the archive page
+#[derive(Debug, Clone)]
+pub struct ArchiveSection {
+    pub url: String,
+    pub content_html: String,
+}
+
+/// A word in a conversation's tag cloud
+#[derive(Debug, Clone)]
+pub struct CloudTerm {
+    pub term: String,
+    pub count: usize,
+    /// Relative frequency from 1 (rarest shown) to 5 (most frequent), for sizing
+    pub weight: usize,
 }
 
 #[derive(Debug, Clone)]
 pub struct Message {
     pub message_type: String,
+    /// Web search results and tool calls are folded under this label
+    pub tool_label: Option<&'static str>,
     pub content_html: String,
-    pub inserted_at: Option<DateTime<Utc>>,
+    pub inserted_at: Option<DateTime<Tz>>,
+    /// Fenced code blocks in this message
+    pub code_blocks: Vec<CodeBlock>,
+    /// Who wrote the message, shown in its header
+    pub author: Author,
+    /// Element id for links to the message, stable across regenerations
+    pub anchor: String,
+    /// Images embedded in the export, shown below the text
+    pub images: Vec<MessageImage>,
+}
+
+/// An embedded image: a `data:` URL, or a file next to the page if it was too large
+#[derive(Debug, Clone)]
+pub struct MessageImage {
+    pub src: String,
+    pub alt: String,
+}
+
+/// Label and avatar shown above a message
+#[derive(Debug, Clone)]
+pub struct Author {
+    /// "user" or "assistant", for CSS
+    pub role: &'static str,
+    pub label: String,
+    /// Avatar image URL; `initials` are shown in a colored circle without one
+    pub avatar_url: Option<String>,
+    pub initials: String,
+}
+
+/// A fenced code block, anchored on the page as `#code-{number}`
+#[derive(Debug, Clone)]
+pub struct CodeBlock {
+    /// 1-based, counted across the whole conversation
+    pub number: usize,
+    /// Element id of the block on its page
+    pub id: String,
+    pub lang: String,
+    pub lines: usize,
+    /// The code as written, without highlighting
+    pub code: String,
 }
 
 #[derive(Template)]
 #[template(path = "index.html")]
 pub struct IndexTemplate {
-    pub total_conversations: usize,
+    /// Formatted for the configured language
+    pub total_conversations: String,
     pub conversations_by_month: Vec<MonthGroup>,
 }
 
 #[derive(Debug, Clone)]
 pub struct MonthGroup {
     pub label: String,
+    /// Number of conversations, formatted for the configured language
+    pub count: String,
     pub conversations: Vec<ConversationMeta>,
 }
 
@@ -43,7 +216,9 @@ pub struct MonthGroup {
 pub struct ConversationMeta {
     pub id: String,
     pub title: String,
+    /// Start of the first message, if `index_preview_chars` is set
+    pub preview: Option<String>,
     pub url: String,
-    pub inserted_at: Option<DateTime<Utc>>,
+    pub inserted_at: Option<DateTime<Tz>>,
 }
 
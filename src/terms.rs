@@ -0,0 +1,74 @@
+use once_cell::sync::Lazy;
+use serde::Serialize;
+use std::collections::{HashMap, HashSet};
+
+use crate::model::{self, ExtractedMessage};
+
+/// Words too common to tell conversations apart (Russian and English)
+pub const STOP_WORDS: &[&str] = &[
+    // Russian
+    "без", "более", "больше", "будет", "будто", "бывает", "был", "была", "были", "было", "быть",
+    "вам", "вас", "ведь", "весь", "вот", "впрочем", "все", "всегда", "всего", "всех", "всю",
+    "где", "даже", "два", "для", "его", "если", "есть", "ещё", "еще", "зачем", "здесь", "или",
+    "иногда", "их", "как", "какая", "какие", "какой", "когда", "конечно", "которая", "которое",
+    "которые", "который", "куда", "лучше", "между", "меня", "мне", "много", "может", "можно",
+    "мой", "моя", "над", "надо", "наконец", "нас", "него", "нее", "неё", "нельзя", "нет", "ним",
+    "них", "ничего", "нужно", "однако", "один", "она", "они", "оно", "опять", "очень", "перед",
+    "под", "после", "потом", "потому", "почти", "при", "про", "раз", "разве", "свой", "свою",
+    "своя", "себе", "себя", "сейчас", "сам", "совсем", "так", "также", "такая", "такие", "такое", "такой", "там", "тебя",
+    "тем", "теперь", "то", "тогда", "того", "тоже", "только", "том", "тот", "три", "тут",
+    "уже", "хоть", "хорошо", "чего", "чем", "через", "что", "чтоб", "чтобы", "чуть", "эта",
+    "эти", "это", "этого", "этой", "этом", "этот", "эту",
+    // English
+    "about", "after", "all", "also", "and", "any", "are", "because", "been", "but", "can",
+    "could", "did", "does", "each", "for", "from", "had", "has", "have", "her", "here", "his",
+    "how", "into", "its", "just", "like", "more", "not", "one", "only", "other", "our", "out",
+    "over", "said", "she", "should", "some", "such", "than", "that", "the", "their", "them",
+    "then", "there", "these", "they", "this", "those", "use", "very", "was", "were", "what",
+    "when", "where", "which", "who", "why", "will", "with", "would", "you", "your",
+];
+
+static STOP_WORD_SET: Lazy<HashSet<&'static str>> = Lazy::new(|| STOP_WORDS.iter().copied().collect());
+
+/// Shorter words are skipped
+const MIN_TERM_CHARS: usize = 3;
+
+/// A word and how many times it occurs
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct TermCount {
+    pub term: String,
+    pub count: usize,
+}
+
+/// The `limit` most frequent words of a conversation, most frequent first
+/// (ties alphabetically). Stop words, numbers and words shorter than three
+/// letters are left out, as are web search results and tool calls.
+pub fn top_terms(messages: &[ExtractedMessage], limit: usize) -> Vec<TermCount> {
+    let mut counts: HashMap<String, usize> = HashMap::new();
+
+    let texts = messages
+        .iter()
+        .filter(|m| !model::is_tool_fragment(&m.fragment_type))
+        .map(|m| m.content.as_str());
+
+    for text in texts {
+        for word in text.split(|c: char| !c.is_alphanumeric()) {
+            if word.chars().count() < MIN_TERM_CHARS || word.chars().all(|c| c.is_numeric()) {
+                continue;
+            }
+            let word = word.to_lowercase();
+            if !STOP_WORD_SET.contains(word.as_str()) {
+                *counts.entry(word).or_default() += 1;
+            }
+        }
+    }
+
+    let mut terms: Vec<TermCount> = counts
+        .into_iter()
+        .map(|(term, count)| TermCount { term, count })
+        .collect();
+    terms.sort_by(|a, b| b.count.cmp(&a.count).then_with(|| a.term.cmp(&b.term)));
+    terms.truncate(limit);
+
+    terms
+}
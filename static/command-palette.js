@@ -0,0 +1,221 @@
+// Ctrl+K: jump to a conversation by part of its title, its id or a pasted link
+(function() {
+    const MAX_RESULTS = 10;
+    // Largest page /api/conversations/meta returns
+    const PAGE_SIZE = 1000;
+
+    // Lowercase and strip diacritics, so "cafe" finds "Café" and "ежик" finds "Ёжик"
+    function normalize(text) {
+        return text.normalize('NFD').replace(/[\u0300-\u036f]/g, '').toLowerCase();
+    }
+
+    let conversations = null;
+    let loading = null;
+
+    function fetchPage(offset) {
+        return fetch(`/api/conversations/meta?sort=updated_at&limit=${PAGE_SIZE}&offset=${offset}`)
+            .then(response => {
+                if (!response.ok) {
+                    throw new Error(`HTTP ${response.status}`);
+                }
+                return response.json();
+            });
+    }
+
+    async function fetchAll() {
+        let all = [];
+        let total = Infinity;
+        while (all.length < total) {
+            const page = await fetchPage(all.length);
+            total = page.total;
+            if (page.conversations.length === 0) {
+                break;
+            }
+            all = all.concat(page.conversations);
+        }
+        return all.map(conv => ({ id: conv.conversation_id, title: conv.title, url: conv.url }));
+    }
+
+    // Without the server (e.g. pages opened offline) only the sidebar's conversations are known
+    function fromSidebar() {
+        return Array.from(document.querySelectorAll('.conversation-item[data-id]')).map(item => ({
+            id: item.dataset.id,
+            title: item.querySelector('.conversation-title')?.textContent || item.dataset.id,
+            url: item.querySelector('a')?.getAttribute('href') || `/conversations/${item.dataset.id}/`
+        }));
+    }
+
+    function load() {
+        if (!loading) {
+            loading = fetchAll()
+                .catch(error => {
+                    console.warn('Could not load conversations, using the sidebar:', error);
+                    return fromSidebar();
+                })
+                .then(list => {
+                    conversations = list.map(conv => Object.assign(conv, { key: normalize(conv.title || '') }));
+                });
+        }
+        return loading;
+    }
+
+    // A pasted page link or short link opens as is; an id opens its conversation
+    function directTarget(query) {
+        let path = null;
+        try {
+            path = new URL(query).pathname;
+        } catch (e) {
+            if (query.startsWith('/')) {
+                path = query;
+            }
+        }
+        if (path && /^\/(conversations|c)\/[^/]+/.test(path)) {
+            return path;
+        }
+
+        const byId = conversations && conversations.find(conv => conv.id === query);
+        return byId ? byId.url : null;
+    }
+
+    // Every query char in order, scored higher for runs and word starts; -1 if they aren't all there
+    function fuzzyScore(key, query) {
+        let score = 0;
+        let run = 0;
+        let from = 0;
+        for (const char of query) {
+            const idx = key.indexOf(char, from);
+            if (idx === -1) {
+                return -1;
+            }
+            run = idx === from ? run + 1 : 1;
+            score += run;
+            if (idx === 0 || /[\s\-_.,:;«"(]/.test(key[idx - 1])) {
+                score += 2;
+            }
+            from = idx + 1;
+        }
+        // Of equal matches, prefer shorter titles
+        return score - key.length / 1000;
+    }
+
+    function match(query) {
+        const normalized = normalize(query);
+        return conversations
+            .map(conv => ({
+                conv: conv,
+                // The start of an id beats any title match
+                score: conv.id.startsWith(query) ? Number.MAX_SAFE_INTEGER : fuzzyScore(conv.key, normalized)
+            }))
+            .filter(result => result.score >= 0)
+            .sort((a, b) => b.score - a.score)
+            .slice(0, MAX_RESULTS)
+            .map(result => result.conv);
+    }
+
+    let overlay = null;
+    let input = null;
+    let list = null;
+    let results = [];
+    let selected = 0;
+
+    function render() {
+        list.innerHTML = '';
+        results.forEach((conv, idx) => {
+            const item = document.createElement('li');
+            item.className = 'command-palette-item' + (idx === selected ? ' selected' : '');
+            const title = document.createElement('span');
+            title.className = 'command-palette-title';
+            title.textContent = conv.title || conv.id;
+            const id = document.createElement('span');
+            id.className = 'command-palette-id';
+            id.textContent = conv.id;
+            item.append(title, id);
+            item.addEventListener('mousedown', e => {
+                e.preventDefault();
+                window.location.href = conv.url;
+            });
+            list.appendChild(item);
+        });
+        list.hidden = results.length === 0;
+    }
+
+    function update() {
+        const query = input.value.trim();
+        selected = 0;
+        results = query && conversations ? match(query) : [];
+        render();
+    }
+
+    function go() {
+        const query = input.value.trim();
+        const target = directTarget(query) || results[selected]?.url;
+        if (target) {
+            window.location.href = target;
+        }
+    }
+
+    function close() {
+        overlay.hidden = true;
+        input.value = '';
+        results = [];
+        render();
+    }
+
+    function create() {
+        overlay = document.createElement('div');
+        overlay.className = 'command-palette-overlay';
+        overlay.hidden = true;
+        overlay.innerHTML = `
+            <div class="command-palette" role="dialog" aria-label="Переход к беседе">
+                <input type="text" class="command-palette-input" placeholder="Название, id или ссылка на беседу..." autocomplete="off">
+                <ul class="command-palette-results" hidden></ul>
+            </div>`;
+        document.body.appendChild(overlay);
+
+        input = overlay.querySelector('.command-palette-input');
+        list = overlay.querySelector('.command-palette-results');
+
+        input.addEventListener('input', update);
+        input.addEventListener('keydown', e => {
+            if (e.key === 'ArrowDown' || e.key === 'ArrowUp') {
+                e.preventDefault();
+                if (results.length) {
+                    selected = (selected + (e.key === 'ArrowDown' ? 1 : results.length - 1)) % results.length;
+                    render();
+                }
+            } else if (e.key === 'Enter') {
+                e.preventDefault();
+                go();
+            } else if (e.key === 'Escape') {
+                e.preventDefault();
+                close();
+            }
+        });
+        overlay.addEventListener('mousedown', e => {
+            if (e.target === overlay) {
+                close();
+            }
+        });
+    }
+
+    function open() {
+        if (!overlay) {
+            create();
+        }
+        overlay.hidden = false;
+        input.focus();
+        // Typed before the list arrived: match it now
+        load().then(update);
+    }
+
+    document.addEventListener('keydown', e => {
+        if ((e.ctrlKey || e.metaKey) && e.key === 'k') {
+            e.preventDefault();
+            if (overlay && !overlay.hidden) {
+                close();
+            } else {
+                open();
+            }
+        }
+    });
+})();
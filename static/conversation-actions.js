@@ -0,0 +1,205 @@
+// Conversation page actions: copy the conversation ID, show its source JSON,
+// copy shareable message links and follow them (`?highlight=term#m-anchor`)
+// Wrapped in IIFE to avoid global variable conflicts
+(function() {
+    const CHECK_ICON = '<svg width="14" height="14" viewBox="0 0 16 16" fill="none"><path d="M13 4L6 11L3 8" stroke="currentColor" stroke-width="2" stroke-linecap="round" stroke-linejoin="round"/></svg>';
+
+    function flash(button, html, color) {
+        const originalText = button.innerHTML;
+        button.innerHTML = html;
+        button.style.color = color;
+        setTimeout(() => {
+            button.innerHTML = originalText;
+            button.style.color = '';
+        }, 2000);
+    }
+
+    async function copyText(text) {
+        if (window.__TAURI__ && window.__TAURI__.clipboard && window.__TAURI__.clipboard.writeText) {
+            await window.__TAURI__.clipboard.writeText(text);
+        } else {
+            await navigator.clipboard.writeText(text);
+        }
+    }
+
+    // Modal with the conversation's entry from the export file
+    function showSource(json) {
+        let dialog = document.getElementById('source-dialog');
+        if (!dialog) {
+            dialog = document.createElement('dialog');
+            dialog.id = 'source-dialog';
+            dialog.className = 'source-dialog';
+            dialog.innerHTML =
+                '<div class="source-dialog-header">' +
+                '<span>Исходный JSON</span>' +
+                '<button type="button" class="code-btn source-copy-btn">Копировать</button>' +
+                '<button type="button" class="code-btn source-close-btn">Закрыть</button>' +
+                '</div>' +
+                '<pre class="source-dialog-body"></pre>';
+            dialog.querySelector('.source-close-btn').addEventListener('click', () => dialog.close());
+            dialog.querySelector('.source-copy-btn').addEventListener('click', async function() {
+                try {
+                    await copyText(dialog.querySelector('.source-dialog-body').textContent);
+                    flash(this, CHECK_ICON + 'Скопировано', '#10b981');
+                } catch (err) {
+                    console.error('Failed to copy source JSON:', err);
+                    flash(this, 'Ошибка', '#ef4444');
+                }
+            });
+            document.body.appendChild(dialog);
+        }
+
+        dialog.querySelector('.source-dialog-body').textContent = json;
+        dialog.showModal();
+    }
+
+    // Link to a message that also highlights `term` when opened
+    function shareUrl(anchor, term) {
+        const url = new URL(window.location.href);
+        url.search = '';
+        if (term) {
+            url.searchParams.set('highlight', term);
+        }
+        url.hash = anchor;
+        return url.toString();
+    }
+
+    // Text selected inside `element`, if any
+    function selectedText(element) {
+        const selection = window.getSelection();
+        if (!selection || selection.isCollapsed || !element.contains(selection.anchorNode)) {
+            return '';
+        }
+        return selection.toString().trim();
+    }
+
+    // Wrap each occurrence of `term` in message texts in <mark>
+    function highlightTerm(term) {
+        const needle = term.toLowerCase();
+        const marks = [];
+        document.querySelectorAll('.message-content').forEach(content => {
+            const walker = document.createTreeWalker(content, NodeFilter.SHOW_TEXT);
+            const nodes = [];
+            while (walker.nextNode()) {
+                nodes.push(walker.currentNode);
+            }
+            nodes.forEach(node => {
+                let index = node.nodeValue.toLowerCase().indexOf(needle);
+                while (index !== -1) {
+                    const match = node.splitText(index);
+                    node = match.splitText(term.length);
+                    const mark = document.createElement('mark');
+                    mark.className = 'page-highlight';
+                    match.parentNode.replaceChild(mark, match);
+                    mark.appendChild(match);
+                    marks.push(mark);
+                    index = node.nodeValue.toLowerCase().indexOf(needle);
+                }
+            });
+        });
+        return marks;
+    }
+
+    function showNotice(text) {
+        const notice = document.createElement('div');
+        notice.className = 'share-notice';
+        notice.setAttribute('role', 'status');
+        notice.textContent = text;
+        const container = document.querySelector('.messages-container') || document.body;
+        container.prepend(notice);
+    }
+
+    // Apply `?highlight=` and scroll to the `#m-...` message of a shared link
+    function followSharedLink() {
+        const term = (new URLSearchParams(window.location.search).get('highlight') || '').trim();
+        const anchor = decodeURIComponent(window.location.hash.slice(1));
+        const marks = term ? highlightTerm(term) : [];
+
+        if (anchor.startsWith('m-')) {
+            const target = document.getElementById(anchor);
+            if (target) {
+                // Highlighting may have moved it since the browser scrolled
+                target.scrollIntoView({ block: 'start' });
+                return;
+            }
+            showNotice(marks.length
+                ? 'Сообщение по ссылке не найдено, показано первое совпадение.'
+                : 'Сообщение по ссылке не найдено: возможно, оно изменилось или находится на другой странице.');
+        }
+        if (marks.length) {
+            marks[0].scrollIntoView({ block: 'center' });
+        }
+    }
+
+    function initConversationActions() {
+        const isInTauri = window.__TAURI__ !== undefined;
+
+        followSharedLink();
+
+        // The message link copies a shareable URL: with the text selected in
+        // the message (or the term highlighted now) to highlight when opened
+        document.querySelectorAll('.message-link').forEach(link => {
+            // Read before the click, which may clear the selection
+            let selected = '';
+            link.addEventListener('mousedown', function() {
+                selected = selectedText(this.closest('.message'));
+            });
+            link.addEventListener('click', async function(event) {
+                event.preventDefault();
+                const anchor = this.getAttribute('href').slice(1);
+                const term = selected
+                    || selectedText(this.closest('.message'))
+                    || new URLSearchParams(window.location.search).get('highlight')
+                    || '';
+                selected = '';
+                const url = shareUrl(anchor, term);
+                history.replaceState(null, '', url);
+                try {
+                    await copyText(url);
+                    flash(this, CHECK_ICON, '#10b981');
+                } catch (err) {
+                    console.error('Failed to copy message link:', err);
+                    flash(this, '!', '#ef4444');
+                }
+            });
+        });
+
+        document.querySelectorAll('[data-copy-id]').forEach(button => {
+            button.addEventListener('click', async function() {
+                try {
+                    await copyText(this.dataset.copyId);
+                    flash(this, CHECK_ICON + 'Скопировано', '#10b981');
+                } catch (err) {
+                    console.error('Failed to copy conversation ID:', err);
+                    flash(this, 'Ошибка', '#ef4444');
+                }
+            });
+        });
+
+        // The source JSON is read from the imported file by the desktop app
+        if (!isInTauri || !window.__TAURI__.core) {
+            return;
+        }
+
+        document.querySelectorAll('[data-source-id]').forEach(button => {
+            button.hidden = false;
+            button.addEventListener('click', async function() {
+                try {
+                    const json = await window.__TAURI__.core.invoke('get_raw_conversation', {
+                        conversationId: this.dataset.sourceId
+                    });
+                    showSource(json);
+                } catch (err) {
+                    console.error('Failed to load source JSON:', err);
+                    flash(this, 'Ошибка', '#ef4444');
+                }
+            });
+        });
+    }
+
+    if (document.readyState === 'loading') {
+        document.addEventListener('DOMContentLoaded', initConversationActions);
+    } else {
+        initConversationActions();
+    }
+})();
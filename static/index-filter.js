@@ -0,0 +1,77 @@
+// Filter the index page's month groups by conversation title
+(function() {
+    // Lowercase and strip diacritics, so "cafe" finds "Café" and "ежик" finds "Ёжик"
+    function normalize(text) {
+        return text.normalize('NFD').replace(/[\u0300-\u036f]/g, '').toLowerCase();
+    }
+
+    function init() {
+        const input = document.getElementById('indexFilter');
+        if (!input) {
+            return;
+        }
+
+        const groups = Array.from(document.querySelectorAll('[data-month-group]')).map(function(group) {
+            return {
+                element: group,
+                count: group.querySelector('.month-count'),
+                items: Array.from(group.querySelectorAll('.month-conversation')).map(function(item) {
+                    return { element: item, title: normalize(item.dataset.title || '') };
+                })
+            };
+        });
+
+        // Remember which groups the user opened, to restore them when the filter is cleared
+        let userOpen = null;
+
+        function apply() {
+            const query = normalize(input.value.trim());
+
+            if (!query) {
+                groups.forEach(function(group) {
+                    group.element.hidden = false;
+                    group.items.forEach(function(item) { item.element.hidden = false; });
+                    group.count.textContent = group.count.dataset.total;
+                });
+                if (userOpen) {
+                    groups.forEach(function(group, i) { group.element.open = userOpen[i]; });
+                    userOpen = null;
+                }
+                return;
+            }
+
+            if (!userOpen) {
+                userOpen = groups.map(function(group) { return group.element.open; });
+            }
+
+            groups.forEach(function(group) {
+                let visible = 0;
+                group.items.forEach(function(item) {
+                    const match = item.title.includes(query);
+                    item.element.hidden = !match;
+                    if (match) {
+                        visible++;
+                    }
+                });
+
+                group.element.hidden = visible === 0;
+                group.element.open = visible > 0;
+                group.count.textContent = visible + ' из ' + group.count.dataset.total;
+            });
+        }
+
+        input.addEventListener('input', apply);
+        input.addEventListener('keydown', function(e) {
+            if (e.key === 'Escape') {
+                input.value = '';
+                apply();
+            }
+        });
+    }
+
+    if (document.readyState === 'loading') {
+        document.addEventListener('DOMContentLoaded', init);
+    } else {
+        init();
+    }
+})();
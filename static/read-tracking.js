@@ -0,0 +1,78 @@
+// Read/unread conversations: opening a conversation marks it read, and the
+// sidebar and index mark the ones never opened. The desktop app keeps the
+// ids in its config so they survive a new import; browsers use localStorage.
+(function() {
+    const STORAGE_KEY = 'readConversations';
+
+    function tauriCore() {
+        return window.__TAURI__ && window.__TAURI__.core ? window.__TAURI__.core : null;
+    }
+
+    function loadLocal() {
+        try {
+            return JSON.parse(localStorage.getItem(STORAGE_KEY) || '[]');
+        } catch (error) {
+            return [];
+        }
+    }
+
+    function saveLocal(ids) {
+        try {
+            localStorage.setItem(STORAGE_KEY, JSON.stringify(Array.from(ids)));
+        } catch (error) {
+            console.warn('Could not save read conversations:', error);
+        }
+    }
+
+    async function loadRead() {
+        const core = tauriCore();
+        if (core) {
+            try {
+                return new Set(await core.invoke('get_read_conversations'));
+            } catch (error) {
+                console.error('Failed to load read conversations:', error);
+            }
+        }
+        return new Set(loadLocal());
+    }
+
+    async function markRead(id, read) {
+        read.add(id);
+        saveLocal(read);
+        const core = tauriCore();
+        if (core) {
+            try {
+                await core.invoke('mark_conversation_read', { conversationId: id });
+            } catch (error) {
+                console.error('Failed to save read conversation:', error);
+            }
+        }
+    }
+
+    // The conversation this page shows; none on the index and the archive
+    function currentConversation() {
+        const articles = document.querySelectorAll('article[data-conversation-id]');
+        return articles.length === 1 ? articles[0].dataset.conversationId : null;
+    }
+
+    async function init() {
+        const read = await loadRead();
+
+        const current = currentConversation();
+        if (current && !read.has(current)) {
+            await markRead(current, read);
+        }
+
+        document.querySelectorAll('.conversation-item[data-id], .month-conversation[data-id]').forEach(item => {
+            const isRead = read.has(item.dataset.id);
+            item.classList.toggle('read', isRead);
+            item.classList.toggle('unread', !isRead);
+        });
+    }
+
+    if (document.readyState === 'loading') {
+        document.addEventListener('DOMContentLoaded', init);
+    } else {
+        init();
+    }
+})();
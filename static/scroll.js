@@ -0,0 +1,74 @@
+// Scroll progress bar and "back to top" button for long conversations
+(function() {
+    const reduceMotion = window.matchMedia('(prefers-reduced-motion: reduce)');
+
+    // Pages normally scroll the window, but .main-content scrolls on its own
+    // when the layout gives it a fixed height
+    function scroller() {
+        const main = document.querySelector('.main-content');
+        if (main && main.scrollHeight > main.clientHeight + 1) {
+            const overflow = getComputedStyle(main).overflowY;
+            if (overflow === 'auto' || overflow === 'scroll') {
+                return main;
+            }
+        }
+        return document.scrollingElement || document.documentElement;
+    }
+
+    function init() {
+        const bar = document.createElement('div');
+        bar.className = 'scroll-progress';
+        bar.setAttribute('aria-hidden', 'true');
+        document.body.appendChild(bar);
+
+        const button = document.createElement('button');
+        button.className = 'back-to-top';
+        button.type = 'button';
+        button.title = 'Наверх';
+        button.setAttribute('aria-label', 'Наверх');
+        button.textContent = '↑';
+        document.body.appendChild(button);
+
+        let scheduled = false;
+
+        function update() {
+            scheduled = false;
+            const el = scroller();
+            const max = el.scrollHeight - el.clientHeight;
+            const progress = max > 0 ? el.scrollTop / max : 0;
+
+            bar.style.transform = 'scaleX(' + progress + ')';
+            // Only offer the button once the first screen is out of view
+            button.classList.toggle('visible', el.scrollTop > el.clientHeight);
+        }
+
+        function schedule() {
+            if (!scheduled) {
+                scheduled = true;
+                requestAnimationFrame(update);
+            }
+        }
+
+        button.addEventListener('click', function() {
+            scroller().scrollTo({
+                top: 0,
+                behavior: reduceMotion.matches ? 'auto' : 'smooth'
+            });
+        });
+
+        window.addEventListener('scroll', schedule, { passive: true });
+        window.addEventListener('resize', schedule);
+        const main = document.querySelector('.main-content');
+        if (main) {
+            main.addEventListener('scroll', schedule, { passive: true });
+        }
+
+        update();
+    }
+
+    if (document.readyState === 'loading') {
+        document.addEventListener('DOMContentLoaded', init);
+    } else {
+        init();
+    }
+})();
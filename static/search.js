@@ -2,10 +2,30 @@
 class RustSearch {
     constructor() {
         this.apiUrl = '/api/search';
+        this.autocompleteUrl = '/api/autocomplete';
         this.debounceMs = 100; // Faster realtime search
         this.timeout = null;
         this.cache = new Map();
         this.minQueryLength = 2; // Ngram tokenizer требует минимум 2 символа
+        this.recentAvailable = true;
+    }
+
+    // Latest conversations, or null if the server doesn't list them for an empty query
+    async recent(limit = 50) {
+        if (!this.recentAvailable) return null;
+        try {
+            const response = await fetch(`${this.apiUrl}?q=&limit=${limit}`);
+            if (response.status === 400) {
+                this.recentAvailable = false;
+                return null;
+            }
+            if (!response.ok) throw new Error(`Search failed: ${response.status}`);
+            const data = await response.json();
+            return data.results.map(toResult);
+        } catch (error) {
+            console.error('Recent conversations error:', error);
+            return null;
+        }
     }
 
     async search(query, limit = 50) {
@@ -29,13 +49,7 @@ class RustSearch {
             
             console.log(`🚀 Search: "${query}" → ${data.total} results in ${totalTime.toFixed(1)}ms (server: ${data.time_ms}ms)`);
             
-            const results = data.results.map(r => ({
-                id: r.conversation_id,
-                title: r.title,
-                url: `/conversations/${r.conversation_id}/`,
-                snippet: r.snippet,
-                score: r.score
-            }));
+            const results = data.results.map(toResult);
 
             // Cache results
             this.cache.set(cacheKey, results);
@@ -50,6 +64,29 @@ class RustSearch {
             return [];
         }
     }
+
+    async suggest(prefix, limit = 8) {
+        try {
+            const url = `${this.autocompleteUrl}?prefix=${encodeURIComponent(prefix)}&limit=${limit}`;
+            const response = await fetch(url);
+            if (!response.ok) return [];
+            return await response.json();
+        } catch (error) {
+            console.error('Autocomplete error:', error);
+            return [];
+        }
+    }
+}
+
+function toResult(r) {
+    return {
+        id: r.conversation_id,
+        title: r.title,
+        url: r.url || `/conversations/${r.conversation_id}/`,
+        snippet: r.snippet,
+        snippetHtml: r.snippet_html,
+        score: r.score
+    };
 }
 
 const search = new RustSearch();
@@ -58,9 +95,59 @@ document.addEventListener('DOMContentLoaded', () => {
     const searchInput = document.getElementById('searchInput');
     const searchResults = document.getElementById('searchResults');
     const conversationsList = document.getElementById('conversationsList');
+    const searchSuggestions = document.getElementById('searchSuggestions');
+    const highlightToggle = document.getElementById('searchHighlightToggle');
 
     if (!searchInput) return;
 
+    // Whether matches are wrapped in <mark>, remembered across pages
+    const HIGHLIGHT_KEY = 'searchHighlight';
+    let highlightMatches = true;
+    try {
+        highlightMatches = localStorage.getItem(HIGHLIGHT_KEY) !== 'off';
+    } catch (error) {
+        // Storage can be unavailable (e.g. disabled cookies), keep the default
+    }
+
+    // Shown results, redrawn without re-querying when the toggle changes
+    let lastResults = null;
+    let lastQuery = '';
+
+    if (highlightToggle) {
+        highlightToggle.checked = highlightMatches;
+        highlightToggle.addEventListener('change', () => {
+            highlightMatches = highlightToggle.checked;
+            try {
+                localStorage.setItem(HIGHLIGHT_KEY, highlightMatches ? 'on' : 'off');
+            } catch (error) {
+                console.warn('Could not save the highlight setting:', error);
+            }
+            if (lastResults && searchResults.style.display !== 'none') {
+                displayResults(lastResults, lastQuery);
+            }
+        });
+    }
+
+    // Complete the last word of the query
+    async function updateSuggestions(query) {
+        if (!searchSuggestions) return;
+
+        const words = query.split(/\s+/);
+        const lastWord = words.pop();
+        if (lastWord.length < search.minQueryLength) {
+            searchSuggestions.innerHTML = '';
+            return;
+        }
+
+        const suggestions = await search.suggest(lastWord);
+        searchSuggestions.innerHTML = '';
+        suggestions.forEach(term => {
+            const option = document.createElement('option');
+            option.value = [...words, term].join(' ');
+            searchSuggestions.appendChild(option);
+        });
+    }
+
     // Search input handler
     searchInput.addEventListener('input', (e) => {
         const query = e.target.value.trim();
@@ -87,18 +174,40 @@ document.addEventListener('DOMContentLoaded', () => {
         conversationsList.style.display = 'none';
 
         search.timeout = setTimeout(async () => {
+            updateSuggestions(query);
             const results = await search.search(query);
             displayResults(results, query);
         }, search.debounceMs);
     });
 
+    // With `empty_search: "recent"` an empty box lists the latest conversations
+    searchInput.addEventListener('focus', async () => {
+        if (searchInput.value.trim()) return;
+        const results = await search.recent();
+        if (!results || searchInput.value.trim()) return;
+        displayResults(results, '');
+        searchResults.style.display = 'block';
+        conversationsList.style.display = 'none';
+    });
+
     function displayResults(results, query) {
+        lastResults = results;
+        lastQuery = query;
+
         if (!results.length) {
             searchResults.innerHTML = '<div class="search-no-results">Ничего не найдено</div>';
             return;
         }
 
         const fragment = document.createDocumentFragment();
+
+        const copyButton = document.createElement('button');
+        copyButton.type = 'button';
+        copyButton.className = 'search-copy-btn';
+        copyButton.textContent = 'Копировать ссылки';
+        copyButton.title = 'Скопировать результаты списком ссылок Markdown';
+        copyButton.addEventListener('click', () => copyResults(results, copyButton));
+        fragment.appendChild(copyButton);
         
         results.forEach(r => {
             const item = document.createElement('a');
@@ -107,13 +216,18 @@ document.addEventListener('DOMContentLoaded', () => {
             
             const title = document.createElement('div');
             title.className = 'search-result-title';
-            title.innerHTML = highlight(r.title, query);
+            setText(title, r.title, query);
             item.appendChild(title);
             
             if (r.snippet) {
                 const snippet = document.createElement('div');
                 snippet.className = 'search-result-snippet';
-                snippet.innerHTML = highlight(r.snippet, query);
+                // The server marks what matched, ngrams included
+                if (highlightMatches && r.snippetHtml) {
+                    snippet.innerHTML = r.snippetHtml;
+                } else {
+                    setText(snippet, r.snippet, query);
+                }
                 item.appendChild(snippet);
             }
             
@@ -124,6 +238,44 @@ document.addEventListener('DOMContentLoaded', () => {
         searchResults.appendChild(fragment);
     }
 
+    // Results as a Markdown list of links
+    function resultsMarkdown(results) {
+        return results
+            .map(r => `- [${r.title.replace(/([\\\[\]])/g, '\\$1')}](${r.url})`)
+            .join('\n');
+    }
+
+    async function copyResults(results, button) {
+        const originalText = button.textContent;
+        try {
+            if (!results.length) {
+                throw new Error('No results to copy');
+            }
+            const markdown = resultsMarkdown(results);
+            if (window.__TAURI__ && window.__TAURI__.clipboard && window.__TAURI__.clipboard.writeText) {
+                await window.__TAURI__.clipboard.writeText(markdown);
+            } else {
+                await navigator.clipboard.writeText(markdown);
+            }
+            button.textContent = `Скопировано: ${results.length}`;
+        } catch (error) {
+            console.error('Copy results error:', error);
+            button.textContent = 'Не удалось скопировать';
+        }
+        setTimeout(() => {
+            button.textContent = originalText;
+        }, 2000);
+    }
+
+    // Fill `element` with `text`, matches of `query` marked if highlighting is on
+    function setText(element, text, query) {
+        if (highlightMatches && query) {
+            element.innerHTML = highlight(text, query);
+        } else {
+            element.textContent = text;
+        }
+    }
+
     function highlight(text, query) {
         const regex = new RegExp(`(${escapeRegex(query)})`, 'gi');
         return text.replace(regex, '<mark>$1</mark>');
@@ -135,8 +287,9 @@ document.addEventListener('DOMContentLoaded', () => {
 
     // Keyboard shortcuts
     document.addEventListener('keydown', (e) => {
-        // Ctrl/Cmd + K
-        if ((e.ctrlKey || e.metaKey) && e.key === 'k') {
+        // "/" outside a text field (Ctrl+K opens the command palette)
+        const typing = e.target.closest && e.target.closest('input, textarea, [contenteditable]');
+        if (e.key === '/' && !typing && !e.ctrlKey && !e.metaKey && !e.altKey) {
             e.preventDefault();
             searchInput.focus();
             searchInput.select();
@@ -0,0 +1,83 @@
+// Settings page: loads the saved settings from /api/config into the form
+// and saves changes with PATCH. Only the server has this API; opened
+// anywhere else, the page says so instead of showing the form.
+(function() {
+    const NUMBER_FIELDS = ['messages_per_page', 'archive_page_conversations', 'index_preview_chars'];
+    // Pages only change when the site is generated again: the server does it
+    // when started with --force, the desktop app on every import
+    const REGENERATE_HINT = 'Чтобы применить их к страницам, сгенерируйте сайт заново: '
+        + 'перезапустите сервер с --force или, в приложении, снова импортируйте экспорт.';
+
+    function showStatus(text, isError) {
+        const status = document.getElementById('settingsStatus');
+        status.textContent = text;
+        status.classList.toggle('settings-status-error', Boolean(isError));
+    }
+
+    function fillForm(form, settings) {
+        Object.entries(settings).forEach(([name, value]) => {
+            const field = form.elements[name];
+            if (field) {
+                field.value = value;
+            }
+        });
+    }
+
+    function readForm(form) {
+        const settings = {};
+        new FormData(form).forEach((value, name) => {
+            settings[name] = NUMBER_FIELDS.includes(name) ? Number(value) : value;
+        });
+        return settings;
+    }
+
+    async function save(form) {
+        const response = await fetch('/api/config', {
+            method: 'PATCH',
+            headers: { 'Content-Type': 'application/json' },
+            body: JSON.stringify(readForm(form)),
+        });
+        if (!response.ok) {
+            throw new Error(await response.text() || response.statusText);
+        }
+        return response.json();
+    }
+
+    function init() {
+        const form = document.getElementById('settingsForm');
+        if (!form) {
+            return;
+        }
+
+        fetch('/api/config')
+            .then(response => response.ok ? response.json() : Promise.reject(new Error(response.statusText)))
+            .then(config => {
+                fillForm(form, config.settings);
+                form.hidden = false;
+                if (config.regenerate_required) {
+                    showStatus('Сохранённые настройки ещё не применены. ' + REGENERATE_HINT);
+                }
+            })
+            .catch(error => {
+                console.warn('Could not load settings:', error);
+                showStatus('Настройки можно изменить, только когда сайт открыт через сервер.', true);
+            });
+
+        form.addEventListener('submit', event => {
+            event.preventDefault();
+            showStatus('Сохранение...');
+            save(form)
+                .then(config => {
+                    fillForm(form, config.settings);
+                    showStatus(config.regenerate_required ? 'Сохранено. ' + REGENERATE_HINT : 'Сохранено.');
+                })
+                .catch(error => showStatus('Не удалось сохранить: ' + error.message, true));
+        });
+    }
+
+    if (document.readyState === 'loading') {
+        document.addEventListener('DOMContentLoaded', init);
+    } else {
+        init();
+    }
+})();
@@ -0,0 +1,57 @@
+// Service worker for reading the site offline (enabled by `offline_pages`).
+// The generator stamps CACHE_VERSION on every run, so a regenerated site
+// starts with a fresh cache instead of serving stale pages.
+const CACHE_VERSION = '__CACHE_VERSION__';
+const CACHE_PREFIX = 'deepseek-viewer';
+const CACHE_NAME = CACHE_PREFIX + '-' + CACHE_VERSION;
+
+self.addEventListener('install', () => self.skipWaiting());
+
+self.addEventListener('activate', (event) => {
+    event.waitUntil(
+        caches.keys()
+            .then((names) => Promise.all(names
+                .filter((name) => name.startsWith(CACHE_PREFIX) && name !== CACHE_NAME)
+                .map((name) => caches.delete(name))))
+            .then(() => self.clients.claim())
+    );
+});
+
+function store(request, response) {
+    if (response.ok) {
+        const copy = response.clone();
+        caches.open(CACHE_NAME).then((cache) => cache.put(request, copy));
+    }
+    return response;
+}
+
+// Styles and scripts only change when the site is regenerated
+function cacheFirst(request) {
+    return caches.match(request).then((cached) => cached || fetch(request).then((response) => store(request, response)));
+}
+
+// Pages come fresh from the server when it's reachable. Offline, a page
+// opened from a shared link (`?highlight=...`) is the page without the query.
+function networkFirst(request) {
+    return fetch(request)
+        .then((response) => store(request, response))
+        .catch(() => caches.match(request, { ignoreSearch: true }).then((cached) => cached || Response.error()));
+}
+
+self.addEventListener('fetch', (event) => {
+    const request = event.request;
+    const url = new URL(request.url);
+
+    // Search, import and other live endpoints always go to the server
+    if (request.method !== 'GET' || url.origin !== self.location.origin
+        || url.pathname.startsWith('/api/') || url.pathname.startsWith('/ws/')
+        || url.pathname.startsWith('/import')) {
+        return;
+    }
+
+    if (url.pathname.startsWith('/assets/')) {
+        event.respondWith(cacheFirst(request));
+    } else if (request.mode === 'navigate') {
+        event.respondWith(networkFirst(request));
+    }
+});
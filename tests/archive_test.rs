@@ -0,0 +1,55 @@
+use anyhow::Result;
+use std::fs;
+use std::io::{Cursor, Read};
+use tempfile::TempDir;
+
+use deepseek_app::archive::{self, ArchiveScope};
+use deepseek_app::config::AppConfig;
+use deepseek_app::generator;
+
+const ARRAY_EXPORT: &str = "tests/fixtures/array_export.json";
+
+/// Generate the fixture site with raw markdown next to each page
+async fn generate(temp_dir: &TempDir) -> Result<std::path::PathBuf> {
+    let output_dir = temp_dir.path().join("dist");
+    let config = AppConfig { write_raw_markdown: true, ..AppConfig::default() };
+    generator::generate_site(ARRAY_EXPORT, output_dir.to_str().unwrap(), &config).await?;
+    Ok(output_dir)
+}
+
+#[tokio::test]
+async fn test_zip_contains_the_whole_site() -> Result<()> {
+    let temp_dir = TempDir::new()?;
+    let output_dir = generate(&temp_dir).await?;
+
+    let mut buffer = Vec::new();
+    let files = archive::write_zip(&output_dir, ArchiveScope::Site, &mut buffer)?;
+
+    let mut zip = zip::ZipArchive::new(Cursor::new(buffer))?;
+    assert_eq!(zip.len(), files);
+    for name in ["index.html", "assets/css/main.css", "conversations/conv-1/index.html", "conversations/conv-1/raw.md"] {
+        assert!(zip.by_name(name).is_ok(), "missing {}", name);
+    }
+
+    let mut page = String::new();
+    zip.by_name("conversations/conv-1/index.html")?.read_to_string(&mut page)?;
+    assert_eq!(page, fs::read_to_string(output_dir.join("conversations/conv-1/index.html"))?);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_zip_markdown_scope() -> Result<()> {
+    let temp_dir = TempDir::new()?;
+    let output_dir = generate(&temp_dir).await?;
+
+    let mut buffer = Vec::new();
+    let files = archive::write_zip(&output_dir, ArchiveScope::Markdown, &mut buffer)?;
+
+    let zip = zip::ZipArchive::new(Cursor::new(buffer))?;
+    let names: Vec<&str> = zip.file_names().collect();
+    assert!(files > 0);
+    assert!(names.iter().all(|name| name.ends_with("raw.md")), "only markdown: {:?}", names);
+
+    Ok(())
+}
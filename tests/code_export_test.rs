@@ -0,0 +1,92 @@
+use anyhow::Result;
+use serde_json::json;
+use std::io::{Cursor, Read};
+
+use deepseek_app::code_export;
+use deepseek_app::config::AppConfig;
+use deepseek_app::generator;
+use deepseek_app::model::Conversation;
+
+/// A conversation whose answer has several code blocks
+fn conversation() -> Result<Conversation> {
+    let answer = "Python:\n\n```python\nprint('hi')\n```\n\nRust:\n\n```rust\nfn main() {}\n```\n\nAnd a note:\n\n```\nplain text\n```\n";
+    Ok(serde_json::from_value(json!({
+        "id": "code",
+        "title": "Hello in two languages",
+        "mapping": {
+            "root": { "children": ["q"] },
+            "q": {
+                "message": { "fragments": [{"type": "REQUEST", "content": "```js\nnot an answer\n```"}] },
+                "children": ["a"]
+            },
+            "a": {
+                "message": { "fragments": [{"type": "RESPONSE", "content": answer}] },
+                "children": []
+            }
+        }
+    }))?)
+}
+
+#[test]
+fn test_extract_code_blocks() -> Result<()> {
+    let blocks = generator::extract_code_blocks(&conversation()?, &AppConfig::default())?;
+
+    // Only responses are rendered as markdown
+    let found: Vec<(usize, &str, &str)> = blocks
+        .iter()
+        .map(|b| (b.number, b.lang.as_str(), b.code.as_str()))
+        .collect();
+    assert_eq!(found, vec![
+        (1, "python", "print('hi')\n"),
+        (2, "rust", "fn main() {}\n"),
+        (3, "", "plain text\n"),
+    ]);
+
+    Ok(())
+}
+
+#[test]
+fn test_code_blocks_follow_the_page_settings() -> Result<()> {
+    // A fence split between two responses
+    let export = std::fs::read_to_string("tests/fixtures/split_fence_messages.json")?;
+    let conversation = serde_json::from_str::<Vec<Conversation>>(&export)?.remove(0);
+
+    let config = AppConfig { merge_consecutive_messages: true, ..AppConfig::default() };
+    let blocks = generator::extract_code_blocks(&conversation, &config)?;
+    assert_eq!(blocks.len(), 1, "merged as on the page");
+    assert_eq!(blocks[0].code, "fn main() {\n    println!(\"hello\");\n}\n");
+
+    // Numbered in reading order even when the page shows the newest first
+    let config = AppConfig { newest_first: true, ..AppConfig::default() };
+    let numbers: Vec<usize> = generator::extract_code_blocks(&conversation, &config)?
+        .iter()
+        .map(|block| block.number)
+        .collect();
+    assert_eq!(numbers, vec![1, 2]);
+
+    Ok(())
+}
+
+#[test]
+fn test_code_zip_has_a_file_per_block() -> Result<()> {
+    let blocks = generator::extract_code_blocks(&conversation()?, &AppConfig::default())?;
+
+    let mut buffer = Vec::new();
+    let files = code_export::write_zip(&blocks, &mut buffer)?;
+
+    let mut zip = zip::ZipArchive::new(Cursor::new(buffer))?;
+    assert_eq!(zip.len(), files);
+    let names: Vec<&str> = zip.file_names().collect();
+    assert_eq!(names, ["01-python.py", "02-rust.rs", "03-text.txt", code_export::ALL_CODE_FILE]);
+
+    let mut code = String::new();
+    zip.by_name("02-rust.rs")?.read_to_string(&mut code)?;
+    assert_eq!(code, "fn main() {}\n");
+
+    let mut all = String::new();
+    zip.by_name(code_export::ALL_CODE_FILE)?.read_to_string(&mut all)?;
+    assert!(all.starts_with("## 1. python\n\n```python\nprint('hi')\n```\n"), "{}", all);
+    assert!(all.contains("## 3. text\n\n```\nplain text\n```\n"), "{}", all);
+
+    Ok(())
+}
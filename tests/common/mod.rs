@@ -0,0 +1,27 @@
+//! Fixtures shared by the integration tests
+
+// Each test binary compiles its own copy and uses only some of these
+#![allow(dead_code)]
+
+use serde_json::{json, Value};
+
+/// A conversation with a single user message, as it appears in `conversations.json`
+pub fn conversation(id: &str, title: &str, text: &str) -> Value {
+    conversation_at(id, title, "2024-01-01T00:00:00Z", text)
+}
+
+/// [`conversation`] created at `inserted_at`
+pub fn conversation_at(id: &str, title: &str, inserted_at: &str, text: &str) -> Value {
+    json!({
+        "id": id,
+        "title": title,
+        "inserted_at": inserted_at,
+        "mapping": {
+            "root": { "children": ["msg1"] },
+            "msg1": {
+                "message": { "fragments": [{"type": "REQUEST", "content": text}] },
+                "children": []
+            }
+        }
+    })
+}
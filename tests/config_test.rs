@@ -0,0 +1,182 @@
+use deepseek_app::config::AppConfig;
+
+#[test]
+fn test_missing_fields_use_defaults() {
+    // Config files written before a setting existed must keep loading
+    let config: AppConfig = serde_json::from_str(r#"{"conversations_file_path": "/tmp/c.json"}"#).unwrap();
+
+    assert_eq!(config.conversations_file_path.as_deref(), Some("/tmp/c.json"));
+    assert_eq!(config.search_default_limit, 20);
+    assert_eq!(config.search_max_limit, 200);
+    assert_eq!(config.ngram_sizes(), (2, 10));
+    assert_eq!(config.messages_per_page, 0, "conversations stay on one page unless asked");
+}
+
+#[test]
+fn test_invalid_ngram_sizes_fall_back() {
+    let config = |min, max| AppConfig { ngram_min: min, ngram_max: max, ..AppConfig::default() };
+
+    assert_eq!(config(1, 4).ngram_sizes(), (1, 4));
+    assert_eq!(config(3, 3).ngram_sizes(), (3, 3));
+    assert_eq!(config(0, 4).ngram_sizes(), (2, 10));
+    assert_eq!(config(5, 4).ngram_sizes(), (2, 10));
+}
+
+#[test]
+fn test_fonts_are_allowlisted() {
+    let mut config = AppConfig::default();
+    assert!(config.body_font_stack().starts_with("Inter"));
+    assert!(config.code_font_stack().contains("SFMono-Regular"));
+
+    config.body_font = Some("Serif".to_string());
+    config.code_font = Some("jetbrains-mono".to_string());
+    assert_eq!(config.body_font_stack(), r#"Georgia, "Times New Roman", serif"#);
+    assert_eq!(config.code_font_stack(), r#""JetBrains Mono", monospace"#);
+
+    // Anything else falls back to the default instead of reaching the CSS
+    config.body_font = Some("x; } body { display: none".to_string());
+    assert!(config.body_font_stack().starts_with("Inter"));
+}
+
+#[test]
+fn test_timezone_and_date_format_fall_back() {
+    let mut config = AppConfig::default();
+    assert_eq!(config.timezone(), chrono_tz::UTC);
+    assert_eq!(config.date_format(), deepseek_app::config::DEFAULT_DATE_FORMAT);
+
+    config.timezone = Some("Mars/Olympus_Mons".to_string());
+    config.date_format = Some("%Y-%m-%d %Q".to_string());
+    assert_eq!(config.timezone(), chrono_tz::UTC);
+    assert_eq!(config.date_format(), deepseek_app::config::DEFAULT_DATE_FORMAT);
+}
+
+#[tokio::test]
+async fn test_pages_use_configured_timezone() -> anyhow::Result<()> {
+    let temp_dir = tempfile::TempDir::new()?;
+    let conversations_path = temp_dir.path().join("conversations.json");
+    let output_dir = temp_dir.path().join("dist");
+
+    // Late evening UTC on January 31st is already February 1st in Tokyo
+    std::fs::write(&conversations_path, serde_json::json!([
+        {
+            "id": "tz",
+            "title": "Timezones",
+            "inserted_at": "2024-01-31T20:00:00Z",
+            "mapping": {
+                "root": { "children": ["q"] },
+                "q": {
+                    "message": {
+                        "inserted_at": "2024-01-31T20:30:00Z",
+                        "fragments": [{"type": "REQUEST", "content": "What time is it?"}]
+                    },
+                    "children": []
+                }
+            }
+        }
+    ]).to_string())?;
+
+    let config = AppConfig {
+        timezone: Some("Asia/Tokyo".to_string()),
+        date_format: Some("%Y-%m-%d %H:%M".to_string()),
+        ..AppConfig::default()
+    };
+    deepseek_app::generator::generate_site(
+        conversations_path.to_str().unwrap(),
+        output_dir.to_str().unwrap(),
+        &config,
+    ).await?;
+
+    let page = std::fs::read_to_string(output_dir.join("conversations/tz/index.html"))?;
+    assert!(page.contains("2024-02-01 05:00"), "conversation date not converted");
+    assert!(page.contains("05:30"), "message time not converted");
+    assert!(page.contains("Февраль 2024"), "sidebar not grouped by local month");
+    assert!(!page.contains("Январь 2024"));
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_long_conversations_are_paginated() -> anyhow::Result<()> {
+    let temp_dir = tempfile::TempDir::new()?;
+    let conversations_path = temp_dir.path().join("conversations.json");
+    let output_dir = temp_dir.path().join("dist");
+
+    // A chain of 5 messages and a single-message conversation
+    let mut mapping = serde_json::json!({ "root": { "children": ["m1"] } });
+    for i in 1..=5 {
+        let children = if i < 5 { vec![format!("m{}", i + 1)] } else { vec![] };
+        mapping[format!("m{}", i)] = serde_json::json!({
+            "message": { "fragments": [{"type": "REQUEST", "content": format!("message number {}", i)}] },
+            "children": children
+        });
+    }
+    std::fs::write(&conversations_path, serde_json::json!([
+        { "id": "long", "title": "Long", "inserted_at": "2024-01-01T10:00:00Z", "mapping": mapping },
+        {
+            "id": "short",
+            "title": "Short",
+            "inserted_at": "2024-01-02T10:00:00Z",
+            "mapping": {
+                "root": { "children": ["q"] },
+                "q": { "message": { "fragments": [{"type": "REQUEST", "content": "hi"}] }, "children": [] }
+            }
+        }
+    ]).to_string())?;
+
+    let config = AppConfig { messages_per_page: 2, ..AppConfig::default() };
+    deepseek_app::generator::generate_site(
+        conversations_path.to_str().unwrap(),
+        output_dir.to_str().unwrap(),
+        &config,
+    ).await?;
+
+    let long_dir = output_dir.join("conversations/long");
+    let first = std::fs::read_to_string(long_dir.join("index.html"))?;
+    let second = std::fs::read_to_string(long_dir.join("page-2.html"))?;
+    let last = std::fs::read_to_string(long_dir.join("page-3.html"))?;
+    assert!(!long_dir.join("page-4.html").exists());
+
+    assert!(first.contains("message number 2") && !first.contains("message number 3"));
+    assert!(second.contains("message number 3") && second.contains("message number 4"));
+    assert!(last.contains("message number 5"));
+
+    assert!(first.contains(r#"href="page-2.html""#) && !first.contains("page-prev"));
+    assert!(second.contains(r#"href="./""#) && second.contains(r#"href="page-3.html""#));
+    assert!(last.contains("page-prev") && !last.contains("page-next"));
+    assert!(first.contains("Страница 1 из 3"));
+
+    // Short conversations stay on one page without navigation
+    let short = std::fs::read_to_string(output_dir.join("conversations/short/index.html"))?;
+    assert!(!short.contains("conversation-pages"));
+    assert!(!output_dir.join("conversations/short/page-2.html").exists());
+
+    // Not split by default
+    deepseek_app::generator::generate_site(
+        conversations_path.to_str().unwrap(),
+        output_dir.to_str().unwrap(),
+        &AppConfig::default(),
+    ).await?;
+    assert!(std::fs::read_to_string(long_dir.join("index.html"))?.contains("message number 5"));
+    assert!(!long_dir.join("page-2.html").exists());
+
+    Ok(())
+}
+
+#[test]
+fn test_default_syntax_theme_is_listed() {
+    let themes = deepseek_app::generator::syntax_themes();
+    assert!(themes.iter().any(|t| t == deepseek_app::generator::SYNTAX_THEME));
+    assert!(themes.windows(2).all(|w| w[0] <= w[1]), "themes should be sorted");
+}
+
+#[test]
+fn test_unknown_syntax_theme_falls_back_to_the_default() {
+    let mut config = AppConfig::default();
+    assert_eq!(deepseek_app::generator::syntax_theme_name(&config), deepseek_app::generator::SYNTAX_THEME);
+
+    config.syntax_theme = Some("InspiredGitHub".to_string());
+    assert_eq!(deepseek_app::generator::syntax_theme_name(&config), "InspiredGitHub");
+
+    config.syntax_theme = Some("no-such-theme".to_string());
+    assert_eq!(deepseek_app::generator::syntax_theme_name(&config), deepseek_app::generator::SYNTAX_THEME);
+}
@@ -0,0 +1,155 @@
+use anyhow::Result;
+use std::fs;
+use tempfile::TempDir;
+
+use deepseek_app::config::AppConfig;
+use deepseek_app::export::{
+    decode_export, load_export, parse_conversations, read_export, validate_export_file, ExportFormat, ExportSummary,
+};
+use deepseek_app::search::SearchEngine;
+use deepseek_app::{generator, indexer};
+
+const ARRAY_EXPORT: &str = "tests/fixtures/array_export.json";
+const VERSIONED_EXPORT: &str = "tests/fixtures/versioned_export.json";
+const WRAPPED_EXPORT: &str = "tests/fixtures/wrapped_export.json";
+const BOM_EXPORT: &str = "tests/fixtures/bom_export.json";
+
+#[test]
+fn test_detects_export_format() -> Result<()> {
+    let (format, conversations): (_, Vec<serde_json::Value>) =
+        parse_conversations(&fs::read_to_string(ARRAY_EXPORT)?)?;
+    assert_eq!(format, ExportFormat::Array);
+    assert_eq!(conversations.len(), 1);
+
+    let (format, conversations): (_, Vec<serde_json::Value>) =
+        parse_conversations(&fs::read_to_string(VERSIONED_EXPORT)?)?;
+    assert_eq!(format, ExportFormat::Versioned("2.0".to_string()));
+    assert_eq!(conversations.len(), 1);
+
+    let (format, conversations): (_, Vec<serde_json::Value>) =
+        parse_conversations(&fs::read_to_string(WRAPPED_EXPORT)?)?;
+    assert_eq!(format, ExportFormat::Wrapped);
+    assert_eq!(conversations.len(), 1);
+
+    Ok(())
+}
+
+#[test]
+fn test_wrapped_export_matches_array() -> Result<()> {
+    let (_, array): (_, Vec<serde_json::Value>) =
+        parse_conversations(&fs::read_to_string(ARRAY_EXPORT)?)?;
+    let (_, wrapped): (_, Vec<serde_json::Value>) =
+        parse_conversations(&fs::read_to_string(WRAPPED_EXPORT)?)?;
+    assert_eq!(array, wrapped);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_bom_prefixed_export() -> Result<()> {
+    let data = read_export(BOM_EXPORT).await?;
+    assert!(!data.starts_with('\u{feff}'), "BOM should be stripped");
+
+    let (_, conversations): (_, Vec<serde_json::Value>) = parse_conversations(&data)?;
+    assert_eq!(conversations.len(), 1);
+
+    Ok(())
+}
+
+#[test]
+fn test_invalid_utf8_is_replaced() {
+    let mut bytes = br#"[{"title": "caf"#.to_vec();
+    bytes.push(0xE9); // Latin-1 "é"
+    bytes.extend_from_slice(br#""}]"#);
+
+    let data = decode_export(bytes);
+    assert_eq!(data, "[{\"title\": \"caf\u{fffd}\"}]");
+}
+
+#[tokio::test]
+async fn test_index_and_site_for_all_shapes() -> Result<()> {
+    for fixture in [ARRAY_EXPORT, VERSIONED_EXPORT, WRAPPED_EXPORT, BOM_EXPORT] {
+        let temp_dir = TempDir::new()?;
+        let index_path = temp_dir.path().join("test_index");
+        let output_dir = temp_dir.path().join("dist");
+
+        indexer::build_index(fixture, index_path.to_str().unwrap()).await?;
+        let search = SearchEngine::new(index_path.to_str().unwrap())?;
+        let results = search.search("грав", 10)?;
+        assert_eq!(results.len(), 1, "{} should be indexed", fixture);
+        assert_eq!(results[0].conversation_id, "conv-1");
+
+        generator::generate_site(fixture, output_dir.to_str().unwrap(), &AppConfig::default()).await?;
+        assert!(
+            output_dir.join("conversations/conv-1/index.html").exists(),
+            "{} should produce a conversation page",
+            fixture
+        );
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_validate_export_file() -> Result<()> {
+    let temp_dir = TempDir::new()?;
+
+    // Shape and conversation count, without parsing the conversations
+    let summary = validate_export_file(std::path::Path::new(ARRAY_EXPORT), 0)?;
+    assert_eq!(summary, ExportSummary { format: ExportFormat::Array, conversations: 1 });
+    let summary = validate_export_file(std::path::Path::new(WRAPPED_EXPORT), 0)?;
+    assert_eq!(summary, ExportSummary { format: ExportFormat::Wrapped, conversations: 1 });
+    let summary = validate_export_file(std::path::Path::new(VERSIONED_EXPORT), 0)?;
+    assert!(matches!(summary.format, ExportFormat::Versioned(_)));
+    assert_eq!(validate_export_file(std::path::Path::new(BOM_EXPORT), 0)?.conversations, 1);
+
+    // Rejected by size before it is read
+    let size = fs::metadata(ARRAY_EXPORT)?.len();
+    let err = validate_export_file(std::path::Path::new(ARRAY_EXPORT), size - 1).expect_err("over the limit");
+    assert!(format!("{:#}", err).contains("max_import_bytes"), "{:#}", err);
+    validate_export_file(std::path::Path::new(ARRAY_EXPORT), size)?;
+
+    // Fails on the first bad token, naming where it is
+    let broken = temp_dir.path().join("broken.json");
+    fs::write(&broken, format!("[{{\"id\": \"1\",, {}]", "\"x\", ".repeat(100_000)))?;
+    let err = validate_export_file(&broken, 0).expect_err("malformed");
+    assert!(format!("{:#}", err).contains("line 1 column 13"), "{:#}", err);
+
+    let truncated = temp_dir.path().join("truncated.json");
+    fs::write(&truncated, r#"[{"id": "1", "mapping": {"#)?;
+    assert!(validate_export_file(&truncated, 0).is_err());
+
+    let trailing = temp_dir.path().join("trailing.json");
+    fs::write(&trailing, "[] []")?;
+    assert!(validate_export_file(&trailing, 0).is_err());
+
+    // Valid JSON, but not an export
+    for (name, json) in [("object.json", r#"{"chats": []}"#), ("numbers.json", "[1, 2]"), ("string.json", r#""x""#)] {
+        let path = temp_dir.path().join(name);
+        fs::write(&path, json)?;
+        assert!(validate_export_file(&path, 0).is_err(), "{} should be rejected", json);
+    }
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_load_export_matches_parse() -> Result<()> {
+    for fixture in [ARRAY_EXPORT, VERSIONED_EXPORT, WRAPPED_EXPORT, BOM_EXPORT] {
+        let streamed: (_, Vec<serde_json::Value>) = load_export(fixture).await?;
+        let parsed: (_, Vec<serde_json::Value>) = parse_conversations(&read_export(fixture).await?)?;
+        assert_eq!(streamed, parsed, "{}", fixture);
+    }
+
+    // Invalid UTF-8 still loads, via the lossy text
+    let temp_dir = TempDir::new()?;
+    let path = temp_dir.path().join("latin1.json");
+    let mut bytes = br#"[{"id": "1", "title": "caf"#.to_vec();
+    bytes.push(0xE9);
+    bytes.extend_from_slice(br#""}]"#);
+    fs::write(&path, bytes)?;
+    let (_, conversations): (_, Vec<serde_json::Value>) = load_export(path.to_str().unwrap()).await?;
+    assert_eq!(conversations[0]["title"], "caf\u{fffd}");
+
+    Ok(())
+}
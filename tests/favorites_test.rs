@@ -0,0 +1,63 @@
+use anyhow::Result;
+use serde_json::json;
+use std::fs;
+use tempfile::TempDir;
+
+use deepseek_app::favorites::{self, FavoritesFormat};
+use deepseek_app::json_export::ExportDocument;
+
+mod common;
+use common::conversation;
+
+/// Three conversations, two of them (and a deleted one) favorited
+fn setup(temp_dir: &TempDir) -> Result<(String, Vec<String>)> {
+    let conversations_path = temp_dir.path().join("conversations.json");
+    fs::write(&conversations_path, json!([
+        conversation("1", "Гравитация", "Вопрос про гравитацию"),
+        conversation("2", "Рецепты", "Вопрос про рецепты"),
+        conversation("3", "Кванты", "Вопрос про кванты"),
+    ]).to_string())?;
+
+    let favorites = vec!["3".to_string(), "1".to_string(), "deleted".to_string()];
+    Ok((conversations_path.to_string_lossy().into_owned(), favorites))
+}
+
+#[tokio::test]
+async fn test_export_favorites_json() -> Result<()> {
+    let temp_dir = TempDir::new()?;
+    let (conversations_path, favorites) = setup(&temp_dir)?;
+    let output_path = temp_dir.path().join("favorites.json");
+
+    let exported = favorites::export_favorites(
+        &conversations_path,
+        output_path.to_str().unwrap(),
+        &favorites,
+        FavoritesFormat::Json,
+        false,
+    ).await?;
+    assert_eq!(exported, 2);
+
+    let document: ExportDocument = serde_json::from_str(&fs::read_to_string(&output_path)?)?;
+    let ids: Vec<&str> = document.conversations.iter().map(|c| c.id.as_str()).collect();
+    assert_eq!(ids, vec!["1", "3"], "only favorites, in export order");
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_export_favorites_markdown() -> Result<()> {
+    let temp_dir = TempDir::new()?;
+    let (conversations_path, favorites) = setup(&temp_dir)?;
+    let output_path = temp_dir.path().join("favorites.md");
+    let output_path = output_path.to_str().unwrap();
+
+    let format = FavoritesFormat::from_path(output_path);
+    assert_eq!(format, FavoritesFormat::Markdown);
+    favorites::export_favorites(&conversations_path, output_path, &favorites, format, false).await?;
+
+    let markdown = fs::read_to_string(output_path)?;
+    assert!(markdown.contains("# Гравитация") && markdown.contains("# Кванты"));
+    assert!(!markdown.contains("Рецепты"), "not a favorite");
+
+    Ok(())
+}
@@ -0,0 +1,1027 @@
+use anyhow::Result;
+use std::fs;
+use tempfile::TempDir;
+
+use deepseek_app::config::{AppConfig, Language, MathRender};
+use deepseek_app::generator;
+use deepseek_app::indexer;
+use deepseek_app::search::SearchEngine;
+use deepseek_app::templates::AssetVersions;
+
+const ARRAY_EXPORT: &str = "tests/fixtures/array_export.json";
+const ATTACHMENT_EXPORT: &str = "tests/fixtures/attachment_export.json";
+const CODE_EXPORT: &str = "tests/fixtures/code_export.json";
+const SPLIT_FENCE_MESSAGES: &str = "tests/fixtures/split_fence_messages.json";
+const SPLIT_FENCE_FRAGMENTS: &str = "tests/fixtures/split_fence_fragments.json";
+const SPLIT_MIDLINE_FRAGMENTS: &str = "tests/fixtures/split_midline_fragments.json";
+const MATH_EXPORT: &str = "tests/fixtures/math_export.json";
+const FOOTNOTE_EXPORT: &str = "tests/fixtures/footnote_export.json";
+const TYPOGRAPHY_EXPORT: &str = "tests/fixtures/typography_export.json";
+const IMAGE_EXPORT: &str = "tests/fixtures/image_export.json";
+const DEEP_NESTING_EXPORT: &str = "tests/fixtures/deep_nesting_export.json";
+const META_EXPORT: &str = "tests/fixtures/meta_export.json";
+const TOOL_EXPORT: &str = "tests/fixtures/tool_export.json";
+
+#[tokio::test]
+async fn test_unwritable_output_dir_error_names_the_path() -> Result<()> {
+    let temp_dir = TempDir::new()?;
+
+    // A file where the output directory should be
+    let output_dir = temp_dir.path().join("dist");
+    fs::write(&output_dir, "not a directory")?;
+
+    let err = generator::generate_site(ARRAY_EXPORT, output_dir.to_str().unwrap(), &AppConfig::default())
+        .await
+        .expect_err("generation should fail");
+
+    let message = format!("{:#}", err);
+    assert!(
+        message.contains(&output_dir.join("conversations").display().to_string()),
+        "error doesn't name the path: {}",
+        message
+    );
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_merge_consecutive_messages_joins_split_code_fence() -> Result<()> {
+    let temp_dir = TempDir::new()?;
+    let output_dir = temp_dir.path().join("dist");
+    let page_path = output_dir.join("conversations/split-fence/index.html");
+
+    let config = AppConfig { merge_consecutive_messages: true, ..AppConfig::default() };
+    generator::generate_site(SPLIT_FENCE_MESSAGES, output_dir.to_str().unwrap(), &config).await?;
+    let page = fs::read_to_string(&page_path)?;
+
+    assert_eq!(page.matches(r#"class="message message-response""#).count(), 1, "responses should be merged");
+    assert_eq!(page.matches(r#"class="code-block-wrapper""#).count(), 1);
+    assert!(page.contains("data-code=\"fn main() {\n    println!(&quot;hello&quot;);\n}\n\""), "the code block should hold both halves");
+    assert!(page.contains("<p>Готово.</p>"), "text after the fence is markdown again");
+
+    // Off by default
+    generator::generate_site(SPLIT_FENCE_MESSAGES, output_dir.to_str().unwrap(), &AppConfig::default()).await?;
+    let page = fs::read_to_string(&page_path)?;
+    assert_eq!(page.matches(r#"class="message message-response""#).count(), 2);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_fragments_of_one_message_render_together() -> Result<()> {
+    let temp_dir = TempDir::new()?;
+    let output_dir = temp_dir.path().join("dist");
+
+    generator::generate_site(SPLIT_FENCE_FRAGMENTS, output_dir.to_str().unwrap(), &AppConfig::default()).await?;
+    let page = fs::read_to_string(output_dir.join("conversations/split-fragments/index.html"))?;
+
+    assert_eq!(page.matches(r#"class="code-block-wrapper""#).count(), 1, "the fence should render as one block");
+    assert!(page.contains("data-code=\"def add(a, b):\n    return a + b\n\""));
+    assert!(!page.contains("```"), "no stray fences");
+
+    // The table's last row came in a fragment of its own
+    assert_eq!(page.matches("<table>").count(), 1);
+    let table = &page[page.find("<table>").unwrap()..page.find("</table>").unwrap()];
+    assert!(table.contains(">1<") && table.contains(">2<"), "the last row belongs to the table");
+
+    // Thinking stays a message of its own
+    assert_eq!(page.matches(r#"class="message message-think""#).count(), 1);
+    assert_eq!(page.matches(r#"class="message message-response""#).count(), 1);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_fragments_split_mid_line_join_as_they_are() -> Result<()> {
+    let temp_dir = TempDir::new()?;
+    let output_dir = temp_dir.path().join("dist");
+
+    generator::generate_site(SPLIT_MIDLINE_FRAGMENTS, output_dir.to_str().unwrap(), &AppConfig::default()).await?;
+    let page = fs::read_to_string(output_dir.join("conversations/split-midline/index.html"))?;
+
+    // Split mid-word
+    assert!(page.contains("<p>Привет, вот таблица:</p>"), "the word should be whole");
+
+    // Split mid-row: both cells are in one row
+    let table = &page[page.find("<table>").unwrap()..page.find("</table>").unwrap()];
+    assert_eq!(table.matches("<tr>").count(), 2, "{}", table);
+    assert!(table.contains("<td>1</td><td>2</td>"), "{}", table);
+
+    // Split inside the fence's info string
+    assert!(page.contains(r#"data-lang="python""#), "the language should be whole");
+    assert!(page.contains("data-code=\"print(1)\n\""));
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_footnotes_link_within_their_message() -> Result<()> {
+    let temp_dir = TempDir::new()?;
+    let output_dir = temp_dir.path().join("dist");
+
+    generator::generate_site(FOOTNOTE_EXPORT, output_dir.to_str().unwrap(), &AppConfig::default()).await?;
+    let page = fs::read_to_string(output_dir.join("conversations/footnotes/index.html"))?;
+
+    // Both responses use the same label, each links to its own note
+    for prefix in ["m1", "m2"] {
+        assert!(page.contains(&format!(r##"<a href="#fn-{prefix}-src">1</a>"##)));
+        assert!(page.contains(&format!(r#"<div class="footnote-definition" id="fn-{prefix}-src">"#)));
+        assert!(page.contains(&format!(r##"<a href="#fnref-{prefix}-src">1</a>"##)));
+    }
+    // Only the first reference is the target of the back link
+    assert_eq!(page.matches(r#"id="fnref-m1-src""#).count(), 1);
+    assert_eq!(page.matches(r##"href="#fn-m1-src""##).count(), 2);
+    assert!(page.contains("Перепись 2021 года."));
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_archive_footnotes_and_messages_link_within_their_conversation() -> Result<()> {
+    let temp_dir = TempDir::new()?;
+    let output_dir = temp_dir.path().join("dist");
+
+    // Two conversations with the same footnotes and messages on one archive page
+    let export_path = export_with_copy(FOOTNOTE_EXPORT, &temp_dir)?;
+    generator::generate_site(export_path.to_str().unwrap(), output_dir.to_str().unwrap(), &AppConfig::default()).await?;
+    let archive = fs::read_to_string(output_dir.join("all/index.html"))?;
+
+    for prefix in ["c-footnotes-", "c-footnotes-copy-"] {
+        assert_eq!(archive.matches(&format!(r#"id="{prefix}fn-m1-src""#)).count(), 1);
+        assert_eq!(archive.matches(&format!(r#"id="{prefix}fnref-m1-src""#)).count(), 1);
+        assert_eq!(archive.matches(&format!(r##"href="#{prefix}fn-m1-src""##)).count(), 2);
+    }
+    assert!(!archive.contains(r#"id="fn-m1-src""#));
+
+    let anchors: Vec<&str> = archive
+        .match_indices(r#"class="message message-"#)
+        .map(|(idx, _)| {
+            let rest = &archive[idx..];
+            let rest = &rest[rest.find(r#"id=""#).unwrap() + 4..];
+            &rest[..rest.find('"').unwrap()]
+        })
+        .collect();
+    assert_eq!(anchors.len(), 6);
+    let unique: std::collections::HashSet<&str> = anchors.iter().copied().collect();
+    assert_eq!(unique.len(), anchors.len(), "message anchors should be unique: {:?}", anchors);
+    assert!(anchors.iter().all(|anchor| anchor.starts_with("c-footnotes-")));
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_smart_punctuation_and_heading_attributes_are_opt_in() -> Result<()> {
+    let temp_dir = TempDir::new()?;
+    let output_dir = temp_dir.path().join("dist");
+    let page_path = output_dir.join("conversations/typography/index.html");
+
+    generator::generate_site(TYPOGRAPHY_EXPORT, output_dir.to_str().unwrap(), &AppConfig::default()).await?;
+    let page = fs::read_to_string(&page_path)?;
+    assert!(page.contains("<h2>Установка {#install}</h2>"));
+    assert!(page.contains(r#"It's "quoted" -- wait..."#));
+
+    let config = AppConfig { smart_punctuation: true, heading_attributes: true, ..AppConfig::default() };
+    generator::generate_site(TYPOGRAPHY_EXPORT, output_dir.to_str().unwrap(), &config).await?;
+    let page = fs::read_to_string(&page_path)?;
+    assert!(page.contains(r#"<h2 id="install">Установка</h2>"#));
+    assert!(page.contains("It’s “quoted” – wait…"));
+    // Requests are shown as typed
+    assert!(page.contains(r#"Покажи "кавычки" -- и тире"#));
+
+    Ok(())
+}
+
+/// Generate the math fixture with `math_render` and return the conversation page
+async fn math_page(temp_dir: &TempDir, math_render: MathRender) -> Result<String> {
+    let output_dir = temp_dir.path().join("dist");
+    let config = AppConfig { math_render, ..AppConfig::default() };
+    generator::generate_site(MATH_EXPORT, output_dir.to_str().unwrap(), &config).await?;
+    Ok(fs::read_to_string(output_dir.join("conversations/math/index.html"))?)
+}
+
+#[tokio::test]
+async fn test_client_math_loads_katex() -> Result<()> {
+    let temp_dir = TempDir::new()?;
+
+    let page = math_page(&temp_dir, MathRender::Client).await?;
+    assert!(page.contains("katex.min.js"));
+    assert!(page.contains("$E=mc^2$") && page.contains("$$E = mc^2$$"), "formulas are left to KaTeX");
+    assert!(!page.contains("<math"));
+
+    let page = math_page(&temp_dir, MathRender::None).await?;
+    assert!(!page.contains("katex.min.js") && !page.contains("renderMathInElement"), "no KaTeX when math is off");
+    assert!(page.contains("$E=mc^2$"), "formulas are shown as written");
+
+    Ok(())
+}
+
+#[cfg(any(unix, all(windows, target_env = "gnu")))]
+#[tokio::test]
+async fn test_server_math_renders_inline_and_block_formulas() -> Result<()> {
+    let temp_dir = TempDir::new()?;
+    let page = math_page(&temp_dir, MathRender::Server).await?;
+
+    assert!(!page.contains("katex.min.js") && !page.contains("renderMathInElement"), "no KaTeX needed");
+    assert!(!page.contains('$'), "every formula should be rendered");
+    // Inline formulas in the request and the response, two display formulas
+    assert_eq!(page.matches("<math").count(), 4);
+    assert_eq!(page.matches(r#"display="block""#).count(), 2);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_site_is_installable() -> Result<()> {
+    let temp_dir = TempDir::new()?;
+    let output_dir = temp_dir.path().join("dist");
+
+    generator::generate_site(ARRAY_EXPORT, output_dir.to_str().unwrap(), &AppConfig::default()).await?;
+
+    let manifest: serde_json::Value = serde_json::from_str(&fs::read_to_string(output_dir.join("manifest.webmanifest"))?)?;
+    assert_eq!(manifest["start_url"], "/");
+    assert_eq!(manifest["icons"][0]["src"], "/favicon.svg");
+    assert!(output_dir.join("favicon.svg").exists());
+    assert!(output_dir.join("sw.js").exists(), "the service worker must be at the root to control every page");
+
+    let page = fs::read_to_string(output_dir.join("conversations/conv-1/index.html"))?;
+    assert!(page.contains(r#"<link rel="manifest" href="/manifest.webmanifest">"#));
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_offline_pages_service_worker() -> Result<()> {
+    let temp_dir = TempDir::new()?;
+    let output_dir = temp_dir.path().join("dist");
+    let page_path = output_dir.join("conversations/conv-1/index.html");
+
+    generator::generate_site(ARRAY_EXPORT, output_dir.to_str().unwrap(), &AppConfig::default()).await?;
+    let page = fs::read_to_string(&page_path)?;
+    assert!(!page.contains("serviceWorker.register"), "offline pages are opt-in");
+    assert!(page.contains("unregister()"), "a worker from an earlier run is removed");
+
+    let config = AppConfig { offline_pages: true, ..AppConfig::default() };
+    generator::generate_site(ARRAY_EXPORT, output_dir.to_str().unwrap(), &config).await?;
+    let page = fs::read_to_string(&page_path)?;
+    assert!(page.contains("serviceWorker.register('/sw.js')"));
+
+    let worker = fs::read_to_string(output_dir.join("sw.js"))?;
+    assert!(!worker.contains("__CACHE_VERSION__"), "the cache version is stamped in");
+    assert!(worker.contains("'/api/'"), "API calls bypass the cache");
+
+    // Regenerating changes the cache version
+    std::thread::sleep(std::time::Duration::from_millis(5));
+    generator::generate_site(ARRAY_EXPORT, output_dir.to_str().unwrap(), &config).await?;
+    assert_ne!(fs::read_to_string(output_dir.join("sw.js"))?, worker);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_conversation_page_has_copy_id_and_source_buttons() -> Result<()> {
+    let temp_dir = TempDir::new()?;
+    let output_dir = temp_dir.path().join("dist");
+
+    generator::generate_site(ARRAY_EXPORT, output_dir.to_str().unwrap(), &AppConfig::default()).await?;
+    let page = fs::read_to_string(output_dir.join("conversations/conv-1/index.html"))?;
+
+    assert!(page.contains(r#"data-copy-id="conv-1""#));
+    // Only the desktop app can read the export, so the script reveals it there
+    assert!(page.contains(r#"data-source-id="conv-1" title="Показать исходную запись JSON" hidden"#));
+    assert!(page.contains("/assets/js/conversation-actions.js"));
+    assert!(output_dir.join("assets/js/conversation-actions.js").exists());
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_conversation_path_checks_the_page_exists() -> Result<()> {
+    let temp_dir = TempDir::new()?;
+    let output_dir = temp_dir.path().join("dist");
+
+    generator::generate_site(ARRAY_EXPORT, output_dir.to_str().unwrap(), &AppConfig::default()).await?;
+
+    assert_eq!(generator::conversation_path(&output_dir, "conv-1").as_deref(), Some("/conversations/conv-1/"));
+    assert_eq!(generator::conversation_path(&output_dir, "missing"), None);
+    assert_eq!(generator::conversation_path(&output_dir, "../conversations/conv-1"), None);
+    assert_eq!(generator::conversation_path(&output_dir, ""), None);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_archive_inlines_every_conversation() -> Result<()> {
+    let temp_dir = TempDir::new()?;
+    let output_dir = temp_dir.path().join("dist");
+    let archive_dir = output_dir.join("all");
+
+    generator::generate_site(ATTACHMENT_EXPORT, output_dir.to_str().unwrap(), &AppConfig::default()).await?;
+    let page = fs::read_to_string(archive_dir.join("index.html"))?;
+    assert_eq!(page.matches(r#"class="archive-section""#).count(), 2);
+    // Newest first, with the messages themselves
+    let archive = &page[page.find(r#"class="archive""#).unwrap()..];
+    assert!(archive.find("Без вложений").unwrap() < archive.find("Разбор договора").unwrap());
+    assert!(page.contains("Документ выглядит корректно."));
+    assert!(page.contains(r#"href="/conversations/conv-plain/""#));
+    assert!(!archive_dir.join(".sections").exists(), "the scratch sections are removed");
+
+    // One conversation per page
+    let config = AppConfig { archive_page_conversations: 1, ..AppConfig::default() };
+    generator::generate_site(ATTACHMENT_EXPORT, output_dir.to_str().unwrap(), &config).await?;
+    let first = fs::read_to_string(archive_dir.join("index.html"))?;
+    let second = fs::read_to_string(archive_dir.join("page-2.html"))?;
+    assert_eq!(first.matches(r#"class="archive-section""#).count(), 1);
+    assert!(first.contains("Страница 1 из 2") && first.contains(r#"href="page-2.html""#));
+    assert!(second.contains("Документ выглядит корректно."));
+
+    // Back to one page, the second is gone
+    generator::generate_site(ATTACHMENT_EXPORT, output_dir.to_str().unwrap(), &AppConfig::default()).await?;
+    assert!(!archive_dir.join("page-2.html").exists());
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_pages_link_to_assets_by_content_hash() -> Result<()> {
+    let temp_dir = TempDir::new()?;
+    let output_dir = temp_dir.path().join("dist");
+
+    generator::generate_site(ARRAY_EXPORT, output_dir.to_str().unwrap(), &AppConfig::default()).await?;
+    let page = fs::read_to_string(output_dir.join("conversations/conv-1/index.html"))?;
+
+    let mut versions = AssetVersions::default();
+    versions.insert("/assets/css/main.css", &fs::read(output_dir.join("assets/css/main.css"))?);
+    versions.insert("/assets/js/search.js", &fs::read(output_dir.join("assets/js/search.js"))?);
+    for path in ["/assets/css/main.css", "/assets/js/search.js"] {
+        let url = versions.url(path);
+        assert!(url.starts_with(&format!("{}?v=", path)));
+        assert!(page.contains(&format!(r#""{}""#, url)), "{} isn't linked", url);
+    }
+
+    // A different file gets a different version
+    let mut changed = AssetVersions::default();
+    changed.insert("/assets/css/main.css", b"body { color: red; }");
+    assert_ne!(changed.url("/assets/css/main.css"), versions.url("/assets/css/main.css"));
+    assert_eq!(versions.url("/favicon.svg"), "/favicon.svg", "unknown assets are left alone");
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_code_index_links_to_each_code_block() -> Result<()> {
+    let temp_dir = TempDir::new()?;
+    let output_dir = temp_dir.path().join("dist");
+    let conv_dir = output_dir.join("conversations/code");
+
+    generator::generate_site(CODE_EXPORT, output_dir.to_str().unwrap(), &AppConfig::default()).await?;
+    let page = fs::read_to_string(conv_dir.join("index.html"))?;
+    assert!(page.contains(r#"<div class="code-block-wrapper" id="code-1">"#), "blocks are anchored either way");
+    assert!(!page.contains("conversation-index"), "the index is opt-in");
+
+    // Two messages per page: one code block on each
+    let config = AppConfig { code_index: true, messages_per_page: 2, ..AppConfig::default() };
+    generator::generate_site(CODE_EXPORT, output_dir.to_str().unwrap(), &config).await?;
+    let first = fs::read_to_string(conv_dir.join("index.html"))?;
+    let second = fs::read_to_string(conv_dir.join("page-2.html"))?;
+
+    assert!(first.contains(r##"<a href="#code-1">rust</a> <span class="code-index-lines">3 стр.</span>"##));
+    assert!(first.contains(r##"<a href="page-2.html#code-2">код</a>"##), "a block without a language");
+    assert!(second.contains(r##"<a href="./#code-1">rust</a>"##));
+    assert!(second.contains(r##"<a href="#code-2">код</a>"##));
+    assert!(second.contains(r#"<div class="code-block-wrapper" id="code-2">"#));
+    assert!(first.contains("<li>build.log</li>"), "attachments are listed too");
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_code_blocks_have_unique_linkable_ids() -> Result<()> {
+    let temp_dir = TempDir::new()?;
+    let output_dir = temp_dir.path().join("dist");
+
+    generator::generate_site(CODE_EXPORT, output_dir.to_str().unwrap(), &AppConfig::default()).await?;
+    let page = fs::read_to_string(output_dir.join("conversations/code/index.html"))?;
+
+    let ids: Vec<&str> = page
+        .match_indices(r#"<div class="code-block-wrapper" id=""#)
+        .map(|(idx, prefix)| {
+            let rest = &page[idx + prefix.len()..];
+            &rest[..rest.find('"').unwrap()]
+        })
+        .collect();
+    assert_eq!(ids, vec!["code-1", "code-2"], "one id per block, numbered across messages");
+    for id in &ids {
+        assert_eq!(page.matches(&format!(r#"id="{}""#, id)).count(), 1, "{} should be unique", id);
+        assert!(page.contains(&format!(r##"class="code-btn code-link" href="#{}""##, id)));
+    }
+
+    // Regenerating gives the same ids
+    generator::generate_site(CODE_EXPORT, output_dir.to_str().unwrap(), &AppConfig::default()).await?;
+    assert_eq!(fs::read_to_string(output_dir.join("conversations/code/index.html"))?, page);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_archive_code_block_ids_are_unique_across_conversations() -> Result<()> {
+    let temp_dir = TempDir::new()?;
+    let output_dir = temp_dir.path().join("dist");
+
+    // Two conversations with the same code blocks on one archive page
+    let export_path = export_with_copy(CODE_EXPORT, &temp_dir)?;
+    let config = AppConfig { code_index: true, ..AppConfig::default() };
+    generator::generate_site(export_path.to_str().unwrap(), output_dir.to_str().unwrap(), &config).await?;
+    let archive = fs::read_to_string(output_dir.join("all/index.html"))?;
+
+    for id in ["c-code-code-1", "c-code-code-2", "c-code-copy-code-1", "c-code-copy-code-2"] {
+        assert_eq!(archive.matches(&format!(r#"id="{}""#, id)).count(), 1, "{} should be unique", id);
+        assert!(archive.contains(&format!(r##"class="code-btn code-link" href="#{}""##, id)));
+        assert!(archive.contains(&format!(r##"<a href="#{}">"##, id)), "the code index links to {}", id);
+    }
+    assert!(!archive.contains(r#"id="code-1""#));
+    // The conversation's own page keeps its ids
+    let page = fs::read_to_string(output_dir.join("conversations/code/index.html"))?;
+    assert!(page.contains(r#"id="code-1""#));
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_archive_prefixes_only_ids_in_tags() -> Result<()> {
+    let temp_dir = TempDir::new()?;
+    let export_path = temp_dir.path().join("conversations.json");
+    let output_dir = temp_dir.path().join("dist");
+
+    let text = r##"<a id="top" href="#top">"##;
+    let node = |content: &str, kind: &str, children: &[&str]| serde_json::json!({
+        "message": { "fragments": [{"type": kind, "content": content}] },
+        "children": children,
+    });
+    fs::write(&export_path, serde_json::json!([{
+        "id": "ids",
+        "title": "Ids",
+        "mapping": {
+            "root": { "children": ["q"] },
+            "q": node(text, "REQUEST", &["a"]),
+            "a": node(&format!("`{}`", text), "RESPONSE", &[]),
+        },
+    }]).to_string())?;
+    generator::generate_site(export_path.to_str().unwrap(), output_dir.to_str().unwrap(), &AppConfig::default()).await?;
+
+    let page = fs::read_to_string(output_dir.join("conversations/ids/index.html"))?;
+    let archive = fs::read_to_string(output_dir.join("all/index.html"))?;
+    assert!(page.contains("&lt;a id=\"top\" href=\"#top\"&gt;"));
+    assert!(archive.contains("&lt;a id=\"top\" href=\"#top\"&gt;"), "text isn't taken for a tag");
+    assert!(archive.contains("<code>&lt;a id=\"top\" href=\"#top\"&gt;</code>"));
+    assert!(!archive.contains("c-ids-top"));
+    assert!(archive.contains(r#"class="message message-request" id="c-ids-m-"#));
+
+    Ok(())
+}
+
+/// `path` with a copy of its first conversation added, with `-copy` after its id
+fn export_with_copy(path: &str, temp_dir: &TempDir) -> Result<std::path::PathBuf> {
+    let mut conversations: Vec<serde_json::Value> = serde_json::from_str(&fs::read_to_string(path)?)?;
+    let mut copy = conversations[0].clone();
+    copy["id"] = format!("{}-copy", copy["id"].as_str().unwrap()).into();
+    conversations.push(copy);
+    let export_path = temp_dir.path().join("conversations.json");
+    fs::write(&export_path, serde_json::to_string(&conversations)?)?;
+    Ok(export_path)
+}
+
+#[tokio::test]
+async fn test_messages_show_author_label_and_avatar() -> Result<()> {
+    let temp_dir = TempDir::new()?;
+    let output_dir = temp_dir.path().join("dist");
+    let page_path = output_dir.join("conversations/code/index.html");
+
+    generator::generate_site(CODE_EXPORT, output_dir.to_str().unwrap(), &AppConfig::default()).await?;
+    let page = fs::read_to_string(&page_path)?;
+    assert!(page.contains(r#"<span class="message-author">Вы</span>"#));
+    assert!(page.contains(r#"<span class="message-author">DeepSeek</span>"#));
+    assert!(page.contains("message-avatar-assistant"));
+    assert!(!page.contains("<img src=\"\""), "no avatar image by default");
+
+    let config = AppConfig {
+        user_label: Some("Иван Петров".to_string()),
+        assistant_avatar: Some("/assets/bot.png".to_string()),
+        ..AppConfig::default()
+    };
+    generator::generate_site(CODE_EXPORT, output_dir.to_str().unwrap(), &config).await?;
+    let page = fs::read_to_string(&page_path)?;
+    assert!(page.contains(r#"<span class="message-author">Иван Петров</span>"#));
+    assert!(page.contains("ИП"), "initials stand in for a missing avatar");
+    assert!(page.contains(r#"<img src="/assets/bot.png" alt="">"#));
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_regenerate_one_leaves_other_pages_alone() -> Result<()> {
+    let temp_dir = TempDir::new()?;
+    let output_dir = temp_dir.path().join("dist");
+    let output = output_dir.to_str().unwrap();
+
+    generator::generate_site(ATTACHMENT_EXPORT, output, &AppConfig::default()).await?;
+    let target = output_dir.join("conversations/conv-attachment/index.html");
+    let other = output_dir.join("conversations/conv-plain/index.html");
+    let other_before = fs::read_to_string(&other)?;
+    let other_modified = fs::metadata(&other)?.modified()?;
+    let index_modified = fs::metadata(output_dir.join("index.html"))?.modified()?;
+
+    let config = AppConfig { user_label: Some("Тестер".to_string()), ..AppConfig::default() };
+    assert!(generator::regenerate_one(ATTACHMENT_EXPORT, output, "conv-attachment", &config).await?);
+
+    let page = fs::read_to_string(&target)?;
+    assert!(page.contains(r#"<span class="message-author">Тестер</span>"#), "re-rendered with the new config");
+    assert!(page.contains("/assets/css/main.css?v="), "still links to the site's assets");
+    // Its section in the archive too, and only that one
+    let archive = fs::read_to_string(output_dir.join("all/index.html"))?;
+    assert_eq!(archive.matches(r#"<span class="message-author">Тестер</span>"#).count(), 1);
+    assert!(archive.contains(r#"<span class="message-author">Вы</span>"#));
+    assert_eq!(archive.matches(r#"class="archive-section""#).count(), 2);
+    assert!(archive.contains(r#"id="c-conv-attachment-m-"#));
+    assert_eq!(fs::read_to_string(&other)?, other_before);
+    assert_eq!(fs::metadata(&other)?.modified()?, other_modified, "other pages aren't rewritten");
+    assert_eq!(fs::metadata(output_dir.join("index.html"))?.modified()?, index_modified);
+
+    assert!(!generator::regenerate_one(ATTACHMENT_EXPORT, output, "missing", &config).await?);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_minify_preserves_code_block_whitespace() -> Result<()> {
+    let temp_dir = TempDir::new()?;
+    let output_dir = temp_dir.path().join("dist");
+    let page_path = output_dir.join("conversations/code/index.html");
+
+    generator::generate_site(CODE_EXPORT, output_dir.to_str().unwrap(), &AppConfig::default()).await?;
+    let plain = fs::read_to_string(&page_path)?;
+
+    let config = AppConfig { minify: true, ..AppConfig::default() };
+    generator::generate_site(CODE_EXPORT, output_dir.to_str().unwrap(), &config).await?;
+    let minified = fs::read_to_string(&page_path)?;
+
+    assert!(minified.len() < plain.len());
+
+    // Every highlighted block and its copy/download source survive unchanged
+    let blocks = |page: &str| -> Vec<String> {
+        page.match_indices(r#"<div class="highlight""#)
+            .map(|(idx, _)| {
+                let rest = &page[idx..];
+                rest[..rest.find("</pre>").unwrap()].to_string()
+            })
+            .collect()
+    };
+    let plain_blocks = blocks(&plain);
+    assert_eq!(plain_blocks.len(), 2);
+    assert!(plain_blocks.iter().any(|block| block.contains("\n    ")), "the fixture has indented code");
+    assert_eq!(blocks(&minified), plain_blocks);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_code_is_highlighted_with_the_configured_theme() -> Result<()> {
+    let temp_dir = TempDir::new()?;
+    let output_dir = temp_dir.path().join("dist");
+    let page_path = output_dir.join("conversations/code/index.html");
+    let css_path = output_dir.join("assets/css/syntax.css");
+
+    generator::generate_site(CODE_EXPORT, output_dir.to_str().unwrap(), &AppConfig::default()).await?;
+    let default_page = fs::read_to_string(&page_path)?;
+    let default_css = fs::read_to_string(&css_path)?;
+
+    let config = AppConfig { syntax_theme: Some("Solarized (dark)".to_string()), ..AppConfig::default() };
+    generator::generate_site(CODE_EXPORT, output_dir.to_str().unwrap(), &config).await?;
+    assert_ne!(fs::read_to_string(&page_path)?, default_page);
+    assert_ne!(fs::read_to_string(&css_path)?, default_css);
+
+    // An unknown theme falls back to the default one
+    let config = AppConfig { syntax_theme: Some("no-such-theme".to_string()), ..AppConfig::default() };
+    generator::generate_site(CODE_EXPORT, output_dir.to_str().unwrap(), &config).await?;
+    assert_eq!(fs::read_to_string(&page_path)?, default_page);
+    assert_eq!(fs::read_to_string(&css_path)?, default_css);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_precompress_writes_brotli_copies() -> Result<()> {
+    let temp_dir = TempDir::new()?;
+    let output_dir = temp_dir.path().join("dist");
+    let output = output_dir.to_str().unwrap();
+    let decompress = |path: &std::path::Path| -> Result<Vec<u8>> {
+        let mut contents = Vec::new();
+        std::io::Read::read_to_end(&mut brotli::Decompressor::new(fs::File::open(path)?, 4096), &mut contents)?;
+        Ok(contents)
+    };
+
+    let config = AppConfig { precompress: true, ..AppConfig::default() };
+    generator::generate_site(ARRAY_EXPORT, output, &config).await?;
+
+    for file in ["index.html", "conversations/conv-1/index.html", "assets/css/main.css", "assets/js/search.js"] {
+        let path = output_dir.join(file);
+        assert_eq!(decompress(&output_dir.join(format!("{}.br", file)))?, fs::read(&path)?, "{}", file);
+    }
+    assert!(fs::metadata(output_dir.join("favicon.svg"))?.len() < 1024);
+    assert!(!output_dir.join("favicon.svg.br").exists(), "small files aren't compressed");
+
+    // Nothing changed, so nothing needs compressing again
+    assert_eq!(generator::precompress(&output_dir, true)?, 0);
+
+    // A page changed after its copy was written, even if the copy looks newer
+    let page = output_dir.join("index.html");
+    let page_br = output_dir.join("index.html.br");
+    let old_copy = fs::read(&page_br)?;
+    fs::write(&page, format!("{}<!-- changed -->", fs::read_to_string(&page)?))?;
+    fs::write(&page_br, old_copy)?;
+    assert_eq!(generator::precompress(&output_dir, true)?, 1);
+    assert_eq!(decompress(&page_br)?, fs::read(&page)?);
+
+    // Without the option, copies that would be stale are removed
+    generator::generate_site(ARRAY_EXPORT, output, &AppConfig::default()).await?;
+    assert!(!output_dir.join("index.html.br").exists());
+    assert!(!output_dir.join("assets/css/main.css.br").exists());
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_message_anchors_survive_earlier_messages() -> Result<()> {
+    let temp_dir = TempDir::new()?;
+    let export_path = temp_dir.path().join("conversations.json");
+    let output_dir = temp_dir.path().join("dist");
+    let page_path = output_dir.join("conversations/anchors/index.html");
+
+    let node = |content: &str, kind: &str, children: &[&str]| serde_json::json!({
+        "message": { "fragments": [{"type": kind, "content": content}] },
+        "children": children,
+    });
+    let export = |mapping: serde_json::Value| serde_json::json!([{
+        "id": "anchors",
+        "title": "Якоря",
+        "inserted_at": "2024-03-06T12:00:00Z",
+        "mapping": mapping,
+    }]).to_string();
+    // The anchor of the message with `text`, read from its link
+    let anchor_of = |page: &str, text: &str| -> String {
+        let before = &page[..page.find(text).unwrap()];
+        let prefix = r##"class="message-link" href="#"##;
+        let rest = &before[before.rfind(prefix).unwrap() + prefix.len()..];
+        rest[..rest.find('"').unwrap()].to_string()
+    };
+
+    fs::write(&export_path, export(serde_json::json!({
+        "root": { "children": ["q"] },
+        "q": node("Как дела?", "REQUEST", &["a"]),
+        "a": node("Отлично", "RESPONSE", &["again"]),
+        "again": node("Отлично", "RESPONSE", &[]),
+    })))?;
+    generator::generate_site(export_path.to_str().unwrap(), output_dir.to_str().unwrap(), &AppConfig::default()).await?;
+    let page = fs::read_to_string(&page_path)?;
+    let question = anchor_of(&page, "Как дела?");
+    assert!(question.starts_with("m-"));
+    assert!(page.contains(&format!(r#"id="{}""#, question)));
+    let first = anchor_of(&page, "Отлично");
+    assert!(page.contains(&format!(r##"href="#{}-2""##, first)), "a repeated message gets a suffix");
+
+    // A new exchange at the start doesn't move the old ones
+    fs::write(&export_path, export(serde_json::json!({
+        "root": { "children": ["hello"] },
+        "hello": node("Привет", "REQUEST", &["hi"]),
+        "hi": node("Здравствуйте", "RESPONSE", &["q"]),
+        "q": node("Как дела?", "REQUEST", &["a"]),
+        "a": node("Отлично", "RESPONSE", &["again"]),
+        "again": node("Отлично", "RESPONSE", &[]),
+    })))?;
+    generator::generate_site(export_path.to_str().unwrap(), output_dir.to_str().unwrap(), &AppConfig::default()).await?;
+    let page = fs::read_to_string(&page_path)?;
+    assert_eq!(anchor_of(&page, "Как дела?"), question);
+    assert_eq!(anchor_of(&page, "Отлично"), first);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_newest_first_reverses_messages() -> Result<()> {
+    let temp_dir = TempDir::new()?;
+    let output_dir = temp_dir.path().join("dist");
+    let conv_dir = output_dir.join("conversations/code");
+
+    let config = AppConfig { newest_first: true, code_index: true, messages_per_page: 2, ..AppConfig::default() };
+    generator::generate_site(CODE_EXPORT, output_dir.to_str().unwrap(), &config).await?;
+    let first = fs::read_to_string(conv_dir.join("index.html"))?;
+    let second = fs::read_to_string(conv_dir.join("page-2.html"))?;
+
+    // The latest exchange is on the first page, its answer above its question
+    let answer = first.find("cargo clean").unwrap();
+    let question = first.find("Почему не собирается?").unwrap();
+    assert!(answer < question);
+    assert!(second.contains("hello world"));
+
+    // Code blocks keep their anchors and the index still points at them
+    assert!(first.contains(r#"<div class="code-block-wrapper" id="code-2">"#));
+    assert!(first.contains(r##"<a href="#code-2">код</a>"##));
+    assert!(first.contains(r##"<a href="page-2.html#code-1">rust</a>"##));
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_embedded_images_are_inlined_or_written_out() -> Result<()> {
+    let temp_dir = TempDir::new()?;
+    let output_dir = temp_dir.path().join("dist");
+    let conv_dir = output_dir.join("conversations/images");
+
+    generator::generate_site(IMAGE_EXPORT, output_dir.to_str().unwrap(), &AppConfig::default()).await?;
+    let page = fs::read_to_string(conv_dir.join("index.html"))?;
+    assert!(page.contains(r#"<img src="data:image/png;base64,iVBORw0KGgo"#));
+    assert!(page.contains(r#"alt="Скриншот ошибки" loading="lazy""#));
+    assert!(page.contains(r#"<img src="data:image/gif;base64,R0lGODlh"#), "images in `files` too");
+    assert!(page.contains(r#"alt="pixel.gif""#));
+    assert!(!page.contains(">data:image"), "the image isn't shown as text");
+    assert!(!conv_dir.join("assets").exists());
+
+    // Both are over the limit: written next to the page instead
+    let config = AppConfig { inline_image_max_bytes: 50, ..AppConfig::default() };
+    generator::generate_site(IMAGE_EXPORT, output_dir.to_str().unwrap(), &config).await?;
+    let page = fs::read_to_string(conv_dir.join("index.html"))?;
+    assert!(!page.contains("data:image"));
+
+    let files: Vec<String> = fs::read_dir(conv_dir.join("assets"))?
+        .map(|entry| entry.unwrap().file_name().to_string_lossy().into_owned())
+        .collect();
+    assert_eq!(files.len(), 2);
+    for name in &files {
+        assert!(page.contains(&format!(r#"<img src="/conversations/images/assets/{}""#, name)));
+    }
+    let png = files.iter().find(|name| name.ends_with(".png")).unwrap();
+    assert!(fs::read(conv_dir.join("assets").join(png))?.starts_with(b"\x89PNG"));
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_generation_threads_gives_the_same_site() -> Result<()> {
+    let temp_dir = TempDir::new()?;
+    let default_dir = temp_dir.path().join("default");
+    let single_dir = temp_dir.path().join("single");
+
+    generator::generate_site(ATTACHMENT_EXPORT, default_dir.to_str().unwrap(), &AppConfig::default()).await?;
+    let config = AppConfig { generation_threads: 1, ..AppConfig::default() };
+    generator::generate_site(ATTACHMENT_EXPORT, single_dir.to_str().unwrap(), &config).await?;
+
+    for page in ["index.html", "conversations/conv-attachment/index.html", "conversations/conv-plain/index.html"] {
+        assert_eq!(fs::read_to_string(single_dir.join(page))?, fs::read_to_string(default_dir.join(page))?, "{}", page);
+    }
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_conversation_ids_for_read_tracking() -> Result<()> {
+    let temp_dir = TempDir::new()?;
+    let output_dir = temp_dir.path().join("dist");
+
+    generator::generate_site(ATTACHMENT_EXPORT, output_dir.to_str().unwrap(), &AppConfig::default()).await?;
+    let index = fs::read_to_string(output_dir.join("index.html"))?;
+    let page = fs::read_to_string(output_dir.join("conversations/conv-plain/index.html"))?;
+
+    assert!(index.contains(r#"<li class="month-conversation" data-id="conv-plain""#));
+    assert!(page.contains(r#"<li class="conversation-item" data-id="conv-attachment">"#));
+    assert!(page.contains(r#"<article class="conversation" data-conversation-id="conv-plain">"#));
+    assert!(page.contains("/assets/js/read-tracking.js"));
+    assert!(output_dir.join("assets/js/read-tracking.js").exists());
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_conversation_details_panel() -> Result<()> {
+    let temp_dir = TempDir::new()?;
+    let output_dir = temp_dir.path().join("dist");
+
+    generator::generate_site(ATTACHMENT_EXPORT, output_dir.to_str().unwrap(), &AppConfig::default()).await?;
+    let tagged = fs::read_to_string(output_dir.join("conversations/conv-attachment/index.html"))?;
+    let plain = fs::read_to_string(output_dir.join("conversations/conv-plain/index.html"))?;
+
+    assert!(tagged.contains(r#"<details class="conversation-details">"#));
+    assert!(tagged.contains("<dd><code>conv-attachment</code></dd>"));
+    assert!(tagged.contains("<dt>Обновлено</dt>"));
+    assert!(tagged.contains("<dt>Слов</dt>\n                <dd>7</dd>"));
+    assert!(tagged.contains(r#"<span class="conversation-tag">договоры</span>"#));
+
+    // No update date or tags in the export: those rows are left out
+    assert!(plain.contains("<dt>Создано</dt>"));
+    assert!(!plain.contains("<dt>Обновлено</dt>"));
+    assert!(!plain.contains("<dt>Теги</dt>"));
+    assert!(plain.contains("<dt>Слов</dt>\n                <dd>4</dd>"));
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_deeply_nested_markdown_is_cut_off() -> Result<()> {
+    let temp_dir = TempDir::new()?;
+    let output_dir = temp_dir.path().join("dist");
+
+    generator::generate_site(DEEP_NESTING_EXPORT, output_dir.to_str().unwrap(), &AppConfig::default()).await?;
+    let page = fs::read_to_string(output_dir.join("conversations/conv-deep/index.html"))?;
+
+    assert!(page.contains("Начало ответа."));
+    assert!(page.contains("пункт 20"));
+    assert!(!page.contains("пункт 150"), "the rest of the message is left out");
+    assert!(page.contains(r#"<p class="markdown-truncated">"#));
+    // Whatever was open is closed again, so the page around it is intact
+    let start = page.find("Начало ответа.").unwrap();
+    let end = page.find("markdown-truncated").unwrap();
+    let message = &page[start..end];
+    assert_eq!(message.matches("<ul>").count(), message.matches("</ul>").count());
+    assert_eq!(message.matches("<li>").count(), message.matches("</li>").count());
+    assert!(page.contains("Следующий ответ <strong>в порядке</strong>."));
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_rename_rewrites_only_the_renamed_conversation() -> Result<()> {
+    let temp_dir = TempDir::new()?;
+    let output_dir = temp_dir.path().join("dist");
+    let output = output_dir.to_str().unwrap();
+    let export_path = temp_dir.path().join("conversations.json");
+    let export = export_path.to_str().unwrap();
+
+    let mut conversations: serde_json::Value = serde_json::from_str(&fs::read_to_string(ATTACHMENT_EXPORT)?)?;
+    fs::write(&export_path, conversations.to_string())?;
+    generator::generate_site(export, output, &AppConfig::default()).await?;
+
+    // Marks pages that haven't been written since
+    let untouched = "<!-- untouched -->";
+    let plain_page = output_dir.join("conversations/conv-plain/index.html");
+    let renamed_page = output_dir.join("conversations/conv-attachment/index.html");
+    for page in [&plain_page, &renamed_page] {
+        fs::write(page, format!("{}{}", fs::read_to_string(page)?, untouched))?;
+    }
+
+    conversations[0]["title"] = "Разбор договора аренды".into();
+    fs::write(&export_path, conversations.to_string())?;
+    generator::generate_site(export, output, &AppConfig::default()).await?;
+
+    // Unrelated pages only get the new sidebar
+    let sidebar_title = r#"<div class="conversation-title">Разбор договора аренды</div>"#;
+    let plain = fs::read_to_string(&plain_page)?;
+    assert!(plain.ends_with(untouched), "unrelated pages aren't rendered again");
+    assert!(plain.contains(sidebar_title), "the sidebar has the new title");
+    assert!(!plain.contains(r#"<div class="conversation-title">Разбор договора</div>"#));
+    let renamed = fs::read_to_string(&renamed_page)?;
+    assert!(!renamed.ends_with(untouched));
+    assert!(renamed.contains("<h1>Разбор договора аренды</h1>"));
+    assert!(fs::read_to_string(output_dir.join("index.html"))?.contains("Разбор договора аренды"));
+    let archive = fs::read_to_string(output_dir.join("all/index.html"))?;
+    assert!(archive.contains(sidebar_title));
+    assert!(archive.contains("<h1>Разбор договора аренды</h1>"), "the archive section has the new title");
+    assert!(archive.contains("<h1>Без вложений</h1>"));
+
+    // Anything besides the title changed: everything is generated again
+    conversations[1]["mapping"]["msg1"]["message"]["fragments"][0]["content"] = "Какой договор выбрать?".into();
+    fs::write(&export_path, conversations.to_string())?;
+    generator::generate_site(export, output, &AppConfig::default()).await?;
+    assert!(!fs::read_to_string(&plain_page)?.ends_with(untouched));
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_index_and_sidebar_months_follow_language() -> Result<()> {
+    let temp_dir = TempDir::new()?;
+
+    for (language, months) in [
+        (Language::Ru, ["Март 2024", "Февраль 2024", "Январь 2024"]),
+        (Language::En, ["March 2024", "February 2024", "January 2024"]),
+    ] {
+        let output_dir = temp_dir.path().join(format!("{:?}", language));
+        let config = AppConfig { language, ..AppConfig::default() };
+        generator::generate_site(META_EXPORT, output_dir.to_str().unwrap(), &config).await?;
+        let index = fs::read_to_string(output_dir.join("index.html"))?;
+        let page = fs::read_to_string(output_dir.join("conversations/conv-a/index.html"))?;
+
+        // Newest month first in both, with the same names
+        let index_months: Vec<usize> = months.iter().map(|m| index.find(&format!("<h3>{}</h3>", m)).unwrap()).collect();
+        let sidebar_months: Vec<usize> = months
+            .iter()
+            .map(|m| page.find(&format!(r#"<div class="month-header">{}</div>"#, m)).unwrap())
+            .collect();
+        assert!(index_months.is_sorted(), "{:?}", language);
+        assert!(sidebar_months.is_sorted(), "{:?}", language);
+        assert!(index.contains(r#"<span class="month-count" data-total="1">1</span>"#));
+    }
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_index_and_sidebar_month_labels_match() -> Result<()> {
+    let temp_dir = TempDir::new()?;
+    let export_path = temp_dir.path().join("conversations.json");
+    // Still February in UTC, already March in Moscow
+    fs::write(&export_path, serde_json::json!([{
+        "id": "conv-leap",
+        "title": "Високосный день",
+        "inserted_at": "2024-02-29T22:30:00Z",
+        "mapping": {
+            "root": { "children": ["msg1"] },
+            "msg1": { "message": { "fragments": [{ "type": "REQUEST", "content": "Сколько дней в феврале?" }] }, "children": [] }
+        }
+    }]).to_string())?;
+
+    let between = |html: &str, start: &str, end: &str| -> String {
+        let from = html.find(start).unwrap() + start.len();
+        html[from..from + html[from..].find(end).unwrap()].to_string()
+    };
+
+    for (language, label) in [(Language::Ru, "Март 2024"), (Language::En, "March 2024")] {
+        let output_dir = temp_dir.path().join(format!("{:?}", language));
+        let config = AppConfig {
+            language,
+            timezone: Some("Europe/Moscow".to_string()),
+            ..AppConfig::default()
+        };
+        generator::generate_site(export_path.to_str().unwrap(), output_dir.to_str().unwrap(), &config).await?;
+        let index = fs::read_to_string(output_dir.join("index.html"))?;
+
+        let index_label = between(&index[index.find(r#"<div class="stats">"#).unwrap()..], "<h3>", "</h3>");
+        let sidebar_label = between(&index, r#"<div class="month-header">"#, "</div>");
+        assert_eq!(index_label, label);
+        assert_eq!(sidebar_label, label);
+    }
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_every_page_loads_the_command_palette() -> Result<()> {
+    let temp_dir = TempDir::new()?;
+    let output_dir = temp_dir.path().join("dist");
+
+    generator::generate_site(ATTACHMENT_EXPORT, output_dir.to_str().unwrap(), &AppConfig::default()).await?;
+
+    for page in ["index.html", "all/index.html", "conversations/conv-plain/index.html"] {
+        let html = fs::read_to_string(output_dir.join(page))?;
+        assert!(html.contains("/assets/js/command-palette.js"), "{}", page);
+    }
+    let script = fs::read_to_string(output_dir.join("assets/js/command-palette.js"))?;
+    assert!(script.contains("/api/conversations/meta"));
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_search_results_and_tool_calls_are_folded_and_searchable() -> Result<()> {
+    let temp_dir = TempDir::new()?;
+    let output_dir = temp_dir.path().join("dist");
+    let index_path = temp_dir.path().join("index");
+
+    generator::generate_site(TOOL_EXPORT, output_dir.to_str().unwrap(), &AppConfig::default()).await?;
+    let page = fs::read_to_string(output_dir.join("conversations/conv-tools/index.html"))?;
+
+    assert!(page.contains("<summary>Результаты веб-поиска</summary>"));
+    assert!(page.contains(r#"<a href="https://example.com/kazan">Прогноз для Казани</a>"#));
+    assert!(page.contains("Завтра облачно, +18, ветер северный."));
+    assert!(page.contains("<summary>Вызов инструмента</summary>"));
+    assert!(page.contains("get_forecast"));
+    assert!(page.contains("<summary>Результат инструмента</summary>"));
+    assert!(page.contains("возможна морось"));
+    // The answer itself isn't folded
+    assert_eq!(page.matches(r#"<details class="message-tool">"#).count(), 3);
+    assert!(page.contains("<strong>+18</strong>"));
+
+    indexer::build_index(TOOL_EXPORT, index_path.to_str().unwrap()).await?;
+    let search = SearchEngine::new(index_path.to_str().unwrap())?;
+    for query in ["морось", "get_forecast", "северный"] {
+        assert_eq!(search.search(query, 10)?.len(), 1, "{}", query);
+    }
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_index_preview_shows_the_first_message() -> Result<()> {
+    let temp_dir = TempDir::new()?;
+    let default_dir = temp_dir.path().join("default");
+    let preview_dir = temp_dir.path().join("preview");
+
+    generator::generate_site(ATTACHMENT_EXPORT, default_dir.to_str().unwrap(), &AppConfig::default()).await?;
+    let index = fs::read_to_string(default_dir.join("index.html"))?;
+    assert!(!index.contains("month-conversation-preview"), "off by default");
+
+    let config = AppConfig { index_preview_chars: 20, ..AppConfig::default() };
+    generator::generate_site(ATTACHMENT_EXPORT, preview_dir.to_str().unwrap(), &config).await?;
+    let index = fs::read_to_string(preview_dir.join("index.html"))?;
+    assert!(index.contains(r#"<p class="month-conversation-preview">Проверь, пожалуйста…</p>"#));
+    assert!(index.contains(r#"<p class="month-conversation-preview">Какой договор аренд…</p>"#));
+
+    Ok(())
+}
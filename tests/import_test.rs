@@ -0,0 +1,61 @@
+use anyhow::Result;
+use serde_json::json;
+use std::fs;
+use tempfile::TempDir;
+
+use deepseek_app::config::AppConfig;
+use deepseek_app::import::{self, MergeSummary};
+use deepseek_app::search::SearchEngine;
+use deepseek_app::indexer;
+
+mod common;
+use common::conversation;
+
+#[tokio::test]
+async fn test_merge_import_updates_index_in_place() -> Result<()> {
+    let temp_dir = TempDir::new()?;
+    let index_path = temp_dir.path().join("index");
+    let index_path = index_path.to_str().unwrap();
+    let first_path = temp_dir.path().join("first.json");
+    let second_path = temp_dir.path().join("second.json");
+    let merged_path = temp_dir.path().join("merged.json");
+    
+    fs::write(&first_path, json!([
+        conversation("1", "Гравитация", "Что такое гравитация?"),
+        conversation("2", "Квантовая механика", "Расскажи про кванты"),
+    ]).to_string())?;
+    // Newer export: conversation 1 unchanged, 2 continued, 3 is new
+    fs::write(&second_path, json!([
+        conversation("1", "Гравитация", "Что такое гравитация?"),
+        conversation("2", "Квантовая механика", "Расскажи про запутанность"),
+        conversation("3", "Рецепты", "Как испечь хлеб?"),
+    ]).to_string())?;
+    
+    indexer::build_index(first_path.to_str().unwrap(), index_path).await?;
+    
+    let (changed, summary) = import::merge_exports(
+        first_path.to_str().unwrap(),
+        second_path.to_str().unwrap(),
+        merged_path.to_str().unwrap(),
+    ).await?;
+    assert_eq!(summary, MergeSummary { added: 1, updated: 1, unchanged: 1 });
+    
+    let ids: Vec<&str> = changed.iter().map(|c| c.id.as_str()).collect();
+    assert_eq!(ids, vec!["2", "3"]);
+    
+    indexer::update_index(index_path, &changed, &AppConfig::default())?;
+    
+    let search = SearchEngine::new(index_path)?;
+    assert_eq!(search.search("гравитация", 10)?.len(), 1, "Unchanged conversations stay indexed");
+    assert_eq!(search.search("хлеб", 10)?.len(), 1, "New conversations are indexed");
+    assert_eq!(search.search("запутанность", 10)?.len(), 1, "Updated conversations are re-indexed");
+    assert!(search.search("кванты", 10)?.is_empty(), "The old version of an updated conversation is gone");
+    assert_eq!(search.search("механика", 10)?.len(), 1, "Updated conversations aren't duplicated");
+    
+    // The merged export holds every conversation once, in order
+    let merged: Vec<serde_json::Value> = serde_json::from_str(&fs::read_to_string(&merged_path)?)?;
+    let ids: Vec<&str> = merged.iter().map(|c| c["id"].as_str().unwrap()).collect();
+    assert_eq!(ids, vec!["1", "2", "3"]);
+    
+    Ok(())
+}
@@ -0,0 +1,146 @@
+use anyhow::Result;
+use serde_json::json;
+use std::fs;
+use tempfile::TempDir;
+
+use deepseek_app::config::AppConfig;
+use deepseek_app::{generator, indexer};
+use deepseek_app::json_export::{export_json, ExportDocument, EXPORT_VERSION};
+use deepseek_app::model::Role;
+use deepseek_app::search::SearchEngine;
+
+const FIXTURE: &str = "tests/fixtures/array_export.json";
+const IMAGE_EXPORT: &str = "tests/fixtures/image_export.json";
+
+#[tokio::test]
+async fn test_export_keeps_metadata() -> Result<()> {
+    let temp_dir = TempDir::new()?;
+    let export_path = temp_dir.path().join("export.json");
+
+    export_json(FIXTURE, export_path.to_str().unwrap(), true).await?;
+
+    let document: ExportDocument = serde_json::from_str(&fs::read_to_string(&export_path)?)?;
+    assert_eq!(document.version, EXPORT_VERSION);
+    assert_eq!(document.conversations.len(), 1);
+
+    let mapping = &document.conversations[0].mapping;
+    assert_eq!(mapping["root"].children, vec!["msg1"]);
+    assert_eq!(mapping["msg2"].parent.as_deref(), Some("msg1"));
+
+    let request = mapping["msg1"].message.as_ref().unwrap();
+    assert_eq!(request.role, Some(Role::User));
+    assert_eq!(request.inserted_at.as_deref(), Some("2024-01-01T10:00:00Z"));
+    assert_eq!(request.fragments[0].fragment_type, "REQUEST");
+
+    let response = mapping["msg2"].message.as_ref().unwrap();
+    assert_eq!(response.role, Some(Role::Assistant));
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_export_without_metadata() -> Result<()> {
+    let temp_dir = TempDir::new()?;
+    let export_path = temp_dir.path().join("export.json");
+
+    export_json(FIXTURE, export_path.to_str().unwrap(), false).await?;
+
+    let document: ExportDocument = serde_json::from_str(&fs::read_to_string(&export_path)?)?;
+    let mapping = &document.conversations[0].mapping;
+    assert_eq!(mapping["root"].children, vec!["1"]);
+
+    let message = mapping["1"].message.as_ref().unwrap();
+    assert!(message.role.is_none() && message.inserted_at.is_none());
+    assert!(mapping["1"].parent.is_none());
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_export_round_trip() -> Result<()> {
+    let temp_dir = TempDir::new()?;
+    let export_path = temp_dir.path().join("export.json");
+    let index_path = temp_dir.path().join("test_index");
+
+    export_json(FIXTURE, export_path.to_str().unwrap(), true).await?;
+    indexer::build_index(export_path.to_str().unwrap(), index_path.to_str().unwrap()).await?;
+
+    let search = SearchEngine::new(index_path.to_str().unwrap())?;
+    let results = search.search("притяжение", 10)?;
+    assert_eq!(results.len(), 1, "Re-imported export should be searchable");
+    assert_eq!(results[0].conversation_id, "conv-1");
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_export_is_lossless() -> Result<()> {
+    let temp_dir = TempDir::new()?;
+    let source_path = temp_dir.path().join("conversations.json");
+    let export_path = temp_dir.path().join("export.json");
+
+    let source = json!([{
+        "id": "raw",
+        "title": "Как есть",
+        "mapping": {
+            "root": { "children": ["msg1"] },
+            "msg1": {
+                "message": {
+                    "inserted_at": "2024-01-01T10:00:00.000+00:00",
+                    "fragments": [{ "type": "REQUEST", "content": ["Первая часть, ", { "type": "text", "text": "вторая" }] }]
+                },
+                "children": ["msg2"]
+            },
+            // No fragments (e.g. a cancelled response)
+            "msg2": { "message": { "inserted_at": "вчера", "fragments": [] }, "children": ["msg3"] },
+            "msg3": {
+                "message": {
+                    "inserted_at": 1704103200,
+                    "fragments": [{ "type": "SEARCH", "results": [{ "title": "Rust", "url": "https://rust-lang.org" }] }]
+                },
+                "children": []
+            }
+        }
+    }]);
+    fs::write(&source_path, source.to_string())?;
+
+    export_json(source_path.to_str().unwrap(), export_path.to_str().unwrap(), true).await?;
+    let exported: serde_json::Value = serde_json::from_str(&fs::read_to_string(&export_path)?)?;
+    let mapping = &exported["conversations"][0]["mapping"];
+
+    // Timestamps as they were, whatever their format
+    assert_eq!(mapping["msg1"]["message"]["inserted_at"], "2024-01-01T10:00:00.000+00:00");
+    assert_eq!(mapping["msg2"]["message"]["inserted_at"], "вчера");
+    assert_eq!(mapping["msg3"]["message"]["inserted_at"], 1704103200);
+
+    // The empty node stays in the chain
+    assert_eq!(mapping["msg1"]["children"], json!(["msg2"]));
+    assert_eq!(mapping["msg2"]["message"]["fragments"], json!([]));
+
+    // Fragments as exported, not as rendered
+    let source_mapping = &source[0]["mapping"];
+    assert_eq!(mapping["msg1"]["message"]["fragments"], source_mapping["msg1"]["message"]["fragments"]);
+    assert_eq!(mapping["msg3"]["message"]["fragments"], source_mapping["msg3"]["message"]["fragments"]);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_export_round_trip_keeps_images() -> Result<()> {
+    let temp_dir = TempDir::new()?;
+
+    for include_metadata in [true, false] {
+        let export_path = temp_dir.path().join(format!("export-{}.json", include_metadata));
+        let output_dir = temp_dir.path().join(format!("dist-{}", include_metadata));
+        export_json(IMAGE_EXPORT, export_path.to_str().unwrap(), include_metadata).await?;
+
+        // The export, imported again, renders the same images
+        generator::generate_site(export_path.to_str().unwrap(), output_dir.to_str().unwrap(), &AppConfig::default()).await?;
+        let page = fs::read_to_string(output_dir.join("conversations/images/index.html"))?;
+        assert!(page.contains(r#"<img src="data:image/png;base64,iVBORw0KGgo"#), "the image fragment is lost");
+        assert!(page.contains(r#"alt="Скриншот ошибки""#));
+        assert!(page.contains(r#"<img src="data:image/gif;base64,R0lGODlh"#), "the image in `files` is lost");
+    }
+
+    Ok(())
+}
@@ -0,0 +1,24 @@
+use deepseek_app::config::Language;
+use deepseek_app::locale::{format_count, month_label};
+
+#[test]
+fn test_format_count_groups_thousands() {
+    assert_eq!(format_count(0, Language::Ru), "0");
+    assert_eq!(format_count(999, Language::Ru), "999");
+    assert_eq!(format_count(1234, Language::Ru), "1\u{a0}234");
+    assert_eq!(format_count(1234567, Language::Ru), "1\u{a0}234\u{a0}567");
+
+    assert_eq!(format_count(999, Language::En), "999");
+    assert_eq!(format_count(1234, Language::En), "1,234");
+    assert_eq!(format_count(123456, Language::En), "123,456");
+    assert_eq!(format_count(1234567, Language::En), "1,234,567");
+}
+
+#[test]
+fn test_month_label() {
+    assert_eq!(month_label(2024, 1, Language::Ru), "Январь 2024");
+    assert_eq!(month_label(2024, 12, Language::Ru), "Декабрь 2024");
+    assert_eq!(month_label(2024, 3, Language::En), "March 2024");
+    assert_eq!(month_label(2024, 0, Language::En), "2024-00");
+    assert_eq!(month_label(2024, 13, Language::Ru), "2024-13");
+}
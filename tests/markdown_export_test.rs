@@ -0,0 +1,66 @@
+use anyhow::Result;
+use serde_json::json;
+use std::fs;
+use tempfile::TempDir;
+
+use deepseek_app::config::AppConfig;
+use deepseek_app::generator;
+use deepseek_app::markdown_export::conversation_to_markdown;
+use deepseek_app::Conversation;
+
+const ARRAY_EXPORT: &str = "tests/fixtures/array_export.json";
+
+#[test]
+fn test_conversation_to_markdown() -> Result<()> {
+    let conversation: Conversation = serde_json::from_value(json!({
+        "id": "md",
+        "title": "Sorting",
+        "inserted_at": "2024-01-01T10:00:00Z",
+        "mapping": {
+            "root": { "children": ["q"] },
+            "q": {
+                "message": { "fragments": [{"type": "REQUEST", "content": "How do I sort a Vec?"}] },
+                "children": ["a"]
+            },
+            "a": {
+                "message": { "fragments": [
+                    {"type": "SEARCH", "content": "search results"},
+                    {"type": "THINK", "content": "Use sort.\n\nOr sort_unstable."},
+                    {"type": "RESPONSE", "content": "Use `sort`:\n\n```rust\nv.sort();\n```\n"}
+                ] },
+                "children": []
+            }
+        }
+    }))?;
+
+    assert_eq!(
+        conversation_to_markdown(&conversation),
+        "# Sorting\n\n_2024-01-01T10:00:00Z_\n\n## User\n\nHow do I sort a Vec?\n\n## Assistant\n\n> Use sort.\n>\n> Or sort_unstable.\n\nUse `sort`:\n\n```rust\nv.sort();\n```\n"
+    );
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_raw_markdown_is_written_only_when_enabled() -> Result<()> {
+    let temp_dir = TempDir::new()?;
+    let output_dir = temp_dir.path().join("dist");
+    let raw_path = output_dir.join("conversations/conv-1/raw.md");
+
+    generator::generate_site(ARRAY_EXPORT, output_dir.to_str().unwrap(), &AppConfig::default()).await?;
+    assert!(!raw_path.exists());
+
+    let config = AppConfig { write_raw_markdown: true, ..AppConfig::default() };
+    generator::generate_site(ARRAY_EXPORT, output_dir.to_str().unwrap(), &config).await?;
+    let raw = fs::read_to_string(&raw_path)?;
+    assert!(raw.contains("Гравитация — это **притяжение** между телами."));
+
+    let page = fs::read_to_string(output_dir.join("conversations/conv-1/index.html"))?;
+    assert!(page.contains(r#"href="raw.md""#));
+
+    // Turning the option off again removes the stale file
+    generator::generate_site(ARRAY_EXPORT, output_dir.to_str().unwrap(), &AppConfig::default()).await?;
+    assert!(!raw_path.exists());
+
+    Ok(())
+}
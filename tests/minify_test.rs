@@ -0,0 +1,23 @@
+use deepseek_app::minify::minify_html;
+
+#[test]
+fn test_minify_collapses_whitespace_and_drops_comments() {
+    let html = "<div>\n    <!-- sidebar -->\n    <p>Привет,   мир</p>\n\n</div>";
+    assert_eq!(minify_html(html), "<div>\n<p>Привет, мир</p>\n</div>");
+}
+
+#[test]
+fn test_minify_keeps_preformatted_content_and_attributes() {
+    let html = concat!(
+        "<div data-code=\"fn main() {\n    run();\n}\" title=\"a  >  b\">  </div>\n",
+        "<PRE>fn main() {\n    run();\n}</PRE>\n",
+        "<script>// a comment\nlet x  = 1;</script>",
+        "<p>a\u{a0}\u{a0}b</p>",
+    );
+    let minified = minify_html(html);
+
+    assert!(minified.contains("data-code=\"fn main() {\n    run();\n}\" title=\"a  >  b\">"));
+    assert!(minified.contains("<PRE>fn main() {\n    run();\n}</PRE>"));
+    assert!(minified.contains("<script>// a comment\nlet x  = 1;</script>"));
+    assert!(minified.contains("a\u{a0}\u{a0}b"), "non-breaking spaces are content");
+}
@@ -0,0 +1,327 @@
+use anyhow::Result;
+use serde_json::json;
+use std::fs;
+use tempfile::TempDir;
+
+use deepseek_app::config::{AppConfig, UntitledFallback};
+use deepseek_app::model::{extract_messages, Conversation, Role};
+use deepseek_app::search::SearchEngine;
+use deepseek_app::{generator, indexer};
+
+const ARRAY_CONTENT_EXPORT: &str = "tests/fixtures/array_content_export.json";
+
+/// A conversation where the user edited their question, creating two branches
+fn branched_conversation() -> serde_json::Value {
+    json!([
+        {
+            "id": "branched",
+            "title": "Branches",
+            "inserted_at": "2024-01-01T10:00:00Z",
+            "mapping": {
+                "root": { "children": ["q1", "q2"] },
+                "q1": {
+                    "message": {
+                        "inserted_at": "2024-01-01T10:00:00Z",
+                        "fragments": [{"type": "REQUEST", "content": "first <question>"}]
+                    },
+                    "children": ["a1"]
+                },
+                "a1": {
+                    "message": {
+                        "inserted_at": "2024-01-01T10:01:00Z",
+                        "fragments": [
+                            {"type": "THINK", "content": "pondering"},
+                            {"type": "RESPONSE", "content": "**alpha** answer"}
+                        ]
+                    },
+                    "children": []
+                },
+                "q2": {
+                    "message": {
+                        "fragments": [{"type": "REQUEST", "content": "edited question"}]
+                    },
+                    "children": ["a2"]
+                },
+                "a2": {
+                    "message": {
+                        "fragments": [{"type": "RESPONSE", "content": "omega answer"}]
+                    },
+                    "children": []
+                }
+            }
+        }
+    ])
+}
+
+#[test]
+fn test_extract_messages_walks_all_branches() {
+    let data = branched_conversation();
+    let messages = extract_messages(&data[0]["mapping"]);
+
+    let summary: Vec<(&str, &str, Role)> = messages
+        .iter()
+        .map(|m| (m.node_id.as_str(), m.fragment_type.as_str(), m.role))
+        .collect();
+    assert_eq!(summary, vec![
+        ("q1", "REQUEST", Role::User),
+        ("a1", "THINK", Role::Assistant),
+        ("a1", "RESPONSE", Role::Assistant),
+        ("q2", "REQUEST", Role::User),
+        ("a2", "RESPONSE", Role::Assistant),
+    ]);
+
+    assert_eq!(messages[2].content, "**alpha** answer");
+    assert_eq!(messages[0].inserted_at.map(|d| d.to_rfc3339()).as_deref(), Some("2024-01-01T10:00:00+00:00"));
+    assert!(messages[3].inserted_at.is_none());
+}
+
+#[tokio::test]
+async fn test_indexer_and_generator_see_same_messages() -> Result<()> {
+    let temp_dir = TempDir::new()?;
+    let conversations_path = temp_dir.path().join("conversations.json");
+    let index_path = temp_dir.path().join("test_index");
+    let output_dir = temp_dir.path().join("dist");
+    fs::write(&conversations_path, branched_conversation().to_string())?;
+
+    // Indexer: text from both branches is searchable
+    indexer::build_index(conversations_path.to_str().unwrap(), index_path.to_str().unwrap()).await?;
+    let search = SearchEngine::new(index_path.to_str().unwrap())?;
+    assert_eq!(search.search("alpha", 10)?.len(), 1);
+    assert_eq!(search.search("omega", 10)?.len(), 1);
+
+    // Generator: both branches are rendered, requests escaped, responses as markdown
+    generator::generate_site(
+        conversations_path.to_str().unwrap(),
+        output_dir.to_str().unwrap(),
+        &AppConfig::default(),
+    ).await?;
+    let page = fs::read_to_string(output_dir.join("conversations/branched/index.html"))?;
+    assert!(page.contains("first &lt;question&gt;"));
+    assert!(page.contains("<strong>alpha</strong> answer"));
+    assert!(page.contains("omega answer"));
+    assert!(page.contains("message-think"));
+
+    // The index lists the conversation in its month group, ready for filtering
+    let index = fs::read_to_string(output_dir.join("index.html"))?;
+    assert!(index.contains("data-month-group"));
+    assert!(index.contains(r#"data-title="Branches""#));
+    assert!(index.contains(r#"href="/conversations/branched/""#));
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_untitled_conversation_gets_derived_title_and_summary() -> Result<()> {
+    let temp_dir = TempDir::new()?;
+    let conversations_path = temp_dir.path().join("conversations.json");
+    let index_path = temp_dir.path().join("test_index");
+    let output_dir = temp_dir.path().join("dist");
+
+    let data = json!([
+        {
+            "id": "untitled",
+            "inserted_at": "2024-01-01T10:00:00Z",
+            "mapping": {
+                "root": { "children": ["q"] },
+                "q": {
+                    "message": { "fragments": [{"type": "REQUEST", "content": "What is  gravitation?"}] },
+                    "children": ["a"]
+                },
+                "a": {
+                    "message": {
+                        "fragments": [
+                            {"type": "THINK", "content": "Let me recall."},
+                            {"type": "RESPONSE", "content": "Gravitation is the **attraction** between masses. It is weak."}
+                        ]
+                    },
+                    "children": []
+                }
+            }
+        }
+    ]);
+    fs::write(&conversations_path, data.to_string())?;
+
+    let conversation: Conversation = serde_json::from_value(data[0].clone())?;
+    assert_eq!(
+        conversation.summary().as_deref(),
+        Some("What is gravitation? — Gravitation is the attraction between masses.")
+    );
+    let expected = "What is gravitation?";
+    assert_eq!(conversation.display_title(), expected);
+    // The same from messages extracted beforehand
+    let messages = extract_messages(&conversation.mapping);
+    assert_eq!(conversation.display_title_from(&messages, &AppConfig::default()), expected);
+    assert_eq!(conversation.summary_from(&messages), conversation.summary());
+
+    // The summary is searchable, results are titled from the first message
+    indexer::build_index(conversations_path.to_str().unwrap(), index_path.to_str().unwrap()).await?;
+    let search = SearchEngine::new(index_path.to_str().unwrap())?;
+    let results = search.search("attraction", 10)?;
+    assert_eq!(results.len(), 1);
+    assert_eq!(results[0].title, expected);
+
+    // ...and so are the page and the sidebar
+    generator::generate_site(
+        conversations_path.to_str().unwrap(),
+        output_dir.to_str().unwrap(),
+        &AppConfig::default(),
+    ).await?;
+    let page = fs::read_to_string(output_dir.join("conversations/untitled/index.html"))?;
+    assert!(page.contains(&format!("<title>{} - ", expected)));
+    assert!(!page.contains("Untitled"));
+
+    Ok(())
+}
+
+#[test]
+fn test_derived_title_is_cut_at_word_boundary() -> Result<()> {
+    let conversation: Conversation = serde_json::from_value(json!({
+        "id": "long",
+        "mapping": {
+            "root": { "children": ["q"] },
+            "q": {
+                "message": { "fragments": [{
+                    "type": "REQUEST",
+                    "content": "How do I configure a reverse proxy in front of an axum server with TLS termination?"
+                }] },
+                "children": []
+            }
+        }
+    }))?;
+    assert_eq!(
+        conversation.display_title(),
+        "How do I configure a reverse proxy in front of an axum…"
+    );
+
+    let empty: Conversation = serde_json::from_value(json!({ "id": "empty", "mapping": {} }))?;
+    assert_eq!(empty.display_title(), "Untitled");
+
+    Ok(())
+}
+
+#[test]
+fn test_extract_messages_keeps_embedded_images_apart() {
+    let mapping = json!({
+        "root": { "children": ["msg"] },
+        "msg": {
+            "message": {
+                "files": [{ "file_name": "a.gif", "data": "data:image/gif;base64,R0lGODlh" }],
+                "fragments": [
+                    { "type": "REQUEST", "content": "Смотри" },
+                    { "type": "IMAGE", "alt": "снимок", "content": "data:image/png;base64,iVBORw0KGgo=" },
+                    { "type": "IMAGE", "content": "data:text/plain;base64,aGk=" }
+                ]
+            },
+            "children": []
+        }
+    });
+
+    let messages = extract_messages(&mapping);
+    assert_eq!(messages.len(), 3);
+    assert_eq!(messages[0].images.len(), 1, "`files` images go on the first fragment");
+    assert_eq!(messages[0].images[0].alt, "a.gif");
+    assert_eq!(messages[1].role, Role::User, "an image belongs to whoever sent it");
+    assert_eq!(messages[1].content, "", "the base64 isn't treated as text");
+    assert_eq!(messages[1].images[0].mime_type(), "image/png");
+    assert_eq!(messages[1].images[0].base64_data(), "iVBORw0KGgo=");
+    assert!(messages[2].images.is_empty(), "only images are embedded");
+}
+
+#[test]
+fn test_extract_messages_keeps_tool_data_raw() {
+    let search = json!({
+        "type": "SEARCH",
+        "results": [{ "url": "https://example.com/kazan", "title": "Прогноз", "snippet": "Облачно" }]
+    });
+    let mapping = json!({
+        "root": { "children": ["msg"] },
+        "msg": { "message": { "fragments": [search, { "type": "RESPONSE", "content": "Облачно." }] }, "children": [] }
+    });
+
+    let messages = extract_messages(&mapping);
+    // No markdown made up for display: the data is kept as exported
+    assert_eq!(messages[0].content, "");
+    assert_eq!(messages[0].raw.as_ref(), Some(&search));
+    assert_eq!(messages[0].text(), "Прогноз\nhttps://example.com/kazan\nОблачно");
+    assert!(messages[1].raw.is_none(), "the text is in `content`");
+    assert_eq!(messages[1].text(), "Облачно.");
+}
+
+#[tokio::test]
+async fn test_array_valued_content_is_extracted() -> Result<()> {
+    let conversations: Vec<Conversation> = serde_json::from_str(&fs::read_to_string(ARRAY_CONTENT_EXPORT)?)?;
+
+    let messages = extract_messages(&conversations[0].mapping);
+    assert_eq!(messages.len(), 2);
+    assert_eq!(messages[0].content, "Посмотри на квитанцию за электричество\n\n[file: receipt.pdf]");
+    assert_eq!(messages[1].content, "Сумма к оплате: **1520 рублей**.");
+    assert_eq!(messages[1].images.len(), 1, "an image part is embedded, not noted");
+    assert_eq!(messages[1].images[0].alt, "Пиксель");
+
+    // Neither indexing nor rendering drops them
+    let temp_dir = TempDir::new()?;
+    let index_path = temp_dir.path().join("test_index");
+    let output_dir = temp_dir.path().join("dist");
+    indexer::build_index(ARRAY_CONTENT_EXPORT, index_path.to_str().unwrap()).await?;
+    let search = SearchEngine::new(index_path.to_str().unwrap())?;
+    assert_eq!(search.search("электричество", 10)?.len(), 1);
+    assert_eq!(search.search("рублей", 10)?.len(), 1);
+
+    generator::generate_site(ARRAY_CONTENT_EXPORT, output_dir.to_str().unwrap(), &AppConfig::default()).await?;
+    let page = fs::read_to_string(output_dir.join("conversations/array-content/index.html"))?;
+    assert!(page.contains("Посмотри на квитанцию за электричество"));
+    assert!(page.contains("[file: receipt.pdf]"));
+    assert!(page.contains("<strong>1520 рублей</strong>"));
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_untitled_fallback_labels_conversations_without_text() -> Result<()> {
+    let temp_dir = TempDir::new()?;
+    let conversations_path = temp_dir.path().join("conversations.json");
+    fs::write(&conversations_path, json!([{
+        "id": "f3a9c2e1-7b4d-4e8a-9c1f-2d6b8a0e5f47",
+        "title": "  ",
+        "inserted_at": "2024-05-06T07:08:00Z",
+        "mapping": {
+            "root": { "children": ["msg1"] },
+            "msg1": {
+                "message": { "fragments": [
+                    { "type": "IMAGE", "content": "data:image/png;base64,iVBORw0KGgo=" }
+                ] },
+                "children": ["msg2"]
+            },
+            "msg2": {
+                "message": { "fragments": [{ "type": "RESPONSE", "content": "" }] },
+                "children": []
+            }
+        }
+    }]).to_string())?;
+
+    for (fallback, expected) in [
+        (UntitledFallback::Untitled, "Untitled"),
+        (UntitledFallback::Date, "06.05.2024 в 07:08"),
+        (UntitledFallback::Id, "f3a9c2e1…"),
+    ] {
+        let config = AppConfig { untitled_fallback: fallback, ..AppConfig::default() };
+        let dir = temp_dir.path().join(format!("{:?}", fallback));
+        let index_path = dir.join("index");
+        let output_dir = dir.join("dist");
+
+        generator::generate_site(conversations_path.to_str().unwrap(), output_dir.to_str().unwrap(), &config).await?;
+        let index = fs::read_to_string(output_dir.join("index.html"))?;
+        assert!(index.contains(&format!(r#"<div class="conversation-title">{}</div>"#, expected)), "{:?} sidebar", fallback);
+        assert!(index.contains(&format!(">{}</a>", expected)), "{:?} index", fallback);
+
+        indexer::build_index_with_config(conversations_path.to_str().unwrap(), index_path.to_str().unwrap(), &config).await?;
+        let search = SearchEngine::new(index_path.to_str().unwrap())?;
+        // Search results are titled the same, and the label finds the conversation
+        let query = expected.split(' ').next().unwrap().trim_end_matches('…');
+        let results = search.search(query, 10)?;
+        assert_eq!(results.len(), 1, "{:?} search", fallback);
+        assert_eq!(results[0].title, expected, "{:?} search", fallback);
+    }
+
+    Ok(())
+}
@@ -4,8 +4,13 @@ use std::fs;
 use tempfile::TempDir;
 
 // Import from the main crate
-use deepseek_app::search::SearchEngine;
-use deepseek_app::indexer;
+use deepseek_app::config::{AppConfig, IndexTokenizer};
+use deepseek_app::generator;
+use deepseek_app::search::{RoleFilter, SearchEngine, SearchOptions};
+use deepseek_app::indexer::{self, IndexOutdated, MessageLinksOutdated};
+
+mod common;
+use common::{conversation, conversation_at};
 
 #[tokio::test]
 async fn test_ngram_substring_search() -> Result<()> {
@@ -174,6 +179,9 @@ async fn test_search_returns_snippets() -> Result<()> {
         assert!(snippet.ends_with("..."), "Long snippet should end with '...'");
     }
     
+    // Links to the conversation's page, e.g. for copying results as Markdown
+    assert_eq!(results[0].url, "/conversations/1/");
+    
     Ok(())
 }
 
@@ -228,3 +236,1222 @@ async fn test_utf8_safety() -> Result<()> {
     Ok(())
 }
 
+#[tokio::test]
+async fn test_open_or_rebuild_recovers_corrupt_index() -> Result<()> {
+    let temp_dir = TempDir::new()?;
+    let index_path = temp_dir.path().join("test_index");
+    let conversations_path = temp_dir.path().join("conversations.json");
+    
+    let test_data = json!([
+        {
+            "id": "1",
+            "title": "О гравитации",
+            "inserted_at": "2024-01-01T00:00:00Z",
+            "mapping": {
+                "root": {
+                    "children": ["msg1"]
+                },
+                "msg1": {
+                    "message": {
+                        "fragments": [
+                            {"type": "text", "content": "Что такое гравитация?"}
+                        ]
+                    },
+                    "children": []
+                }
+            }
+        }
+    ]);
+    
+    fs::write(&conversations_path, test_data.to_string())?;
+    indexer::build_index(
+        conversations_path.to_str().unwrap(),
+        index_path.to_str().unwrap()
+    ).await?;
+    
+    // Simulate an interrupted write by clobbering the index metadata
+    fs::write(index_path.join("meta.json"), "{ not json")?;
+    assert!(SearchEngine::new(index_path.to_str().unwrap()).is_err(), "Corrupt index should fail to open");
+    
+    let search = SearchEngine::open_or_rebuild(
+        index_path.to_str().unwrap(),
+        conversations_path.to_str().unwrap(),
+        &AppConfig::default()
+    ).await?;
+    
+    let results = search.search("грав", 10)?;
+    assert!(!results.is_empty(), "Rebuilt index should be searchable");
+    
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_outdated_index_is_detected_and_rebuilt() -> Result<()> {
+    let temp_dir = TempDir::new()?;
+    let index_path = temp_dir.path().join("test_index");
+    let index = index_path.to_str().unwrap();
+    let conversations_path = temp_dir.path().join("conversations.json");
+    let conversations = conversations_path.to_str().unwrap();
+
+    let test_data = json!([
+        {
+            "id": "1",
+            "title": "О гравитации",
+            "inserted_at": "2024-01-01T00:00:00Z",
+            "mapping": {
+                "root": { "children": ["msg1"] },
+                "msg1": {
+                    "message": { "fragments": [{"type": "text", "content": "Что такое гравитация?"}] },
+                    "children": []
+                }
+            }
+        }
+    ]);
+    fs::write(&conversations_path, test_data.to_string())?;
+    indexer::build_index(conversations, index).await?;
+    assert_eq!(indexer::schema_version(index), Some(indexer::SCHEMA_VERSION));
+
+    // Built before versions were recorded
+    fs::remove_file(index_path.join(indexer::INDEX_META_FILE))?;
+    let err = SearchEngine::new(index).expect_err("an unversioned index is outdated");
+    let outdated = err.downcast_ref::<IndexOutdated>().expect("should be IndexOutdated");
+    assert_eq!(outdated.found, None);
+
+    // Built by a version with another schema
+    fs::write(index_path.join(indexer::INDEX_META_FILE), r#"{"schema_version":0}"#)?;
+    let err = SearchEngine::new(index).expect_err("an older schema is outdated");
+    assert_eq!(err.downcast_ref::<IndexOutdated>().map(|e| e.found), Some(Some(0)));
+    assert!(indexer::update_index(index, &[], &AppConfig::default()).is_err(), "no updating an old index in place");
+
+    let search = SearchEngine::open_or_rebuild(index, conversations, &AppConfig::default()).await?;
+    assert!(!search.search("грав", 10)?.is_empty(), "Rebuilt index should be searchable");
+    assert_eq!(indexer::schema_version(index), Some(indexer::SCHEMA_VERSION));
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_autocomplete_by_prefix() -> Result<()> {
+    let temp_dir = TempDir::new()?;
+    let index_path = temp_dir.path().join("test_index");
+    let conversations_path = temp_dir.path().join("conversations.json");
+    
+    let test_data = json!([
+        {
+            "id": "1",
+            "title": "Gravity basics",
+            "mapping": {
+                "root": { "children": ["msg1"] },
+                "msg1": {
+                    "message": {
+                        "fragments": [{"type": "text", "content": "Gravity and gravitation explained"}]
+                    },
+                    "children": []
+                }
+            }
+        },
+        {
+            "id": "2",
+            "title": "Graphs",
+            "mapping": {
+                "root": { "children": ["msg2"] },
+                "msg2": {
+                    "message": {
+                        "fragments": [{"type": "text", "content": "Gravity again, with graphs"}]
+                    },
+                    "children": []
+                }
+            }
+        }
+    ]);
+    
+    fs::write(&conversations_path, test_data.to_string())?;
+    indexer::build_index(
+        conversations_path.to_str().unwrap(),
+        index_path.to_str().unwrap()
+    ).await?;
+    
+    let search = SearchEngine::new(index_path.to_str().unwrap())?;
+    
+    // "gravity" is in both conversations, so it ranks first
+    let suggestions = search.autocomplete("Grav", 10)?;
+    assert_eq!(suggestions, vec!["gravity", "gravitation"]);
+    
+    let suggestions = search.autocomplete("gra", 1)?;
+    assert_eq!(suggestions, vec!["gravity"], "Limit should be respected");
+    
+    assert!(search.autocomplete("", 10)?.is_empty(), "Empty prefix has no suggestions");
+    
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_suggest_titles_by_prefix() -> Result<()> {
+    let temp_dir = TempDir::new()?;
+    let index_path = temp_dir.path().join("test_index");
+    let conversations_path = temp_dir.path().join("conversations.json");
+
+    let text = "Рецепт борща и графики";
+    let test_data = json!([
+        conversation("1", "Ремонт квартиры", text),
+        conversation("2", "План ремонта кухни", text),
+        conversation("3", "Ремонт квартиры", text),
+        conversation("4", "Аэрофотосъемка", text),
+        conversation("5", "Графики: matplotlib", text),
+    ]);
+
+    fs::write(&conversations_path, test_data.to_string())?;
+    indexer::build_index(conversations_path.to_str().unwrap(), index_path.to_str().unwrap()).await?;
+    let search = SearchEngine::new(index_path.to_str().unwrap())?;
+
+    // Titles starting with the prefix first, each title once
+    let titles: Vec<String> = search.suggest_titles("Ремон", 10)?.into_iter().map(|s| s.title).collect();
+    assert_eq!(titles, vec!["Ремонт квартиры", "План ремонта кухни"]);
+
+    // Only word starts count, and content isn't looked at
+    assert!(search.suggest_titles("фото", 10)?.is_empty());
+    assert!(search.suggest_titles("борщ", 10)?.is_empty());
+
+    let suggestions = search.suggest_titles("графики:", 10)?;
+    assert_eq!(suggestions.len(), 1);
+    assert_eq!(suggestions[0].conversation_id, "5");
+    assert_eq!(suggestions[0].url, "/conversations/5/");
+
+    assert_eq!(search.suggest_titles("ремонт", 1)?.len(), 1, "Limit should be respected");
+    assert!(search.suggest_titles("", 10)?.is_empty());
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_search_each_stops_early() -> Result<()> {
+    let temp_dir = TempDir::new()?;
+    let index_path = temp_dir.path().join("test_index");
+    let conversations_path = temp_dir.path().join("conversations.json");
+    
+    let conversations: Vec<serde_json::Value> = (1..=5)
+        .map(|i| json!({
+            "id": i.to_string(),
+            "title": format!("Rust note {}", i),
+            "mapping": {}
+        }))
+        .collect();
+    
+    fs::write(&conversations_path, serde_json::Value::from(conversations).to_string())?;
+    indexer::build_index(
+        conversations_path.to_str().unwrap(),
+        index_path.to_str().unwrap()
+    ).await?;
+    
+    let search = SearchEngine::new(index_path.to_str().unwrap())?;
+    
+    let mut seen = Vec::new();
+    let stats = search.search_each("rust", 10, &SearchOptions::default(), |result| {
+        seen.push(result.conversation_id);
+        seen.len() < 2
+    })?;
+    
+    assert_eq!(stats.results, 2, "Returning false should stop the search");
+    assert_eq!(seen.len(), 2);
+    assert_eq!(search.search("rust", 10)?.len(), 5);
+    
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_search_with_stats_reports_segments() -> Result<()> {
+    let temp_dir = TempDir::new()?;
+    let index_path = temp_dir.path().join("test_index");
+    let conversations_path = temp_dir.path().join("conversations.json");
+    
+    let test_data = json!([
+        { "id": "1", "title": "Rust ownership", "mapping": {} },
+        { "id": "2", "title": "Python typing", "mapping": {} }
+    ]);
+    
+    fs::write(&conversations_path, test_data.to_string())?;
+    indexer::build_index(
+        conversations_path.to_str().unwrap(),
+        index_path.to_str().unwrap()
+    ).await?;
+    
+    let search = SearchEngine::new(index_path.to_str().unwrap())?;
+    let (results, stats) = search.search_with_stats("rust", 10, &SearchOptions::default())?;
+    
+    assert_eq!(results.len(), 1);
+    assert_eq!(stats.results, 1);
+    assert!(stats.segments >= 1, "At least one segment should be searched");
+    
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_group_by_conversation() -> Result<()> {
+    let temp_dir = TempDir::new()?;
+    let index_path = temp_dir.path().join("test_index");
+    let conversations_path = temp_dir.path().join("conversations.json");
+    
+    // Five messages mention gravity, one doesn't
+    let mut mapping = serde_json::Map::new();
+    let ids: Vec<String> = (1..=6).map(|i| format!("msg{}", i)).collect();
+    mapping.insert("root".to_string(), json!({ "children": [ids[0]] }));
+    for (i, id) in ids.iter().enumerate() {
+        let content = if i < 5 {
+            format!("Gravity question number {}", i + 1)
+        } else {
+            "Something else entirely".to_string()
+        };
+        mapping.insert(id.clone(), json!({
+            "message": { "fragments": [{"type": "text", "content": content}] },
+            "children": ids.get(i + 1).map(|next| vec![next.clone()]).unwrap_or_default()
+        }));
+    }
+    
+    let test_data = json!([
+        { "id": "1", "title": "Physics", "mapping": mapping },
+        { "id": "2", "title": "Gravity in one line", "mapping": {} },
+        // Merged exports can repeat a conversation
+        { "id": "2", "title": "Gravity in one line", "mapping": {} }
+    ]);
+    
+    fs::write(&conversations_path, test_data.to_string())?;
+    let config = AppConfig { index_messages: true, ..AppConfig::default() };
+    indexer::build_index_with_config(
+        conversations_path.to_str().unwrap(),
+        index_path.to_str().unwrap(),
+        &config
+    ).await?;
+    
+    let search = SearchEngine::with_config(index_path.to_str().unwrap(), &config)?;
+    let grouped = SearchOptions { group_by_conversation: true, ..SearchOptions::default() };
+    
+    let (results, _) = search.search_with_stats("gravity", 10, &grouped)?;
+    assert_eq!(results.len(), 2, "One result per conversation");
+    
+    let physics = results.iter().find(|r| r.conversation_id == "1").unwrap();
+    assert_eq!(physics.match_count, Some(5));
+    
+    let (results, _) = search.search_with_stats("question number", 10, &grouped)?;
+    assert_eq!(results.len(), 1);
+    assert_eq!(results[0].conversation_id, "1");
+    
+    // Ungrouped results repeat the conversation and carry no count
+    let results = search.search("gravity", 10)?;
+    assert_eq!(results.len(), 3);
+    assert!(results.iter().all(|r| r.match_count.is_none()));
+    
+    // Without message documents there is nothing to count
+    fs::remove_dir_all(&index_path)?;
+    indexer::build_index(conversations_path.to_str().unwrap(), index_path.to_str().unwrap()).await?;
+    let search = SearchEngine::new(index_path.to_str().unwrap())?;
+    let (results, _) = search.search_with_stats("gravity", 10, &grouped)?;
+    assert_eq!(results.len(), 2);
+    assert!(results.iter().all(|r| r.match_count.is_none()));
+    
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_normalized_scores() -> Result<()> {
+    let temp_dir = TempDir::new()?;
+    let index_path = temp_dir.path().join("test_index");
+    let conversations_path = temp_dir.path().join("conversations.json");
+    
+    let test_data = json!([
+        { "id": "1", "title": "Rust", "mapping": {} },
+        { "id": "2", "title": "Rust ownership and borrowing in depth", "mapping": {} },
+        { "id": "3", "title": "Python", "mapping": {} }
+    ]);
+    
+    fs::write(&conversations_path, test_data.to_string())?;
+    indexer::build_index(
+        conversations_path.to_str().unwrap(),
+        index_path.to_str().unwrap()
+    ).await?;
+    
+    let search = SearchEngine::new(index_path.to_str().unwrap())?;
+    let options = SearchOptions { normalize_scores: true, ..SearchOptions::default() };
+    let (results, _) = search.search_with_stats("rust", 10, &options)?;
+    
+    assert_eq!(results.len(), 2);
+    assert_eq!(results[0].normalized_score, Some(1.0), "Best hit is 1.0");
+    let second = results[1].normalized_score.unwrap();
+    assert!(second > 0.0 && second < 1.0, "Other hits are relative to the best, got {}", second);
+    assert!((second - results[1].score / results[0].score).abs() < 1e-6);
+    
+    assert!(search.search("rust", 10)?.iter().all(|r| r.normalized_score.is_none()));
+    
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_message_results_link_to_the_message_on_its_page() -> Result<()> {
+    let temp_dir = TempDir::new()?;
+    let index_path = temp_dir.path().join("test_index");
+    let output_dir = temp_dir.path().join("dist");
+    let conversations_path = temp_dir.path().join("conversations.json");
+
+    // The response comes in two fragments, shown as one message
+    let test_data = json!([{
+        "id": "1",
+        "title": "Astronomy",
+        "mapping": {
+            "root": { "children": ["msg0"] },
+            "msg0": { "message": { "fragments": [{ "type": "REQUEST", "content": "Tell me about planets" }] }, "children": ["msg1"] },
+            "msg1": { "message": { "fragments": [
+                { "type": "RESPONSE", "content": "Jupiter is the largest, " },
+                { "type": "RESPONSE", "content": "Mercury the smallest" }
+            ] }, "children": ["msg2"] },
+            "msg2": { "message": { "fragments": [{ "type": "REQUEST", "content": "And Pluto?" }] }, "children": [] }
+        }
+    }]);
+    fs::write(&conversations_path, test_data.to_string())?;
+
+    for config in [
+        AppConfig { messages_per_page: 2, index_messages: true, ..AppConfig::default() },
+        AppConfig { messages_per_page: 2, newest_first: true, index_messages: true, ..AppConfig::default() },
+    ] {
+        indexer::build_index_with_config(conversations_path.to_str().unwrap(), index_path.to_str().unwrap(), &config).await?;
+        generator::generate_site(conversations_path.to_str().unwrap(), output_dir.to_str().unwrap(), &config).await?;
+        let search = SearchEngine::with_config(index_path.to_str().unwrap(), &config)?;
+
+        let mut shown_in = Vec::new();
+        for query in ["planets", "jupiter", "mercury", "pluto"] {
+            let (results, _) = search.search_messages(query, 10, &SearchOptions::default())?;
+            let (path, anchor) = results[0].url.split_once('#').unwrap();
+            let page = path.strip_prefix("/conversations/1/").unwrap();
+            let page = if page.is_empty() { "index.html" } else { page };
+            let html = fs::read_to_string(output_dir.join("conversations/1").join(page))?;
+            assert!(html.contains(&format!(r#"id="{}""#, anchor)), "{} should be on {}", anchor, page);
+            shown_in.push(results[0].url.clone());
+        }
+        assert_eq!(shown_in[1], shown_in[2], "both fragments are in one message");
+        assert_ne!(shown_in[0], shown_in[3]);
+
+        // Pages laid out differently than the links were made for
+        let other_pages = AppConfig { messages_per_page: 3, ..config.clone() };
+        let err = SearchEngine::with_config(index_path.to_str().unwrap(), &other_pages).unwrap_err();
+        assert!(err.downcast_ref::<MessageLinksOutdated>().is_some(), "{}", err);
+        let reopened = SearchEngine::open_or_rebuild(
+            index_path.to_str().unwrap(),
+            conversations_path.to_str().unwrap(),
+            &other_pages,
+        ).await?;
+        assert!(reopened.indexes_messages());
+        fs::remove_dir_all(&index_path)?;
+    }
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_search_messages() -> Result<()> {
+    let temp_dir = TempDir::new()?;
+    let index_path = temp_dir.path().join("test_index");
+    let conversations_path = temp_dir.path().join("conversations.json");
+    
+    let messages = ["Tell me about planets", "Jupiter is the largest planet", "And the smallest?", "Mercury"];
+    let mut mapping = serde_json::Map::new();
+    mapping.insert("root".to_string(), json!({ "children": ["msg0"] }));
+    for (i, content) in messages.iter().enumerate() {
+        mapping.insert(format!("msg{}", i), json!({
+            "message": { "fragments": [{"type": "text", "content": content}] },
+            "children": if i + 1 < messages.len() { vec![format!("msg{}", i + 1)] } else { vec![] }
+        }));
+    }
+    
+    let test_data = json!([
+        { "id": "1", "title": "Astronomy", "inserted_at": "2024-03-01T10:00:00Z", "mapping": mapping },
+        { "id": "2", "title": "Jupiter notebooks", "mapping": {} }
+    ]);
+    
+    fs::write(&conversations_path, test_data.to_string())?;
+    let config = AppConfig { index_messages: true, ..AppConfig::default() };
+    indexer::build_index_with_config(
+        conversations_path.to_str().unwrap(),
+        index_path.to_str().unwrap(),
+        &config
+    ).await?;
+    
+    let search = SearchEngine::with_config(index_path.to_str().unwrap(), &config)?;
+    let (results, stats) = search.search_messages("jupiter", 10, &SearchOptions::default())?;
+    
+    // Only messages match, not the other conversation's title
+    assert_eq!(results.len(), 1);
+    assert_eq!(stats.total_hits, 1);
+    let hit = &results[0];
+    assert_eq!(hit.conversation_id, "1");
+    assert_eq!(hit.message_index, Some(1));
+    assert_eq!(hit.title, "Astronomy");
+    assert!(hit.url.starts_with("/conversations/1/#m-"), "links to the message: {}", hit.url);
+    assert_eq!(hit.date, "2024-03-01T10:00:00Z");
+    assert_eq!(hit.snippet, "Jupiter is the largest planet");
+    assert!(hit.snippet_html.contains("<mark>"), "{}", hit.snippet_html);
+    
+    let (results, stats) = search.search_messages("planet", 10, &SearchOptions::default())?;
+    let mut indexes: Vec<_> = results.iter().map(|r| r.message_index).collect();
+    indexes.sort();
+    assert_eq!(indexes, vec![Some(0), Some(1)]);
+    assert_eq!(stats.total_hits, 2);
+    
+    let second_page = SearchOptions { offset: 1, normalize_scores: true, ..SearchOptions::default() };
+    let (page, _) = search.search_messages("planet", 10, &second_page)?;
+    assert_eq!(page.len(), 1);
+    assert_eq!(page[0].message_index, results[1].message_index);
+    assert!(page[0].normalized_score.is_some());
+    
+    // Conversation search is unchanged and says nothing about messages
+    let results = search.search("jupiter", 10)?;
+    assert_eq!(results.len(), 2);
+    assert!(results.iter().all(|r| r.message_index.is_none()));
+    
+    // Messages get documents of their own only when asked for
+    fs::remove_dir_all(&index_path)?;
+    indexer::build_index(conversations_path.to_str().unwrap(), index_path.to_str().unwrap()).await?;
+    let search = SearchEngine::new(index_path.to_str().unwrap())?;
+    assert!(!search.indexes_messages());
+    assert!(search.search_messages("jupiter", 10, &SearchOptions::default()).is_err());
+    assert_eq!(search.num_docs()?, 2);
+    
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_filter_by_role() -> Result<()> {
+    let temp_dir = TempDir::new()?;
+    let index_path = temp_dir.path().join("test_index");
+    let conversations_path = temp_dir.path().join("conversations.json");
+    
+    let test_data = json!([
+        { "id": "asked", "title": "Borrowing", "mapping": {
+            "root": { "children": ["q"] },
+            "q": { "message": { "fragments": [{"type": "REQUEST", "content": "How do lifetimes work?"}] }, "children": ["a"] },
+            "a": { "message": { "fragments": [{"type": "RESPONSE", "content": "They tie references to scopes."}] }, "children": [] }
+        }},
+        { "id": "answered", "title": "Lifetimes", "mapping": {
+            "root": { "children": ["q"] },
+            "q": { "message": { "fragments": [{"type": "REQUEST", "content": "Why won't this compile?"}] }, "children": ["a"] },
+            "a": { "message": { "fragments": [{"type": "RESPONSE", "content": "The lifetimes of the two references differ."}] }, "children": [] }
+        }}
+    ]);
+    
+    fs::write(&conversations_path, test_data.to_string())?;
+    let config = AppConfig { index_messages: true, ..AppConfig::default() };
+    indexer::build_index_with_config(
+        conversations_path.to_str().unwrap(),
+        index_path.to_str().unwrap(),
+        &config
+    ).await?;
+    
+    let search = SearchEngine::with_config(index_path.to_str().unwrap(), &config)?;
+    let ids = |options: &SearchOptions| -> Result<Vec<String>> {
+        let (results, _) = search.search_with_stats("lifetimes", 10, options)?;
+        let mut ids: Vec<_> = results.into_iter().map(|r| r.conversation_id).collect();
+        ids.sort();
+        Ok(ids)
+    };
+    
+    assert_eq!(ids(&SearchOptions::default())?, vec!["answered", "asked"]);
+    let user = SearchOptions { role: RoleFilter::User, ..SearchOptions::default() };
+    assert_eq!(ids(&user)?, vec!["asked"]);
+    // Titles don't count as anyone's message
+    let assistant = SearchOptions { role: RoleFilter::Assistant, ..SearchOptions::default() };
+    assert_eq!(ids(&assistant)?, vec!["answered"]);
+    
+    // Grouped, only that side's messages are counted
+    let grouped = SearchOptions { group_by_conversation: true, ..user };
+    let (results, _) = search.search_with_stats("lifetimes", 10, &grouped)?;
+    assert_eq!(results.len(), 1);
+    assert_eq!(results[0].match_count, Some(1));
+    
+    let (results, _) = search.search_messages("lifetimes", 10, &assistant)?;
+    assert_eq!(results.len(), 1);
+    assert_eq!(results[0].conversation_id, "answered");
+    assert_eq!(results[0].message_index, Some(1));
+    
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_search_paged() -> Result<()> {
+    let temp_dir = TempDir::new()?;
+    let index_path = temp_dir.path().join("test_index");
+    let conversations_path = temp_dir.path().join("conversations.json");
+    
+    let test_data: Vec<_> = (1..=5)
+        .map(|i| json!({ "id": i.to_string(), "title": format!("Rust part {}", i), "mapping": {} }))
+        .chain([json!({ "id": "6", "title": "Python", "mapping": {} })])
+        .collect();
+    
+    fs::write(&conversations_path, serde_json::to_string(&test_data)?)?;
+    indexer::build_index(
+        conversations_path.to_str().unwrap(),
+        index_path.to_str().unwrap()
+    ).await?;
+    
+    let search = SearchEngine::new(index_path.to_str().unwrap())?;
+    let all = search.search("rust", 10)?;
+    assert_eq!(all.len(), 5);
+    
+    let (first, total_hits) = search.search_paged("rust", 2, 0)?;
+    assert_eq!(total_hits, 5, "Counts every match, not just the page");
+    let (second, _) = search.search_paged("rust", 2, 2)?;
+    let (last, _) = search.search_paged("rust", 2, 4)?;
+    let (past_end, total_hits) = search.search_paged("rust", 2, 10)?;
+    assert!(past_end.is_empty());
+    assert_eq!(total_hits, 5);
+    
+    let paged: Vec<_> = first.iter().chain(&second).chain(&last).map(|r| &r.conversation_id).collect();
+    let unpaged: Vec<_> = all.iter().map(|r| &r.conversation_id).collect();
+    assert_eq!(paged, unpaged);
+    
+    // Grouping pages through conversations
+    let grouped = SearchOptions { group_by_conversation: true, offset: 3, ..SearchOptions::default() };
+    let (results, stats) = search.search_with_stats("rust", 10, &grouped)?;
+    let ids: Vec<_> = results.iter().map(|r| &r.conversation_id).collect();
+    assert_eq!(ids, unpaged[3..]);
+    assert_eq!(stats.total_hits, 5);
+    
+    // Tantivy would try to allocate room for all of them and abort
+    let huge = SearchOptions { offset: 100_000_000_000, ..SearchOptions::default() };
+    assert!(search.search_with_stats("rust", 10, &huge).is_err());
+    assert!(search.search_messages("rust", 10, &huge).is_err());
+    let grouped = SearchOptions { group_by_conversation: true, ..huge };
+    assert!(search.search_with_stats("rust", 10, &grouped).is_err());
+    
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_filter_by_message_count() -> Result<()> {
+    let temp_dir = TempDir::new()?;
+    let index_path = temp_dir.path().join("test_index");
+    let conversations_path = temp_dir.path().join("conversations.json");
+    
+    // A conversation about Rust with `count` messages
+    let conversation = |id: &str, count: usize| {
+        let ids: Vec<String> = (1..=count).map(|i| format!("msg{}", i)).collect();
+        let mut mapping = serde_json::Map::new();
+        mapping.insert("root".to_string(), json!({ "children": ids.first().into_iter().collect::<Vec<_>>() }));
+        for (i, msg_id) in ids.iter().enumerate() {
+            mapping.insert(msg_id.clone(), json!({
+                "message": { "fragments": [{"type": "text", "content": format!("Rust message {}", i + 1)}] },
+                "children": ids.get(i + 1).map(|next| vec![next.clone()]).unwrap_or_default()
+            }));
+        }
+        json!({ "id": id, "title": "Rust", "mapping": mapping })
+    };
+    let test_data = json!([conversation("short", 1), conversation("medium", 4), conversation("long", 10)]);
+    
+    fs::write(&conversations_path, test_data.to_string())?;
+    indexer::build_index(
+        conversations_path.to_str().unwrap(),
+        index_path.to_str().unwrap()
+    ).await?;
+    
+    let search = SearchEngine::new(index_path.to_str().unwrap())?;
+    let ids = |options: SearchOptions| -> Result<Vec<String>> {
+        let (results, _) = search.search_with_stats("rust", 10, &options)?;
+        let mut ids: Vec<_> = results.into_iter().map(|r| r.conversation_id).collect();
+        ids.sort();
+        Ok(ids)
+    };
+    
+    assert_eq!(ids(SearchOptions::default())?, ["long", "medium", "short"]);
+    assert_eq!(ids(SearchOptions { min_messages: Some(4), ..SearchOptions::default() })?, ["long", "medium"]);
+    assert_eq!(ids(SearchOptions { max_messages: Some(4), ..SearchOptions::default() })?, ["medium", "short"]);
+    assert_eq!(
+        ids(SearchOptions { min_messages: Some(2), max_messages: Some(9), ..SearchOptions::default() })?,
+        ["medium"]
+    );
+    assert!(ids(SearchOptions { min_messages: Some(11), ..SearchOptions::default() })?.is_empty());
+    
+    // Grouping filters the same way
+    let grouped = SearchOptions { group_by_conversation: true, min_messages: Some(5), ..SearchOptions::default() };
+    assert_eq!(ids(grouped)?, ["long"]);
+    
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_filter_by_date() -> Result<()> {
+    let temp_dir = TempDir::new()?;
+    let index_path = temp_dir.path().join("test_index");
+    let conversations_path = temp_dir.path().join("conversations.json");
+    
+    let test_data = json!([
+        { "id": "jan", "title": "Rust", "inserted_at": "2024-01-15T10:00:00Z", "mapping": {} },
+        { "id": "feb", "title": "Rust", "inserted_at": "2024-02-20T23:30:00+03:00", "mapping": {} },
+        { "id": "apr", "title": "Rust", "inserted_at": "2024-04-01T00:00:00Z", "mapping": {} },
+        { "id": "undated", "title": "Rust", "mapping": {} }
+    ]);
+    
+    fs::write(&conversations_path, test_data.to_string())?;
+    indexer::build_index(
+        conversations_path.to_str().unwrap(),
+        index_path.to_str().unwrap()
+    ).await?;
+    
+    let search = SearchEngine::new(index_path.to_str().unwrap())?;
+    let date = |s: &str| Some(chrono::DateTime::parse_from_rfc3339(s).unwrap().with_timezone(&chrono::Utc));
+    let ids = |options: SearchOptions| -> Result<Vec<String>> {
+        let (results, _) = search.search_with_stats("rust", 10, &options)?;
+        let mut ids: Vec<_> = results.into_iter().map(|r| r.conversation_id).collect();
+        ids.sort();
+        Ok(ids)
+    };
+    
+    assert_eq!(ids(SearchOptions::default())?.len(), 4);
+    let window = SearchOptions {
+        from: date("2024-01-01T00:00:00Z"),
+        to: date("2024-03-01T00:00:00Z"),
+        ..SearchOptions::default()
+    };
+    assert_eq!(ids(window)?, ["feb", "jan"]);
+    assert_eq!(ids(SearchOptions { from: date("2024-02-20T20:30:00Z"), ..SearchOptions::default() })?, ["apr", "feb"]);
+    // Both bounds are inclusive
+    assert_eq!(ids(SearchOptions { to: date("2024-04-01T00:00:00Z"), ..SearchOptions::default() })?, ["apr", "feb", "jan"]);
+    
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_empty_query_lists_recent_conversations() -> Result<()> {
+    let temp_dir = TempDir::new()?;
+    let index_path = temp_dir.path().join("test_index");
+    let conversations_path = temp_dir.path().join("conversations.json");
+    
+    let message = json!({
+        "root": { "children": ["msg1"] },
+        "msg1": { "message": { "fragments": [{"type": "text", "content": "Hello"}] }, "children": [] }
+    });
+    let test_data = json!([
+        { "id": "old", "title": "Old", "inserted_at": "2023-05-01T10:00:00Z", "mapping": message },
+        { "id": "new", "title": "New", "inserted_at": "2024-05-01T10:00:00Z", "mapping": message },
+        { "id": "undated", "title": "Undated", "mapping": {} },
+        { "id": "middle", "title": "Middle", "inserted_at": "2024-01-01T10:00:00Z", "mapping": message }
+    ]);
+    
+    fs::write(&conversations_path, test_data.to_string())?;
+    indexer::build_index(
+        conversations_path.to_str().unwrap(),
+        index_path.to_str().unwrap()
+    ).await?;
+    
+    let search = SearchEngine::new(index_path.to_str().unwrap())?;
+    let ids = |results: Vec<deepseek_app::search::SearchResult>| -> Vec<String> {
+        results.into_iter().map(|r| r.conversation_id).collect()
+    };
+    
+    // Conversations only, not their messages, newest first
+    assert_eq!(ids(search.search("", 10)?), ["new", "middle", "old", "undated"]);
+    assert_eq!(ids(search.search("  ", 2)?), ["new", "middle"]);
+    
+    let (results, total_hits) = search.search_paged("", 2, 1)?;
+    assert_eq!(ids(results), ["middle", "old"]);
+    assert_eq!(total_hits, 4);
+    
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_quoted_phrase_matches_exactly() -> Result<()> {
+    let temp_dir = TempDir::new()?;
+    let index_path = temp_dir.path().join("test_index");
+    let conversations_path = temp_dir.path().join("conversations.json");
+    
+    let test_data = json!([
+        conversation("phrase", "Team", "Our Code Review checklist for new hires"),
+        conversation("scattered", "Team", "Please review the code before merging"),
+        conversation("other", "Team", "Code reviewers wanted"),
+    ]);
+    
+    fs::write(&conversations_path, test_data.to_string())?;
+    indexer::build_index(
+        conversations_path.to_str().unwrap(),
+        index_path.to_str().unwrap()
+    ).await?;
+    
+    let search = SearchEngine::new(index_path.to_str().unwrap())?;
+    let ids = |query: &str| -> Result<Vec<String>> {
+        let mut ids: Vec<_> = search.search(query, 10)?.into_iter().map(|r| r.conversation_id).collect();
+        ids.sort();
+        Ok(ids)
+    };
+    
+    assert_eq!(ids("code review")?, ["other", "phrase", "scattered"]);
+    assert_eq!(ids("\"code review\"")?, ["phrase"], "Whole words, in order");
+    assert_eq!(ids("\"review the code\" merg")?, ["scattered"], "Bare terms still match substrings");
+    assert_eq!(ids("\"code review\" merg")?, Vec::<String>::new());
+    
+    let results = search.search("\"code review\"", 10)?;
+    assert!(results[0].snippet_html.contains("<mark>Code</mark> <mark>Review</mark>"), "{}", results[0].snippet_html);
+    
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_title_boost_reorders_results() -> Result<()> {
+    let temp_dir = TempDir::new()?;
+    let index_path = temp_dir.path().join("test_index");
+    let conversations_path = temp_dir.path().join("conversations.json");
+    
+    let test_data = json!([
+        conversation("title", "Kotlin", "A short note"),
+        conversation("content", "Misc", "Kotlin kotlin kotlin coroutines"),
+    ]);
+    
+    fs::write(&conversations_path, test_data.to_string())?;
+    indexer::build_index(
+        conversations_path.to_str().unwrap(),
+        index_path.to_str().unwrap()
+    ).await?;
+    let index_path = index_path.to_str().unwrap();
+    
+    let ranking = |title_boost: f32| -> Result<Vec<String>> {
+        let config = AppConfig { search_title_boost: title_boost, ..AppConfig::default() };
+        let search = SearchEngine::with_config(index_path, &config)?;
+        Ok(search.search("kotlin", 10)?.into_iter().map(|r| r.conversation_id).collect())
+    };
+    
+    assert_eq!(ranking(0.1)?, vec!["content", "title"], "Repeated content wins with a weak title boost");
+    assert_eq!(ranking(10.0)?, vec!["title", "content"], "A strong title boost favours the title match");
+    
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_search_attachment_names() -> Result<()> {
+    let temp_dir = TempDir::new()?;
+    let index_path = temp_dir.path().join("test_index");
+    
+    indexer::build_index(
+        "tests/fixtures/attachment_export.json",
+        index_path.to_str().unwrap()
+    ).await?;
+    
+    let search = SearchEngine::new(index_path.to_str().unwrap())?;
+    
+    let results = search.search("attachment:lease", 10)?;
+    assert_eq!(results.len(), 1);
+    assert_eq!(results[0].conversation_id, "conv-attachment");
+    
+    let results = search.search("attachment:scan.png", 10)?;
+    assert_eq!(results.len(), 1, "Image names are indexed too");
+    
+    let results = search.search("attachment:арендодателя", 10)?;
+    assert_eq!(results.len(), 1, "Alt text is indexed too");
+    
+    // Without the prefix attachments don't match
+    assert!(search.search("lease", 10)?.is_empty());
+    
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_index_chars_cap_truncates_content() -> Result<()> {
+    let temp_dir = TempDir::new()?;
+    let conversations_path = temp_dir.path().join("conversations.json");
+    
+    // Huge conversation with a distinctive word at each end
+    let content = format!("Прелюдия {} финальныйаккорд", "текст ".repeat(50_000));
+    let test_data = json!([conversation("1", "Огромный разговор", &content)]);
+    fs::write(&conversations_path, test_data.to_string())?;
+    
+    let capped_path = temp_dir.path().join("capped");
+    let config = AppConfig { max_index_chars_per_conversation: 1_000, ..AppConfig::default() };
+    indexer::build_index_with_config(
+        conversations_path.to_str().unwrap(),
+        capped_path.to_str().unwrap(),
+        &config
+    ).await?;
+    
+    let full_path = temp_dir.path().join("full");
+    indexer::build_index(
+        conversations_path.to_str().unwrap(),
+        full_path.to_str().unwrap()
+    ).await?;
+    
+    let capped = SearchEngine::new(capped_path.to_str().unwrap())?;
+    assert_eq!(capped.search("прелюдия", 10)?.len(), 1, "The start is still indexed");
+    assert!(capped.search("финальныйаккорд", 10)?.is_empty(), "Text past the cap is not indexed");
+    
+    let full = SearchEngine::new(full_path.to_str().unwrap())?;
+    assert_eq!(full.search("финальныйаккорд", 10)?.len(), 1, "No cap by default");
+    
+    let dir_size = |path: &std::path::Path| -> Result<u64> {
+        let mut size = 0;
+        for entry in fs::read_dir(path)? {
+            size += entry?.metadata()?.len();
+        }
+        Ok(size)
+    };
+    assert!(dir_size(&capped_path)? < dir_size(&full_path)?);
+    
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_reindex_range_leaves_other_conversations_untouched() -> Result<()> {
+    let temp_dir = TempDir::new()?;
+    let conversations_path = temp_dir.path().join("conversations.json");
+    let index_path = temp_dir.path().join("index");
+    let index_path = index_path.to_str().unwrap();
+    
+    let conversation = |id: &str, inserted_at: &str, text: &str| {
+        conversation_at(id, &format!("Разговор {}", id), inserted_at, text)
+    };
+    
+    fs::write(&conversations_path, json!([
+        conversation("jan", "2024-01-15T10:00:00Z", "первоначальныйянварь"),
+        conversation("feb", "2024-02-15T10:00:00Z", "первоначальныйфевраль"),
+        conversation("feb-deleted", "2024-02-20T10:00:00Z", "удалённыйразговор"),
+        conversation("mar", "2024-03-15T10:00:00Z", "первоначальныймарт"),
+    ]).to_string())?;
+    indexer::build_index(conversations_path.to_str().unwrap(), index_path).await?;
+    
+    // Every conversation changed, one was deleted
+    fs::write(&conversations_path, json!([
+        conversation("jan", "2024-01-15T10:00:00Z", "обновлённыйянварь"),
+        conversation("feb", "2024-02-15T10:00:00Z", "обновлённыйфевраль"),
+        conversation("mar", "2024-03-15T10:00:00Z", "обновлённыймарт"),
+    ]).to_string())?;
+    
+    let from = chrono::NaiveDate::from_ymd_opt(2024, 2, 1);
+    let to = chrono::NaiveDate::from_ymd_opt(2024, 2, 29);
+    let reindexed = indexer::reindex_range(conversations_path.to_str().unwrap(), index_path, from, to).await?;
+    assert_eq!(reindexed, 1);
+    
+    let search = SearchEngine::new(index_path)?;
+    let ids = |query: &str| -> Result<Vec<String>> {
+        Ok(search.search(query, 10)?.into_iter().map(|r| r.conversation_id).collect())
+    };
+    
+    // In the range: refreshed, the deleted one is gone
+    assert_eq!(ids("обновлённыйфевраль")?, vec!["feb"]);
+    assert!(ids("первоначальныйфевраль")?.is_empty());
+    assert!(ids("удалённыйразговор")?.is_empty());
+    
+    // Outside the range: exactly as indexed before
+    assert_eq!(ids("первоначальныйянварь")?, vec!["jan"]);
+    assert_eq!(ids("первоначальныймарт")?, vec!["mar"]);
+    assert!(ids("обновлённыйянварь")?.is_empty());
+    assert!(ids("обновлённыймарт")?.is_empty());
+    
+    // Each conversation is indexed once
+    assert_eq!(search.search("разговор", 10)?.len(), 3);
+    
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_verify_finds_missing_and_orphaned_conversations() -> Result<()> {
+    let temp_dir = TempDir::new()?;
+    let index_path = temp_dir.path().join("test_index");
+    let index = index_path.to_str().unwrap();
+    let conversations_path = temp_dir.path().join("conversations.json");
+    let conversations = conversations_path.to_str().unwrap();
+
+    let conversation = |id: &str, inserted_at: &str| conversation_at(id, &format!("Беседа {}", id), inserted_at, "Текст");
+
+    fs::write(&conversations_path, json!([
+        conversation("old", "2024-01-01T00:00:00Z"),
+        conversation("kept", "2024-02-01T00:00:00Z"),
+    ]).to_string())?;
+    indexer::build_index(conversations, index).await?;
+
+    let report = indexer::verify(conversations, index).await?;
+    assert!(report.is_consistent());
+    assert_eq!((report.source_conversations, report.indexed_conversations), (2, 2));
+
+    // The export moved on without the index
+    fs::write(&conversations_path, json!([
+        conversation("kept", "2024-02-01T00:00:00Z"),
+        conversation("new", "2024-03-01T00:00:00Z"),
+    ]).to_string())?;
+    let report = indexer::verify(conversations, index).await?;
+    assert!(!report.is_consistent());
+    assert_eq!(report.missing, vec!["new"]);
+    assert_eq!(report.orphaned, vec!["old"]);
+
+    // Deleted documents don't count, even before their segment is merged
+    let jan = chrono::NaiveDate::from_ymd_opt(2024, 1, 1);
+    let mar = chrono::NaiveDate::from_ymd_opt(2024, 3, 31);
+    indexer::reindex_range(conversations, index, jan, mar).await?;
+    let report = indexer::verify(conversations, index).await?;
+    assert!(report.is_consistent(), "{:?}", report);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_ngram_prefix_only_matches_only_field_starts() -> Result<()> {
+    let temp_dir = TempDir::new()?;
+    let conversations_path = temp_dir.path().join("conversations.json");
+    let conversations = conversations_path.to_str().unwrap();
+
+    let content = format!("Гравитация притягивает тела. {}", "Масса искривляет пространство и время. ".repeat(200));
+    fs::write(&conversations_path, json!([conversation("1", "Гравитация", &content)]).to_string())?;
+
+    let full_path = temp_dir.path().join("full");
+    indexer::build_index(conversations, full_path.to_str().unwrap()).await?;
+    let prefix_path = temp_dir.path().join("prefix");
+    let config = AppConfig { ngram_prefix_only: true, ..AppConfig::default() };
+    indexer::build_index_with_config(conversations, prefix_path.to_str().unwrap(), &config).await?;
+    assert!(!indexer::ngram_prefix_only(full_path.to_str().unwrap()));
+    assert!(indexer::ngram_prefix_only(prefix_path.to_str().unwrap()), "the mode is recorded with the index");
+
+    // Opened with the default config: the index decides how queries are tokenized
+    let full = SearchEngine::new(full_path.to_str().unwrap())?;
+    let prefix = SearchEngine::new(prefix_path.to_str().unwrap())?;
+
+    assert_eq!(full.search("грав", 10)?.len(), 1);
+    assert_eq!(prefix.search("грав", 10)?.len(), 1, "the start of a title still matches");
+    assert_eq!(full.search("витац", 10)?.len(), 1);
+    assert!(prefix.search("витац", 10)?.is_empty(), "substrings elsewhere don't");
+    assert!(!full.search("пространство", 10)?.is_empty());
+    assert!(prefix.search("пространство", 10)?.is_empty());
+
+    let size = |path: &std::path::Path| -> Result<u64> {
+        let mut total = 0;
+        for entry in fs::read_dir(path)? {
+            total += entry?.metadata()?.len();
+        }
+        Ok(total)
+    };
+    assert!(size(&prefix_path)? < size(&full_path)?, "the prefix-only index is smaller");
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_ngram_sizes_must_match_the_index() -> Result<()> {
+    let temp_dir = TempDir::new()?;
+    let conversations_path = temp_dir.path().join("conversations.json");
+    let conversations = conversations_path.to_str().unwrap();
+    fs::write(&conversations_path, json!([
+        { "id": "1", "title": "Гравитация", "mapping": {} }
+    ]).to_string())?;
+
+    let index_path = temp_dir.path().join("index");
+    let index = index_path.to_str().unwrap();
+    let config = AppConfig { ngram_min: 3, ngram_max: 6, ..AppConfig::default() };
+    indexer::build_index_with_config(conversations, index, &config).await?;
+    assert_eq!(indexer::ngram_sizes(index), (3, 6), "the sizes are recorded with the index");
+    let info = indexer::index_info(index)?;
+    assert_eq!((info.ngram_min, info.ngram_max), (3, 6));
+
+    let search = SearchEngine::with_config(index, &config)?;
+    assert_eq!(search.search("грав", 10)?.len(), 1);
+    assert_eq!(search.search("гравитация", 10)?.len(), 1, "longer than any ngram");
+    assert!(search.search("гр", 10)?.is_empty(), "shorter than any ngram");
+
+    // Other sizes would tokenize queries differently: a clear error, not zero results
+    let Err(err) = SearchEngine::new(index) else {
+        panic!("opened an index built with other ngram sizes");
+    };
+    let mismatch = err.downcast_ref::<indexer::NgramMismatch>().expect("an NgramMismatch");
+    assert_eq!((mismatch.index, mismatch.config), ((3, 6), (2, 10)));
+
+    // ...which the server fixes by rebuilding
+    let search = SearchEngine::open_or_rebuild(index, conversations, &AppConfig::default()).await?;
+    assert_eq!(indexer::ngram_sizes(index), (2, 10));
+    assert_eq!(search.search("гр", 10)?.len(), 1);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_whole_word_index_finds_words_not_substrings() -> Result<()> {
+    let temp_dir = TempDir::new()?;
+    let conversations_path = temp_dir.path().join("conversations.json");
+    let conversations = conversations_path.to_str().unwrap();
+    fs::write(&conversations_path, json!([
+        {
+            "id": "1",
+            "title": "Закон всемирного тяготения",
+            "mapping": {
+                "root": { "children": ["msg1"] },
+                "msg1": {
+                    "message": { "fragments": [{"type": "text", "content": "Гравитация притягивает тела друг к другу."}] },
+                    "children": []
+                }
+            }
+        },
+        {
+            "id": "2",
+            "title": "Рецепт борща",
+            "mapping": {
+                "root": { "children": ["msg1"] },
+                "msg1": {
+                    "message": { "fragments": [{"type": "text", "content": "Тела свёклы режут соломкой, потом притягивает запах."}] },
+                    "children": []
+                }
+            }
+        }
+    ]).to_string())?;
+
+    let index_path = temp_dir.path().join("words");
+    let index = index_path.to_str().unwrap();
+    let config = AppConfig { index_tokenizer: IndexTokenizer::Words, ..AppConfig::default() };
+    indexer::build_index_with_config(conversations, index, &config).await?;
+    assert_eq!(indexer::index_tokenizer(index), IndexTokenizer::Words, "the mode is recorded with the index");
+    assert_eq!(indexer::index_info(index)?.tokenizer, IndexTokenizer::Words);
+
+    // Opened with the default config: the index decides how queries are matched
+    let search = SearchEngine::new(index)?;
+    let ids = |query: &str| -> Result<Vec<String>> {
+        let mut ids: Vec<String> = search.search(query, 10)?.into_iter().map(|r| r.conversation_id).collect();
+        ids.sort();
+        Ok(ids)
+    };
+
+    assert_eq!(ids("гравитация")?, vec!["1"]);
+    assert_eq!(ids("Тяготения")?, vec!["1"], "case doesn't matter");
+    assert!(ids("грав")?.is_empty(), "no prefixes");
+    assert!(ids("витац")?.is_empty(), "no substrings");
+    // Every word must match, and quotes match a phrase
+    assert_eq!(ids("притягивает тела")?, vec!["1", "2"]);
+    assert_eq!(ids("притягивает гравитация")?, vec!["1"]);
+    assert_eq!(ids("\"притягивает тела\"")?, vec!["1"]);
+    // Words longer than any ngram are marked whole
+    let results = search.search("притягивает", 10)?;
+    assert!(results.iter().all(|r| r.snippet_html.contains("<mark>притягивает</mark>")), "{:?}", results);
+
+    // Title suggestions still complete the word being typed
+    let titles: Vec<String> = search.suggest_titles("закон всемир", 10)?.into_iter().map(|s| s.title).collect();
+    assert_eq!(titles, vec!["Закон всемирного тяготения"]);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_whole_word_matches_rank_above_longer_words() -> Result<()> {
+    let temp_dir = TempDir::new()?;
+    let conversations_path = temp_dir.path().join("conversations.json");
+    let conversations = conversations_path.to_str().unwrap();
+    fs::write(&conversations_path, json!([
+        conversation("cat", "Заметки", "Кот спит на диване весь день."),
+        conversation("cutlets", "Ужин", "Котлеты жарим, котлеты переворачиваем, котлеты подаём с котлетным соусом."),
+        conversation("other", "Погода", "Завтра дождь."),
+    ]).to_string())?;
+
+    let index_path = temp_dir.path().join("index");
+    let index = index_path.to_str().unwrap();
+    indexer::build_index(conversations, index).await?;
+    let ids = |config: &AppConfig| -> Result<Vec<String>> {
+        let search = SearchEngine::with_config(index, config)?;
+        Ok(search.search("кот", 10)?.into_iter().map(|r| r.conversation_id).collect())
+    };
+
+    // Ngrams alone: the conversation full of "котлеты" wins on ngram counts
+    let ngram_only = ids(&AppConfig { search_word_boost: 0.0, ..AppConfig::default() })?;
+    assert_eq!(ngram_only, vec!["cutlets", "cat"]);
+
+    // The whole word ranks first, and substrings are still found
+    let combined = ids(&AppConfig::default())?;
+    assert_eq!(combined, vec!["cat", "cutlets"]);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_index_info_reports_documents_and_size() -> Result<()> {
+    let temp_dir = TempDir::new()?;
+    let index_path = temp_dir.path().join("test_index");
+    let index = index_path.to_str().unwrap();
+
+    indexer::build_index("tests/fixtures/attachment_export.json", index).await?;
+    let info = indexer::index_info(index)?;
+
+    assert_eq!(info.conversations, 2);
+    assert_eq!(info.documents, 2, "one document per conversation");
+    assert!(!info.index_messages);
+    assert_eq!(info.deleted_documents, 0);
+    assert!(info.segments >= 1);
+    assert!(info.files > 0 && info.size_bytes > 0);
+    assert_eq!(info.schema_version, Some(indexer::SCHEMA_VERSION));
+    assert!(!info.ngram_prefix_only);
+
+    let json = serde_json::to_value(&info)?;
+    assert_eq!(json["conversations"], 2);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_snippet_is_centered_on_the_match() -> Result<()> {
+    let temp_dir = TempDir::new()?;
+    let index_path = temp_dir.path().join("test_index");
+    let conversations_path = temp_dir.path().join("conversations.json");
+
+    let test_data = json!([
+        conversation("middle", "Середина", &("слово ".repeat(200) + "ГРАВИТАЦИЯ" + &" слово".repeat(200))),
+        conversation("start", "Начало", &("Про телескоп: ".to_string() + &"слово ".repeat(200))),
+        conversation("end", "Конец", &("слово ".repeat(200) + "орбита")),
+    ]);
+    fs::write(&conversations_path, test_data.to_string())?;
+    indexer::build_index(conversations_path.to_str().unwrap(), index_path.to_str().unwrap()).await?;
+    let search = SearchEngine::new(index_path.to_str().unwrap())?;
+
+    // About 100 chars on either side, any case, found by the start of a word
+    let snippet = &search.search("гравит", 10)?[0].snippet;
+    assert!(snippet.starts_with("...") && snippet.ends_with("..."), "{}", snippet);
+    let before = snippet.find("ГРАВИТАЦИЯ").expect("the match is in the snippet");
+    assert_eq!(snippet[..before].chars().count(), 3 + 100);
+
+    // Near the start: the leading window, as before
+    let snippet = &search.search("телескоп", 10)?[0].snippet;
+    assert!(snippet.starts_with("Про телескоп:") && snippet.ends_with("..."), "{}", snippet);
+    assert_eq!(snippet.chars().count(), 200 + 3);
+
+    // Near the end: still a full window, ending at the end
+    let snippet = &search.search("орбита", 10)?[0].snippet;
+    assert!(snippet.starts_with("...") && snippet.trim_end().ends_with("орбита"), "{}", snippet);
+    assert_eq!(snippet.chars().count(), 3 + 200);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_snippet_html_marks_matches() -> Result<()> {
+    let temp_dir = TempDir::new()?;
+    let conversations_path = temp_dir.path().join("conversations.json");
+    let test_data = json!([{
+        "id": "1",
+        "title": "Физика",
+        "inserted_at": "2024-01-01T00:00:00Z",
+        "mapping": {
+            "root": { "children": ["msg1"] },
+            "msg1": {
+                "message": { "fragments": [{
+                    "type": "RESPONSE",
+                    "content": "Правда ли, что <b>Гравитация</b> — это 🚀 ГРАВИТАЦИЯ?"
+                }] },
+                "children": []
+            }
+        }
+    }]);
+    fs::write(&conversations_path, test_data.to_string())?;
+
+    for tokenizer in [IndexTokenizer::Ngram, IndexTokenizer::Words] {
+        let index_path = temp_dir.path().join(format!("{:?}", tokenizer));
+        let config = AppConfig { index_tokenizer: tokenizer, ..AppConfig::default() };
+        indexer::build_index_with_config(conversations_path.to_str().unwrap(), index_path.to_str().unwrap(), &config).await?;
+        let search = SearchEngine::with_config(index_path.to_str().unwrap(), &config)?;
+
+        let query = if tokenizer == IndexTokenizer::Ngram { "грав" } else { "гравитация" };
+        let results = search.search(query, 10)?;
+        let html = &results[0].snippet_html;
+        // Plain text stays as it was; the HTML is escaped, and only the query is marked,
+        // not the "ра" and "ав" it shares with "Правда"
+        assert_eq!(results[0].snippet, "Правда ли, что <b>Гравитация</b> — это 🚀 ГРАВИТАЦИЯ? ");
+        let expected = match tokenizer {
+            IndexTokenizer::Ngram => "Правда ли, что &lt;b&gt;<mark>Грав</mark>итация&lt;/b&gt; — это 🚀 <mark>ГРАВ</mark>ИТАЦИЯ? ",
+            IndexTokenizer::Words => "Правда ли, что &lt;b&gt;<mark>Гравитация</mark>&lt;/b&gt; — это 🚀 <mark>ГРАВИТАЦИЯ</mark>? ",
+        };
+        assert_eq!(html, expected, "{:?}", tokenizer);
+    }
+
+    Ok(())
+}
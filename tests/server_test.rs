@@ -0,0 +1,667 @@
+use anyhow::Result;
+use futures_util::{SinkExt, StreamExt};
+use tempfile::TempDir;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio_tungstenite::tungstenite::Message;
+
+use deepseek_app::config::{AppConfig, EmptySearch, Language};
+use deepseek_app::search::SearchEngine;
+use deepseek_app::{generator, indexer, server};
+
+const ARRAY_EXPORT: &str = "tests/fixtures/array_export.json";
+const META_EXPORT: &str = "tests/fixtures/meta_export.json";
+
+/// Index the fixture export and serve it on a random local port
+async fn spawn_server(temp_dir: &TempDir) -> Result<std::net::SocketAddr> {
+    spawn_server_with(temp_dir, ARRAY_EXPORT).await
+}
+
+/// Like [`spawn_server`], for another export
+async fn spawn_server_with(temp_dir: &TempDir, export: &str) -> Result<std::net::SocketAddr> {
+    spawn_server_with_config(temp_dir, export, AppConfig::default()).await
+}
+
+/// Like [`spawn_server_with`], with settings other than the defaults
+async fn spawn_server_with_config(temp_dir: &TempDir, export: &str, config: AppConfig) -> Result<std::net::SocketAddr> {
+    let index_path = temp_dir.path().join("index");
+    let index_path = index_path.to_str().unwrap();
+    indexer::build_index_with_config(export, index_path, &config).await?;
+
+    let app = server::router(
+        SearchEngine::with_config(index_path, &config)?,
+        temp_dir.path().to_str().unwrap(),
+        AppConfig {
+            conversations_file_path: Some(export.to_string()),
+            ..config
+        },
+    );
+
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await?;
+    let addr = listener.local_addr()?;
+    tokio::spawn(async move { axum::serve(listener, app).await });
+
+    Ok(addr)
+}
+
+/// Minimal HTTP/1.1 GET, returning the raw response
+async fn http_get(addr: std::net::SocketAddr, path: &str) -> Result<String> {
+    let mut stream = tokio::net::TcpStream::connect(addr).await?;
+    stream
+        .write_all(format!("GET {} HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n", path).as_bytes())
+        .await?;
+
+    let mut response = Vec::new();
+    stream.read_to_end(&mut response).await?;
+    Ok(String::from_utf8_lossy(&response).into_owned())
+}
+
+/// Like [`http_get`], for a request with a JSON body
+async fn http_json(addr: std::net::SocketAddr, method: &str, path: &str, body: &serde_json::Value) -> Result<String> {
+    http_json_from(addr, method, path, body, None).await
+}
+
+/// Like [`http_json`], sent by a page of `origin`
+async fn http_json_from(
+    addr: std::net::SocketAddr,
+    method: &str,
+    path: &str,
+    body: &serde_json::Value,
+    origin: Option<&str>,
+) -> Result<String> {
+    let body = body.to_string();
+    let origin = origin.map(|origin| format!("Origin: {}\r\n", origin)).unwrap_or_default();
+    let mut stream = tokio::net::TcpStream::connect(addr).await?;
+    stream
+        .write_all(
+            format!(
+                "{} {} HTTP/1.1\r\nHost: localhost\r\n{}Content-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                method,
+                path,
+                origin,
+                body.len(),
+                body
+            )
+            .as_bytes(),
+        )
+        .await?;
+
+    let mut response = Vec::new();
+    stream.read_to_end(&mut response).await?;
+    Ok(String::from_utf8_lossy(&response).into_owned())
+}
+
+/// Body of a raw HTTP response, joining the chunks of a chunked one
+fn response_body(response: &str) -> String {
+    let (head, body) = response.split_once("\r\n\r\n").unwrap_or((response, ""));
+    if !head.to_lowercase().contains("transfer-encoding: chunked") {
+        return body.to_string();
+    }
+
+    let mut rest = body.as_bytes();
+    let mut joined = Vec::new();
+    while let Some(line_end) = rest.windows(2).position(|w| w == b"\r\n") {
+        let size = usize::from_str_radix(std::str::from_utf8(&rest[..line_end]).unwrap().trim(), 16).unwrap();
+        if size == 0 {
+            break;
+        }
+        joined.extend_from_slice(&rest[line_end + 2..line_end + 2 + size]);
+        rest = &rest[line_end + 2 + size + 2..];
+    }
+    String::from_utf8(joined).unwrap()
+}
+
+async fn next_json(
+    ws: &mut (impl StreamExt<Item = Result<Message, tokio_tungstenite::tungstenite::Error>> + Unpin),
+) -> Result<serde_json::Value> {
+    loop {
+        match ws.next().await {
+            Some(Ok(Message::Text(text))) => return Ok(serde_json::from_str(&text)?),
+            Some(Ok(_)) => continue,
+            other => anyhow::bail!("socket closed: {:?}", other),
+        }
+    }
+}
+
+#[tokio::test]
+async fn test_ws_search_echoes_sequence_number() -> Result<()> {
+    let temp_dir = TempDir::new()?;
+    let addr = spawn_server(&temp_dir).await?;
+
+    let (mut ws, _) = tokio_tungstenite::connect_async(format!("ws://{}/ws/search", addr)).await?;
+
+    ws.send(Message::Text(r#"{"seq": 7, "q": "гравитация"}"#.into())).await?;
+    let response = next_json(&mut ws).await?;
+
+    assert_eq!(response["seq"], 7);
+    assert_eq!(response["query"], "гравитация");
+    assert_eq!(response["total"], 1);
+    assert_eq!(response["results"][0]["conversation_id"], "conv-1");
+
+    // Plain text frames are queries too, numbered after the last one
+    ws.send(Message::Text("притяжение".into())).await?;
+    let response = next_json(&mut ws).await?;
+
+    assert_eq!(response["seq"], 8);
+    assert_eq!(response["total"], 1);
+
+    // Numbering wraps around instead of overflowing
+    ws.send(Message::Text(format!(r#"{{"seq": {}, "q": "гравитация"}}"#, u64::MAX))).await?;
+    assert_eq!(next_json(&mut ws).await?["seq"], u64::MAX);
+    ws.send(Message::Text("гравитация".into())).await?;
+    assert_eq!(next_json(&mut ws).await?["seq"], 0);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_ws_search_refuses_huge_offsets() -> Result<()> {
+    let temp_dir = TempDir::new()?;
+    let addr = spawn_server(&temp_dir).await?;
+
+    let (mut ws, _) = tokio_tungstenite::connect_async(format!("ws://{}/ws/search", addr)).await?;
+    ws.send(Message::Text(r#"{"seq": 1, "q": "гравитация", "offset": 100000000000}"#.into())).await?;
+    let response = next_json(&mut ws).await?;
+    assert_eq!(response["seq"], 1);
+    assert_eq!(response["total"], 0);
+    assert_eq!(response["error"], "offset too large");
+
+    // The session is still there
+    ws.send(Message::Text(r#"{"seq": 2, "q": "гравитация"}"#.into())).await?;
+    let response = next_json(&mut ws).await?;
+    assert_eq!(response["total"], 1);
+    assert!(response.get("error").is_none());
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_ws_search_answers_latest_query() -> Result<()> {
+    let temp_dir = TempDir::new()?;
+    let addr = spawn_server(&temp_dir).await?;
+
+    let (mut ws, _) = tokio_tungstenite::connect_async(format!("ws://{}/ws/search", addr)).await?;
+
+    for (seq, q) in ["г", "гр", "гра", "грав"].iter().enumerate() {
+        ws.send(Message::Text(format!(r#"{{"seq": {}, "q": "{}"}}"#, seq, q))).await?;
+    }
+    ws.send(Message::Text(r#"{"seq": 99, "q": "zzqqxxjj"}"#.into())).await?;
+
+    // Earlier queries may or may not have been answered, but never after a newer one
+    let mut last_seq = -1;
+    loop {
+        let response = next_json(&mut ws).await?;
+        let seq = response["seq"].as_i64().unwrap();
+        assert!(seq > last_seq, "seq {} arrived after {}", seq, last_seq);
+        last_seq = seq;
+
+        if seq == 99 {
+            assert_eq!(response["total"], 0);
+            break;
+        }
+    }
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_standalone_export_endpoint() -> Result<()> {
+    let temp_dir = TempDir::new()?;
+    let addr = spawn_server(&temp_dir).await?;
+
+    let response = http_get(addr, "/api/conversation/conv-1/standalone").await?;
+    assert!(response.starts_with("HTTP/1.1 200"), "unexpected response: {}", response.lines().next().unwrap_or(""));
+    assert!(response.to_lowercase().contains(r#"content-disposition: attachment; filename="conv-1.html""#));
+    assert!(response.contains("О гравитации"));
+    assert!(!response.contains("/assets/"));
+
+    let response = http_get(addr, "/api/conversation/missing/standalone").await?;
+    assert!(response.starts_with("HTTP/1.1 404"));
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_code_zip_endpoint() -> Result<()> {
+    let temp_dir = TempDir::new()?;
+    let addr = spawn_server(&temp_dir).await?;
+
+    let response = http_get(addr, "/api/conversation/conv-1/code.zip").await?;
+    assert!(response.starts_with("HTTP/1.1 200"), "unexpected response: {}", response.lines().next().unwrap_or(""));
+    let head = response.split("\r\n\r\n").next().unwrap().to_lowercase();
+    assert!(head.contains("content-type: application/zip"), "{}", head);
+    assert!(head.contains(r#"content-disposition: attachment; filename="conv-1-code.zip""#), "{}", head);
+
+    let response = http_get(addr, "/api/conversation/missing/code.zip").await?;
+    assert!(response.starts_with("HTTP/1.1 404"));
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_conversation_terms_endpoint() -> Result<()> {
+    let temp_dir = TempDir::new()?;
+    let addr = spawn_server(&temp_dir).await?;
+
+    let response = http_get(addr, "/api/conversation/conv-1/terms?limit=2").await?;
+    assert!(response.starts_with("HTTP/1.1 200"));
+    let terms: serde_json::Value = serde_json::from_str(&response_body(&response))?;
+    assert_eq!(terms, serde_json::json!([
+        { "term": "гравитация", "count": 2 },
+        { "term": "притяжение", "count": 1 }
+    ]));
+
+    let response = http_get(addr, "/api/conversation/missing/terms").await?;
+    assert!(response.starts_with("HTTP/1.1 404"));
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_raw_conversation_endpoint() -> Result<()> {
+    let temp_dir = TempDir::new()?;
+    let addr = spawn_server(&temp_dir).await?;
+
+    let response = http_get(addr, "/api/conversation/conv-1/raw").await?;
+    assert!(response.starts_with("HTTP/1.1 200"));
+    assert!(response.to_lowercase().contains("content-type: application/json"));
+
+    let body = response_body(&response);
+    assert!(body.contains("\n  \"id\": \"conv-1\""), "should be pretty-printed: {}", body);
+
+    // The entry exactly as exported
+    let fixture: Vec<serde_json::Value> = serde_json::from_str(&std::fs::read_to_string(ARRAY_EXPORT)?)?;
+    let raw: serde_json::Value = serde_json::from_str(&body)?;
+    assert_eq!(raw, fixture[0]);
+
+    let response = http_get(addr, "/api/conversation/missing/raw").await?;
+    assert!(response.starts_with("HTTP/1.1 404"));
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_export_zip_endpoint() -> Result<()> {
+    let temp_dir = TempDir::new()?;
+    let addr = spawn_server(&temp_dir).await?;
+
+    let response = http_get(addr, "/api/export.zip").await?;
+    assert!(response.starts_with("HTTP/1.1 200"));
+    let head = response.split_once("\r\n\r\n").unwrap().0.to_lowercase();
+    assert!(head.contains("content-type: application/zip"));
+    assert!(head.contains("content-disposition: attachment; filename=\"deepseek-archive.zip\""));
+    assert!(!head.contains("content-encoding"), "a ZIP isn't worth gzipping");
+    // Streamed, so the length isn't known up front
+    assert!(head.contains("transfer-encoding: chunked"));
+    assert!(response.contains("PK\u{3}\u{4}"), "should start a ZIP local file header");
+
+    let response = http_get(addr, "/api/export.zip?scope=markdown").await?;
+    assert!(response.contains("filename=\"deepseek-markdown.zip\""));
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_conversations_meta_sorts_and_pages() -> Result<()> {
+    let temp_dir = TempDir::new()?;
+    let addr = spawn_server_with(&temp_dir, META_EXPORT).await?;
+    let ids = |body: &serde_json::Value| -> Vec<String> {
+        body["conversations"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|c| c["conversation_id"].as_str().unwrap().to_string())
+            .collect()
+    };
+    let get = |path: &'static str| async move {
+        let response = http_get(addr, path).await?;
+        assert!(response.starts_with("HTTP/1.1 200"), "{}", response);
+        anyhow::Ok(serde_json::from_str::<serde_json::Value>(&response_body(&response))?)
+    };
+
+    // Newest first by default, with everything the index knows
+    let body = get("/api/conversations/meta").await?;
+    assert_eq!(body["total"], 3);
+    assert_eq!(ids(&body), vec!["conv-a", "conv-c", "conv-b"]);
+    let first = &body["conversations"][0];
+    assert_eq!(first["title"], "Бюджет на отпуск");
+    assert_eq!(first["url"], "/conversations/conv-a/");
+    assert_eq!(first["inserted_at"], "2024-03-01T10:00:00Z");
+    assert_eq!(first["updated_at"], "2024-03-05T10:00:00Z");
+    assert_eq!(first["message_count"], 3);
+    assert_eq!(first["tags"], serde_json::json!(["путешествия", "деньги"]));
+    assert_eq!(body["conversations"][2]["updated_at"], serde_json::Value::Null);
+
+    let body = get("/api/conversations/meta?sort=title&order=asc").await?;
+    assert_eq!(ids(&body), vec!["conv-b", "conv-a", "conv-c"], "case doesn't matter");
+    let body = get("/api/conversations/meta?sort=message_count").await?;
+    assert_eq!(ids(&body), vec!["conv-a", "conv-c", "conv-b"]);
+
+    // Paging keeps the total
+    let body = get("/api/v1/conversations/meta?sort=inserted_at&order=asc&offset=1&limit=1").await?;
+    assert_eq!(body["total"], 3);
+    assert_eq!(ids(&body), vec!["conv-c"]);
+    let body = get("/api/conversations/meta?offset=5").await?;
+    assert!(ids(&body).is_empty());
+
+    let response = http_get(addr, "/api/conversations/meta?sort=size").await?;
+    assert!(response.starts_with("HTTP/1.1 400"), "{}", response);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_ready_waits_for_the_site() -> Result<()> {
+    let temp_dir = TempDir::new()?;
+    let addr = spawn_server(&temp_dir).await?;
+
+    // Indexed, but nothing generated yet
+    let response = http_get(addr, "/api/ready").await?;
+    assert!(response.starts_with("HTTP/1.1 503"), "{}", response);
+    let body: serde_json::Value = serde_json::from_str(&response_body(&response))?;
+    assert_eq!(body["ready"], false);
+    assert_eq!(body["site"], false);
+    assert!(body["documents"].as_u64().unwrap() > 0);
+
+    generator::generate_site(ARRAY_EXPORT, temp_dir.path().to_str().unwrap(), &AppConfig::default()).await?;
+    let response = http_get(addr, "/api/ready").await?;
+    assert!(response.starts_with("HTTP/1.1 200"), "{}", response);
+    let body: serde_json::Value = serde_json::from_str(&response_body(&response))?;
+    assert_eq!(body["ready"], true);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_archive_is_served_at_all() -> Result<()> {
+    let temp_dir = TempDir::new()?;
+    generator::generate_site(ARRAY_EXPORT, temp_dir.path().to_str().unwrap(), &AppConfig::default()).await?;
+    let addr = spawn_server(&temp_dir).await?;
+
+    let response = http_get(addr, "/all").await?;
+    assert!(response.starts_with("HTTP/1.1 30"), "should redirect to the directory: {}", response);
+    assert!(response.to_lowercase().contains("location: /all/"));
+
+    let response = http_get(addr, "/all/").await?;
+    assert!(response.starts_with("HTTP/1.1 200"));
+    assert!(response_body(&response).contains(r#"class="archive-section""#));
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_settings_are_saved_to_the_config_file() -> Result<()> {
+    let temp_dir = TempDir::new()?;
+    generator::generate_site(ARRAY_EXPORT, temp_dir.path().to_str().unwrap(), &AppConfig::default()).await?;
+    let index_path = temp_dir.path().join("index");
+    indexer::build_index(ARRAY_EXPORT, index_path.to_str().unwrap()).await?;
+    let config_path = temp_dir.path().join("config").join("config.json");
+    let app = server::router_with_config_file(
+        SearchEngine::new(index_path.to_str().unwrap())?,
+        temp_dir.path().to_str().unwrap(),
+        AppConfig::default(),
+        Some(config_path.clone()),
+    );
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await?;
+    let addr = listener.local_addr()?;
+    tokio::spawn(async move { axum::serve(listener, app).await });
+
+    let response = http_get(addr, "/settings/").await?;
+    assert!(response.starts_with("HTTP/1.1 200"));
+    assert!(response_body(&response).contains(r#"id="settingsForm""#));
+
+    // No config file yet: the defaults, which the site was generated with
+    let response = http_get(addr, "/api/config").await?;
+    assert!(response.starts_with("HTTP/1.1 200"));
+    let config: serde_json::Value = serde_json::from_str(&response_body(&response))?;
+    assert_eq!(config["settings"]["language"], "ru");
+    assert_eq!(config["settings"]["body_font"], "inter");
+    assert_eq!(config["settings"]["syntax_theme"], generator::SYNTAX_THEME);
+    assert_eq!(config["regenerate_required"], false);
+
+    let patch = serde_json::json!({ "language": "en", "messages_per_page": 20 });
+    let response = http_json(addr, "PATCH", "/api/config", &patch).await?;
+    assert!(response.starts_with("HTTP/1.1 200"), "{}", response);
+    let config: serde_json::Value = serde_json::from_str(&response_body(&response))?;
+    assert_eq!(config["settings"]["language"], "en");
+    assert_eq!(config["settings"]["messages_per_page"], 20);
+    assert_eq!(config["regenerate_required"], true);
+
+    let saved = AppConfig::load_from(&config_path)?;
+    assert_eq!(saved.language, Language::En);
+    assert_eq!(saved.messages_per_page, 20);
+    // Untouched settings keep their values
+    assert_eq!(saved.archive_page_conversations, AppConfig::default().archive_page_conversations);
+
+    let response = http_json(addr, "PATCH", "/api/config", &serde_json::json!({ "body_font": "comic-sans" })).await?;
+    assert!(response.starts_with("HTTP/1.1 400"), "{}", response);
+    let response = http_json(addr, "PATCH", "/api/config", &serde_json::json!({ "syntax_theme": "no-such-theme" })).await?;
+    assert!(response.starts_with("HTTP/1.1 400"), "{}", response);
+    let response = http_json(addr, "PATCH", "/api/config", &serde_json::json!({ "syntax_theme": "InspiredGitHub" })).await?;
+    assert!(response.starts_with("HTTP/1.1 200"), "{}", response);
+    assert_eq!(AppConfig::load_from(&config_path)?.syntax_theme.as_deref(), Some("InspiredGitHub"));
+    let response = http_json(addr, "PATCH", "/api/config", &serde_json::json!({ "search_max_limit": 1 })).await?;
+    assert!(response.starts_with("HTTP/1.1 4"), "only the settings page's settings can be changed: {}", response);
+    assert_eq!(AppConfig::load_from(&config_path)?.search_max_limit, AppConfig::default().search_max_limit);
+
+    // Only the site's own pages may change settings
+    let patch = serde_json::json!({ "language": "ru" });
+    let response = http_json_from(addr, "PATCH", "/api/config", &patch, Some("http://evil.example")).await?;
+    assert!(response.starts_with("HTTP/1.1 403"), "{}", response);
+    assert_eq!(AppConfig::load_from(&config_path)?.language, Language::En);
+    let response = http_json_from(addr, "PATCH", "/api/config", &patch, Some("http://localhost")).await?;
+    assert!(response.starts_with("HTTP/1.1 200"), "{}", response);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_regenerate_refuses_other_sites() -> Result<()> {
+    let temp_dir = TempDir::new()?;
+    generator::generate_site(ARRAY_EXPORT, temp_dir.path().to_str().unwrap(), &AppConfig::default()).await?;
+    let addr = spawn_server(&temp_dir).await?;
+    let path = "/api/conversation/conv-1/regenerate";
+
+    let response = http_json_from(addr, "POST", path, &serde_json::json!({}), Some("http://evil.example")).await?;
+    assert!(response.starts_with("HTTP/1.1 403"), "{}", response);
+    let response = http_json_from(addr, "POST", path, &serde_json::json!({}), Some("http://localhost")).await?;
+    assert!(response.starts_with("HTTP/1.1 204"), "{}", response);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_search_endpoint() -> Result<()> {
+    let temp_dir = TempDir::new()?;
+    let config = AppConfig { index_messages: true, ..AppConfig::default() };
+    let addr = spawn_server_with_config(&temp_dir, ARRAY_EXPORT, config).await?;
+
+    let response = http_get(addr, "/api/search?q=%D0%B3%D1%80%D0%B0%D0%B2").await?;
+    assert!(response.starts_with("HTTP/1.1 200"), "{}", response);
+    let body: serde_json::Value = serde_json::from_str(&response_body(&response))?;
+    assert_eq!(body["total"], 1);
+    assert_eq!(body["results"][0]["conversation_id"], "conv-1");
+    let snippet_html = body["results"][0]["snippet_html"].as_str().unwrap();
+    assert!(snippet_html.starts_with("Что такое <mark>грав</mark>итация? <mark>Грав</mark>итация"), "{}", snippet_html);
+    assert_eq!(body["total_hits"], 1);
+    assert_eq!(body["offset"], 0);
+
+    let response = http_get(addr, "/api/search?q=%D0%B3%D1%80%D0%B0%D0%B2&offset=1").await?;
+    let body: serde_json::Value = serde_json::from_str(&response_body(&response))?;
+    assert_eq!(body["total"], 0);
+    assert_eq!(body["total_hits"], 1);
+    assert!(body["results"].as_array().unwrap().iter().all(|r| r.get("message_index").is_none()));
+
+    // Refused rather than handed to tantivy, which would abort the server
+    for path in ["/api/search?q=x&offset=100000000000", "/api/search?q=x&offset=100000000000&by_message=true"] {
+        let response = http_get(addr, path).await?;
+        assert!(response.starts_with("HTTP/1.1 400"), "{}", response);
+    }
+    let response = http_get(addr, "/api/search/stream?q=x&offset=100000000000").await?;
+    assert!(response.contains("event: error\ndata: offset too large"), "{}", response);
+    let response = http_get(addr, "/api/health").await?;
+    assert!(response.starts_with("HTTP/1.1 200"), "{}", response);
+
+    // Both messages mention it: the question and the answer
+    let response = http_get(addr, "/api/search?q=%D0%B3%D1%80%D0%B0%D0%B2&by_message=true").await?;
+    assert!(response.starts_with("HTTP/1.1 200"), "{}", response);
+    let body: serde_json::Value = serde_json::from_str(&response_body(&response))?;
+    assert_eq!(body["total"], 2);
+    let mut indexes: Vec<u64> = body["results"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|r| r["message_index"].as_u64().unwrap())
+        .collect();
+    indexes.sort();
+    assert_eq!(indexes, vec![0, 1]);
+
+    let response = http_get(addr, "/api/search?q=test&by_message=true&group_by_conversation=true").await?;
+    assert!(response.starts_with("HTTP/1.1 400"), "{}", response);
+
+    // "притяжение" is only in the answer
+    let response = http_get(addr, "/api/search?q=%D0%BF%D1%80%D0%B8%D1%82%D1%8F%D0%B6%D0%B5%D0%BD%D0%B8%D0%B5&role=user").await?;
+    let body: serde_json::Value = serde_json::from_str(&response_body(&response))?;
+    assert_eq!(body["total"], 0);
+    let response = http_get(addr, "/api/search?q=%D0%BF%D1%80%D0%B8%D1%82%D1%8F%D0%B6%D0%B5%D0%BD%D0%B8%D0%B5&role=assistant").await?;
+    let body: serde_json::Value = serde_json::from_str(&response_body(&response))?;
+    assert_eq!(body["total"], 1);
+    let response = http_get(addr, "/api/search?q=test&role=model").await?;
+    assert!(response.starts_with("HTTP/1.1 400"), "{}", response);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_by_message_needs_message_documents() -> Result<()> {
+    let temp_dir = TempDir::new()?;
+    let addr = spawn_server(&temp_dir).await?;
+
+    let response = http_get(addr, "/api/search?q=%D0%B3%D1%80%D0%B0%D0%B2&by_message=true").await?;
+    assert!(response.starts_with("HTTP/1.1 400"), "{}", response);
+    let response = http_get(addr, "/api/search?q=%D0%B3%D1%80%D0%B0%D0%B2").await?;
+    assert!(response.starts_with("HTTP/1.1 200"), "{}", response);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_search_filters_by_date() -> Result<()> {
+    let temp_dir = TempDir::new()?;
+    let addr = spawn_server_with(&temp_dir, META_EXPORT).await?;
+    let total = |response: String| -> Result<serde_json::Value> {
+        assert!(response.starts_with("HTTP/1.1 200"), "{}", response);
+        let body: serde_json::Value = serde_json::from_str(&response_body(&response))?;
+        Ok(body["total"].clone())
+    };
+
+    // "ноутбук", created on 2024-02-10
+    let query = "/api/search?q=%D0%BD%D0%BE%D1%83%D1%82%D0%B1%D1%83%D0%BA";
+    assert_eq!(total(http_get(addr, query).await?)?, 1);
+    // A whole day `to` includes that day
+    assert_eq!(total(http_get(addr, &format!("{}&from=2024-02-01&to=2024-02-10", query)).await?)?, 1);
+    assert_eq!(total(http_get(addr, &format!("{}&to=2024-02-09", query)).await?)?, 0);
+    assert_eq!(total(http_get(addr, &format!("{}&from=2024-02-10T10:00:01Z", query)).await?)?, 0);
+
+    let response = http_get(addr, &format!("{}&from=yesterday", query)).await?;
+    assert!(response.starts_with("HTTP/1.1 400"), "{}", response);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_empty_search_lists_recent_conversations() -> Result<()> {
+    let temp_dir = TempDir::new()?;
+    let addr = spawn_server_with(&temp_dir, META_EXPORT).await?;
+    let response = http_get(addr, "/api/search?q=").await?;
+    assert!(response.starts_with("HTTP/1.1 400"), "{}", response);
+
+    let temp_dir = TempDir::new()?;
+    let config = AppConfig { empty_search: EmptySearch::Recent, ..AppConfig::default() };
+    let addr = spawn_server_with_config(&temp_dir, META_EXPORT, config).await?;
+    let response = http_get(addr, "/api/search?q=&limit=2").await?;
+    assert!(response.starts_with("HTTP/1.1 200"), "{}", response);
+    let body: serde_json::Value = serde_json::from_str(&response_body(&response))?;
+    let ids: Vec<_> = body["results"].as_array().unwrap().iter().map(|r| r["conversation_id"].clone()).collect();
+    assert_eq!(ids, ["conv-a", "conv-c"], "newest first");
+    assert_eq!(body["total_hits"], 3);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_api_v1_matches_unversioned_routes() -> Result<()> {
+    let temp_dir = TempDir::new()?;
+    let addr = spawn_server(&temp_dir).await?;
+
+    for path in ["/conversations", "/conversation/conv-1/raw"] {
+        let versioned = http_get(addr, &format!("/api/v1{}", path)).await?;
+        let alias = http_get(addr, &format!("/api{}", path)).await?;
+        assert!(versioned.starts_with("HTTP/1.1 200"), "{}", versioned);
+        assert_eq!(response_body(&versioned), response_body(&alias), "{}", path);
+    }
+
+    // Timings differ between the two, the results don't
+    let search = |response: String| -> Result<serde_json::Value> {
+        let body: serde_json::Value = serde_json::from_str(&response_body(&response))?;
+        Ok(body["results"].clone())
+    };
+    let versioned = search(http_get(addr, "/api/v1/search?q=%D0%B3%D1%80%D0%B0%D0%B2").await?)?;
+    let alias = search(http_get(addr, "/api/search?q=%D0%B3%D1%80%D0%B0%D0%B2").await?)?;
+    assert_eq!(versioned[0]["conversation_id"], "conv-1");
+    assert_eq!(versioned, alias);
+
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn test_concurrent_searches_all_complete() -> Result<()> {
+    let temp_dir = TempDir::new()?;
+    let addr = spawn_server(&temp_dir).await?;
+
+    // Far more requests than runtime workers; searches mustn't starve them
+    let requests: Vec<_> = (0..64)
+        .map(|i| {
+            let path = if i % 2 == 0 {
+                "/api/search?q=%D0%B3%D1%80%D0%B0%D0%B2".to_string()
+            } else {
+                format!("/api/autocomplete?prefix=%D0%B3%D1%80&limit={}", i % 5 + 1)
+            };
+            tokio::spawn(async move { http_get(addr, &path).await })
+        })
+        .collect();
+
+    let responses = tokio::time::timeout(std::time::Duration::from_secs(30), futures_util::future::join_all(requests)).await?;
+    for response in responses {
+        let response = response??;
+        assert!(response.starts_with("HTTP/1.1 200"), "{}", response);
+    }
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_precompressed_pages_are_served() -> Result<()> {
+    let temp_dir = TempDir::new()?;
+    let config = AppConfig { precompress: true, ..AppConfig::default() };
+    generator::generate_site(ARRAY_EXPORT, temp_dir.path().to_str().unwrap(), &config).await?;
+    let addr = spawn_server(&temp_dir).await?;
+
+    let mut stream = tokio::net::TcpStream::connect(addr).await?;
+    stream
+        .write_all(b"GET /assets/css/main.css HTTP/1.1\r\nHost: localhost\r\nAccept-Encoding: br\r\nConnection: close\r\n\r\n")
+        .await?;
+    let mut response = Vec::new();
+    stream.read_to_end(&mut response).await?;
+
+    let split = response.windows(4).position(|w| w == b"\r\n\r\n").unwrap();
+    let head = String::from_utf8_lossy(&response[..split]).to_lowercase();
+    assert!(head.starts_with("http/1.1 200"));
+    assert!(head.contains("content-encoding: br"));
+    assert!(head.contains("content-type: text/css"));
+
+    let mut body = Vec::new();
+    std::io::Read::read_to_end(&mut brotli::Decompressor::new(&response[split + 4..], 4096), &mut body)?;
+    assert_eq!(body, std::fs::read(temp_dir.path().join("assets/css/main.css"))?);
+
+    Ok(())
+}
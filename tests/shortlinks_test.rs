@@ -0,0 +1,56 @@
+use anyhow::Result;
+use std::fs;
+use tempfile::TempDir;
+
+use deepseek_app::config::AppConfig;
+use deepseek_app::generator;
+use deepseek_app::shortlinks::{self, ShortLinks};
+
+#[test]
+fn test_short_ids_are_prefixes() {
+    let links = shortlinks::assign(&ShortLinks::new(), &["0123456789abcdef", "short"]);
+
+    assert_eq!(links.get("01234567").map(String::as_str), Some("0123456789abcdef"));
+    assert_eq!(links.get("short").map(String::as_str), Some("short"));
+}
+
+#[test]
+fn test_short_id_collisions() {
+    let links = shortlinks::assign(
+        &ShortLinks::new(),
+        &["abcdefgh-1111", "abcdefgh-2222", "abcdefgh"],
+    );
+
+    assert_eq!(links.len(), 3);
+    assert_eq!(links.get("abcdefgh").map(String::as_str), Some("abcdefgh-1111"));
+    assert_eq!(links.get("abcdefgh-").map(String::as_str), Some("abcdefgh-2222"));
+    // The bare id is already taken as a prefix, so it gets a suffix
+    assert_eq!(links.get("abcdefgh-2").map(String::as_str), Some("abcdefgh"));
+}
+
+#[test]
+fn test_short_ids_are_stable() {
+    let first = shortlinks::assign(&ShortLinks::new(), &["abcdefgh-2222"]);
+
+    // An older conversation sharing the prefix shows up later on
+    let second = shortlinks::assign(&first, &["abcdefgh-1111", "abcdefgh-2222"]);
+
+    assert_eq!(second.get("abcdefgh").map(String::as_str), Some("abcdefgh-2222"));
+    assert_eq!(second.get("abcdefgh-").map(String::as_str), Some("abcdefgh-1111"));
+}
+
+#[tokio::test]
+async fn test_generate_site_writes_shortlinks() -> Result<()> {
+    let temp_dir = TempDir::new()?;
+    let output_dir = temp_dir.path().join("dist");
+
+    generator::generate_site("tests/fixtures/array_export.json", output_dir.to_str().unwrap(), &AppConfig::default()).await?;
+
+    let links = shortlinks::load(&output_dir);
+    assert_eq!(links.get("conv-1").map(String::as_str), Some("conv-1"));
+
+    let page = fs::read_to_string(output_dir.join("conversations/conv-1/index.html"))?;
+    assert!(page.contains(r#"href="/c/conv-1""#), "Page should link to its short id");
+
+    Ok(())
+}
@@ -0,0 +1,69 @@
+use anyhow::Result;
+use serde_json::json;
+
+use deepseek_app::config::AppConfig;
+use deepseek_app::generator::render_standalone;
+use deepseek_app::math::render_offline;
+use deepseek_app::Conversation;
+
+fn conversation_with_math() -> Result<Conversation> {
+    Ok(serde_json::from_value(json!({
+        "id": "math",
+        "title": "Energy",
+        "inserted_at": "2024-01-01T10:00:00Z",
+        "mapping": {
+            "root": { "children": ["q"] },
+            "q": {
+                "message": { "fragments": [{"type": "REQUEST", "content": "What is $E=mc^2$?"}] },
+                "children": ["a"]
+            },
+            "a": {
+                "message": { "fragments": [{
+                    "type": "RESPONSE",
+                    "content": "Mass-energy equivalence:\n\n\\[E = mc^2\\]\n\n```rust\nlet price = \"$5 and $6\";\n```"
+                }] },
+                "children": []
+            }
+        }
+    }))?)
+}
+
+#[test]
+fn test_standalone_page_has_no_external_assets() -> Result<()> {
+    let html = render_standalone(&conversation_with_math()?, &AppConfig::default())?;
+
+    assert!(html.contains("<title>Energy - DeepSeek Chat History</title>"));
+    assert!(!html.contains("/assets/"), "page links to site assets");
+    assert!(!html.contains("cdn.jsdelivr.net"), "page needs the network");
+    assert!(!html.contains("/c/"), "short links don't work outside the site");
+
+    // Styles and code actions are inlined, code is highlighted
+    assert!(html.contains("--body-font"));
+    assert!(html.contains("code-block-wrapper"));
+    assert!(html.contains(".highlight"));
+
+    Ok(())
+}
+
+#[cfg(any(unix, all(windows, target_env = "gnu")))]
+#[test]
+fn test_standalone_page_renders_math_offline() -> Result<()> {
+    let html = render_standalone(&conversation_with_math()?, &AppConfig::default())?;
+
+    assert!(html.contains("<math"), "formulas not converted to MathML");
+    assert!(html.contains(r#"display="block""#), "display formula rendered inline");
+    // Dollars inside code (and the copy button's data-code attribute) are left alone
+    assert_eq!(html.matches("$5 and $6").count(), 2);
+
+    Ok(())
+}
+
+#[cfg(any(unix, all(windows, target_env = "gnu")))]
+#[test]
+fn test_render_offline_skips_code_and_invalid_formulas() {
+    let html = render_offline(r#"<p>$x^2$ and $\frac{$</p><div data-x="$a$"><pre><code>echo $HOME $PATH</code></pre></div>"#);
+
+    assert!(html.contains("<math"));
+    assert!(html.contains("$\\frac{$"), "invalid formula should be kept");
+    assert!(html.contains(r#"<div data-x="$a$"><pre><code>echo $HOME $PATH</code></pre>"#));
+}
@@ -0,0 +1,71 @@
+use anyhow::Result;
+use serde_json::json;
+use std::fs;
+use tempfile::TempDir;
+
+use deepseek_app::config::AppConfig;
+use deepseek_app::model::extract_messages;
+use deepseek_app::terms::{top_terms, TermCount};
+use deepseek_app::generator;
+
+fn mapping() -> serde_json::Value {
+    json!({
+        "root": { "children": ["msg1"] },
+        "msg1": {
+            "message": {
+                "fragments": [
+                    { "type": "REQUEST", "content": "Что такое гравитация? И как гравитация связана с массой?" },
+                    { "type": "SEARCH", "content": "википедия википедия википедия" }
+                ]
+            },
+            "children": ["msg2"]
+        },
+        "msg2": {
+            "message": {
+                "fragments": [
+                    { "type": "RESPONSE", "content": "Гравитация — это притяжение. Масса 100 кг притягивает, и масса важна." }
+                ]
+            },
+            "children": []
+        }
+    })
+}
+
+#[test]
+fn test_top_terms_counts_words_without_stop_words() {
+    let terms = top_terms(&extract_messages(&mapping()), 3);
+
+    assert_eq!(terms, vec![
+        TermCount { term: "гравитация".to_string(), count: 3 },
+        TermCount { term: "масса".to_string(), count: 2 },
+        TermCount { term: "важна".to_string(), count: 1 },
+    ]);
+
+    let all = top_terms(&extract_messages(&mapping()), 100);
+    for skipped in ["что", "это", "100", "википедия"] {
+        assert!(all.iter().all(|t| t.term != skipped), "{} should be skipped", skipped);
+    }
+}
+
+#[tokio::test]
+async fn test_tag_cloud_on_conversation_page() -> Result<()> {
+    let temp_dir = TempDir::new()?;
+    let conversations_path = temp_dir.path().join("conversations.json");
+    let output_dir = temp_dir.path().join("dist");
+
+    fs::write(&conversations_path, json!([
+        { "id": "1", "title": "Гравитация", "mapping": mapping() }
+    ]).to_string())?;
+
+    generator::generate_site(conversations_path.to_str().unwrap(), output_dir.to_str().unwrap(), &AppConfig::default()).await?;
+    let page = fs::read_to_string(output_dir.join("conversations/1/index.html"))?;
+    assert!(!page.contains("tag-cloud"), "No tag cloud by default");
+
+    let config = AppConfig { tag_cloud_terms: 5, ..AppConfig::default() };
+    generator::generate_site(conversations_path.to_str().unwrap(), output_dir.to_str().unwrap(), &config).await?;
+    let page = fs::read_to_string(output_dir.join("conversations/1/index.html"))?;
+    assert!(page.contains(r#"<span class="tag-cloud-term tag-weight-5" title="3">гравитация</span>"#));
+    assert!(page.contains(r#"title="2">масса</span>"#));
+
+    Ok(())
+}